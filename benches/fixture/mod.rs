@@ -0,0 +1,157 @@
+//! Synthetic marketplace served on localhost for the benchmarks
+//!
+//! The server answers the extension queries with a single version of the
+//! requested extension and serves the same generated VSIX package for every
+//! download, so the pipeline runs against it with `--bench-mode <origin>`.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Path of the extension query endpoint
+const QUERY_PATH: &str = "/_apis/public/gallery/extensionquery";
+
+/// Version of every synthetic extension
+pub const FIXTURE_VERSION: &str = "1.0.0";
+
+/// Marketplace fixture listening on an ephemeral port
+pub struct FixtureServer {
+    origin: String,
+}
+
+impl FixtureServer {
+    /// Start a fixture serving packages with a payload of `package_size` bytes
+    pub fn start(package_size: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind the fixture server");
+        let origin = format!("http://{}", listener.local_addr().unwrap());
+        let package = Arc::new(build_package(package_size));
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let package = package.clone();
+                thread::spawn(move || serve_connection(stream, &package));
+            }
+        });
+        Self { origin }
+    }
+
+    /// Get the origin to pass to `--bench-mode`
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+}
+
+/// Build a valid VSIX package with a payload of `size` bytes
+fn build_package(size: usize) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    writer
+        .start_file("extension.vsixmanifest", options)
+        .unwrap();
+    writer
+        .write_all(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><PackageManifest/>")
+        .unwrap();
+    writer.start_file("extension/payload.bin", options).unwrap();
+    // A cheap pseudo-random payload, so the deflate stage does real work
+    let payload: Vec<u8> = (0..size)
+        .map(|index| (index.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    writer.write_all(&payload).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+/// Serve the requests of a keep-alive connection until the client closes it
+fn serve_connection(stream: TcpStream, package: &[u8]) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let (status, content_type, content) = if path == QUERY_PATH {
+            let query: Value = serde_json::from_slice(&body).unwrap_or_default();
+            let id = query["filters"][0]["criteria"][0]["value"]
+                .as_str()
+                .unwrap_or_default();
+            let response = query_response(id).to_string().into_bytes();
+            ("200 OK", "application/json", response)
+        } else if path.contains("/vspackage") {
+            ("200 OK", "application/octet-stream", package.to_vec())
+        } else {
+            ("404 Not Found", "text/plain", Vec::new())
+        };
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            status,
+            content_type,
+            content.len()
+        );
+        if writer.write_all(head.as_bytes()).is_err() || writer.write_all(&content).is_err() {
+            return;
+        }
+    }
+}
+
+/// Build the answer of the marketplace to the query of an extension
+fn query_response(id: &str) -> Value {
+    let (publisher, name) = id.split_once('.').unwrap_or((id, id));
+    json!({
+        "results": [{
+            "extensions": [{
+                "publisher": {
+                    "publisherId": publisher,
+                    "publisherName": publisher,
+                    "displayName": publisher,
+                    "flags": "verified",
+                    "domain": null,
+                    "isDomainVerified": false
+                },
+                "extensionId": id,
+                "extensionName": name,
+                "displayName": name,
+                "flags": "validated, public",
+                "lastUpdated": "2025-01-01T00:00:00Z",
+                "publishedDate": "2025-01-01T00:00:00Z",
+                "releaseDate": "2025-01-01T00:00:00Z",
+                "shortDescription": "Synthetic extension",
+                "versions": [{
+                    "version": FIXTURE_VERSION,
+                    "flags": "validated",
+                    "lastUpdated": "2025-01-01T00:00:00Z",
+                    "files": [],
+                    "properties": null,
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                }],
+                "deploymentType": 0
+            }],
+            "resultMetadata": []
+        }]
+    })
+}
@@ -0,0 +1,73 @@
+//! Benchmarks of the resolution and download pipeline
+//!
+//! Each iteration runs the harvester binary in `--bench-mode` against a local
+//! fixture server, so the measures do not depend on the marketplace or on the
+//! network. Run them with `cargo bench --bench pipeline`.
+
+mod fixture;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fixture::FixtureServer;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Sizes of the synthetic manifests
+const EXTENSION_COUNTS: [usize; 2] = [10, 50];
+
+/// Payload size of the synthetic packages
+const PACKAGE_SIZE: usize = 256 * 1024;
+
+/// Write a manifest of `count` synthetic universal extensions
+fn write_manifest(dir: &Path, count: usize) -> String {
+    let ids: Vec<String> = (0..count)
+        .map(|index| format!("bench.extension{}", index))
+        .collect();
+    let path = dir.join("extensions.json");
+    fs::write(&path, json!({ "universal": ids }).to_string()).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Run the harvester against the fixture server
+fn harvest(server: &FixtureServer, input: &str, destination: &Path, no_cache: bool) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vsixHarvester"));
+    command
+        .args(["--input", input])
+        .arg("--destination")
+        .arg(destination)
+        .args(["--bench-mode", server.origin()])
+        .env_remove("RUST_LOG");
+    if no_cache {
+        command.arg("--no-cache");
+    }
+    let status = command.status().expect("run the harvester");
+    assert!(status.success(), "the harvest failed");
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let server = FixtureServer::start(PACKAGE_SIZE);
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    for count in EXTENSION_COUNTS {
+        let dir = tempfile::tempdir().unwrap();
+        let input = write_manifest(dir.path(), count);
+        let destination = dir.path().join("extensions");
+        group.throughput(Throughput::Elements(count as u64));
+
+        // Resolution and download of every package
+        group.bench_with_input(BenchmarkId::new("download", count), &count, |b, _| {
+            b.iter(|| harvest(&server, &input, &destination, true))
+        });
+
+        // Resolution only, the packages are already in the destination
+        harvest(&server, &input, &destination, false);
+        group.bench_with_input(BenchmarkId::new("resolve", count), &count, |b, _| {
+            b.iter(|| harvest(&server, &input, &destination, false))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+/*
+ * C ABI of the vsixHarvester library, built with `cargo build --release --features ffi`
+ * into libvsixharvester.so (vsixharvester.dll, libvsixharvester.dylib).
+ *
+ * Every function blocks until done. Failed calls record their error, read with
+ * vsixharvester_last_error() on the same thread.
+ */
+#ifndef VSIXHARVESTER_H
+#define VSIXHARVESTER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Resolve the version of an extension ("publisher.name") to download.
+ * target_platform (e.g. "linux-x64") and engine_version may be NULL.
+ * Returns the version, to release with vsixharvester_string_free(), or NULL on failure.
+ */
+char *vsixharvester_resolve_version(const char *id, const char *target_platform,
+                                    const char *engine_version);
+
+/*
+ * Download the latest compatible version of an extension to file_path.
+ * target_platform and engine_version may be NULL.
+ * Returns 0 on success, -1 on failure.
+ */
+int vsixharvester_download(const char *id, const char *target_platform,
+                           const char *engine_version, const char *file_path);
+
+/*
+ * Error message of the last failed call of the thread, NULL if none.
+ * Owned by the library, valid until the next failed call of the thread.
+ */
+const char *vsixharvester_last_error(void);
+
+/* Release a string returned by the library. */
+void vsixharvester_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VSIXHARVESTER_H */
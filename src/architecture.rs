@@ -1,6 +1,8 @@
 use crate::error::VsixHarvesterError;
-use crate::Extensions;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -14,22 +16,37 @@ pub enum Architecture {
     Universal,
 }
 
+/// The built-in architectures with their platform field name and target platform identifier
+const ARCHITECTURES: [(Architecture, &str, Option<&str>); 7] = [
+    (Architecture::Universal, "universal", None),
+    (Architecture::LinuxX64, "linux_x64", Some("linux-x64")),
+    (Architecture::LinuxArm64, "linux_arm64", Some("linux-arm64")),
+    (Architecture::DarwinX64, "darwin_x64", Some("darwin-x64")),
+    (
+        Architecture::DarwinArm64,
+        "darwin_arm64",
+        Some("darwin-arm64"),
+    ),
+    (Architecture::Win32X64, "win32_x64", Some("win32-x64")),
+    (Architecture::Win32Arm64, "win32_arm64", Some("win32-arm64")),
+];
+
 impl Architecture {
+    /// Get the row of the architecture in the table of the built-in architectures
+    fn row(self) -> &'static (Architecture, &'static str, Option<&'static str>) {
+        ARCHITECTURES
+            .iter()
+            .find(|(architecture, _, _)| *architecture == self)
+            .expect("every architecture is in the table")
+    }
+
     /// Get the target platform identifier for a specific architecture
     ///
     /// # Returns
     ///
     /// An Option containing the target platform identifier or None if the architecture is universal
-    pub fn to_target_platform(&self) -> Option<&'static str> {
-        match self {
-            Self::LinuxX64 => Some("linux-x64"),
-            Self::LinuxArm64 => Some("linux-arm64"),
-            Self::DarwinX64 => Some("darwin-x64"),
-            Self::DarwinArm64 => Some("darwin-arm64"),
-            Self::Win32X64 => Some("win32-x64"),
-            Self::Win32Arm64 => Some("win32-arm64"),
-            Self::Universal => None,
-        }
+    pub fn to_target_platform(self) -> Option<&'static str> {
+        self.row().2
     }
 
     /// Get the platform field name for a specific architecture
@@ -37,16 +54,24 @@ impl Architecture {
     /// # Returns
     ///
     /// The platform field name
-    pub fn to_field_name(&self) -> &'static str {
-        match self {
-            Self::LinuxX64 => "linux_x64",
-            Self::LinuxArm64 => "linux_arm64",
-            Self::DarwinX64 => "darwin_x64",
-            Self::DarwinArm64 => "darwin_arm64",
-            Self::Win32X64 => "win32_x64",
-            Self::Win32Arm64 => "win32_arm64",
-            Self::Universal => "universal",
-        }
+    pub fn to_field_name(self) -> &'static str {
+        self.row().1
+    }
+
+    /// Get the architecture of a target platform identifier
+    ///
+    /// # Arguments
+    ///
+    /// * `target_platform` - The target platform identifier (e.g., "linux-x64")
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the architecture or None if the target platform is not supported
+    pub fn from_target_platform(target_platform: &str) -> Option<Self> {
+        ARCHITECTURES
+            .iter()
+            .find(|(_, _, candidate)| *candidate == Some(target_platform))
+            .map(|(architecture, _, _)| *architecture)
     }
 
     /// Get all available architectures
@@ -54,60 +79,29 @@ impl Architecture {
     /// # Returns
     ///
     /// A vector of all available architectures
-    #[allow(dead_code)]
     pub fn all() -> Vec<Self> {
-        vec![
-            Self::Universal,
-            Self::LinuxX64,
-            Self::LinuxArm64,
-            Self::DarwinX64,
-            Self::DarwinArm64,
-            Self::Win32X64,
-            Self::Win32Arm64,
-        ]
+        ARCHITECTURES
+            .iter()
+            .map(|(architecture, _, _)| *architecture)
+            .collect()
     }
 
-    /// Get available architectures with their target platform identifiers
+    /// Get the architecture of the running host
     ///
     /// # Returns
     ///
-    /// An array of tuples containing the platform field name and the target platform identifier
-    pub fn available_architectures() -> [(&'static str, Option<&'static str>); 7] {
-        [
-            ("universal", None),
-            ("linux_x64", Some("linux-x64")),
-            ("linux_arm64", Some("linux-arm64")),
-            ("darwin_x64", Some("darwin-x64")),
-            ("darwin_arm64", Some("darwin-arm64")),
-            ("win32_x64", Some("win32-x64")),
-            ("win32_arm64", Some("win32-arm64")),
-        ]
+    /// An Option containing the architecture or None if the host has no built-in architecture
+    pub fn host() -> Option<Self> {
+        host_target_platform()?.architecture()
     }
 
-    /// Get extensions list for a specific platform
-    ///
-    /// # Arguments
-    ///
-    /// * `platform_field` - The platform field name (e.g., "linux_x64")
-    /// * `extensions` - The extensions structure
+    /// Get the typed target platform of the architecture
     ///
     /// # Returns
     ///
-    /// An Option containing a reference to the extensions list for the given platform
-    pub fn get_extensions_list<'a>(
-        platform_field: &str,
-        extensions: &'a Extensions,
-    ) -> Option<&'a Vec<String>> {
-        match platform_field {
-            "universal" => extensions.universal.as_ref(),
-            "linux_x64" => extensions.linux_x64.as_ref(),
-            "linux_arm64" => extensions.linux_arm64.as_ref(),
-            "darwin_x64" => extensions.darwin_x64.as_ref(),
-            "darwin_arm64" => extensions.darwin_arm64.as_ref(),
-            "win32_x64" => extensions.win32_x64.as_ref(),
-            "win32_arm64" => extensions.win32_arm64.as_ref(),
-            _ => None,
-        }
+    /// An Option containing the target platform or None if the architecture is universal
+    pub fn target_platform(self) -> Option<TargetPlatform> {
+        TargetPlatform::try_from(self).ok()
     }
 
     /// Get the architecture from a CLI argument
@@ -124,25 +118,619 @@ impl Architecture {
     }
 }
 
-impl FromStr for Architecture {
+/// The marketplace target platform of a package, None (universal) being modeled as `Option`
+///
+/// The identifiers of the built-in architectures are parsed to their architecture, the
+/// other ones (e.g., "alpine-x64", "web") are kept as custom identifiers. Target platforms
+/// are serialized, compared and sorted as their identifier.
+#[derive(Debug, Clone)]
+pub struct TargetPlatform(TargetPlatformKind);
+
+/// The representation of a target platform, private so a built-in one is never universal
+#[derive(Debug, Clone)]
+enum TargetPlatformKind {
+    /// The target platform of a built-in architecture, never universal
+    Builtin(Architecture),
+    /// A target platform without built-in architecture (e.g., "alpine-x64")
+    Custom(String),
+}
+
+impl TargetPlatform {
+    /// Create a target platform from its identifier
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier` - The target platform identifier (e.g., "linux-x64")
+    pub fn new(identifier: &str) -> Self {
+        Self(match Architecture::from_target_platform(identifier) {
+            Some(architecture) => TargetPlatformKind::Builtin(architecture),
+            None => TargetPlatformKind::Custom(identifier.to_string()),
+        })
+    }
+
+    /// Get the target platform identifier (e.g., "linux-x64")
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            TargetPlatformKind::Builtin(architecture) => architecture
+                .to_target_platform()
+                .expect("a built-in target platform is never universal"),
+            TargetPlatformKind::Custom(identifier) => identifier,
+        }
+    }
+
+    /// Get the built-in architecture of the target platform
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the architecture or None for a custom target platform
+    pub fn architecture(&self) -> Option<Architecture> {
+        match &self.0 {
+            TargetPlatformKind::Builtin(architecture) => Some(*architecture),
+            TargetPlatformKind::Custom(_) => None,
+        }
+    }
+}
+
+/// The universal architecture has no target platform, it is refused
+impl TryFrom<Architecture> for TargetPlatform {
+    type Error = VsixHarvesterError;
+
+    fn try_from(architecture: Architecture) -> std::result::Result<Self, Self::Error> {
+        match architecture.to_target_platform() {
+            Some(_) => Ok(Self(TargetPlatformKind::Builtin(architecture))),
+            None => Err(VsixHarvesterError::InvalidArchitecture(
+                architecture.to_string(),
+            )),
+        }
+    }
+}
+
+impl From<&str> for TargetPlatform {
+    fn from(identifier: &str) -> Self {
+        Self::new(identifier)
+    }
+}
+
+/// Parse a target platform identifier of the command line, "universal" and empty
+/// identifiers are refused since universal packages have no target platform
+impl FromStr for TargetPlatform {
     type Err = VsixHarvesterError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "linux_x64" => Ok(Self::LinuxX64),
-            "linux_arm64" => Ok(Self::LinuxArm64),
-            "darwin_x64" => Ok(Self::DarwinX64),
-            "darwin_arm64" => Ok(Self::DarwinArm64),
-            "win32_x64" => Ok(Self::Win32X64),
-            "win32_arm64" => Ok(Self::Win32Arm64),
-            "universal" => Ok(Self::Universal),
+        let identifier = s.trim();
+        if identifier.is_empty() || identifier == Architecture::Universal.to_field_name() {
+            return Err(VsixHarvesterError::InvalidArchitecture(s.to_string()));
+        }
+        Ok(Self::new(identifier))
+    }
+}
+
+impl fmt::Display for TargetPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for TargetPlatform {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for TargetPlatform {}
+
+impl PartialEq<str> for TargetPlatform {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for TargetPlatform {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Hash for TargetPlatform {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialOrd for TargetPlatform {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TargetPlatform {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// Target platforms are serialized as their identifier (e.g., "linux-x64")
+impl Serialize for TargetPlatform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TargetPlatform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(Self::new(&String::deserialize(deserializer)?))
+    }
+}
+
+/// Get the name of a target platform in the reports
+///
+/// # Returns
+///
+/// The target platform identifier, "universal" for universal packages
+pub fn target_platform_name(target_platform: Option<&TargetPlatform>) -> &str {
+    target_platform.map_or(
+        Architecture::Universal.to_field_name(),
+        TargetPlatform::as_str,
+    )
+}
+
+/// Join target platform identifiers with a separator (e.g., "linux-x64, web")
+pub fn join_target_platforms<'a>(
+    target_platforms: impl IntoIterator<Item = &'a TargetPlatform>,
+    separator: &str,
+) -> String {
+    target_platforms
+        .into_iter()
+        .map(TargetPlatform::as_str)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Get the target platform of the running host, from its operating system and CPU
+///
+/// # Returns
+///
+/// The target platform (e.g., "linux-x64"), None for unknown hosts
+pub fn host_target_platform() -> Option<TargetPlatform> {
+    let identifier = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => "linux-x64",
+        ("linux", "aarch64") => "linux-arm64",
+        ("linux", "arm") => "linux-armhf",
+        ("macos", "x86_64") => "darwin-x64",
+        ("macos", "aarch64") => "darwin-arm64",
+        ("windows", "x86_64") => "win32-x64",
+        ("windows", "aarch64") => "win32-arm64",
+        _ => return None,
+    };
+    Some(TargetPlatform::new(identifier))
+}
+
+/// Normalize an architecture to a target platform identifier
+///
+/// The separator may be `-` or `_`, and the usual names of the operating systems
+/// and CPUs are translated (e.g., "macos_aarch64" to "darwin-arm64").
+///
+/// # Arguments
+///
+/// * `arch` - The architecture (e.g., "linux_x64", "linux-arm64", "windows-amd64")
+///
+/// # Returns
+///
+/// The target platform, None for universal packages
+pub fn normalize_target_platform(arch: &str) -> Option<TargetPlatform> {
+    let arch = arch.trim().to_lowercase().replace('_', "-");
+    if arch.is_empty() || arch == "universal" {
+        return None;
+    }
+    let Some((os, cpu)) = arch.split_once('-') else {
+        return Some(TargetPlatform::new(&arch));
+    };
+    let os = match os {
+        "windows" | "win" => "win32",
+        "macos" | "mac" | "osx" => "darwin",
+        os => os,
+    };
+    let cpu = match cpu {
+        "amd64" | "x86-64" => "x64",
+        "aarch64" => "arm64",
+        "arm" | "armv7" | "armv7l" => "armhf",
+        "x86" | "i386" | "i686" => "ia32",
+        cpu => cpu,
+    };
+    Some(TargetPlatform::new(&format!("{}-{}", os, cpu)))
+}
+
+/// A platform selected on the command line, universal or a target platform
+///
+/// It is parsed by `normalize_target_platform`, so the built-in field names (e.g.,
+/// "linux_x64"), the target platform identifiers (e.g., "alpine-arm64") and the
+/// usual aliases (e.g., "macos-aarch64") are accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlatformSelector {
+    /// The universal packages
+    Universal,
+    /// The packages of a target platform
+    Target(TargetPlatform),
+}
+
+impl PlatformSelector {
+    /// Get the selected target platform, None for universal packages
+    pub fn target_platform(&self) -> Option<&TargetPlatform> {
+        match self {
+            Self::Universal => None,
+            Self::Target(target_platform) => Some(target_platform),
+        }
+    }
+}
+
+impl From<Option<TargetPlatform>> for PlatformSelector {
+    fn from(target_platform: Option<TargetPlatform>) -> Self {
+        target_platform.map_or(Self::Universal, Self::Target)
+    }
+}
+
+impl FromStr for PlatformSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(normalize_target_platform(s).into())
+    }
+}
+
+impl fmt::Display for PlatformSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(target_platform_name(self.target_platform()))
+    }
+}
+
+/// A platform section of the manifest and the target platform of its packages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    /// The platform field name of the manifest (e.g., "linux_x64")
+    pub field_name: String,
+    /// The marketplace target platform, None for universal packages
+    pub target_platform: Option<TargetPlatform>,
+}
+
+impl FromStr for Platform {
+    type Err = VsixHarvesterError;
+
+    /// Parse a custom platform, `field_name=target-platform` (e.g., "alpine_x64=alpine-x64")
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((field_name, target_platform))
+                if !field_name.trim().is_empty() && !target_platform.trim().is_empty() =>
+            {
+                Ok(Self {
+                    field_name: field_name.trim().to_string(),
+                    target_platform: Some(TargetPlatform::new(target_platform.trim())),
+                })
+            }
             _ => Err(VsixHarvesterError::InvalidArchitecture(s.to_string())),
         }
     }
 }
 
+/// The platform sections of a manifest, the built-in architectures and the custom platforms
+#[derive(Debug, Clone)]
+pub struct PlatformRegistry {
+    platforms: Vec<Platform>,
+}
+
+impl Default for PlatformRegistry {
+    fn default() -> Self {
+        Self {
+            platforms: Architecture::all()
+                .into_iter()
+                .map(|architecture| Platform {
+                    field_name: architecture.to_field_name().to_string(),
+                    target_platform: architecture.target_platform(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PlatformRegistry {
+    /// Create the registry of the built-in architectures extended by custom platforms
+    ///
+    /// # Arguments
+    ///
+    /// * `custom` - The custom platforms (e.g., "alpine_x64=alpine-x64")
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the registry or an error if a custom platform is invalid
+    pub fn with_custom(custom: &[Platform]) -> Result<Self, VsixHarvesterError> {
+        let mut registry = Self::default();
+        for platform in custom {
+            registry.register(platform.clone())?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a platform section
+    ///
+    /// # Arguments
+    ///
+    /// * `platform` - The platform, its field name must not be registered yet
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error if the field name is already registered
+    pub fn register(&mut self, platform: Platform) -> Result<(), VsixHarvesterError> {
+        if self.contains(&platform.field_name) {
+            return Err(VsixHarvesterError::InvalidArchitecture(format!(
+                "{} is already a platform",
+                platform.field_name
+            )));
+        }
+        self.platforms.push(platform);
+        Ok(())
+    }
+
+    /// Get the platforms in the processing order
+    pub fn platforms(&self) -> &[Platform] {
+        &self.platforms
+    }
+
+    /// Check if a platform field name is registered
+    pub fn contains(&self, field_name: &str) -> bool {
+        self.platforms
+            .iter()
+            .any(|platform| platform.field_name == field_name)
+    }
+
+    /// Get the field names of the registered platforms among target platforms
+    ///
+    /// # Arguments
+    ///
+    /// * `target_platforms` - The target platforms (e.g., ["linux-x64", "web"])
+    ///
+    /// # Returns
+    ///
+    /// The field names of the registered ones, in the processing order
+    pub fn field_names_of(&self, target_platforms: &[&TargetPlatform]) -> Vec<&str> {
+        self.platforms
+            .iter()
+            .filter(|platform| {
+                platform
+                    .target_platform
+                    .as_ref()
+                    .is_some_and(|target_platform| target_platforms.contains(&target_platform))
+            })
+            .map(|platform| platform.field_name.as_str())
+            .collect()
+    }
+}
+
+/// Selection of the platform sections of a manifest processed by a run
+#[derive(Debug, Clone, Default)]
+pub struct PlatformFilter {
+    /// Only these platform field names are processed, all of them when empty
+    pub only: Vec<String>,
+    /// These platform field names are never processed
+    pub skip: Vec<String>,
+}
+
+impl PlatformFilter {
+    /// Create a filter from platforms of the command line
+    ///
+    /// # Arguments
+    ///
+    /// * `only` - The platforms to process, all of them when empty
+    /// * `skip` - The platforms to skip
+    /// * `registry` - The known platforms
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the filter or an error if a platform is not registered
+    pub fn new(
+        only: &[PlatformSelector],
+        skip: &[PlatformSelector],
+        registry: &PlatformRegistry,
+    ) -> Result<Self, VsixHarvesterError> {
+        let field_names = |platforms: &[PlatformSelector]| {
+            let mut field_names = Vec::new();
+            for platform in platforms {
+                match platform.target_platform() {
+                    None => field_names.push(Architecture::Universal.to_field_name().to_string()),
+                    Some(target_platform) => {
+                        let registered = registry.field_names_of(&[target_platform]);
+                        if registered.is_empty() {
+                            return Err(VsixHarvesterError::InvalidArchitecture(
+                                target_platform.to_string(),
+                            ));
+                        }
+                        field_names.extend(registered.into_iter().map(str::to_string));
+                    }
+                }
+            }
+            Ok(field_names)
+        };
+        Ok(Self {
+            only: field_names(only)?,
+            skip: field_names(skip)?,
+        })
+    }
+
+    /// Check if the section of a platform is processed
+    pub fn matches(&self, field_name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|only| only == field_name))
+            && !self.skip.iter().any(|skip| skip == field_name)
+    }
+}
+
+impl FromStr for Architecture {
+    type Err = VsixHarvesterError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ARCHITECTURES
+            .iter()
+            .find(|(_, field_name, _)| *field_name == s)
+            .map(|(architecture, _, _)| *architecture)
+            .ok_or_else(|| VsixHarvesterError::InvalidArchitecture(s.to_string()))
+    }
+}
+
 impl fmt::Display for Architecture {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.to_field_name())
     }
 }
+
+/// Architectures are serialized as their platform field name (e.g., "linux_x64")
+impl Serialize for Architecture {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.to_field_name())
+    }
+}
+
+/// Architectures are deserialized from their platform field name (e.g., "linux_x64")
+impl<'de> Deserialize<'de> for Architecture {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let field_name = String::deserialize(deserializer)?;
+        field_name.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_architecture_serde() {
+        let architectures: Vec<Architecture> =
+            serde_json::from_str(r#"["linux_x64", "universal"]"#).unwrap();
+        assert_eq!(
+            architectures,
+            vec![Architecture::LinuxX64, Architecture::Universal]
+        );
+        assert!(serde_json::from_str::<Architecture>(r#""linux-x64""#).is_err());
+        assert_eq!(
+            serde_json::to_string(&architectures).unwrap(),
+            r#"["linux_x64","universal"]"#
+        );
+    }
+
+    #[test]
+    fn test_target_platform() {
+        let linux: TargetPlatform = "linux-x64".into();
+        assert_eq!(linux.architecture(), Some(Architecture::LinuxX64));
+        assert_eq!(
+            Architecture::LinuxX64.target_platform(),
+            Some(linux.clone())
+        );
+        assert_eq!(Architecture::Universal.target_platform(), None);
+        assert!(TargetPlatform::try_from(Architecture::Universal).is_err());
+        assert_eq!("linux-x64".parse::<TargetPlatform>().unwrap(), linux);
+        assert!("universal".parse::<TargetPlatform>().is_err());
+        assert!("".parse::<TargetPlatform>().is_err());
+        let alpine = TargetPlatform::new("alpine-x64");
+        assert_eq!(alpine.as_str(), "alpine-x64");
+        assert_eq!(alpine.architecture(), None);
+        assert!(alpine < linux);
+
+        // Target platforms keep the identifiers of the JSON files
+        let target_platforms: Vec<Option<TargetPlatform>> =
+            serde_json::from_str(r#"["darwin-arm64", "web", null]"#).unwrap();
+        assert_eq!(
+            target_platforms,
+            vec![Some("darwin-arm64".into()), Some("web".into()), None]
+        );
+        assert_eq!(
+            target_platforms[0].as_ref().unwrap().architecture(),
+            Some(Architecture::DarwinArm64)
+        );
+        assert_eq!(
+            serde_json::to_string(&target_platforms).unwrap(),
+            r#"["darwin-arm64","web",null]"#
+        );
+        assert_eq!(target_platform_name(None), "universal");
+        assert_eq!(
+            join_target_platforms(target_platforms.iter().flatten(), ", "),
+            "darwin-arm64, web"
+        );
+    }
+
+    #[test]
+    fn test_host_architecture() {
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(Architecture::host(), Some(Architecture::LinuxX64));
+        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+        assert_eq!(Architecture::host(), Some(Architecture::DarwinArm64));
+        if let Some(host) = host_target_platform() {
+            assert_eq!(normalize_target_platform(host.as_str()), Some(host));
+        }
+        // The host is never universal, its builds are the ones of its target platform
+        if let Some(host) = Architecture::host() {
+            assert_eq!(host.target_platform(), host_target_platform());
+        }
+    }
+
+    #[test]
+    fn test_platform_filter() {
+        let registry = PlatformRegistry::default();
+        let selectors = |platforms: &[&str]| -> Vec<PlatformSelector> {
+            platforms
+                .iter()
+                .map(|platform| platform.parse().unwrap())
+                .collect()
+        };
+        let filter = PlatformFilter::new(
+            &selectors(&["linux_x64", "universal"]),
+            &selectors(&["universal"]),
+            &registry,
+        )
+        .unwrap();
+        assert!(filter.matches("linux_x64"));
+        assert!(!filter.matches("universal"));
+        assert!(!filter.matches("win32_arm64"));
+
+        // Target platform identifiers and aliases select the same sections
+        let filter = PlatformFilter::new(&[], &selectors(&["win32-arm64"]), &registry).unwrap();
+        assert!(filter.matches("darwin_arm64"));
+        assert!(!filter.matches("win32_arm64"));
+        let filter = PlatformFilter::new(&selectors(&["macos_aarch64"]), &[], &registry).unwrap();
+        assert!(filter.matches("darwin_arm64"));
+
+        assert!(PlatformFilter::new(&selectors(&["alpine-x64"]), &[], &registry).is_err());
+    }
+
+    #[test]
+    fn test_platform_registry() {
+        for architecture in Architecture::all() {
+            assert_eq!(
+                architecture
+                    .to_field_name()
+                    .parse::<Architecture>()
+                    .unwrap(),
+                architecture
+            );
+            if let Some(target_platform) = architecture.to_target_platform() {
+                assert_eq!(
+                    Architecture::from_target_platform(target_platform),
+                    Some(architecture)
+                );
+            }
+        }
+
+        let alpine: Platform = "alpine_x64=alpine-x64".parse().unwrap();
+        let registry = PlatformRegistry::with_custom(&[alpine]).unwrap();
+        assert_eq!(registry.platforms().len(), 8);
+        assert_eq!(
+            registry.platforms()[7],
+            Platform {
+                field_name: "alpine_x64".to_string(),
+                target_platform: Some("alpine-x64".into()),
+            }
+        );
+        assert_eq!(
+            registry.field_names_of(&[&"alpine-x64".into(), &"web".into(), &"linux-x64".into()]),
+            vec!["linux_x64", "alpine_x64"]
+        );
+        let linux: Platform = "linux_x64=linux-x64".parse().unwrap();
+        assert!(PlatformRegistry::with_custom(&[linux]).is_err());
+        assert!("alpine_x64".parse::<Platform>().is_err());
+    }
+}
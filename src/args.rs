@@ -1,39 +1,104 @@
-use crate::config::{DEFAULT_FILE_NAME, DEFAULT_PATH, VERSION};
+use crate::architecture::{Architecture, Platform, PlatformSelector, TargetPlatform};
+use crate::budget::SizeBudgetPolicy;
+use crate::checksums::ChecksumOutputs;
+use crate::compression::StoreCompression;
+use crate::config::{
+    DEFAULT_FILE_NAME, DEFAULT_PATH, DEFAULT_PUBLISH_MESSAGE, MAX_CONCURRENT_DOWNLOADS,
+    POLITE_CONCURRENT_DOWNLOADS, QUERY_WINDOW_SIZE, RETRY_DELAY_MS, VERSION,
+};
+use crate::curate::CurationSort;
+use crate::destination::Destination;
+use crate::diff::DiffFormat;
+use crate::error::Result;
+use crate::export::ExportFormat;
+use crate::hash::HashAlgorithm;
+use crate::install_script::InstallScript;
+use crate::lockfile::lockfile_path;
+use crate::marketplace::{
+    DownloadOptions, NoCompatiblePolicy, SourcePreference, VersionPreference,
+};
+use crate::naming::NamingPreset;
+use crate::paths::{parse_destination, parse_path};
+use crate::policy::{HarvestPolicy, PolicyMode};
+use crate::pool::HarvestProgress;
+use crate::provenance::ProvenanceStorage;
+use crate::registry::RegistrySource;
+use crate::sbom::SbomFormat;
+use crate::search::SearchSort;
+use crate::signature::SignaturePolicy;
+use crate::storage::LocalStorage;
+use crate::timezone::ReportTimezone;
+use chrono::NaiveDate;
 pub use clap::Parser;
+use clap::Subcommand;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
-#[derive(Parser)]
+#[derive(Parser, Default, Clone)]
 #[command(
     version = VERSION,
     about = "Download VSCode extensions for offline use"
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to extensions.json
-    #[arg(short, long, default_value_t = format!("./{}", DEFAULT_FILE_NAME), env = "EXTENSIONS_FILE")]
+    #[arg(short, long, default_value_t = format!("./{}", DEFAULT_FILE_NAME), env = "EXTENSIONS_FILE", value_parser = parse_path)]
     pub input: String,
 
-    /// Output directory
-    #[arg(short, long, default_value_t = format!("./{}", DEFAULT_PATH), env = "OUTPUT_DIR")]
-    pub destination: String,
+    /// Output directory, may be repeated to also copy the packages to other directories
+    /// ('platform=directory' only receives the packages of that platform)
+    #[arg(short, long, default_values_t = [format!("./{}", DEFAULT_PATH)], env = "OUTPUT_DIR", value_parser = parse_destination)]
+    pub destination: Vec<String>,
 
     /// Force redownload if exists
     #[arg(long, default_value = "false", env = "NO_CACHE")]
     pub no_cache: bool,
 
+    /// Skip the resolution of the extensions not updated in the marketplace since their packages were last downloaded
+    #[arg(long, default_value = "false", env = "SINCE_LAST_RUN")]
+    pub since_last_run: bool,
+
     /// Specify proxy url
     #[arg(long, env = "PROXY")]
     pub proxy: Option<String>,
 
+    /// Credentials of the proxy: `keyring:<service>[/<account>]`, `env:<NAME>` or `username:password`
+    #[arg(long, env = "PROXY_CREDENTIALS", requires = "proxy")]
+    pub proxy_credentials: Option<String>,
+
     /// Show verbose infomation
     #[arg(short, long, default_value = "false", env = "VERBOSE")]
     pub verbose: bool,
 
-    /// Download a single extension (e.g., 'golang.Go')
+    /// Do not draw the progress bars of the downloads on a terminal
+    #[arg(long, default_value = "false", env = "NO_PROGRESS")]
+    pub no_progress: bool,
+
+    /// Query the smallest marketplace payloads (no statistics, files or optional includes) and never dump responses
+    #[arg(long, default_value = "false", env = "MINIMAL_REQUESTS")]
+    pub minimal_requests: bool,
+
+    /// Download a single extension (e.g., 'golang.Go' or its Marketplace extension GUID)
     #[arg(short = 'D', long = "download", env = "DOWNLOAD")]
     pub download: Option<String>,
 
-    /// Architecture for single extension download (e.g., 'linux_x64', 'darwin_arm64')
+    /// Architecture for single extension download (e.g., 'linux_x64', 'darwin_arm64', 'universal'), the host one by default
     #[arg(short, long, env = "ARCH")]
-    pub arch: Option<String>,
+    pub arch: Option<PlatformSelector>,
+
+    /// Download the package of every architecture the extension publishes, for single extension download
+    #[arg(
+        long,
+        default_value = "false",
+        env = "ALL_ARCHS",
+        conflicts_with = "arch"
+    )]
+    pub all_archs: bool,
 
     /// Engine version to be compatible with
     #[arg(short, long, env)]
@@ -50,4 +115,732 @@ pub struct Args {
     /// Allow or not downloading extensions marked as "pre-release"
     #[arg(long, default_value = "false", env = "ALLOW_PRE_RELEASE")]
     pub allow_pre_release: bool,
+
+    /// Record the SHA256 of downloaded versions in the lockfile and refuse changed content
+    #[arg(long, default_value = "false", env = "PIN_HASHES")]
+    pub pin_hashes: bool,
+
+    /// Download exactly the versions of the lockfile, failing if the resolution differs
+    #[arg(
+        long,
+        default_value = "false",
+        env = "LOCKED",
+        conflicts_with = "upgrade_only"
+    )]
+    pub locked: bool,
+
+    /// Accept the differences between the manifest and the lockfile of --locked runs
+    /// without asking, updating only the affected lockfile entries
+    #[arg(long, default_value = "false", env = "ACCEPT_NEW", requires = "locked")]
+    pub accept_new: bool,
+
+    /// Also download the extensions listed in the extensionDependencies of the downloaded packages, recursively
+    #[arg(long, default_value = "false", env = "WITH_DEPENDENCIES")]
+    pub with_dependencies: bool,
+
+    /// Also download the members of the extension packs, recursively
+    #[arg(long, default_value = "false", env = "EXPAND_PACKS")]
+    pub expand_packs: bool,
+
+    /// Resolve the versions and print the changes to the mirror without downloading anything
+    #[arg(
+        long,
+        default_value = "false",
+        env = "DRY_RUN",
+        conflicts_with = "download"
+    )]
+    pub dry_run: bool,
+
+    /// Format of the changes printed by --dry-run
+    #[arg(long, value_enum, default_value_t = DiffFormat::Text, env = "DIFF_FORMAT")]
+    pub diff_format: DiffFormat,
+
+    /// Path of the lockfile, defaults to the input file with a .lock extension
+    #[arg(long, env = "LOCKFILE", value_parser = parse_path)]
+    pub lockfile: Option<String>,
+
+    /// Keep the locked versions except the entries matching these filters
+    /// (`publisher.name`, `publisher.*` or `older-than:<N>d`)
+    #[arg(long, value_delimiter = ',', env = "UPGRADE_ONLY")]
+    pub upgrade_only: Vec<String>,
+
+    /// Maximum total size of the downloads (e.g., '10G', '500M')
+    #[arg(long, env = "MAX_TOTAL_SIZE")]
+    pub max_total_size: Option<String>,
+
+    /// What to do when the downloads exceed --max-total-size
+    #[arg(long, value_enum, default_value_t = SizeBudgetPolicy::Fail, env = "SIZE_BUDGET_POLICY")]
+    pub size_budget_policy: SizeBudgetPolicy,
+
+    /// Also store normalized uncompressed copies of the packages for rsync/zsync delta transfers
+    #[arg(long, default_value = "false", env = "DELTA_COPIES")]
+    pub delta_copies: bool,
+
+    /// Also save the extension/package.json of each package as publisher.name-version.manifest.json
+    #[arg(long, default_value = "false", env = "SAVE_MANIFEST")]
+    pub save_manifest: bool,
+
+    /// Also store a compressed copy of each package (e.g., 'zstd')
+    #[arg(long, value_enum, env = "STORE_COMPRESSED")]
+    pub store_compressed: Option<StoreCompression>,
+
+    /// Where to download the pinned packages from
+    #[arg(long, value_enum, default_value_t = SourcePreference::Marketplace, requires_if("store", "store_url"), env = "PREFER_SOURCE")]
+    pub prefer_source: SourcePreference,
+
+    /// Base URL of an S3-compatible store of previously harvested packages
+    /// (e.g., `https://s3.example.com/mirror/extensions`)
+    #[arg(long, env = "STORE_URL")]
+    pub store_url: Option<String>,
+
+    /// Publish the harvest after downloading (e.g., 'oci://registry/repo:tag' or 'git@host:mirror.git')
+    #[arg(long, env = "PUBLISH")]
+    pub publish: Option<String>,
+
+    /// Also commit the packages to a Git publish target, through Git LFS
+    #[arg(long, default_value = "false", env = "PUBLISH_LFS")]
+    pub publish_lfs: bool,
+
+    /// Commit message of a Git publish target ({date}, {packages} and {input} are replaced)
+    #[arg(long, default_value = DEFAULT_PUBLISH_MESSAGE, env = "PUBLISH_MESSAGE")]
+    pub publish_message: String,
+
+    /// Cache the icon of each extension version in the icons directory of the destination
+    #[arg(long, default_value = "false", env = "MIRROR_ICONS")]
+    pub mirror_icons: bool,
+
+    /// Download the universal entries for these platforms instead (e.g., 'linux_x64,win32_x64')
+    #[arg(long, value_delimiter = ',', env = "PLATFORM_DEFAULT")]
+    pub platform_default: Vec<Architecture>,
+
+    /// Move the universal entries which publish platform-specific builds to the platform lists
+    #[arg(long, default_value = "false", env = "FIX_MANIFEST")]
+    pub fix_manifest: bool,
+
+    /// Cap the concurrency at 2, space out the requests and retry throttled ones
+    #[arg(long, default_value = "false", env = "POLITE")]
+    pub polite: bool,
+
+    /// Retry a request failing with a 5xx, 429, connection error or reset this number of times
+    #[arg(long, default_value_t = 0, env = "RETRIES")]
+    pub retries: u32,
+
+    /// Milliseconds to wait before the first retry of a request, doubled at each retry with jitter
+    #[arg(long, value_name = "MS", default_value_t = RETRY_DELAY_MS, env = "RETRY_DELAY")]
+    pub retry_delay: u64,
+
+    /// Number of extensions per batched metadata query of large manifests, 0 to query them one by one
+    #[arg(long, value_name = "N", default_value_t = QUERY_WINDOW_SIZE, env = "QUERY_WINDOW")]
+    pub query_window: usize,
+
+    /// Milliseconds to wait between two batched metadata queries
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        env = "QUERY_WINDOW_DELAY"
+    )]
+    pub query_window_delay: u64,
+
+    /// Additional platform sections of the manifest with their target platform
+    /// (e.g., 'alpine_x64=alpine-x64,linux_armhf=linux-armhf')
+    #[arg(long, value_delimiter = ',', env = "CUSTOM_PLATFORMS")]
+    pub custom_platforms: Vec<Platform>,
+
+    /// Only process these platform sections of the manifest (e.g., 'linux_x64,universal')
+    #[arg(long, value_delimiter = ',', env = "ONLY_PLATFORMS")]
+    pub only_platforms: Vec<PlatformSelector>,
+
+    /// Skip these platform sections of the manifest (e.g., 'win32_arm64')
+    #[arg(long, value_delimiter = ',', env = "SKIP_PLATFORMS")]
+    pub skip_platforms: Vec<PlatformSelector>,
+
+    /// Write the popularity drift of the extensions since the previous harvest to this JSON file
+    #[arg(long, env = "DRIFT_REPORT", value_parser = parse_path)]
+    pub drift_report: Option<String>,
+
+    /// What to do when no version of an extension is compatible with --engine-version
+    #[arg(long, value_enum, default_value_t = NoCompatiblePolicy::Latest, env = "ON_NO_COMPATIBLE")]
+    pub on_no_compatible: NoCompatiblePolicy,
+
+    /// How to pick among the versions equally compatible with --engine-version and the update policy
+    #[arg(long, value_enum, default_value_t = VersionPreference::ReleaseDate, env = "PREFER")]
+    pub prefer: VersionPreference,
+
+    /// Check the downloaded packages against the digests of their Marketplace signature manifest,
+    /// refusing mismatches (the PKCS#7 signature itself is not validated)
+    #[arg(long, default_value = "false", env = "VERIFY_SIGNATURES")]
+    pub verify_signatures: bool,
+
+    /// Refuse the packages without a Marketplace signature (implies --verify-signatures)
+    #[arg(long, default_value = "false", env = "REQUIRE_SIGNATURE")]
+    pub require_signature: bool,
+
+    /// Write a SHA256SUMS file listing every package of the destination
+    #[arg(long, default_value = "false", env = "CHECKSUMS")]
+    pub checksums: bool,
+
+    /// Write a .sha256 file next to every package of the destination
+    #[arg(long, default_value = "false", env = "CHECKSUM_SIDECARS")]
+    pub checksum_sidecars: bool,
+
+    /// Write a cosign-compatible attestation predicate of the packages of the destination
+    #[arg(long, default_value = "false", env = "ATTESTATION")]
+    pub attestation: bool,
+
+    /// JSON policy file of rules the resolved packages must follow (publishers, verified domain, size, licenses, pre-releases, age)
+    #[arg(long, env = "POLICY", value_parser = parse_path)]
+    pub policy: Option<String>,
+
+    /// Fail the harvest on any violation of the policy, the default
+    #[arg(
+        long,
+        default_value = "false",
+        env = "POLICY_ENFORCE",
+        group = "policy_mode"
+    )]
+    pub enforce: bool,
+
+    /// Only log the violations of the policy and download the packages anyway
+    #[arg(
+        long,
+        default_value = "false",
+        env = "POLICY_WARN",
+        group = "policy_mode"
+    )]
+    pub warn: bool,
+
+    /// Write an install script of the downloaded packages, dependencies first, in the destination
+    #[arg(long, value_enum, env = "EMIT_INSTALL_SCRIPT")]
+    pub emit_install_script: Option<InstallScript>,
+
+    /// Write a software bill of materials of the packages of the destination
+    #[arg(long, value_enum, env = "SBOM")]
+    pub sbom: Option<SbomFormat>,
+
+    /// Unpack each downloaded package into a publisher.name-version folder of the destination
+    #[arg(long, default_value = "false", env = "UNPACK")]
+    pub unpack: bool,
+
+    /// Timezone of the timestamps of the reports and of the service sync window: UTC, local
+    /// or an IANA name (e.g., 'Europe/Paris'), UTC for reports and local for the window by default
+    #[arg(long, env = "TIMEZONE")]
+    pub timezone: Option<ReportTimezone>,
+
+    /// Hash algorithm of the checksums, the attestation and the lockfile digests
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256, env = "HASH")]
+    pub hash: HashAlgorithm,
+
+    /// Abort a download taking longer than this number of seconds
+    #[arg(long, env = "TASK_TIMEOUT")]
+    pub task_timeout: Option<u64>,
+
+    /// Retry a download failing with a network error or a timeout this number of times
+    #[arg(long, default_value_t = 0, env = "TASK_RETRIES")]
+    pub task_retries: u32,
+
+    /// Speak HTTP/2 without negotiation, for proxies mishandling the upgrade
+    #[arg(long, default_value = "false", env = "HTTP2_PRIOR_KNOWLEDGE")]
+    pub http2_prior_knowledge: bool,
+
+    /// Close pooled connections idle for this number of seconds
+    #[arg(long, env = "POOL_IDLE_TIMEOUT")]
+    pub pool_idle_timeout: Option<u64>,
+
+    /// Send TCP keepalive probes every this number of seconds
+    #[arg(long, env = "TCP_KEEPALIVE")]
+    pub tcp_keepalive: Option<u64>,
+
+    /// Fleet file mapping hostnames or roles to a platform, engine version and group,
+    /// harvested into one sub-directory of the destination each
+    #[arg(long, env = "FLEET", value_parser = parse_path)]
+    pub fleet: Option<String>,
+
+    /// Also harvest the extensions of these collection files, curated sets shared as a unit
+    #[arg(long = "collection", value_delimiter = ',', env = "COLLECTIONS", value_parser = parse_path)]
+    pub collections: Vec<String>,
+
+    /// Also harvest the VS Code language packs of these locales (e.g., 'fr,de,ja')
+    #[arg(long, value_delimiter = ',', env = "LOCALES")]
+    pub locales: Vec<String>,
+
+    /// JSON file mapping renamed extension identifiers to their successor, extending the built-in map
+    #[arg(long, env = "RENAME_MAP", value_parser = parse_path)]
+    pub rename_map: Option<String>,
+
+    /// Harvest the successor of the renamed extensions instead of warning only
+    #[arg(long, default_value = "false", env = "APPLY_RENAMES")]
+    pub apply_renames: bool,
+
+    /// Print the final failure as a JSON object on stderr (code, message, extension, phase, retryable)
+    #[arg(long, default_value = "false", env = "ERRORS_JSON")]
+    pub errors_json: bool,
+
+    /// File naming convention of the packages (harvester: publisher.name-version@platform.vsix)
+    #[arg(long, value_enum, default_value_t = NamingPreset::Harvester, env = "NAMING_PRESET")]
+    pub naming_preset: NamingPreset,
+
+    /// Registry the extensions are queried and downloaded from
+    #[arg(long, value_enum, default_value_t = RegistrySource::Marketplace, env = "REGISTRY")]
+    pub registry: RegistrySource,
+
+    /// Record the source URL, retrieval time and verification of the downloaded packages
+    #[arg(long, value_enum, env = "PROVENANCE")]
+    pub provenance: Option<ProvenanceStorage>,
+
+    /// Also download the VS Code server and CLI archives of the harvested platforms for the engine version
+    #[arg(long, default_value = "false", env = "VSCODE_SERVER")]
+    pub vscode_server: bool,
+
+    /// Also harvest the extensions recommended by the repositories under this directory (.vscode/extensions.json, devcontainer.json)
+    #[arg(long, value_name = "ROOT", env = "SCAN_WORKSPACES", value_parser = parse_path)]
+    pub scan_workspaces: Option<String>,
+
+    /// Run the pipeline against a fixture server at this origin instead of the marketplace (benchmarks)
+    #[arg(long, value_name = "ORIGIN", env = "BENCH_MODE")]
+    pub bench_mode: Option<String>,
+
+    /// Record the marketplace responses of the harvest, with stubs of the packages, in a fixture bundle
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "CAPTURE_FIXTURE",
+        value_parser = parse_path,
+        conflicts_with_all = ["bench_mode", "replay_fixture"]
+    )]
+    pub capture_fixture: Option<String>,
+
+    /// Run the harvest offline against a fixture bundle recorded by --capture-fixture
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "REPLAY_FIXTURE",
+        value_parser = parse_path,
+        conflicts_with = "bench_mode"
+    )]
+    pub replay_fixture: Option<String>,
+
+    /// Token cancelling the harvest, set by embedding applications or on Ctrl-C
+    #[arg(skip)]
+    pub cancellation: CancellationToken,
+
+    /// Counters of the downloaded packages, read by embedding applications
+    #[arg(skip)]
+    pub progress: HarvestProgress,
+
+    /// Variables expanded in the manifest before the environment, set by fleet profiles
+    #[arg(skip)]
+    pub manifest_variables: BTreeMap<String, String>,
+
+    /// Maximum number of concurrent downloads, set by service schedules
+    #[arg(skip)]
+    pub concurrency_limit: Option<usize>,
+
+    /// Never ask on the terminal, set by the service
+    #[arg(skip)]
+    pub no_prompt: bool,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Manage the registries extensions are harvested from
+    Registries {
+        #[command(subcommand)]
+        action: RegistriesCommand,
+    },
+    /// Manage the collection files, curated extension sets shared between organizations
+    Collection {
+        #[command(subcommand)]
+        action: CollectionCommand,
+    },
+    /// Export the lockfile for other tools
+    Export {
+        /// The export format
+        #[arg(value_enum)]
+        format: ExportFormat,
+
+        /// Output file, defaults to a file named after the format in the current directory
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Lay out the packages of the destination directory with JSON indexes for static file hosting
+    ExportMirror {
+        /// Directory of the static mirror
+        #[arg(value_parser = parse_path)]
+        output: String,
+    },
+    /// Unpack the packages of the destination directory as the extensions directory of VS Code Servers
+    ExportServerExtensions {
+        /// Extensions directory to write, to share with the Remote-SSH and tunnels hosts
+        #[arg(value_parser = parse_path)]
+        output: String,
+
+        /// Target platform of the hosts
+        #[arg(long, default_value = "linux-x64")]
+        target_platform: TargetPlatform,
+    },
+    /// Install harvested packages into the local VS Code, dependencies first
+    Install {
+        /// Packages to install, the newest package of every extension of the destination directory by default
+        #[arg(value_parser = parse_path)]
+        files: Vec<String>,
+
+        /// VS Code command running `--install-extension`, searched on the PATH by default
+        #[arg(long, value_parser = parse_path)]
+        code_binary: Option<String>,
+
+        /// Extensions directory to unpack the packages into instead of running VS Code
+        #[arg(long, value_parser = parse_path)]
+        extensions_dir: Option<String>,
+
+        /// Target platform of the packages to select, the one of the host by default
+        #[arg(long)]
+        target_platform: Option<TargetPlatform>,
+    },
+    /// Remove the temporary files and partial downloads left in the destination directory by killed harvests
+    Clean {
+        /// Age from which an unmodified temporary file is a leftover (e.g., '1h', '1d')
+        #[arg(long, default_value = "1d")]
+        max_age: String,
+    },
+    /// Summarize the mirror of the destination directory for dashboards
+    Status {
+        /// Number of days after which a missing latest version makes an extension stale
+        #[arg(long, default_value_t = 30)]
+        stale_days: i64,
+
+        /// Do not query the marketplace for the latest versions
+        #[arg(long, default_value = "false")]
+        offline: bool,
+
+        /// Print JSON instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Compare the packages of the destination directory with the latest marketplace versions
+    Outdated {
+        /// Print JSON instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Summarize the storage used by the destination directory and what pruning would free
+    Stats {
+        /// Number of largest extensions to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Print JSON instead of tables
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Re-check the marketplace state of the mirrored versions (unpublished, removed, not validated)
+    Verify {
+        /// Move the flagged and corrupt packages to the quarantine directory
+        #[arg(long, default_value = "false")]
+        quarantine: bool,
+
+        /// Only check the integrity of the packages, without querying the marketplace
+        #[arg(long, default_value = "false")]
+        offline: bool,
+
+        /// Also check the packages against their digests stored in SHA256SUMS (or SHA512SUMS, B3SUMS), sidecar files or the lockfile
+        #[arg(long, default_value = "false")]
+        checksums: bool,
+
+        /// Print JSON instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Display the recorded provenance of a VSIX file (source URL, retrieval time, verification)
+    Provenance {
+        /// Path of the VSIX file
+        path: String,
+
+        /// Print JSON instead of text
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Remove a version from the mirror, its lockfile entries and the inventory in one operation
+    Yank {
+        /// The version to yank in the format 'publisher.name@version'
+        target: String,
+
+        /// Move the packages to the quarantine directory instead of deleting them
+        #[arg(long, default_value = "false")]
+        quarantine: bool,
+
+        /// The reason recorded with the quarantined packages
+        #[arg(long, default_value = "yanked")]
+        reason: String,
+    },
+    /// Check if a newer compatible version of a VSIX file is available
+    CheckUpdate {
+        /// Path of the VSIX file
+        path: String,
+
+        /// Replace the file by the newer version
+        #[arg(long, default_value = "false")]
+        download: bool,
+    },
+    /// List the marketplace extensions matching statistics criteria, candidates for the mirror
+    Curate {
+        /// Order of the extensions
+        #[arg(long, value_enum, default_value_t = CurationSort::TrendingMonthly)]
+        sort: CurationSort,
+
+        /// Minimum install count of the candidates
+        #[arg(long, default_value_t = 0)]
+        min_installs: u64,
+
+        /// Only the extensions released since this date (e.g., '2025-01-01')
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only the extensions matching this search text
+        #[arg(long)]
+        search: Option<String>,
+
+        /// Maximum number of candidates
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+
+        /// Download the latest universal version of the candidates
+        #[arg(long, default_value = "false")]
+        download: bool,
+
+        /// Print JSON instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Search the marketplace and print the id, version, installs and rating of the extensions found
+    Search {
+        /// The search text (e.g., 'python')
+        text: String,
+
+        /// Only the extensions of this category (e.g., 'Linters', 'Programming Languages')
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Order of the extensions
+        #[arg(long, value_enum, default_value_t = SearchSort::Relevance)]
+        sort: SearchSort,
+
+        /// Maximum number of extensions
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Print JSON instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Alias of the cache step of `serve --prewarm`, without serving: cache the icon, readme,
+    /// changelog and manifest of every package of the mirror
+    Prewarm,
+    /// List every version of an extension with its engine, pre-release flag and dependencies
+    Versions {
+        /// The extension identifier (e.g., 'rust-lang.rust-analyzer')
+        id: String,
+
+        /// Print JSON with every property of the versions instead of a table
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// Serve the harvested packages as an offline gallery for air-gapped VS Code instances
+    Serve {
+        /// Directory of the harvested packages, the destination directory by default
+        #[arg(long, value_parser = parse_path)]
+        dir: Option<String>,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1", env = "GALLERY_HOST")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080, env = "GALLERY_PORT")]
+        port: u16,
+
+        /// Cache the icon, readme, changelog and manifest of every package before serving
+        #[arg(long, default_value = "false", env = "GALLERY_PREWARM")]
+        prewarm: bool,
+    },
+    /// Serve a REST API to submit harvest jobs, follow their progress and fetch their reports
+    Service {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8787", env = "SERVICE_LISTEN")]
+        listen: String,
+
+        /// Bearer token required by the job endpoints, or a reference to it (e.g., 'keyring:vsixharvester-service')
+        #[arg(long, env = "SERVICE_TOKEN")]
+        token: Option<String>,
+
+        /// JSON file of named harvests the service runs periodically
+        #[arg(long, env = "SERVICE_SCHEDULES", value_parser = parse_path)]
+        schedules: Option<String>,
+
+        /// Maximum random delay added to every scheduled run (e.g., '15m')
+        #[arg(long, env = "SERVICE_JITTER")]
+        jitter: Option<String>,
+
+        /// Daily window, in local time, the scheduled runs start in (e.g., '01:00-05:00')
+        #[arg(long, env = "SERVICE_WINDOW")]
+        window: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum RegistriesCommand {
+    /// Probe each configured registry (latency, authentication, API version)
+    Status,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CollectionCommand {
+    /// Check the metadata and extensions of a collection file before sharing or harvesting it
+    Verify {
+        /// Path of the collection file
+        path: String,
+
+        /// Only check the file, without querying the registry
+        #[arg(long, default_value = "false")]
+        offline: bool,
+    },
+}
+
+impl From<&Args> for DownloadOptions {
+    fn from(args: &Args) -> Self {
+        Self {
+            destination: args.primary_destination(),
+            no_cache: args.no_cache,
+            proxy: args.proxy.clone(),
+            proxy_credentials: args.proxy_credentials.clone(),
+            engine_version: args.engine_version.clone(),
+            allow_pre_release: args.allow_pre_release,
+            // Partial upgrades rewrite the lockfile and locked runs check it, every entry needs its hash
+            pin_hashes: args.pin_hashes || args.locked || !args.upgrade_only.is_empty(),
+            hash: args.hash,
+            polite: args.polite,
+            retries: args.retries,
+            retry_delay: Duration::from_millis(args.retry_delay),
+            on_no_compatible: args.on_no_compatible,
+            prefer: args.prefer,
+            signature_policy: if args.require_signature {
+                SignaturePolicy::Require
+            } else if args.verify_signatures {
+                SignaturePolicy::Verify
+            } else {
+                SignaturePolicy::Skip
+            },
+            http2_prior_knowledge: args.http2_prior_knowledge,
+            pool_idle_timeout: args.pool_idle_timeout.map(Duration::from_secs),
+            tcp_keepalive: args.tcp_keepalive.map(Duration::from_secs),
+            source_store: args
+                .store_url
+                .clone()
+                .filter(|_| args.prefer_source == SourcePreference::Store),
+            cancellation: args.cancellation.clone(),
+            marketplace_origin: args.bench_mode.clone(),
+            naming: args.naming_preset,
+            provenance: args.provenance,
+            registry: args.registry.registry(),
+            storage: Arc::new(LocalStorage::default()),
+            progress_bars: None,
+            minimal_requests: args.minimal_requests,
+        }
+    }
+}
+
+impl Args {
+    /// Get the directory where the extensions are downloaded, the first local one without platform
+    pub fn primary_destination(&self) -> String {
+        self.destination
+            .iter()
+            .map(|destination| destination.parse::<Destination>().unwrap())
+            .find(|destination| destination.platform.is_none() && destination.is_local())
+            .map_or_else(
+                || format!("./{}", DEFAULT_PATH),
+                |destination| destination.path,
+            )
+    }
+
+    /// Get the other directories the downloaded packages are copied to
+    pub fn mirror_destinations(&self) -> Vec<Destination> {
+        let primary = self.primary_destination();
+        self.destination
+            .iter()
+            .map(|destination| destination.parse::<Destination>().unwrap())
+            .filter(|destination| destination.platform.is_some() || destination.path != primary)
+            .collect()
+    }
+
+    /// Get the maximum number of concurrent downloads and marketplace queries
+    pub fn concurrency(&self) -> usize {
+        let concurrency = if self.serial {
+            1
+        } else if self.polite {
+            POLITE_CONCURRENT_DOWNLOADS
+        } else {
+            MAX_CONCURRENT_DOWNLOADS
+        };
+        match self.concurrency_limit {
+            Some(limit) => concurrency.min(limit.max(1)),
+            None => concurrency,
+        }
+    }
+
+    /// Check if the progress bars of the downloads are drawn
+    ///
+    /// They are drawn on an interactive stderr only, and not with `--verbose` whose
+    /// log lines would break them.
+    pub fn progress_bars(&self) -> bool {
+        !self.no_progress && !self.verbose && std::io::stderr().is_terminal()
+    }
+
+    /// Get the integrity files to write after the downloads
+    pub fn checksum_outputs(&self) -> ChecksumOutputs {
+        ChecksumOutputs {
+            sums: self.checksums,
+            sidecars: self.checksum_sidecars,
+            attestation: self.attestation,
+            algorithm: self.hash,
+        }
+    }
+
+    /// Get what to do with the violations of the policy, from `--enforce` and `--warn`
+    pub fn policy_mode(&self) -> PolicyMode {
+        if self.warn {
+            PolicyMode::Warn
+        } else {
+            PolicyMode::Enforce
+        }
+    }
+
+    /// Get the path of the lockfile
+    pub fn lockfile_path(&self) -> String {
+        self.lockfile
+            .clone()
+            .unwrap_or_else(|| lockfile_path(&self.input))
+    }
+
+    /// Apply the harvest-wide rules of the `--policy` file to the arguments
+    ///
+    /// `requireSignature` adds to `--require-signature`, `maxTotalSize` and its
+    /// `sizeBudgetPolicy` apply unless `--max-total-size` is given.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the arguments with the rules of the policy or an error if the
+    /// policy file is invalid
+    pub fn with_policy(&self) -> Result<Self> {
+        let mut args = self.clone();
+        let Some(path) = &self.policy else {
+            return Ok(args);
+        };
+        let policy = HarvestPolicy::load(path)?;
+        args.require_signature |= policy.require_signature;
+        if args.max_total_size.is_none() && policy.max_total_size.is_some() {
+            args.max_total_size = policy.max_total_size;
+            args.size_budget_policy = policy.size_budget_policy;
+        }
+        Ok(args)
+    }
 }
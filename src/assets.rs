@@ -0,0 +1,235 @@
+use crate::config::{ASSETS_DIR, ASSET_URL, ICONS_DIR, USER_AGENT};
+use crate::create_directory_if_not_exists;
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{build_client, send_request, DownloadOptions, ResolvedExtension};
+use crate::pool::{TaskMeta, TaskPool};
+use crate::status::scan_mirror;
+use crate::temp::write_atomic;
+use log::{error, info, warn};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Asset type of the default extension icon
+pub const ICON_ASSET_TYPE: &str = "Microsoft.VisualStudio.Services.Icons.Default";
+
+/// Asset types shown by the detail page of an extension, besides its icon
+pub const DETAIL_ASSET_TYPES: [&str; 3] = [
+    "Microsoft.VisualStudio.Services.Content.Details",
+    "Microsoft.VisualStudio.Services.Content.Changelog",
+    "Microsoft.VisualStudio.Code.Manifest",
+];
+
+/// Build the URL of a named asset of an extension version
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version of the extension
+/// * `asset_type` - The asset type (e.g., "Microsoft.VisualStudio.Services.Icons.Default")
+///
+/// # Returns
+///
+/// The URL of the asset
+pub fn build_asset_url(id: &str, version: &str, asset_type: &str) -> String {
+    let (publisher, name) = id.split_once('.').unwrap_or((id, ""));
+    format!(
+        "{}/{}/extension/{}/{}/assetbyname/{}",
+        ASSET_URL, publisher, name, version, asset_type
+    )
+}
+
+/// Get the path of the cached icon of an extension version
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version of the extension
+///
+/// # Returns
+///
+/// The path of the icon in the icons cache
+pub fn icon_cache_path(destination: &str, id: &str, version: &str) -> String {
+    format!("{}/{}/{}-{}.png", destination, ICONS_DIR, id, version)
+}
+
+/// Get the path of a cached detail asset of an extension version
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version of the extension
+/// * `asset_type` - The asset type (e.g., "Microsoft.VisualStudio.Services.Content.Details")
+///
+/// # Returns
+///
+/// The path of the asset in the assets cache
+pub fn asset_cache_path(destination: &str, id: &str, version: &str, asset_type: &str) -> String {
+    format!(
+        "{}/{}/{}-{}/{}",
+        destination, ASSETS_DIR, id, version, asset_type
+    )
+}
+
+/// Download an asset of an extension version into a cache file
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version of the extension
+/// * `asset_type` - The asset type
+/// * `path` - The path of the cache file
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the path of the cached asset, None if the version has no such asset
+pub async fn mirror_asset(
+    id: &str,
+    version: &str,
+    asset_type: &str,
+    path: &str,
+    options: &DownloadOptions,
+) -> Result<Option<String>> {
+    if !options.no_cache && Path::new(path).exists() {
+        info!("Skip asset download: {} is already cached", path);
+        return Ok(Some(path.to_string()));
+    }
+    if let Some(parent) = Path::new(path).parent() {
+        create_directory_if_not_exists(&parent.to_string_lossy())?;
+    }
+
+    let client = build_client(options)?;
+    let asset_url = build_asset_url(id, version, asset_type);
+    info!("Download asset from {}", asset_url);
+    let resp = send_request(
+        client.get(&asset_url).header("User-Agent", USER_AGENT),
+        options,
+    )
+    .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        warn!("{} {} has no {} asset", id, version, asset_type);
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(VsixHarvesterError::DownloadError(format!(
+            "{} of {} ({})",
+            asset_type,
+            id,
+            resp.status()
+        )));
+    }
+    write_atomic(Path::new(path), &resp.bytes().await?)?;
+    info!("Asset saved in {}", path);
+    Ok(Some(path.to_string()))
+}
+
+/// Download the icon of a resolved extension into the icons cache
+///
+/// Icons are keyed by extension and version so the universal and platform specific
+/// packages of the same version share a single cached icon.
+///
+/// # Arguments
+///
+/// * `resolved` - The resolved extension
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the path of the cached icon, None if the extension has no icon
+pub async fn mirror_icon(
+    resolved: &ResolvedExtension,
+    options: &DownloadOptions,
+) -> Result<Option<String>> {
+    let icon_path = icon_cache_path(&options.destination, &resolved.id, &resolved.version);
+    mirror_asset(
+        &resolved.id,
+        &resolved.version,
+        ICON_ASSET_TYPE,
+        &icon_path,
+        options,
+    )
+    .await
+}
+
+/// Cache the icon and detail assets of every package of the mirror
+///
+/// A gallery serving the mirror then answers the detail pages without origin requests.
+///
+/// # Arguments
+///
+/// * `options` - The download options, the mirror is the destination
+/// * `concurrency` - The maximum number of concurrent downloads
+///
+/// # Returns
+///
+/// A Result containing the number of cached assets or an error that occurred
+pub async fn prewarm_assets(options: &DownloadOptions, concurrency: usize) -> Result<usize> {
+    let mut versions = BTreeSet::new();
+    for package in scan_mirror(&options.destination)? {
+        versions.insert((package.id, package.version));
+    }
+    let mut inputs = Vec::new();
+    for (id, version) in versions {
+        let icon_path = icon_cache_path(&options.destination, &id, &version);
+        inputs.push((
+            TaskMeta::new(&id, None),
+            (id.clone(), version.clone(), ICON_ASSET_TYPE, icon_path),
+        ));
+        for asset_type in DETAIL_ASSET_TYPES {
+            let path = asset_cache_path(&options.destination, &id, &version, asset_type);
+            inputs.push((
+                TaskMeta::new(&id, None),
+                (id.clone(), version.clone(), asset_type, path),
+            ));
+        }
+    }
+
+    let shared_options = Arc::new(options.clone());
+    let reports = TaskPool::new(concurrency)
+        .cancellation(options.cancellation.clone())
+        .run(inputs, |(id, version, asset_type, path)| {
+            let options = shared_options.clone();
+            async move { mirror_asset(&id, &version, asset_type, &path, &options).await }
+        })
+        .await;
+    let mut cached = 0;
+    for report in reports {
+        match report.result {
+            Ok(Some(_)) => cached += 1,
+            Ok(None) => {}
+            Err(e) => error!(
+                "Error occurred when caching an asset of {}: {}",
+                report.meta, e
+            ),
+        }
+    }
+    Ok(cached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_asset_url() {
+        assert_eq!(
+            build_asset_url("golang.Go", "0.41.2", ICON_ASSET_TYPE),
+            "https://marketplace.visualstudio.com/_apis/public/gallery/publisher/golang/extension/Go/0.41.2/assetbyname/Microsoft.VisualStudio.Services.Icons.Default"
+        );
+    }
+
+    #[test]
+    fn test_icon_cache_path() {
+        assert_eq!(
+            icon_cache_path("./extensions", "golang.Go", "0.41.2"),
+            "./extensions/icons/golang.Go-0.41.2.png"
+        );
+        assert_eq!(
+            asset_cache_path("./extensions", "golang.Go", "0.41.2", DETAIL_ASSET_TYPES[0]),
+            "./extensions/assets/golang.Go-0.41.2/Microsoft.VisualStudio.Services.Content.Details"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::ResolvedExtension;
+use clap::ValueEnum;
+use log::{info, warn};
+use serde::Deserialize;
+
+/// What to do when the resolved extensions exceed the size budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeBudgetPolicy {
+    /// Fail before downloading anything
+    #[default]
+    Fail,
+    /// Download in manifest order until the budget is exhausted
+    Manifest,
+    /// Download the most installed extensions first until the budget is exhausted
+    Installs,
+}
+
+/// Parse a human readable size (e.g., "10G", "512M", "1.5GiB", "1024")
+///
+/// Units are binary multiples: K = 1024 bytes, M = 1024 K...
+///
+/// # Arguments
+///
+/// * `size` - The size to parse
+///
+/// # Returns
+///
+/// A Result containing the size in bytes or an error if the size is invalid
+pub fn parse_size(size: &str) -> Result<u64> {
+    let invalid = || VsixHarvesterError::InvalidSize(size.to_string());
+    let normalized = size.trim().to_ascii_uppercase();
+    let normalized = normalized
+        .strip_suffix("IB")
+        .or_else(|| normalized.strip_suffix('B'))
+        .unwrap_or(&normalized);
+    let (number, multiplier) = match normalized.chars().last() {
+        Some('K') => (&normalized[..normalized.len() - 1], 1u64 << 10),
+        Some('M') => (&normalized[..normalized.len() - 1], 1 << 20),
+        Some('G') => (&normalized[..normalized.len() - 1], 1 << 30),
+        Some('T') => (&normalized[..normalized.len() - 1], 1 << 40),
+        _ => (normalized, 1),
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    if number < 0.0 {
+        return Err(invalid());
+    }
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Select the extensions fitting in a size budget
+///
+/// # Arguments
+///
+/// * `sized` - The resolved extensions in manifest order with the number of bytes to download
+/// * `budget` - The maximum number of bytes to download
+/// * `policy` - What to do when the budget is exceeded
+///
+/// # Returns
+///
+/// A Result containing the extensions to download or an error if the policy is to fail
+pub fn apply_size_budget(
+    mut sized: Vec<(ResolvedExtension, u64)>,
+    budget: u64,
+    policy: SizeBudgetPolicy,
+) -> Result<Vec<ResolvedExtension>> {
+    let total: u64 = sized.iter().map(|(_, size)| size).sum();
+    info!("Harvest needs {} bytes, budget is {} bytes", total, budget);
+    if total <= budget {
+        return Ok(sized.into_iter().map(|(resolved, _)| resolved).collect());
+    }
+    if policy == SizeBudgetPolicy::Fail {
+        return Err(VsixHarvesterError::SizeBudgetExceeded(total, budget));
+    }
+    if policy == SizeBudgetPolicy::Installs {
+        // Stable sort, extensions with the same install count keep the manifest order
+        sized.sort_by_key(|(resolved, _)| {
+            std::cmp::Reverse(
+                resolved
+                    .metadata
+                    .as_ref()
+                    .map_or(0, |metadata| metadata.get_install_count()),
+            )
+        });
+    }
+
+    let mut remaining = budget;
+    let mut selected = Vec::new();
+    for (resolved, size) in sized {
+        if size <= remaining {
+            remaining -= size;
+            selected.push(resolved);
+        } else {
+            warn!(
+                "Skipping {} {} ({} bytes): size budget exhausted",
+                resolved.id, resolved.version, size
+            );
+        }
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::resolved;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10G").unwrap(), 10 * (1 << 30));
+        assert_eq!(parse_size("512mb").unwrap(), 512 * (1 << 20));
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 * (1 << 29));
+        assert!(parse_size("ten gigs").is_err());
+    }
+
+    #[test]
+    fn test_apply_size_budget() {
+        let sized = vec![
+            (resolved("a.a", "1.0.0"), 60),
+            (resolved("b.b", "1.0.0"), 50),
+            (resolved("c.c", "1.0.0"), 30),
+        ];
+        assert!(apply_size_budget(sized.clone(), 100, SizeBudgetPolicy::Fail).is_err());
+
+        let selected = apply_size_budget(sized.clone(), 100, SizeBudgetPolicy::Manifest).unwrap();
+        let ids: Vec<_> = selected
+            .iter()
+            .map(|resolved| resolved.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["a.a", "c.c"]);
+
+        let selected = apply_size_budget(sized, 200, SizeBudgetPolicy::Fail).unwrap();
+        assert_eq!(selected.len(), 3);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::architecture::TargetPlatform;
+use crate::config::{ATTESTATION_PREDICATE_FILE_NAME, ATTESTATION_PREDICATE_TYPE, VERSION};
+use crate::error::Result;
+use crate::hash::{Digest, HashAlgorithm};
+use crate::marketplace::DownloadOutcome;
+use crate::status::{scan_mirror, MirroredPackage};
+use crate::temp::write_atomic;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A package of the mirror with its digest
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageDigest {
+    pub file: String,
+    pub id: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_platform: Option<TargetPlatform>,
+    /// Serialized as a field named after the algorithm (e.g., "sha256")
+    #[serde(flatten)]
+    pub digest: Digest,
+}
+
+/// Predicate describing a harvest, to be attested with `cosign attest-blob --predicate`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarvestPredicate {
+    pub harvester: String,
+    pub generated_at: DateTime<Utc>,
+    pub packages: Vec<PackageDigest>,
+}
+
+/// Which integrity files are written in the destination directory
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChecksumOutputs {
+    /// A SHA256SUMS file listing every package, named after the algorithm
+    pub sums: bool,
+    /// A `.sha256` file next to every package, named after the algorithm
+    pub sidecars: bool,
+    /// A cosign-compatible attestation predicate
+    pub attestation: bool,
+    /// The hash algorithm of the checksums
+    pub algorithm: HashAlgorithm,
+}
+
+impl ChecksumOutputs {
+    /// Check if any integrity file is requested
+    pub fn any(&self) -> bool {
+        self.sums || self.sidecars || self.attestation
+    }
+}
+
+/// Render a checksum line in the format of `sha256sum`, accepted by GNU `sha256sum -c`,
+/// BSD `shasum -a 256 -c` and their SHA-512 and BLAKE3 (`b3sum -c`) counterparts
+pub fn render_checksum_line(digest: &PackageDigest) -> String {
+    format!("{}  {}\n", digest.digest.value, digest.file)
+}
+
+/// Hash the packages of a mirror
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `packages` - The packages of the mirror
+/// * `algorithm` - The hash algorithm
+/// * `known` - The digests already computed by the run, keyed by file name
+///
+/// # Returns
+///
+/// A Result containing the digests of the packages or an error that occurred
+fn hash_packages(
+    destination: &str,
+    packages: Vec<MirroredPackage>,
+    algorithm: HashAlgorithm,
+    known: &HashMap<&str, Digest>,
+) -> Result<Vec<PackageDigest>> {
+    packages
+        .into_iter()
+        .map(|package| {
+            let digest = match known.get(package.file_name.as_str()) {
+                Some(digest) => digest.clone(),
+                None => {
+                    algorithm.digest(&fs::read(format!("{}/{}", destination, package.file_name))?)
+                }
+            };
+            Ok(PackageDigest {
+                digest,
+                file: package.file_name,
+                id: package.id,
+                version: package.version,
+                target_platform: package.target_platform,
+            })
+        })
+        .collect()
+}
+
+/// Write the integrity files of every package of a destination directory
+///
+/// The packages downloaded by the run were hashed while being downloaded, only the
+/// packages without a known digest are read again.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `outputs` - The integrity files to write
+/// * `outcomes` - The outcomes of the downloads of the run
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_checksums(
+    destination: &str,
+    outputs: ChecksumOutputs,
+    outcomes: &[DownloadOutcome],
+) -> Result<()> {
+    let known: HashMap<&str, Digest> = outcomes
+        .iter()
+        .filter_map(|outcome| {
+            let file_name = Path::new(&outcome.file_path).file_name()?.to_str()?;
+            Some((file_name, outcome.digest(outputs.algorithm)?))
+        })
+        .collect();
+    let digests = hash_packages(
+        destination,
+        scan_mirror(destination)?,
+        outputs.algorithm,
+        &known,
+    )?;
+    if outputs.sums {
+        let sums: String = digests.iter().map(render_checksum_line).collect();
+        let path = format!("{}/{}", destination, outputs.algorithm.sums_file_name());
+        write_atomic(Path::new(&path), sums.as_bytes())?;
+        info!("Checksums saved in {}", path);
+    }
+    if outputs.sidecars {
+        for digest in &digests {
+            write_atomic(
+                Path::new(&format!(
+                    "{}/{}.{}",
+                    destination,
+                    digest.file,
+                    outputs.algorithm.sidecar_extension()
+                )),
+                render_checksum_line(digest).as_bytes(),
+            )?;
+        }
+        info!("{} checksum sidecar(s) saved", digests.len());
+    }
+    if outputs.attestation {
+        let predicate = HarvestPredicate {
+            harvester: format!("vsixHarvester {}", VERSION),
+            generated_at: Utc::now(),
+            packages: digests,
+        };
+        let path = format!("{}/{}", destination, ATTESTATION_PREDICATE_FILE_NAME);
+        write_atomic(
+            Path::new(&path),
+            serde_json::to_string_pretty(&predicate)?.as_bytes(),
+        )?;
+        info!(
+            "Attestation predicate saved in {}, attest it with: cosign attest-blob --predicate {} --type {} <blob>",
+            path, path, ATTESTATION_PREDICATE_TYPE
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CHECKSUMS_FILE_NAME, SHA512_CHECKSUMS_FILE_NAME};
+    use crate::marketplace::sha256_hex;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_checksums() {
+        let temp_dir = TempDir::new().unwrap();
+        let destination = temp_dir.path().display().to_string();
+        fs::write(format!("{}/golang.Go-0.41.2.vsix", destination), "go").unwrap();
+        fs::write(format!("{}/inventory.json", destination), "{}").unwrap();
+        write_checksums(
+            &destination,
+            ChecksumOutputs {
+                sums: true,
+                sidecars: true,
+                attestation: true,
+                ..Default::default()
+            },
+            &[],
+        )
+        .unwrap();
+
+        let line = format!("{}  golang.Go-0.41.2.vsix\n", sha256_hex(b"go"));
+        assert_eq!(
+            fs::read_to_string(format!("{}/{}", destination, CHECKSUMS_FILE_NAME)).unwrap(),
+            line
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/golang.Go-0.41.2.vsix.sha256", destination)).unwrap(),
+            line
+        );
+        let predicate: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(format!(
+                "{}/{}",
+                destination, ATTESTATION_PREDICATE_FILE_NAME
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(predicate["packages"][0]["id"], "golang.Go");
+        assert_eq!(predicate["packages"][0]["sha256"], sha256_hex(b"go"));
+
+        // The digests computed while downloading are not computed again
+        let outcome = DownloadOutcome {
+            id: "golang.Go".to_string(),
+            version: "0.41.2".to_string(),
+            target_platform: None,
+            file_path: format!("{}/golang.Go-0.41.2.vsix", destination),
+            download_url: String::new(),
+            sha256: Some("c0ffee".to_string()),
+            digest: None,
+        };
+        write_checksums(
+            &destination,
+            ChecksumOutputs {
+                sums: true,
+                ..Default::default()
+            },
+            &[outcome],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/{}", destination, CHECKSUMS_FILE_NAME)).unwrap(),
+            "c0ffee  golang.Go-0.41.2.vsix\n"
+        );
+
+        // Other algorithms hash every package again
+        write_checksums(
+            &destination,
+            ChecksumOutputs {
+                sums: true,
+                sidecars: true,
+                algorithm: HashAlgorithm::Sha512,
+                ..Default::default()
+            },
+            &[],
+        )
+        .unwrap();
+        let line = format!(
+            "{}  golang.Go-0.41.2.vsix\n",
+            HashAlgorithm::Sha512.digest(b"go").value
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/{}", destination, SHA512_CHECKSUMS_FILE_NAME)).unwrap(),
+            line
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/golang.Go-0.41.2.vsix.sha512", destination)).unwrap(),
+            line
+        );
+    }
+}
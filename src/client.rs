@@ -0,0 +1,213 @@
+use crate::architecture::TargetPlatform;
+use crate::args::Args;
+use crate::create_directory_if_not_exists;
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{
+    download_resolved_extension, resolve_extension, DownloadOptions, DownloadOutcome,
+    ResolvedExtension,
+};
+use crate::registry::Registry;
+use crate::storage::is_local_location;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Entry point of the harvesting logic for embedding applications
+///
+/// A client resolves and downloads extensions into its destination with the same
+/// options as the command line, without spawning the binary.
+///
+/// ```no_run
+/// # async fn provision() -> vsixharvester::error::Result<()> {
+/// let client = vsixharvester::Client::new("/srv/mirror")
+///     .engine_version("1.97.0")
+///     .retries(3);
+/// let outcome = client.download("golang.Go", Some("linux-x64")).await?;
+/// println!("{} {} in {}", outcome.id, outcome.version, outcome.file_path);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    options: DownloadOptions,
+}
+
+impl Client {
+    /// Create a client downloading from the Visual Studio Marketplace
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - The directory where the extensions are saved
+    ///
+    /// # Returns
+    ///
+    /// The client
+    pub fn new(destination: impl Into<String>) -> Self {
+        Self {
+            options: DownloadOptions {
+                destination: destination.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Create a client with the options of a command line
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The command line arguments
+    ///
+    /// # Returns
+    ///
+    /// The client
+    pub fn from_args(args: &Args) -> Self {
+        Self::with_options(DownloadOptions::from(args))
+    }
+
+    /// Create a client with download options
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// The client
+    pub fn with_options(options: DownloadOptions) -> Self {
+        Self { options }
+    }
+
+    /// Set the engine version the extensions must be compatible with
+    pub fn engine_version(mut self, engine_version: impl Into<String>) -> Self {
+        self.options.engine_version = Some(engine_version.into());
+        self
+    }
+
+    /// Allow the versions marked as pre-release
+    pub fn allow_pre_release(mut self, allow_pre_release: bool) -> Self {
+        self.options.allow_pre_release = allow_pre_release;
+        self
+    }
+
+    /// Download the packages again even if they already exist
+    pub fn no_cache(mut self, no_cache: bool) -> Self {
+        self.options.no_cache = no_cache;
+        self
+    }
+
+    /// Go through a proxy (e.g., "http://proxy:3128")
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.options.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Retry the requests failing with a transient error
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.options.retries = retries;
+        self
+    }
+
+    /// Set the delay before the first retry, doubled at each retry
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.options.retry_delay = retry_delay;
+        self
+    }
+
+    /// Query and download the extensions from another registry
+    pub fn registry(mut self, registry: Arc<dyn Registry>) -> Self {
+        self.options.registry = registry;
+        self
+    }
+
+    /// Abort the requests of the client when the token is cancelled
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.options.cancellation = cancellation;
+        self
+    }
+
+    /// Get the download options of the client
+    pub fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+
+    /// Resolve the version of an extension to download
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier, with an optional pinned version (e.g., "golang.Go@0.41.2")
+    /// * `target_platform` - Optional target platform (e.g., "linux-x64")
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the resolved extension or an error that occurred
+    pub async fn resolve(
+        &self,
+        id: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> Result<ResolvedExtension> {
+        resolve_extension(Extension::from_id(id)?, &self.options, target_platform).await
+    }
+
+    /// Resolve and download an extension into the destination
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier, with an optional pinned version (e.g., "golang.Go@0.41.2")
+    /// * `target_platform` - Optional target platform (e.g., "linux-x64")
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the download outcome or an error that occurred
+    pub async fn download(
+        &self,
+        id: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> Result<DownloadOutcome> {
+        let resolved = self.resolve(id, target_platform).await?;
+        self.download_resolved(&resolved).await
+    }
+
+    /// Download an already resolved extension into the destination
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved extension
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the download outcome or an error that occurred
+    pub async fn download_resolved(&self, resolved: &ResolvedExtension) -> Result<DownloadOutcome> {
+        if is_local_location(&self.options.destination) {
+            create_directory_if_not_exists(&self.options.destination)?;
+        }
+        download_resolved_extension(resolved, &self.options, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client() {
+        let client = Client::new("./mirror")
+            .engine_version("1.97.0")
+            .retries(3)
+            .no_cache(true);
+        assert_eq!(client.options().destination, "./mirror");
+        assert_eq!(client.options().engine_version.as_deref(), Some("1.97.0"));
+        assert_eq!(client.options().retries, 3);
+        assert!(client.options().no_cache);
+
+        let resolved = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(client.resolve("golang.Go@0.41.2", Some(&"linux-x64".into())))
+            .unwrap();
+        assert_eq!(resolved.version, "0.41.2");
+        assert_eq!(
+            resolved.file_path,
+            "./mirror/golang.Go-0.41.2@linux-x64.vsix"
+        );
+    }
+}
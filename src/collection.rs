@@ -0,0 +1,319 @@
+use crate::architecture::PlatformRegistry;
+use crate::config::{ALL_PLATFORMS_FIELD, COLLECTION_FIELD};
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::{interpolate_env, Extension, Extensions};
+use crate::marketplace::{query_marketplace_extension, DownloadOptions};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+/// The metadata of a collection, the `collection` object of its file
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct CollectionInfo {
+    /// The name of the collection (e.g., "dark-themes")
+    #[serde(default)]
+    pub name: String,
+    /// What the extensions of the collection are for
+    #[serde(default)]
+    pub description: String,
+    /// The team or organization vetting the collection
+    #[serde(default)]
+    pub owner: String,
+    /// Optional version of the collection, bumped by its owner on every change
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// A curated extension set shared as a unit
+///
+/// A collection file is a manifest with a `collection` object: its platform lists are
+/// the ones of `extensions.json`, so it can also be harvested directly with `--input`.
+pub struct Collection {
+    pub info: CollectionInfo,
+    pub extensions: Extensions,
+}
+
+impl Collection {
+    /// Load a collection file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the collection file
+    /// * `variables` - Variables expanded in the file before the environment
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the collection or an error if the file is not a collection
+    pub fn load(path: &str, variables: &BTreeMap<String, String>) -> Result<Self> {
+        let mut content: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        interpolate_env(&mut content, variables)?;
+        let info = match content.get(COLLECTION_FIELD) {
+            Some(info) => serde_json::from_value(info.clone())?,
+            None => {
+                return Err(VsixHarvesterError::InvalidCollection(format!(
+                    "{} has no {} object",
+                    path, COLLECTION_FIELD
+                )))
+            }
+        };
+        Ok(Self {
+            info,
+            extensions: serde_json::from_value(content)?,
+        })
+    }
+}
+
+/// Add the extensions of collections to the lists of a manifest
+///
+/// The extensions already listed in the same platform list are kept as listed.
+///
+/// # Arguments
+///
+/// * `extensions` - The extension lists of the manifest
+/// * `paths` - The paths of the collection files
+/// * `variables` - Variables expanded in the files before the environment
+///
+/// # Returns
+///
+/// A Result indicating success or an error if a collection cannot be loaded
+pub fn merge_collections(
+    extensions: &mut Extensions,
+    paths: &[String],
+    variables: &BTreeMap<String, String>,
+) -> Result<()> {
+    for path in paths {
+        let collection = Collection::load(path, variables)?;
+        info!(
+            "Harvesting collection {} of {}",
+            collection.info.name, collection.info.owner
+        );
+        for field_name in collection.extensions.field_names() {
+            let section = extensions.section_mut(field_name);
+            for entry in collection
+                .extensions
+                .section(field_name)
+                .into_iter()
+                .flatten()
+            {
+                if !section
+                    .iter()
+                    .any(|listed| listed.id().eq_ignore_ascii_case(entry.id()))
+                {
+                    section.push(entry.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the metadata and entries of a collection without querying the registry
+///
+/// # Arguments
+///
+/// * `collection` - The collection to check
+/// * `registry` - The known platforms
+///
+/// # Returns
+///
+/// The problems found, empty for a valid collection
+pub fn check_collection(collection: &Collection, registry: &PlatformRegistry) -> Vec<String> {
+    let mut problems = Vec::new();
+    let info = &collection.info;
+    for (field, value) in [
+        ("name", &info.name),
+        ("description", &info.description),
+        ("owner", &info.owner),
+    ] {
+        if value.trim().is_empty() {
+            problems.push(format!("the collection has no {}", field));
+        }
+    }
+    if collection.extensions.entries().next().is_none() {
+        problems.push("the collection lists no extension".to_string());
+    }
+    for field_name in collection.extensions.field_names() {
+        if field_name != ALL_PLATFORMS_FIELD && !registry.contains(field_name) {
+            problems.push(format!("{} is not a known platform", field_name));
+        }
+        let mut seen = HashSet::new();
+        for entry in collection
+            .extensions
+            .section(field_name)
+            .into_iter()
+            .flatten()
+        {
+            // GUIDs are specific to a marketplace, shared sets use publisher.name identifiers
+            if Extension::from_id(entry.id()).is_err() {
+                problems.push(format!(
+                    "{}: {} is not a publisher.name identifier",
+                    field_name,
+                    entry.id()
+                ));
+            }
+            if !seen.insert(entry.id().to_lowercase()) {
+                problems.push(format!("{}: {} is listed twice", field_name, entry.id()));
+            }
+            if let Err(e) = entry.package_override() {
+                problems.push(format!("{}: {}", field_name, e));
+            }
+        }
+    }
+    problems
+}
+
+/// Check that every extension of a collection exists in the registry, with its pinned version
+///
+/// # Arguments
+///
+/// * `collection` - The collection to check
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// The problems found, empty when every extension resolves
+async fn check_collection_online(
+    collection: &Collection,
+    options: &DownloadOptions,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in collection.extensions.entries() {
+        let Ok(extension) = Extension::from_id(entry.id()) else {
+            continue;
+        };
+        let version = entry.version();
+        if !seen.insert((entry.id().to_lowercase(), version)) {
+            continue;
+        }
+        match query_marketplace_extension(&extension, options, version.is_some()).await {
+            Ok(metadata) => {
+                if let Some(version) = version {
+                    if !metadata
+                        .versions
+                        .iter()
+                        .any(|listed| listed.version == version)
+                    {
+                        problems.push(format!(
+                            "{}: version {} is not published",
+                            entry.id(),
+                            version
+                        ));
+                    }
+                }
+            }
+            Err(e) => problems.push(format!("{}: {}", entry.id(), e)),
+        }
+    }
+    problems
+}
+
+/// Verify a collection file before sharing or harvesting it, and print the problems found
+///
+/// # Arguments
+///
+/// * `path` - The path of the collection file
+/// * `options` - The download options
+/// * `registry` - The known platforms
+/// * `offline` - Whether to skip the registry queries
+///
+/// # Returns
+///
+/// A Result indicating success or an error if the collection is invalid
+pub async fn verify_collection(
+    path: &str,
+    options: &DownloadOptions,
+    registry: &PlatformRegistry,
+    offline: bool,
+) -> Result<()> {
+    let collection = Collection::load(path, &BTreeMap::new())?;
+    let mut problems = check_collection(&collection, registry);
+    if !offline {
+        problems.extend(check_collection_online(&collection, options).await);
+    }
+    let info = &collection.info;
+    println!(
+        "{} {} ({}): {} extension(s)",
+        info.name,
+        info.version.as_deref().unwrap_or("unversioned"),
+        info.owner,
+        collection.extensions.entries().count()
+    );
+    if problems.is_empty() {
+        println!("  ok");
+        return Ok(());
+    }
+    for problem in &problems {
+        error!("{}: {}", path, problem);
+        println!("  error: {}", problem);
+    }
+    Err(VsixHarvesterError::InvalidCollection(format!(
+        "{} problem(s) found in {}",
+        problems.len(),
+        path
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collections() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("themes.json");
+        let path = path.to_str().unwrap().to_string();
+        fs::write(
+            &path,
+            r#"{
+                "collection": {
+                    "name": "themes",
+                    "description": "Vetted color themes",
+                    "owner": "${THEMES_OWNER}",
+                    "version": "1.2.0"
+                },
+                "universal": ["dracula-theme.theme-dracula", "GitHub.github-vscode-theme@6.3.5"],
+                "linux_x64": ["golang.Go"]
+            }"#,
+        )
+        .unwrap();
+        let variables = BTreeMap::from([("THEMES_OWNER".to_string(), "platform-team".to_string())]);
+        let collection = Collection::load(&path, &variables).unwrap();
+        assert_eq!(collection.info.owner, "platform-team");
+        let registry = PlatformRegistry::default();
+        assert!(check_collection(&collection, &registry).is_empty());
+
+        let mut extensions: Extensions =
+            serde_json::from_str(r#"{"universal": ["dracula-theme.theme-dracula@2.25.0"]}"#)
+                .unwrap();
+        merge_collections(&mut extensions, &[path], &variables).unwrap();
+        let universal = extensions.section("universal").unwrap();
+        // The entry of the manifest keeps its pinned version
+        assert_eq!(universal.len(), 2);
+        assert_eq!(universal[0].version(), Some("2.25.0"));
+        assert_eq!(
+            extensions.section("linux_x64").unwrap()[0].id(),
+            "golang.Go"
+        );
+
+        let invalid: Extensions = serde_json::from_str(
+            r#"{"universal": ["golang.Go", "golang.go", "go"], "haiku_x64": ["a.b"]}"#,
+        )
+        .unwrap();
+        let invalid = Collection {
+            info: CollectionInfo {
+                name: "broken".to_string(),
+                ..Default::default()
+            },
+            extensions: invalid,
+        };
+        let problems = check_collection(&invalid, &registry);
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+
+        let manifest = temp_dir.path().join("extensions.json");
+        fs::write(&manifest, r#"{"universal": []}"#).unwrap();
+        assert!(Collection::load(manifest.to_str().unwrap(), &BTreeMap::new()).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::ZSTD_LEVEL;
+use crate::error::Result;
+use crate::temp::write_atomic;
+use clap::ValueEnum;
+use log::{error, info};
+use std::fs;
+use std::path::Path;
+
+/// Compression of the stored copies of the packages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StoreCompression {
+    /// Keep a `.vsix.zst` sibling of each package
+    Zstd,
+}
+
+impl StoreCompression {
+    /// Get the suffix appended to the file name of the compressed copies
+    pub fn suffix(self) -> &'static str {
+        match self {
+            StoreCompression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Get the path of the compressed sibling of a package
+pub fn compressed_path(vsix_path: &str, compression: StoreCompression) -> String {
+    format!("{}{}", vsix_path, compression.suffix())
+}
+
+/// Write the compressed siblings of the downloaded packages
+///
+/// VSIX packages are deflate-compressed ZIP archives, zstd at its highest levels still
+/// saves a significant part of their size on large mirrors.
+///
+/// # Arguments
+///
+/// * `paths` - The paths of the downloaded packages
+/// * `compression` - The compression of the siblings
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_compressed_copies(paths: &[&str], compression: StoreCompression) -> Result<()> {
+    for path in paths {
+        let output_path = compressed_path(path, compression);
+        if Path::new(&output_path).exists() {
+            continue;
+        }
+        let content = fs::read(path)?;
+        let compressed = match compression {
+            StoreCompression::Zstd => zstd::encode_all(content.as_slice(), ZSTD_LEVEL),
+        };
+        match compressed {
+            Ok(compressed) => {
+                write_atomic(Path::new(&output_path), &compressed)?;
+                info!(
+                    "Compressed copy saved in {} ({} -> {} bytes)",
+                    output_path,
+                    content.len(),
+                    compressed.len()
+                );
+            }
+            Err(e) => error!("Failed to compress {}: {}", path, e),
+        }
+    }
+    Ok(())
+}
+
+/// Read a package, decompressing its stored copy when only this one is kept
+///
+/// # Arguments
+///
+/// * `path` - The path of the package, either the `.vsix` or its `.vsix.zst` sibling
+///
+/// # Returns
+///
+/// A Result containing the content of the VSIX package or an error that occurred
+pub fn read_package(path: &str) -> Result<Vec<u8>> {
+    if path.ends_with(StoreCompression::Zstd.suffix()) {
+        return Ok(zstd::decode_all(fs::File::open(path)?)?);
+    }
+    if !Path::new(path).exists() {
+        let compressed = compressed_path(path, StoreCompression::Zstd);
+        if Path::new(&compressed).exists() {
+            return read_package(&compressed);
+        }
+    }
+    Ok(fs::read(path)?)
+}
+
+/// Get the package file name of a stored copy, without the compression suffix
+pub fn package_file_name(path: &str) -> Option<String> {
+    let file_name = Path::new(path).file_name()?.to_string_lossy();
+    Some(
+        file_name
+            .strip_suffix(StoreCompression::Zstd.suffix())
+            .unwrap_or(&file_name)
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compressed_copies() {
+        let temp_dir = TempDir::new().unwrap();
+        let vsix_path = temp_dir
+            .path()
+            .join("golang.Go-0.41.2.vsix")
+            .to_string_lossy()
+            .into_owned();
+        let content = b"PK package content package content package content".repeat(16);
+        fs::write(&vsix_path, &content).unwrap();
+
+        write_compressed_copies(&[vsix_path.as_str()], StoreCompression::Zstd).unwrap();
+        let zst_path = compressed_path(&vsix_path, StoreCompression::Zstd);
+        assert!(fs::metadata(&zst_path).unwrap().len() < content.len() as u64);
+        assert_eq!(read_package(&zst_path).unwrap(), content);
+
+        // The stored copy is decompressed when the canonical package was removed
+        fs::remove_file(&vsix_path).unwrap();
+        assert_eq!(read_package(&vsix_path).unwrap(), content);
+        assert_eq!(
+            package_file_name(&zst_path).as_deref(),
+            Some("golang.Go-0.41.2.vsix")
+        );
+    }
+}
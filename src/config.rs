@@ -1,10 +1,97 @@
+pub const MARKETPLACE_ORIGIN: &str = "https://marketplace.visualstudio.com";
 pub const API_URL: &str =
     "https://marketplace.visualstudio.com/_apis/public/gallery/extensionquery";
 pub const MARKETPLACE_URL: &str =
     "https://marketplace.visualstudio.com/_apis/public/gallery/publishers";
+pub const OPENVSX_API_URL: &str = "https://open-vsx.org/api";
+pub const VSCODE_UPDATE_URL: &str = "https://update.code.visualstudio.com";
+pub const ASSET_URL: &str = "https://marketplace.visualstudio.com/_apis/public/gallery/publisher";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const USER_AGENT: &str = concat!("Offline VSIX/", env!("CARGO_PKG_VERSION"));
 pub const MARKETPLACE_API_VERSION: &str = "3.0-preview.1";
 pub const DEFAULT_FILE_NAME: &str = "extensions.json";
+pub const ALL_PLATFORMS_FIELD: &str = "allPlatforms";
+pub const COLLECTION_FIELD: &str = "collection";
 pub const DEFAULT_PATH: &str = "./extensions";
 pub const MAX_CONCURRENT_DOWNLOADS: usize = 5;
+pub const POLITE_CONCURRENT_DOWNLOADS: usize = 2;
+pub const POLITE_REQUEST_DELAY_MS: u64 = 1000;
+pub const POLITE_MAX_RETRIES: u32 = 3;
+pub const POLITE_RETRY_DELAY_MS: u64 = 5000;
+pub const TASK_RETRY_DELAY_MS: u64 = 1000;
+pub const RETRY_DELAY_MS: u64 = 1000;
+pub const ICONS_DIR: &str = "icons";
+pub const ASSETS_DIR: &str = "assets";
+pub const VSCODE_SERVER_DIR: &str = "vscode-server";
+pub const INVENTORY_FILE_NAME: &str = "inventory.json";
+pub const NORMALIZED_DIR: &str = "normalized";
+pub const QUARANTINE_DIR: &str = "quarantine";
+pub const PARTIAL_SUFFIX: &str = ".part";
+pub const TEMP_DIR: &str = ".vsixharvester-tmp";
+pub const PROVENANCE_SIDECAR_SUFFIX: &str = ".provenance.json";
+pub const MANIFEST_SIDECAR_SUFFIX: &str = ".manifest.json";
+pub const PROVENANCE_XATTR: &str = "user.vsixharvester.provenance";
+pub const GIT_PUBLISH_DIR: &str = ".publish-git";
+pub const DEFAULT_PUBLISH_MESSAGE: &str = "Harvest of {date} ({packages} packages)";
+pub const ZSTD_LEVEL: i32 = 19;
+pub const CURATION_PAGE_SIZE: usize = 200;
+pub const LAST_UPDATED_BATCH_SIZE: usize = 100;
+pub const QUERY_WINDOW_SIZE: usize = 50;
+pub const GALLERY_DEFAULT_PAGE_SIZE: usize = 50;
+/// Manifests with more extensions to query resolve them in batched query windows
+pub const QUERY_WINDOW_THRESHOLD: usize = 100;
+/// The local servers (gallery, fixture, service) refuse the larger requests
+pub const HTTP_MAX_BODY_SIZE: usize = 1 << 20;
+pub const HTTP_MAX_HEAD_SIZE: usize = 64 << 10;
+pub const DRIFT_INSTALL_DROP_RATIO: f64 = 0.05;
+pub const DRIFT_RATING_DROP: f64 = 0.5;
+/// The target platforms of the Visual Studio Marketplace, which no longer accepts 32-bit Windows packages
+pub const MARKETPLACE_TARGET_PLATFORMS: [&str; 10] = [
+    "win32-x64",
+    "win32-arm64",
+    "linux-x64",
+    "linux-arm64",
+    "linux-armhf",
+    "alpine-x64",
+    "alpine-arm64",
+    "darwin-x64",
+    "darwin-arm64",
+    "web",
+];
+/// The target platforms of Open VSX
+pub const OPENVSX_TARGET_PLATFORMS: [&str; 11] = [
+    "win32-x64",
+    "win32-ia32",
+    "win32-arm64",
+    "linux-x64",
+    "linux-arm64",
+    "linux-armhf",
+    "alpine-x64",
+    "alpine-arm64",
+    "darwin-x64",
+    "darwin-arm64",
+    "web",
+];
+pub const FIXTURE_INDEX_FILE_NAME: &str = "fixture.json";
+pub const FIXTURE_RESPONSES_DIR: &str = "responses";
+pub const FIXTURE_MANIFEST_FILE_NAME: &str = "extensions.json";
+/// The entries of a package kept in its fixture stub
+pub const FIXTURE_STUB_ENTRIES: [&str; 3] = [
+    "[Content_Types].xml",
+    "extension.vsixmanifest",
+    "extension/package.json",
+];
+pub const CHECKSUMS_FILE_NAME: &str = "SHA256SUMS";
+pub const SHA512_CHECKSUMS_FILE_NAME: &str = "SHA512SUMS";
+pub const BLAKE3_CHECKSUMS_FILE_NAME: &str = "B3SUMS";
+pub const EXTENSIONS_JSON_FILE_NAME: &str = "extensions.json";
+pub const VSIXMANIFEST_FILE_NAME: &str = ".vsixmanifest";
+pub const OBSOLETE_FILE_NAME: &str = ".obsolete";
+pub const INSTALL_SCRIPT_FILE_NAME: &str = "install-extensions";
+pub const CYCLONEDX_SBOM_FILE_NAME: &str = "sbom.cdx.json";
+pub const SPDX_SBOM_FILE_NAME: &str = "sbom.spdx.json";
+pub const STATIC_MIRROR_INDEX_FILE_NAME: &str = "index.json";
+pub const STATIC_MIRROR_EXTENSIONS_DIR: &str = "extensions";
+pub const ATTESTATION_PREDICATE_FILE_NAME: &str = "harvest.predicate.json";
+pub const ATTESTATION_PREDICATE_TYPE: &str =
+    "https://github.com/sctg-development/vsixHarvester/attestation/harvest/v1";
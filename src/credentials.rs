@@ -0,0 +1,151 @@
+use crate::error::{Result, VsixHarvesterError};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+/// The account of the credential store entries when a reference names none
+pub const KEYRING_DEFAULT_ACCOUNT: &str = "vsixHarvester";
+
+/// Secrets already read from the credential store, so the store is not asked for each client
+static KEYRING_SECRETS: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A username and password, the password is never printed
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+/// Resolve a secret reference
+///
+/// # Arguments
+///
+/// * `reference` - `keyring:<service>[/<account>]` for an entry of the OS credential store
+///   (account `vsixHarvester` by default), `env:<NAME>` for an environment variable, or the
+///   secret itself
+///
+/// # Returns
+///
+/// A Result containing the secret or an error that occurred
+pub fn resolve_secret(reference: &str) -> Result<String> {
+    if let Some(entry) = reference.strip_prefix("keyring:") {
+        let (service, account) = entry
+            .split_once('/')
+            .unwrap_or((entry, KEYRING_DEFAULT_ACCOUNT));
+        let mut secrets = KEYRING_SECRETS.lock().unwrap();
+        if let Some(secret) = secrets.get(entry) {
+            return Ok(secret.clone());
+        }
+        let secret = read_keyring(service, account)?;
+        secrets.insert(entry.to_string(), secret.clone());
+        return Ok(secret);
+    }
+    if let Some(name) = reference.strip_prefix("env:") {
+        return std::env::var(name)
+            .map_err(|_| VsixHarvesterError::CredentialError(format!("{} is not set", name)));
+    }
+    Ok(reference.to_string())
+}
+
+/// Resolve a credentials reference, the secret being `username:password`
+///
+/// # Arguments
+///
+/// * `reference` - A secret reference (see `resolve_secret`)
+///
+/// # Returns
+///
+/// A Result containing the credentials or an error that occurred
+pub fn resolve_credentials(reference: &str) -> Result<Credentials> {
+    let secret = resolve_secret(reference)?;
+    let (username, password) = secret.split_once(':').ok_or_else(|| {
+        VsixHarvesterError::CredentialError(format!(
+            "{} does not hold username:password",
+            describe_reference(reference)
+        ))
+    })?;
+    Ok(Credentials {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+/// Read an optional secret from an environment variable, which may hold a secret reference
+///
+/// # Arguments
+///
+/// * `name` - The name of the variable (e.g., "OCI_PASSWORD")
+///
+/// # Returns
+///
+/// A Result containing the secret, None if the variable is not set, or an error that occurred
+pub fn secret_from_env(name: &str) -> Result<Option<String>> {
+    std::env::var(name)
+        .ok()
+        .map(|reference| resolve_secret(&reference))
+        .transpose()
+}
+
+/// Describe a reference in messages, without the secret it may be
+fn describe_reference(reference: &str) -> &str {
+    if reference.starts_with("keyring:") || reference.starts_with("env:") {
+        reference
+    } else {
+        "the credentials"
+    }
+}
+
+/// Read the secret of an entry of the OS credential store
+#[cfg(feature = "keyring")]
+fn read_keyring(service: &str, account: &str) -> Result<String> {
+    // The Secret Service backend deadlocks when called from a thread of the tokio runtime
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| keyring::Entry::new(service, account)?.get_password())
+            .join()
+            .unwrap_or_else(|_| Err(keyring::Error::NoEntry))
+    })
+    .map_err(|e| {
+        VsixHarvesterError::CredentialError(format!("keyring:{}/{}: {}", service, account, e))
+    })
+}
+
+/// Read the secret of an entry of the OS credential store
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(service: &str, account: &str) -> Result<String> {
+    Err(VsixHarvesterError::CredentialError(format!(
+        "keyring:{}/{} requires a build with the keyring feature",
+        service, account
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_credentials() {
+        let credentials = resolve_credentials("alice:s3cr:et").unwrap();
+        assert_eq!(credentials.username, "alice");
+        assert_eq!(credentials.password, "s3cr:et");
+        assert!(!format!("{:?}", credentials).contains("s3cr"));
+
+        std::env::set_var("VSIXHARVESTER_TEST_PROXY_CREDENTIALS", "bob:hunter2");
+        let credentials = resolve_credentials("env:VSIXHARVESTER_TEST_PROXY_CREDENTIALS").unwrap();
+        assert_eq!(credentials.username, "bob");
+
+        let error = resolve_credentials("hunter2").unwrap_err().to_string();
+        assert!(!error.contains("hunter2"));
+        assert!(resolve_secret("env:VSIXHARVESTER_TEST_UNSET").is_err());
+    }
+}
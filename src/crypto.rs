@@ -0,0 +1,110 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::io;
+
+/// A SHA-256 computed over streamed content
+#[derive(Debug, Clone, Default)]
+pub struct Sha256Hasher(Sha256);
+
+impl Sha256Hasher {
+    /// Create a hasher of empty content
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash the next bytes of the content
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Get the digest of the hashed content
+    pub fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+impl io::Write for Sha256Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A SHA-512 computed over streamed content
+#[derive(Debug, Clone, Default)]
+pub struct Sha512Hasher(Sha512);
+
+impl Sha512Hasher {
+    /// Hash the next bytes of the content
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Get the digest of the hashed content
+    pub fn finalize(self) -> [u8; 64] {
+        self.0.finalize().into()
+    }
+}
+
+/// A BLAKE3 computed over streamed content
+#[derive(Debug, Clone, Default)]
+pub struct Blake3Hasher(blake3::Hasher);
+
+impl Blake3Hasher {
+    /// Hash the next bytes of the content
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Get the digest of the hashed content
+    pub fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+/// Compute the SHA-256 of some content
+pub fn sha256(content: &[u8]) -> [u8; 32] {
+    Sha256::digest(content).into()
+}
+
+/// Compute the SHA-512 of some content
+pub fn sha512(content: &[u8]) -> [u8; 64] {
+    Sha512::digest(content).into()
+}
+
+/// Compute the BLAKE3 of some content
+pub fn blake3(content: &[u8]) -> [u8; 32] {
+    blake3::hash(content).into()
+}
+
+/// Compute the HMAC-SHA256 of some data
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_primitives() {
+        let empty_sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(hex::encode(sha256(b"")), empty_sha256);
+        let mut hasher = Sha256Hasher::new();
+        hasher.write_all(b"abc").unwrap();
+        assert_eq!(hex::encode(hasher.finalize()), hex::encode(sha256(b"abc")));
+        assert_eq!(hex::encode(&sha512(b"abc")[..8]), "ddaf35a193617aba");
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
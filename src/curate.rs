@@ -0,0 +1,340 @@
+use crate::config::{API_URL, CURATION_PAGE_SIZE, MARKETPLACE_API_VERSION, USER_AGENT};
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::{Extension, Extensions};
+use crate::marketplace::{build_client, download_extension, send_request, DownloadOptions, Flags};
+use crate::pool::{TaskMeta, TaskPool};
+use crate::types::{Extension as MarketplaceExtension, MarketplaceResponse};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use log::{error, info};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+
+/// Order of the extensions returned by a curation query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CurationSort {
+    /// Most installed first
+    Installs,
+    /// Most installed over the last day first
+    TrendingDaily,
+    /// Most installed over the last week first
+    TrendingWeekly,
+    /// Most installed over the last month first
+    TrendingMonthly,
+    /// Best rated first
+    Rating,
+}
+
+impl CurationSort {
+    /// Get the `sortBy` value of the marketplace query
+    pub fn sort_by(self) -> u32 {
+        match self {
+            CurationSort::Installs => 4,
+            CurationSort::Rating => 6,
+            CurationSort::TrendingDaily => 7,
+            CurationSort::TrendingWeekly => 8,
+            CurationSort::TrendingMonthly => 9,
+        }
+    }
+
+    /// Get the name of the statistic the extensions are sorted by
+    pub fn statistic(self) -> &'static str {
+        match self {
+            CurationSort::Installs => "install",
+            CurationSort::Rating => "averagerating",
+            CurationSort::TrendingDaily => "trendingdaily",
+            CurationSort::TrendingWeekly => "trendingweekly",
+            CurationSort::TrendingMonthly => "trendingmonthly",
+        }
+    }
+}
+
+/// Statistics criteria of a curation query
+#[derive(Debug, Clone)]
+pub struct CurationQuery {
+    pub sort: CurationSort,
+    /// Minimum install count of the candidates
+    pub min_installs: u64,
+    /// Only the extensions released since this date
+    pub since: Option<NaiveDate>,
+    /// Optional search text (e.g., "python")
+    pub search: Option<String>,
+    /// Maximum number of candidates
+    pub limit: usize,
+}
+
+/// An extension matching the curation criteria
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CurationCandidate {
+    pub id: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    pub version: Option<String>,
+    pub installs: u64,
+    /// The value of the statistic the extensions are sorted by
+    pub score: f64,
+    #[serde(rename = "releaseDate")]
+    pub release_date: String,
+}
+
+/// Build the marketplace query of a curation
+///
+/// # Arguments
+///
+/// * `query` - The curation criteria
+/// * `minimal` - Whether to only ask for the latest version and the statistics
+///
+/// # Returns
+///
+/// The JSON payload of the extensionquery API
+pub fn build_curation_payload(query: &CurationQuery, minimal: bool) -> Value {
+    let mut criteria = vec![
+        json!({"filterType": 8, "value": "Microsoft.VisualStudio.Code"}),
+        // Exclude the unpublished extensions
+        json!({"filterType": 12, "value": Flags::UNPUBLISHED.bits().to_string()}),
+    ];
+    if let Some(search) = &query.search {
+        criteria.push(json!({"filterType": 10, "value": search}));
+    }
+    json!({
+        "filters": [{
+            "criteria": criteria,
+            "pageNumber": 1,
+            "pageSize": CURATION_PAGE_SIZE.max(query.limit),
+            "sortBy": query.sort.sort_by(),
+            "sortOrder": 0
+        }],
+        "flags": Flags::search(minimal).bits()
+    })
+}
+
+/// Select the candidates of a curation among the extensions returned by the marketplace
+///
+/// # Arguments
+///
+/// * `extensions` - The extensions in the marketplace order
+/// * `query` - The curation criteria
+/// * `excluded` - The lowercase identifiers already mirrored
+///
+/// # Returns
+///
+/// The candidates, at most `query.limit`
+pub fn select_candidates(
+    extensions: &[MarketplaceExtension],
+    query: &CurationQuery,
+    excluded: &HashSet<String>,
+) -> Vec<CurationCandidate> {
+    extensions
+        .iter()
+        .filter(|extension| !excluded.contains(&extension.get_identifier().to_lowercase()))
+        .filter(|extension| extension.get_install_count() >= query.min_installs)
+        .filter(|extension| {
+            query.since.is_none_or(|since| {
+                DateTime::parse_from_rfc3339(&extension.release_date).is_ok_and(|release_date| {
+                    release_date.with_timezone(&Utc).date_naive() >= since
+                })
+            })
+        })
+        .take(query.limit)
+        .map(|extension| CurationCandidate {
+            id: extension.get_identifier(),
+            display_name: extension.display_name.clone(),
+            version: extension
+                .versions
+                .first()
+                .map(|version| version.version.clone()),
+            installs: extension.get_install_count(),
+            score: extension
+                .get_statistic(query.sort.statistic())
+                .unwrap_or_default(),
+            release_date: extension.release_date.clone(),
+        })
+        .collect()
+}
+
+/// Get the lowercase identifiers listed in a manifest, empty if it cannot be read
+fn manifest_ids(manifest_path: &str) -> HashSet<String> {
+    fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Extensions>(&content).ok())
+        .map(|extensions| {
+            extensions
+                .entries()
+                .map(|entry| entry.id().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Query the marketplace for the extensions matching curation criteria
+///
+/// # Arguments
+///
+/// * `query` - The curation criteria
+/// * `manifest_path` - The manifest whose extensions are not candidates
+/// * `options` - The download options (proxy, polite mode)
+///
+/// # Returns
+///
+/// A Result containing the candidates or an error that occurred
+pub async fn query_candidates(
+    query: &CurationQuery,
+    manifest_path: &str,
+    options: &DownloadOptions,
+) -> Result<Vec<CurationCandidate>> {
+    let client = build_client(options)?;
+    let resp = send_request(
+        client
+            .post(API_URL)
+            .header("Content-Type", "application/json")
+            .header(
+                "Accept",
+                format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
+            )
+            .header("User-Agent", USER_AGENT)
+            .json(&build_curation_payload(query, options.minimal_requests)),
+        options,
+    )
+    .await?;
+    if !resp.status().is_success() {
+        error!("Failed curation query for Marketplace API");
+        return Err(VsixHarvesterError::ApiError(format!(
+            "curation query failed ({})",
+            resp.status()
+        )));
+    }
+    let response: MarketplaceResponse = resp.json().await?;
+    let extensions: Vec<MarketplaceExtension> = response
+        .results
+        .into_iter()
+        .flat_map(|result| result.extensions)
+        .collect();
+    Ok(select_candidates(
+        &extensions,
+        query,
+        &manifest_ids(manifest_path),
+    ))
+}
+
+/// Print the candidates of a curation query and optionally download them
+///
+/// # Arguments
+///
+/// * `query` - The curation criteria
+/// * `manifest_path` - The manifest whose extensions are not candidates
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent downloads
+/// * `download` - Whether to download the latest universal version of the candidates
+/// * `json` - Whether to print JSON instead of a table
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_candidates(
+    query: &CurationQuery,
+    manifest_path: &str,
+    options: &DownloadOptions,
+    concurrency: usize,
+    download: bool,
+    json: bool,
+) -> Result<()> {
+    let candidates = query_candidates(query, manifest_path, options).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&candidates)?);
+    } else {
+        println!(
+            "{:<50} {:<12} {:>12} {:>12}  RELEASED",
+            "EXTENSION", "VERSION", "INSTALLS", "SCORE"
+        );
+        for candidate in &candidates {
+            println!(
+                "{:<50} {:<12} {:>12} {:>12.2}  {}",
+                candidate.id,
+                candidate.version.as_deref().unwrap_or("-"),
+                candidate.installs,
+                candidate.score,
+                candidate.release_date.get(..10).unwrap_or_default()
+            );
+        }
+    }
+
+    if download {
+        let inputs = candidates
+            .iter()
+            .map(|candidate| (TaskMeta::new(&candidate.id, None), candidate.id.clone()))
+            .collect();
+        let options = Arc::new(options.clone());
+        let reports =
+            TaskPool::new(concurrency)
+                .cancellation(options.cancellation.clone())
+                .run(inputs, |id: String| {
+                    let options = options.clone();
+                    async move {
+                        download_extension(Extension::from_id(&id)?, &options, None, None).await
+                    }
+                })
+                .await;
+        for report in reports {
+            match report.result {
+                Ok(outcome) => info!("Candidate saved in {}", outcome.file_path),
+                Err(e) => error!("Error occurred when downloading {}: {}", report.meta, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn marketplace_extension(
+        name: &str,
+        installs: f64,
+        release_date: &str,
+    ) -> MarketplaceExtension {
+        test_support::metadata(
+            &format!("curated.{}", name),
+            json!({
+                "extensionId": name,
+                "lastUpdated": release_date,
+                "publishedDate": release_date,
+                "releaseDate": release_date,
+                "statistics": [
+                    {"statisticName": "install", "value": installs},
+                    {"statisticName": "trendingmonthly", "value": 12.5}
+                ]
+            }),
+        )
+    }
+
+    #[test]
+    fn test_select_candidates() {
+        let extensions = vec![
+            marketplace_extension("popular", 50000.0, "2025-02-01T10:00:00.5Z"),
+            marketplace_extension("old", 90000.0, "2019-05-01T10:00:00Z"),
+            marketplace_extension("niche", 20.0, "2025-03-01T10:00:00Z"),
+            marketplace_extension("mirrored", 70000.0, "2025-03-01T10:00:00Z"),
+        ];
+        let query = CurationQuery {
+            sort: CurationSort::TrendingMonthly,
+            min_installs: 1000,
+            since: NaiveDate::from_ymd_opt(2025, 1, 1),
+            search: None,
+            limit: 10,
+        };
+        let excluded = HashSet::from(["curated.mirrored".to_string()]);
+        let candidates = select_candidates(&extensions, &query, &excluded);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].id, "curated.popular");
+        assert_eq!(candidates[0].installs, 50000);
+        assert_eq!(candidates[0].score, 12.5);
+
+        let payload = build_curation_payload(&query, false);
+        assert_eq!(payload["filters"][0]["sortBy"], 9);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::architecture::{Architecture, TargetPlatform};
+use crate::error::Result;
+use crate::marketplace::{DownloadOptions, DownloadOutcome};
+use crate::provenance::copy_provenance;
+use crate::storage::{is_local_location, open_storage};
+use log::info;
+use std::convert::Infallible;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A directory the downloaded extensions are written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Destination {
+    /// The directory, or a remote storage (`s3://bucket/prefix`, `webdav://host/path`)
+    pub path: String,
+    /// The only platform written to this directory, all of them when None
+    pub platform: Option<Architecture>,
+}
+
+impl Destination {
+    /// Check if the package of a target platform is written to this destination
+    ///
+    /// # Arguments
+    ///
+    /// * `target_platform` - The target platform of the package, None for universal packages
+    pub fn matches(&self, target_platform: Option<&TargetPlatform>) -> bool {
+        self.platform
+            .is_none_or(|platform| platform.target_platform().as_ref() == target_platform)
+    }
+
+    /// Check if the destination is a directory of the local file system
+    pub fn is_local(&self) -> bool {
+        is_local_location(&self.path)
+    }
+}
+
+impl FromStr for Destination {
+    type Err = Infallible;
+
+    /// Parse a destination, either a directory or `platform=directory` (e.g., "linux_x64=/mnt/linux")
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let qualified = s.split_once('=').and_then(|(platform, path)| {
+            Architecture::from_cli_arg(platform).map(|platform| (platform, path))
+        });
+        Ok(match qualified {
+            Some((platform, path)) => Self {
+                path: path.to_string(),
+                platform: Some(platform),
+            },
+            None => Self {
+                path: s.to_string(),
+                platform: None,
+            },
+        })
+    }
+}
+
+/// Copy the downloaded packages to the additional destinations
+///
+/// Packages already present with the same size are not copied again.
+///
+/// # Arguments
+///
+/// * `destinations` - The additional destinations
+/// * `outcomes` - The successful downloads
+/// * `options` - The download options (proxy and connection tuning of remote storages)
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn copy_to_destinations(
+    destinations: &[Destination],
+    outcomes: &[DownloadOutcome],
+    options: &DownloadOptions,
+) -> Result<()> {
+    for destination in destinations {
+        let storage = open_storage(&destination.path, options)?;
+        for outcome in outcomes
+            .iter()
+            .filter(|outcome| destination.matches(outcome.target_platform.as_ref()))
+        {
+            let source = Path::new(&outcome.file_path);
+            let Some(file_name) = source.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let source_size = fs::metadata(source)?.len();
+            if storage.size(file_name).await? == Some(source_size) {
+                continue;
+            }
+            storage.write(file_name, &fs::read(source)?).await?;
+            if let Some(target) = storage.local_path(file_name) {
+                copy_provenance(&outcome.file_path, &target.to_string_lossy())?;
+            }
+            info!(
+                "Copied {} to {}",
+                outcome.file_path,
+                storage.location(file_name)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_destination() {
+        let destination: Destination = "linux_x64=/mnt/linux".parse().unwrap();
+        assert_eq!(destination.path, "/mnt/linux");
+        assert!(destination.matches(Some(&"linux-x64".into())));
+        assert!(!destination.matches(None));
+
+        let destination: Destination = "/mnt/a=b".parse().unwrap();
+        assert_eq!(destination.path, "/mnt/a=b");
+        assert!(destination.matches(Some(&"win32-x64".into())));
+        assert!(destination.matches(None));
+
+        let destination: Destination = "win32_x64=s3://mirror/windows".parse().unwrap();
+        assert_eq!(destination.path, "s3://mirror/windows");
+        assert!(!destination.is_local());
+    }
+
+    #[test]
+    fn test_copy_to_destinations() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().display().to_string();
+        let outcome = |file_name: &str, target_platform: Option<&str>| {
+            let file_path = format!("{}/{}", root, file_name);
+            fs::write(&file_path, file_name).unwrap();
+            DownloadOutcome {
+                id: "golang.Go".to_string(),
+                version: "0.41.2".to_string(),
+                target_platform: target_platform.map(TargetPlatform::new),
+                file_path,
+                download_url: String::new(),
+                sha256: None,
+                digest: None,
+            }
+        };
+        let outcomes = [
+            outcome("golang.Go-0.41.2.vsix", None),
+            outcome("golang.Go-0.41.2@linux-x64.vsix", Some("linux-x64")),
+        ];
+        let destinations = [
+            format!("{}/nfs", root).parse().unwrap(),
+            format!("linux_x64={}/linux", root).parse().unwrap(),
+        ];
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(copy_to_destinations(
+                &destinations,
+                &outcomes,
+                &DownloadOptions::default(),
+            ))
+            .unwrap();
+        assert!(Path::new(&format!("{}/nfs/golang.Go-0.41.2.vsix", root)).exists());
+        assert!(Path::new(&format!("{}/nfs/golang.Go-0.41.2@linux-x64.vsix", root)).exists());
+        assert!(!Path::new(&format!("{}/linux/golang.Go-0.41.2.vsix", root)).exists());
+        assert!(Path::new(&format!("{}/linux/golang.Go-0.41.2@linux-x64.vsix", root)).exists());
+    }
+}
@@ -0,0 +1,290 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::marketplace::ResolvedExtension;
+use crate::status::MirroredPackage;
+use crate::types::compare_versions;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How the changes of a dry run are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// A plain list for terminals
+    #[default]
+    Text,
+    /// A Markdown table, ready to post as a pull request comment
+    Markdown,
+    /// JSON for tooling
+    Json,
+}
+
+/// A change the harvest would make to the mirror
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorChange {
+    pub id: String,
+    pub target_platform: Option<TargetPlatform>,
+    /// The newest mirrored version, None for additions
+    pub from_version: Option<String>,
+    /// The resolved version, None for removals
+    pub to_version: Option<String>,
+    /// The bytes to download, or the bytes of the mirrored packages for removals
+    pub size: u64,
+}
+
+/// The changes a harvest would make to the mirror
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct MirrorDiff {
+    pub added: Vec<MirrorChange>,
+    pub updated: Vec<MirrorChange>,
+    pub removed: Vec<MirrorChange>,
+}
+
+impl MirrorDiff {
+    /// Compare the resolved extensions with the packages of the mirror
+    ///
+    /// Only the platforms harvested by the run are compared, the packages of the
+    /// other platforms are never reported as removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `packages` - The packages of the mirror
+    /// * `sized` - The resolved extensions with their download size
+    ///
+    /// # Returns
+    ///
+    /// The additions, updates and removals, sorted by extension and platform
+    pub fn new(packages: &[MirroredPackage], sized: &[(ResolvedExtension, u64)]) -> Self {
+        let key = |id: &str, target_platform: &Option<TargetPlatform>| {
+            (id.to_lowercase(), target_platform.clone())
+        };
+        let mut mirrored: BTreeMap<_, Vec<&MirroredPackage>> = BTreeMap::new();
+        for package in packages {
+            mirrored
+                .entry(key(&package.id, &package.target_platform))
+                .or_default()
+                .push(package);
+        }
+        let platforms: BTreeSet<_> = sized
+            .iter()
+            .map(|(resolved, _)| resolved.target_platform.clone())
+            .collect();
+
+        let mut diff = Self::default();
+        for (resolved, size) in sized {
+            let versions = mirrored
+                .remove(&key(&resolved.id, &resolved.target_platform))
+                .unwrap_or_default();
+            if versions
+                .iter()
+                .any(|package| package.version == resolved.version)
+            {
+                continue;
+            }
+            let change = MirrorChange {
+                id: resolved.id.clone(),
+                target_platform: resolved.target_platform.clone(),
+                from_version: newest(&versions).map(|package| package.version.clone()),
+                to_version: Some(resolved.version.clone()),
+                size: *size,
+            };
+            if change.from_version.is_some() {
+                diff.updated.push(change);
+            } else {
+                diff.added.push(change);
+            }
+        }
+        for ((_, target_platform), versions) in mirrored {
+            if !platforms.contains(&target_platform) {
+                continue;
+            }
+            let Some(package) = newest(&versions) else {
+                continue;
+            };
+            diff.removed.push(MirrorChange {
+                id: package.id.clone(),
+                target_platform,
+                from_version: Some(package.version.clone()),
+                to_version: None,
+                size: versions.iter().map(|package| package.size).sum(),
+            });
+        }
+        for changes in [&mut diff.added, &mut diff.updated, &mut diff.removed] {
+            changes.sort_by(|a, b| {
+                (a.id.to_lowercase(), &a.target_platform)
+                    .cmp(&(b.id.to_lowercase(), &b.target_platform))
+            });
+        }
+        diff
+    }
+
+    /// Check if the harvest would not change the mirror
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+
+    /// Render the changes in a format
+    pub fn render(&self, format: DiffFormat) -> String {
+        match format {
+            DiffFormat::Text => self.render_text(),
+            DiffFormat::Markdown => self.render_markdown(),
+            DiffFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+        }
+    }
+
+    /// Summarize the number of changes and the sizes (e.g., "1 addition, 2 updates, ...")
+    fn summary(&self) -> String {
+        let count = |changes: &[MirrorChange], singular: &str, plural: &str| {
+            format!(
+                "{} {}",
+                changes.len(),
+                if changes.len() == 1 { singular } else { plural }
+            )
+        };
+        let download: u64 = self.added.iter().chain(&self.updated).map(|c| c.size).sum();
+        let removed: u64 = self.removed.iter().map(|c| c.size).sum();
+        format!(
+            "{}, {}, {} ({} to download, {} removed)",
+            count(&self.added, "addition", "additions"),
+            count(&self.updated, "update", "updates"),
+            count(&self.removed, "removal", "removals"),
+            format_size(download),
+            format_size(removed)
+        )
+    }
+
+    /// Every change with its kind, additions first
+    fn changes(&self) -> impl Iterator<Item = (&'static str, &MirrorChange)> {
+        self.added
+            .iter()
+            .map(|change| ("Added", change))
+            .chain(self.updated.iter().map(|change| ("Updated", change)))
+            .chain(self.removed.iter().map(|change| ("Removed", change)))
+    }
+
+    fn render_text(&self) -> String {
+        if self.is_empty() {
+            return "No change to the mirror\n".to_string();
+        }
+        let mut text = format!("{}\n", self.summary());
+        for (kind, change) in self.changes() {
+            text.push_str(&format!(
+                "  {:<8} {} ({}) {} [{}]\n",
+                kind,
+                change.id,
+                platform_name(change),
+                version_change(change, "->"),
+                format_size(change.size)
+            ));
+        }
+        text
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut markdown = "### Mirror changes\n\n".to_string();
+        if self.is_empty() {
+            markdown.push_str("No change to the mirror.\n");
+            return markdown;
+        }
+        markdown.push_str(&format!("{}.\n\n", self.summary()));
+        markdown.push_str("| Change | Extension | Platform | Version | Size |\n");
+        markdown.push_str("| --- | --- | --- | --- | ---: |\n");
+        for (kind, change) in self.changes() {
+            markdown.push_str(&format!(
+                "| {} | `{}` | {} | {} | {} |\n",
+                kind,
+                change.id,
+                platform_name(change),
+                version_change(change, "→"),
+                format_size(change.size)
+            ));
+        }
+        markdown
+    }
+}
+
+/// Get the newest of the mirrored versions of an extension
+fn newest<'a>(versions: &[&'a MirroredPackage]) -> Option<&'a MirroredPackage> {
+    versions
+        .iter()
+        .copied()
+        .max_by(|a, b| compare_versions(&a.version, &b.version).cmp(&0))
+}
+
+/// Get the platform of a change, "universal" for universal packages
+fn platform_name(change: &MirrorChange) -> &str {
+    target_platform_name(change.target_platform.as_ref())
+}
+
+/// Describe the versions of a change (e.g., "0.41.1 -> 0.41.2")
+fn version_change(change: &MirrorChange, arrow: &str) -> String {
+    match (&change.from_version, &change.to_version) {
+        (Some(from), Some(to)) => format!("{} {} {}", from, arrow, to),
+        (None, Some(version)) | (Some(version), None) => version.clone(),
+        (None, None) => String::new(),
+    }
+}
+
+/// Format a number of bytes with a binary unit (e.g., "1.5 MiB")
+pub fn format_size(size: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn package(id: &str, version: &str, target_platform: Option<&str>) -> MirroredPackage {
+        test_support::mirrored_package(id, version, target_platform, 3 << 20)
+    }
+
+    fn resolved(id: &str, version: &str, size: u64) -> (ResolvedExtension, u64) {
+        (test_support::resolved(id, version), size)
+    }
+
+    #[test]
+    fn test_mirror_diff_markdown() {
+        let packages = [
+            package("golang.Go", "0.41.1", None),
+            package("golang.Go", "0.40.0", None),
+            package("ms-python.python", "2025.4.0", None),
+            package("rust-lang.rust-analyzer", "0.3.2500", Some("linux-x64")),
+            package("vscjava.vscode-java-pack", "0.29.0", None),
+        ];
+        let sized = [
+            resolved("golang.Go", "0.41.2", 5 << 20),
+            resolved("ms-python.python", "2025.4.0", 0),
+            resolved("redhat.vscode-yaml", "1.17.0", 1536),
+        ];
+        let diff = MirrorDiff::new(&packages, &sized);
+        assert_eq!(diff.updated[0].from_version.as_deref(), Some("0.41.1"));
+        assert_eq!(
+            diff.render(DiffFormat::Markdown),
+            "### Mirror changes
+
+1 addition, 1 update, 1 removal (5.0 MiB to download, 3.0 MiB removed).
+
+| Change | Extension | Platform | Version | Size |
+| --- | --- | --- | --- | ---: |
+| Added | `redhat.vscode-yaml` | universal | 1.17.0 | 1.5 KiB |
+| Updated | `golang.Go` | universal | 0.41.1 → 0.41.2 | 5.0 MiB |
+| Removed | `vscjava.vscode-java-pack` | universal | 0.29.0 | 3.0 MiB |
+"
+        );
+        assert_eq!(
+            MirrorDiff::new(&packages[2..3], &sized[1..2]).render(DiffFormat::Text),
+            "No change to the mirror\n"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use serde::Serialize;
 pub use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum VsixHarvesterError {
@@ -16,10 +17,237 @@ pub enum VsixHarvesterError {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("No version of {0} is compatible with engine {1} (latest requires {2})")]
+    NoCompatibleVersion(String, String, String),
+
+    #[error("Environment variable {0} is not set")]
+    UndefinedVariable(String),
+
+    #[error("Unterminated placeholder in {0}")]
+    InvalidPlaceholder(String),
+
+    #[error("No VS Code language pack for locale {0}")]
+    InvalidLocale(String),
+
+    #[error("Invalid VSIX package: {0}")]
+    InvalidVsix(String),
+
+    #[error("ZIP error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
     #[error("Failed to query marketplace API: {0}")]
     ApiError(String),
 
     #[error("Failed to download extension: {0}")]
     DownloadError(String),
+
+    #[error("Task timed out after {0} seconds")]
+    Timeout(u64),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("Invalid upgrade filter: {0}")]
+    InvalidUpgradeFilter(String),
+
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+
+    #[error("Harvest needs {0} bytes, exceeding the size budget of {1} bytes")]
+    SizeBudgetExceeded(u64, u64),
+
+    #[error("Invalid publish target: {0}")]
+    InvalidPublishTarget(String),
+
+    #[error("Failed to publish: {0}")]
+    PublishError(String),
+
+    #[error("Hash mismatch for {0}: pinned {1}, downloaded {2}")]
+    HashMismatch(String, String, String),
+
+    #[error("Invalid or failed fleet profile: {0}")]
+    InvalidFleetProfile(String),
+
+    #[error("Quarantined {0}: {1}")]
+    Quarantined(String, String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Resolution differs from the lockfile: {0}")]
+    LockfileMismatch(String),
+
+    #[error("Failed to read credentials: {0}")]
+    CredentialError(String),
+
+    #[error("Signature check failed for {0}: {1}")]
+    SignatureMismatch(String, String),
+
+    #[error("Target platform {0} is not supported by {1} (supported: {2})")]
+    UnsupportedTargetPlatform(String, String, String),
+
+    #[error("Harvest policy violated: {0}")]
+    PolicyViolation(String),
+
+    #[error("Failed to install: {0}")]
+    InstallError(String),
+
+    #[error("Invalid collection: {0}")]
+    InvalidCollection(String),
 }
 pub type Result<T> = std::result::Result<T, VsixHarvesterError>;
+
+impl VsixHarvesterError {
+    /// Get the stable code of the error for tooling (e.g., "hash_mismatch")
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidArchitecture(_) => "invalid_architecture",
+            Self::InvalidExtensionId(_) => "invalid_extension_id",
+            Self::IoError(_) => "io_error",
+            Self::HttpError(_) => "http_error",
+            Self::JsonError(_) => "json_error",
+            Self::NoCompatibleVersion(..) => "no_compatible_version",
+            Self::UndefinedVariable(_) => "undefined_variable",
+            Self::InvalidPlaceholder(_) => "invalid_placeholder",
+            Self::InvalidLocale(_) => "invalid_locale",
+            Self::InvalidVsix(_) => "invalid_vsix",
+            Self::ZipError(_) => "zip_error",
+            Self::ApiError(_) => "api_error",
+            Self::DownloadError(_) => "download_error",
+            Self::Timeout(_) => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::InvalidUpgradeFilter(_) => "invalid_upgrade_filter",
+            Self::InvalidSize(_) => "invalid_size",
+            Self::SizeBudgetExceeded(..) => "size_budget_exceeded",
+            Self::InvalidPublishTarget(_) => "invalid_publish_target",
+            Self::PublishError(_) => "publish_error",
+            Self::HashMismatch(..) => "hash_mismatch",
+            Self::InvalidFleetProfile(_) => "invalid_fleet_profile",
+            Self::Quarantined(..) => "quarantined",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::StorageError(_) => "storage_error",
+            Self::LockfileMismatch(_) => "lockfile_mismatch",
+            Self::CredentialError(_) => "credential_error",
+            Self::SignatureMismatch(..) => "signature_mismatch",
+            Self::UnsupportedTargetPlatform(..) => "unsupported_target_platform",
+            Self::PolicyViolation(_) => "policy_violation",
+            Self::InstallError(_) => "install_error",
+            Self::InvalidCollection(_) => "invalid_collection",
+        }
+    }
+
+    /// Get the phase of the harvest the error belongs to
+    pub fn phase(&self) -> &'static str {
+        match self {
+            Self::InvalidArchitecture(_)
+            | Self::InvalidExtensionId(_)
+            | Self::JsonError(_)
+            | Self::UndefinedVariable(_)
+            | Self::InvalidPlaceholder(_)
+            | Self::InvalidLocale(_)
+            | Self::InvalidUpgradeFilter(_)
+            | Self::InvalidSize(_)
+            | Self::InvalidFleetProfile(_)
+            | Self::InvalidCollection(_)
+            | Self::InvalidPublishTarget(_)
+            | Self::InvalidArgument(_)
+            | Self::CredentialError(_)
+            | Self::UnsupportedTargetPlatform(..) => "configuration",
+            Self::NoCompatibleVersion(..)
+            | Self::ApiError(_)
+            | Self::SizeBudgetExceeded(..)
+            | Self::LockfileMismatch(_)
+            | Self::PolicyViolation(_) => "resolution",
+            Self::HttpError(_)
+            | Self::DownloadError(_)
+            | Self::Timeout(_)
+            | Self::Cancelled
+            | Self::InvalidVsix(_)
+            | Self::ZipError(_)
+            | Self::HashMismatch(..)
+            | Self::SignatureMismatch(..)
+            | Self::Quarantined(..) => "download",
+            Self::PublishError(_) => "publish",
+            Self::IoError(_) | Self::StorageError(_) | Self::InstallError(_) => "io",
+        }
+    }
+
+    /// Check if the operation may succeed when retried
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::HttpError(_) | Self::DownloadError(_) | Self::Timeout(_)
+        )
+    }
+
+    /// Get the extension concerned by the error, when it is known
+    pub fn extension(&self) -> Option<String> {
+        match self {
+            Self::InvalidExtensionId(id) | Self::NoCompatibleVersion(id, ..) => Some(id.clone()),
+            Self::DownloadError(id) if id.split_once('.').is_some() && !id.contains(' ') => {
+                Some(id.clone())
+            }
+            Self::HashMismatch(package, ..) | Self::SignatureMismatch(package, _) => Some(
+                package
+                    .split_once('@')
+                    .map_or(package.as_str(), |(id, _)| id)
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Machine-parsable description of a failure, printed on stderr with `--errors-json`
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub extension: Option<String>,
+    pub phase: &'static str,
+    pub retryable: bool,
+}
+
+impl From<&VsixHarvesterError> for ErrorReport {
+    fn from(error: &VsixHarvesterError) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            extension: error.extension(),
+            phase: error.phase(),
+            retryable: error.is_retryable(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_report() {
+        let error = VsixHarvesterError::HashMismatch(
+            "golang.Go@0.41.2".to_string(),
+            "aa".to_string(),
+            "bb".to_string(),
+        );
+        let report = ErrorReport::from(&error);
+        assert_eq!(report.code, "hash_mismatch");
+        assert_eq!(report.extension.as_deref(), Some("golang.Go"));
+        assert_eq!(report.phase, "download");
+        assert!(!report.retryable);
+
+        let report = ErrorReport::from(&VsixHarvesterError::DownloadError("golang.Go".to_string()));
+        assert_eq!(report.extension.as_deref(), Some("golang.Go"));
+        assert!(report.retryable);
+        let report = ErrorReport::from(&VsixHarvesterError::Timeout(30));
+        assert_eq!(report.extension, None);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["code"],
+            serde_json::json!("timeout")
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use crate::architecture::TargetPlatform;
+use crate::create_directory_if_not_exists;
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::lockfile::{LockEntry, Lockfile};
+use crate::marketplace::build_download_url_and_file_path;
+use crate::naming::NamingPreset;
+use crate::temp::write_atomic;
+use clap::ValueEnum;
+use log::info;
+use serde::Serialize;
+use std::path::Path;
+
+/// Formats the lockfile can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Nix list consumable by vscode-utils.extensionsFromVscodeMarketplace
+    Nix,
+    /// Ansible variables file (YAML)
+    Ansible,
+    /// Terraform variables file (JSON)
+    Terraform,
+    /// Directory of per-extension install manifests for Windows deployment tools (Intune, SCCM)
+    Windows,
+}
+
+impl ExportFormat {
+    /// Get the default output path of the format
+    pub fn default_output(&self) -> &'static str {
+        match self {
+            Self::Nix => "./extensions.nix",
+            Self::Ansible => "./vscode_extensions.yml",
+            Self::Terraform => "./vscode_extensions.auto.tfvars.json",
+            Self::Windows => "./windows-manifests",
+        }
+    }
+}
+
+/// Variable holding the extensions in the infrastructure as code exports
+pub const IAC_VARIABLE_NAME: &str = "vscode_extensions";
+
+/// A harvested file as exposed to infrastructure as code tools
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedExtension {
+    pub id: String,
+    pub publisher: String,
+    pub name: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    /// The path of the VSIX file in the destination directory
+    pub file: String,
+    pub sha256: String,
+}
+
+impl ExportedExtension {
+    /// Build the exported extension of a locked entry
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The locked entry
+    /// * `destination` - The directory where the extensions are saved
+    /// * `naming` - The file naming convention of the packages
+    pub fn from_lock_entry(entry: &LockEntry, destination: &str, naming: NamingPreset) -> Self {
+        let (publisher, name) = entry.id.split_once('.').unwrap_or((&entry.id, ""));
+        let (_, file) = build_download_url_and_file_path(
+            Extension {
+                publisher,
+                name,
+                version: None,
+            },
+            &entry.version,
+            destination,
+            entry.target_platform.as_ref(),
+            naming,
+        );
+        Self {
+            id: entry.id.clone(),
+            publisher: publisher.to_string(),
+            name: name.to_string(),
+            version: entry.version.clone(),
+            target_platform: entry.target_platform.clone(),
+            file,
+            sha256: entry.sha256.clone(),
+        }
+    }
+}
+
+/// Render the extensions as an Ansible variables file
+///
+/// Scalars are written as JSON strings, which are valid YAML double quoted scalars.
+pub fn render_ansible(extensions: &[ExportedExtension]) -> Result<String> {
+    let mut yaml = String::from(
+        "# Generated by vsixHarvester from the lockfile, do not edit
+---
+",
+    );
+    yaml.push_str(&format!("{}:", IAC_VARIABLE_NAME));
+    if extensions.is_empty() {
+        yaml.push_str(
+            " []
+",
+        );
+        return Ok(yaml);
+    }
+    yaml.push('\n');
+    for extension in extensions {
+        let value = serde_json::to_value(extension)?;
+        let mut first = true;
+        for (key, value) in value.as_object().into_iter().flatten() {
+            let prefix = if first { "  - " } else { "    " };
+            first = false;
+            yaml.push_str(&format!("{}{}: {}\n", prefix, key, value));
+        }
+    }
+    Ok(yaml)
+}
+
+/// Render the extensions as a Terraform JSON variables file
+pub fn render_terraform(extensions: &[ExportedExtension]) -> Result<String> {
+    let variables = serde_json::json!({ IAC_VARIABLE_NAME: extensions });
+    Ok(serde_json::to_string_pretty(&variables)? + "\n")
+}
+
+/// Install manifest of an extension for Windows software deployment tools
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowsInstallManifest {
+    pub id: String,
+    pub publisher: String,
+    pub name: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    /// The name of the VSIX file to ship with the package
+    pub file: String,
+    pub sha256: String,
+    /// Silent install command, run from the directory containing the VSIX file
+    pub install_command: String,
+    pub uninstall_command: String,
+    /// Folder created by VS Code once the extension is installed, usable as a detection rule
+    pub detection_path: String,
+}
+
+impl WindowsInstallManifest {
+    /// Build the install manifest of an exported extension
+    pub fn from_exported(extension: &ExportedExtension) -> Self {
+        let file = Path::new(&extension.file)
+            .file_name()
+            .map_or(extension.file.clone(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        Self {
+            id: extension.id.clone(),
+            publisher: extension.publisher.clone(),
+            name: extension.name.clone(),
+            version: extension.version.clone(),
+            target_platform: extension.target_platform.clone(),
+            install_command: format!("code --install-extension \"{}\" --force", file),
+            uninstall_command: format!("code --uninstall-extension {}", extension.id),
+            detection_path: format!(
+                "%USERPROFILE%\\.vscode\\extensions\\{}-{}",
+                extension.id.to_lowercase(),
+                extension.version
+            ),
+            file,
+            sha256: extension.sha256.clone(),
+        }
+    }
+
+    /// Get the name of the manifest file
+    pub fn file_name(&self) -> String {
+        match &self.target_platform {
+            Some(target_platform) => format!("{}@{}.json", self.id, target_platform),
+            None => format!("{}.json", self.id),
+        }
+    }
+}
+
+/// Write one install manifest per Windows compatible extension (universal or win32 packages)
+///
+/// # Arguments
+///
+/// * `extensions` - The exported extensions
+/// * `output` - The output directory
+///
+/// # Returns
+///
+/// A Result containing the number of written manifests or an error that occurred
+pub fn write_windows_manifests(extensions: &[ExportedExtension], output: &str) -> Result<usize> {
+    create_directory_if_not_exists(output)?;
+    let mut written = 0;
+    for extension in extensions.iter().filter(|extension| {
+        extension
+            .target_platform
+            .as_ref()
+            .is_none_or(|target_platform| target_platform.as_str().starts_with("win32"))
+    }) {
+        let manifest = WindowsInstallManifest::from_exported(extension);
+        write_atomic(
+            Path::new(&format!("{}/{}", output, manifest.file_name())),
+            serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Quote a string for Nix
+fn nix_string(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace("${", "\\${")
+    )
+}
+
+/// Render the lockfile entries as a Nix list of marketplace references
+///
+/// # Arguments
+///
+/// * `entries` - The locked entries
+///
+/// # Returns
+///
+/// The Nix expression
+pub fn render_nix(entries: &[LockEntry]) -> String {
+    let mut nix = String::from("# Generated by vsixHarvester from the lockfile, do not edit\n[\n");
+    for entry in entries {
+        let (publisher, name) = entry.id.split_once('.').unwrap_or((&entry.id, ""));
+        nix.push_str("  {\n");
+        nix.push_str(&format!("    name = {};\n", nix_string(name)));
+        nix.push_str(&format!("    publisher = {};\n", nix_string(publisher)));
+        nix.push_str(&format!("    version = {};\n", nix_string(&entry.version)));
+        if let Some(target_platform) = &entry.target_platform {
+            nix.push_str(&format!(
+                "    arch = {};\n",
+                nix_string(target_platform.as_str())
+            ));
+        }
+        nix.push_str(&format!("    sha256 = {};\n", nix_string(&entry.sha256)));
+        nix.push_str("  }\n");
+    }
+    nix.push_str("]\n");
+    nix
+}
+
+/// Export the lockfile to another format
+///
+/// # Arguments
+///
+/// * `lockfile_path` - The path of the lockfile
+/// * `format` - The export format
+/// * `output` - Optional output path, defaults to the format default path
+/// * `destination` - The directory where the extensions are saved
+/// * `naming` - The file naming convention of the packages
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn export_lockfile(
+    lockfile_path: &str,
+    format: ExportFormat,
+    output: Option<&str>,
+    destination: &str,
+    naming: NamingPreset,
+) -> Result<()> {
+    let mut lockfile = Lockfile::load(lockfile_path)?;
+    lockfile.sort();
+    let output = output.unwrap_or(format.default_output());
+    let exported = || -> Vec<ExportedExtension> {
+        lockfile
+            .extensions
+            .iter()
+            .map(|entry| ExportedExtension::from_lock_entry(entry, destination, naming))
+            .collect()
+    };
+    let content = match format {
+        ExportFormat::Nix => render_nix(&lockfile.extensions),
+        ExportFormat::Ansible => render_ansible(&exported())?,
+        ExportFormat::Terraform => render_terraform(&exported())?,
+        ExportFormat::Windows => {
+            let written = write_windows_manifests(&exported(), output)?;
+            info!("Exported {} install manifests to {}", written, output);
+            return Ok(());
+        }
+    };
+    write_atomic(Path::new(output), content.as_bytes())?;
+    info!(
+        "Exported {} entries of {} to {}",
+        lockfile.extensions.len(),
+        lockfile_path,
+        output
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_nix() {
+        let entries = vec![LockEntry {
+            id: "rust-lang.rust-analyzer".to_string(),
+            version: "0.3.2500".to_string(),
+            target_platform: Some("linux-x64".into()),
+            download_url: None,
+            sha256: "abc123".to_string(),
+            digest: None,
+            locked_at: None,
+        }];
+        assert_eq!(
+            render_nix(&entries),
+            r#"# Generated by vsixHarvester from the lockfile, do not edit
+[
+  {
+    name = "rust-analyzer";
+    publisher = "rust-lang";
+    version = "0.3.2500";
+    arch = "linux-x64";
+    sha256 = "abc123";
+  }
+]
+"#
+        );
+    }
+
+    fn exported() -> Vec<ExportedExtension> {
+        let entry = LockEntry {
+            id: "golang.Go".to_string(),
+            version: "0.41.2".to_string(),
+            target_platform: None,
+            download_url: None,
+            sha256: "abc123".to_string(),
+            digest: None,
+            locked_at: None,
+        };
+        vec![ExportedExtension::from_lock_entry(
+            &entry,
+            "./extensions",
+            NamingPreset::Harvester,
+        )]
+    }
+
+    #[test]
+    fn test_render_ansible() {
+        assert_eq!(
+            render_ansible(&exported()).unwrap(),
+            r#"# Generated by vsixHarvester from the lockfile, do not edit
+---
+vscode_extensions:
+  - file: "./extensions/golang.Go-0.41.2.vsix"
+    id: "golang.Go"
+    name: "Go"
+    publisher: "golang"
+    sha256: "abc123"
+    target_platform: null
+    version: "0.41.2"
+"#
+        );
+        assert!(render_ansible(&[])
+            .unwrap()
+            .ends_with("vscode_extensions: []\n"));
+    }
+
+    #[test]
+    fn test_render_terraform() {
+        let rendered: serde_json::Value =
+            serde_json::from_str(&render_terraform(&exported()).unwrap()).unwrap();
+        assert_eq!(
+            rendered["vscode_extensions"][0]["file"],
+            "./extensions/golang.Go-0.41.2.vsix"
+        );
+    }
+
+    #[test]
+    fn test_windows_install_manifest() {
+        let manifest = WindowsInstallManifest::from_exported(&exported()[0]);
+        assert_eq!(manifest.file, "golang.Go-0.41.2.vsix");
+        assert_eq!(
+            manifest.install_command,
+            "code --install-extension \"golang.Go-0.41.2.vsix\" --force"
+        );
+        assert_eq!(
+            manifest.detection_path,
+            "%USERPROFILE%\\.vscode\\extensions\\golang.go-0.41.2"
+        );
+        assert_eq!(manifest.file_name(), "golang.Go.json");
+    }
+
+    #[test]
+    fn test_nix_string_escaping() {
+        assert_eq!(nix_string(r#"a"b${c}\"#), r#""a\"b\${c}\\""#);
+    }
+}
@@ -1,41 +1,506 @@
 pub use serde::Deserialize;
 
-use crate::error::VsixHarvesterError;
+use crate::error::{Result, VsixHarvesterError};
+use crate::lockfile::UpdatePolicy;
+use crate::temp::write_atomic;
+use serde::de::Error as _;
+use serde::Deserializer;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 #[derive(Clone)]
 pub struct Extension<'a> {
     pub publisher: &'a str,
     pub name: &'a str,
+    /// Optional pinned version, downloaded instead of the latest one
+    pub version: Option<&'a str>,
+}
+
+/// Split the pinned version of an extension identifier (e.g., "golang.Go@0.41.2")
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier, optionally followed by `@version`
+///
+/// # Returns
+///
+/// A tuple of the identifier without version and the optional version
+pub fn split_version(id: &str) -> (&str, Option<&str>) {
+    match id.split_once('@') {
+        Some((id, version)) if !version.is_empty() => (id, Some(version)),
+        Some((id, _)) => (id, None),
+        None => (id, None),
+    }
+}
+
+/// Check if an extension identifier is a Marketplace extension GUID (`extensionId`)
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier, without pinned version
+///
+/// # Returns
+///
+/// true for a GUID (e.g., "d6f6cfea-4b6f-41f4-b571-6ad2ab7918da"), false for `publisher.name`
+pub fn is_extension_guid(id: &str) -> bool {
+    let groups: Vec<&str> = id.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
 }
 
 impl<'a> Extension<'a> {
     pub fn from_id(id: &'a str) -> std::result::Result<Self, VsixHarvesterError> {
-        let parts: Vec<&str> = id.split('.').collect();
+        let (bare_id, version) = split_version(id);
+        let parts: Vec<&str> = bare_id.split('.').collect();
         if parts.len() != 2 {
             return Err(VsixHarvesterError::InvalidExtensionId(id.to_string()));
         }
         Ok(Self {
             publisher: parts[0],
             name: parts[1],
+            version,
         })
     }
 
     pub fn to_id(&self) -> String {
         format!("{}.{}", self.publisher, self.name)
     }
+}
+
+impl std::fmt::Display for Extension<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Publisher: {}, Name: {}", self.publisher, self.name)
+    }
+}
+
+/// An entry of a platform list of the manifest
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ManifestEntry {
+    /// The extension identifier (e.g., "golang.Go"), optionally pinned (e.g., "golang.Go@0.41.2")
+    Id(String),
+    /// The extension identifier with per-entry settings
+    Detailed(DetailedEntry),
+}
+
+/// A manifest entry with per-entry settings
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DetailedEntry {
+    /// The extension identifier (e.g., "golang.Go")
+    pub id: String,
+    /// Optional pinned version, downloaded instead of the latest one
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Entries with a higher priority are resolved and downloaded first
+    #[serde(default)]
+    pub priority: i32,
+    /// How far the locked version may be updated by a sync
+    #[serde(rename = "updatePolicy", default)]
+    pub update_policy: UpdatePolicy,
+    /// Optional URL of a patched build downloaded instead of the registry package,
+    /// requires `version` and `sha256`
+    #[serde(rename = "overrideUrl", default)]
+    pub override_url: Option<String>,
+    /// The SHA-256 the package of `overrideUrl` must have
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// A patched build replacing the registry package of an extension version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageOverride {
+    /// The URL of the package
+    pub url: String,
+    /// The SHA-256 the package must have
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    /// Gets the extension identifier of the entry, without pinned version
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Id(id) => split_version(id).0,
+            Self::Detailed(entry) => split_version(&entry.id).0,
+        }
+    }
+
+    /// Gets the pinned version of the entry, None to download the latest one
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            Self::Id(id) => split_version(id).1,
+            Self::Detailed(entry) => entry.version.as_deref().or(split_version(&entry.id).1),
+        }
+    }
+
+    /// Replace the extension identifier of the entry, keeping its pinned version and settings
+    pub fn set_id(&mut self, id: &str) {
+        let entry_id = match self {
+            Self::Id(entry_id) => entry_id,
+            Self::Detailed(entry) => &mut entry.id,
+        };
+        *entry_id = match split_version(entry_id).1 {
+            Some(version) => format!("{}@{}", id, version),
+            None => id.to_string(),
+        };
+    }
+
+    /// Gets the priority of the entry, 0 by default
+    pub fn priority(&self) -> i32 {
+        match self {
+            Self::Id(_) => 0,
+            Self::Detailed(entry) => entry.priority,
+        }
+    }
+
+    /// Gets the patched build replacing the registry package of the entry
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the override, None without `overrideUrl`, or an error if
+    /// the entry does not pin the version and the SHA-256 of the build
+    pub fn package_override(&self) -> Result<Option<PackageOverride>> {
+        let Self::Detailed(entry) = self else {
+            return Ok(None);
+        };
+        let Some(url) = &entry.override_url else {
+            return Ok(None);
+        };
+        let invalid = |reason: &str| {
+            VsixHarvesterError::InvalidArgument(format!("{}: overrideUrl {}", self.id(), reason))
+        };
+        if self.version().is_none() {
+            return Err(invalid("requires a pinned version"));
+        }
+        match &entry.sha256 {
+            Some(sha256) if sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Ok(Some(PackageOverride {
+                    url: url.clone(),
+                    sha256: sha256.to_lowercase(),
+                }))
+            }
+            Some(_) => Err(invalid("requires a hexadecimal sha256")),
+            None => Err(invalid("requires a sha256")),
+        }
+    }
 
-    #[allow(dead_code)]
-    pub fn to_string(&self) -> String {
-        format!("Publisher: {}, Name: {}", self.publisher, self.name)
+    /// Gets the update policy of the entry, latest by default
+    pub fn update_policy(&self) -> UpdatePolicy {
+        match self {
+            Self::Id(_) => UpdatePolicy::Latest,
+            Self::Detailed(entry) => entry.update_policy,
+        }
     }
 }
 
-#[derive(Deserialize)]
+/// The extension lists of a manifest keyed by platform field name (e.g., "linux_x64")
+///
+/// Every list of the manifest is kept, the platform registry decides which ones are processed.
+/// Other values, such as `$schema`, are ignored.
 pub struct Extensions {
-    pub universal: Option<Vec<String>>,
-    pub linux_x64: Option<Vec<String>>,
-    pub linux_arm64: Option<Vec<String>>,
-    pub darwin_x64: Option<Vec<String>>,
-    pub darwin_arm64: Option<Vec<String>>,
-    pub win32_x64: Option<Vec<String>>,
-    pub win32_arm64: Option<Vec<String>>,
+    sections: BTreeMap<String, Vec<ManifestEntry>>,
+}
+
+impl<'de> Deserialize<'de> for Extensions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut sections = BTreeMap::new();
+        for (field_name, value) in BTreeMap::<String, Value>::deserialize(deserializer)? {
+            if value.is_array() {
+                let entries = serde_json::from_value(value).map_err(D::Error::custom)?;
+                sections.insert(field_name, entries);
+            }
+        }
+        Ok(Self { sections })
+    }
+}
+
+impl Extensions {
+    /// Get the extension list of a platform
+    ///
+    /// # Arguments
+    ///
+    /// * `field_name` - The platform field name (e.g., "linux_x64")
+    ///
+    /// # Returns
+    ///
+    /// An Option containing a reference to the extension list of the platform
+    pub fn section(&self, field_name: &str) -> Option<&Vec<ManifestEntry>> {
+        self.sections.get(field_name)
+    }
+
+    /// Get the extension list of a platform, created empty if the manifest has none
+    pub fn section_mut(&mut self, field_name: &str) -> &mut Vec<ManifestEntry> {
+        self.sections.entry(field_name.to_string()).or_default()
+    }
+
+    /// Remove a list from the manifest, returning its entries
+    pub fn take_section(&mut self, field_name: &str) -> Vec<ManifestEntry> {
+        self.sections.remove(field_name).unwrap_or_default()
+    }
+
+    /// Get the platform field names of the manifest lists
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    /// Iterate over the entries of every platform list
+    pub fn entries(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.sections.values().flatten()
+    }
+
+    /// Iterate mutably over the entries of every platform list
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut ManifestEntry> {
+        self.sections.values_mut().flatten()
+    }
+}
+
+/// Expand the `${NAME}` and `${NAME:-default}` environment variable placeholders of a string
+///
+/// # Arguments
+///
+/// * `value` - The string to expand
+/// * `lookup` - The function returning the value of an environment variable
+///
+/// # Returns
+///
+/// A Result containing the expanded string or an error if a variable without default is not set
+pub fn expand_placeholders(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let placeholder = &rest[start + 2..];
+        let Some(end) = placeholder.find('}') else {
+            return Err(VsixHarvesterError::InvalidPlaceholder(value.to_string()));
+        };
+        let (name, default) = match placeholder[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&placeholder[..end], None),
+        };
+        match lookup(name).or_else(|| default.map(str::to_string)) {
+            Some(variable) => expanded.push_str(&variable),
+            None => return Err(VsixHarvesterError::UndefinedVariable(name.to_string())),
+        }
+        rest = &placeholder[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expand the environment variable placeholders of every string of a manifest
+///
+/// # Arguments
+///
+/// * `manifest` - The parsed manifest
+/// * `overrides` - Variables taking precedence over the environment (e.g., set by a fleet profile)
+///
+/// # Returns
+///
+/// A Result indicating success or an error if a variable without default is not set
+pub fn interpolate_env(manifest: &mut Value, overrides: &BTreeMap<String, String>) -> Result<()> {
+    match manifest {
+        Value::String(value) => {
+            *value = expand_placeholders(value, &|name| {
+                overrides
+                    .get(name)
+                    .cloned()
+                    .or_else(|| std::env::var(name).ok())
+            })?;
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .try_for_each(|value| interpolate_env(value, overrides))?,
+        Value::Object(fields) => fields
+            .values_mut()
+            .try_for_each(|value| interpolate_env(value, overrides))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Move an extension of the universal list of a manifest to platform lists
+///
+/// The other fields of the manifest are kept as is.
+///
+/// # Arguments
+///
+/// * `manifest_path` - The path of the extensions.json manifest
+/// * `id` - The extension identifier (e.g., "golang.Go")
+/// * `platform_fields` - The platform field names to add the extension to (e.g., "linux_x64")
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn move_to_platforms(manifest_path: &str, id: &str, platform_fields: &[&str]) -> Result<()> {
+    let mut manifest: Value = serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+    let same_id = |entry: &Value| {
+        entry
+            .as_str()
+            .or_else(|| entry["id"].as_str())
+            .is_some_and(|entry| split_version(entry).0.eq_ignore_ascii_case(id))
+    };
+    // Keep the settings of detailed entries
+    let mut moved = Value::String(id.to_string());
+    if let Some(universal) = manifest["universal"].as_array_mut() {
+        if let Some(entry) = universal.iter().find(|entry| same_id(entry)) {
+            moved = entry.clone();
+        }
+        universal.retain(|entry| !same_id(entry));
+    }
+    for platform_field in platform_fields {
+        if !manifest[*platform_field].is_array() {
+            manifest[*platform_field] = Value::Array(Vec::new());
+        }
+        if let Some(list) = manifest[*platform_field].as_array_mut() {
+            if !list.iter().any(same_id) {
+                list.push(moved.clone());
+            }
+        }
+    }
+    write_atomic(
+        Path::new(manifest_path),
+        (serde_json::to_string_pretty(&manifest)? + "\n").as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let lookup = |name: &str| (name == "MIRROR_GROUP").then(|| "team-a".to_string());
+        assert_eq!(
+            expand_placeholders("${MIRROR_GROUP}/${ENGINE_VERSION:-1.97.0}", &lookup).unwrap(),
+            "team-a/1.97.0"
+        );
+        assert_eq!(
+            expand_placeholders("golang.Go", &lookup).unwrap(),
+            "golang.Go"
+        );
+        assert!(matches!(
+            expand_placeholders("${ENGINE_VERSION}", &lookup),
+            Err(VsixHarvesterError::UndefinedVariable(name)) if name == "ENGINE_VERSION"
+        ));
+        assert!(expand_placeholders("${MIRROR_GROUP", &lookup).is_err());
+    }
+
+    #[test]
+    fn test_move_to_platforms() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = format!("{}/extensions.json", temp_dir.path().display());
+        fs::write(
+            &manifest_path,
+            r#"{"universal": ["golang.Go", "ms-python.python"], "linux_x64": ["golang.go"]}"#,
+        )
+        .unwrap();
+        move_to_platforms(&manifest_path, "golang.Go", &["linux_x64", "win32_x64"]).unwrap();
+        let manifest: Value =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(
+            manifest["universal"],
+            serde_json::json!(["ms-python.python"])
+        );
+        assert_eq!(manifest["linux_x64"], serde_json::json!(["golang.go"]));
+        assert_eq!(manifest["win32_x64"], serde_json::json!(["golang.Go"]));
+    }
+
+    #[test]
+    fn test_manifest_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = format!("{}/extensions.json", temp_dir.path().display());
+        let manifest =
+            r#"{"universal": ["golang.Go", {"id": "ms-python.python", "priority": 10}]}"#;
+        let extensions: Extensions = serde_json::from_str(manifest).unwrap();
+        let universal = extensions.section("universal").unwrap();
+        assert_eq!(universal[0].id(), "golang.Go");
+        assert_eq!(universal[0].priority(), 0);
+        assert_eq!(universal[1].id(), "ms-python.python");
+        assert_eq!(universal[1].priority(), 10);
+
+        // Detailed entries are moved with their settings
+        fs::write(&manifest_path, manifest).unwrap();
+        move_to_platforms(&manifest_path, "ms-python.python", &["win32_x64"]).unwrap();
+        let manifest: Value =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest["universal"], serde_json::json!(["golang.Go"]));
+        assert_eq!(
+            manifest["win32_x64"],
+            serde_json::json!([{"id": "ms-python.python", "priority": 10}])
+        );
+    }
+
+    #[test]
+    fn test_pinned_entries() {
+        let extensions: Extensions = serde_json::from_str(
+            r#"{"universal": ["golang.Go@0.41.2", {"id": "ms-python.python", "version": "2024.2.1"}, "dbaeumer.vscode-eslint"]}"#,
+        )
+        .unwrap();
+        let universal = extensions.section("universal").unwrap();
+        assert_eq!(universal[0].id(), "golang.Go");
+        assert_eq!(universal[0].version(), Some("0.41.2"));
+        assert_eq!(universal[1].id(), "ms-python.python");
+        assert_eq!(universal[1].version(), Some("2024.2.1"));
+        assert_eq!(universal[2].version(), None);
+
+        let extension = Extension::from_id("golang.Go@0.41.2").unwrap();
+        assert_eq!(extension.to_id(), "golang.Go");
+        assert_eq!(extension.version, Some("0.41.2"));
+        assert!(Extension::from_id("golang@0.41.2").is_err());
+    }
+
+    #[test]
+    fn test_package_override() {
+        let sha256 = "AB".repeat(32);
+        let extensions: Extensions = serde_json::from_str(&format!(
+            r#"{{"universal": [
+                {{"id": "golang.Go", "version": "0.41.2", "overrideUrl": "https://builds.example.com/go.vsix", "sha256": "{}"}},
+                {{"id": "ms-python.python", "overrideUrl": "https://builds.example.com/python.vsix", "sha256": "{}"}},
+                {{"id": "rust-lang.rust-analyzer@0.3.2", "overrideUrl": "https://builds.example.com/ra.vsix"}},
+                "dbaeumer.vscode-eslint"
+            ]}}"#,
+            sha256, sha256
+        ))
+        .unwrap();
+        let universal = extensions.section("universal").unwrap();
+        assert_eq!(
+            universal[0].package_override().unwrap(),
+            Some(PackageOverride {
+                url: "https://builds.example.com/go.vsix".to_string(),
+                sha256: "ab".repeat(32),
+            })
+        );
+        assert!(universal[1].package_override().is_err());
+        assert!(universal[2].package_override().is_err());
+        assert_eq!(universal[3].package_override().unwrap(), None);
+    }
+
+    #[test]
+    fn test_extension_guid_entries() {
+        let mut extensions: Extensions = serde_json::from_str(
+            r#"{"universal": ["D6F6CFEA-4B6F-41F4-B571-6AD2AB7918DA@0.41.2", {"id": "f1f59ae4-9318-4f3c-a9b5-81b2eaa5f8a5", "priority": 5}, "golang.Go"]}"#,
+        )
+        .unwrap();
+        let guids: Vec<bool> = extensions
+            .entries()
+            .map(|entry| is_extension_guid(entry.id()))
+            .collect();
+        assert_eq!(guids, [true, true, false]);
+        assert!(!is_extension_guid("d6f6cfea-4b6f-41f4-b571-6ad2ab7918dz"));
+
+        let mut entries = extensions.entries_mut();
+        entries.next().unwrap().set_id("golang.Go");
+        entries.next().unwrap().set_id("ms-python.python");
+        drop(entries);
+        let universal = extensions.section("universal").unwrap();
+        assert_eq!(universal[0].id(), "golang.Go");
+        assert_eq!(universal[0].version(), Some("0.41.2"));
+        assert_eq!(universal[1].id(), "ms-python.python");
+        assert_eq!(universal[1].priority(), 5);
+    }
 }
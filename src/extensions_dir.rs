@@ -0,0 +1,392 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::config::{EXTENSIONS_JSON_FILE_NAME, VSIXMANIFEST_FILE_NAME};
+use crate::create_directory_if_not_exists;
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::DownloadOutcome;
+use crate::status::{scan_mirror, MirroredPackage};
+use crate::temp::{write_atomic, TempFile};
+use crate::types::compare_versions;
+use crate::vsix::read_vsix_info;
+use chrono::Utc;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use zip::ZipArchive;
+
+/// An extension unpacked in an extensions directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpackedExtension {
+    pub id: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    /// The name of the directory of the extension in the extensions directory
+    pub dir_name: String,
+}
+
+/// Get the directory name VS Code gives to an installed extension
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version of the extension
+/// * `target_platform` - The target platform of the package, None for universal packages
+///
+/// # Returns
+///
+/// The directory name (e.g., "rust-lang.rust-analyzer-0.3.2-linux-x64")
+pub fn extension_dir_name(
+    id: &str,
+    version: &str,
+    target_platform: Option<&TargetPlatform>,
+) -> String {
+    match target_platform {
+        Some(target_platform) => format!("{}-{}-{}", id.to_lowercase(), version, target_platform),
+        None => format!("{}-{}", id.to_lowercase(), version),
+    }
+}
+
+/// Unpack a VSIX package the way VS Code installs it
+///
+/// The `extension/` folder of the package becomes the directory of the extension,
+/// and the `extension.vsixmanifest` is kept in it as `.vsixmanifest`. The package is
+/// unpacked in the temp workspace, an already unpacked directory is only replaced
+/// once it is complete.
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+/// * `extensions_dir` - The extensions directory (e.g., "~/.vscode-server/extensions")
+///
+/// # Returns
+///
+/// A Result containing the unpacked extension or an error that occurred
+pub fn unpack_vsix(vsix_path: &str, extensions_dir: &str) -> Result<UnpackedExtension> {
+    let info = read_vsix_info(vsix_path)?;
+    let dir_name = extension_dir_name(&info.id(), &info.version, info.target_platform.as_ref());
+    let target = Path::new(extensions_dir).join(&dir_name);
+    let staging = TempFile::for_target(&target)?;
+    fs::create_dir_all(staging.path())?;
+
+    let mut archive = ZipArchive::new(fs::File::open(vsix_path)?)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(name) = entry.enclosed_name() else {
+            return Err(VsixHarvesterError::InvalidVsix(format!(
+                "{}: unsafe entry {}",
+                vsix_path,
+                entry.name()
+            )));
+        };
+        let path = if name == Path::new("extension.vsixmanifest") {
+            staging.path().join(VSIXMANIFEST_FILE_NAME)
+        } else if let Ok(relative) = name.strip_prefix("extension") {
+            staging.path().join(relative)
+        } else {
+            continue;
+        };
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut fs::File::create(&path)?)?;
+    }
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+    staging.persist(&target)?;
+    Ok(UnpackedExtension {
+        id: info.id(),
+        version: info.version,
+        target_platform: info.target_platform,
+        dir_name,
+    })
+}
+
+/// Unpack the packages downloaded by the run in the destination
+///
+/// Each package gets its folder as VS Code names it, ready to be copied into
+/// `~/.vscode/extensions` or the extensions directory of code-server. The folders
+/// already unpacked by a previous run are kept.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `outcomes` - The outcomes of the downloads of the run
+///
+/// # Returns
+///
+/// A Result containing the number of unpacked packages or an error that occurred
+pub fn unpack_outcomes(destination: &str, outcomes: &[DownloadOutcome]) -> Result<usize> {
+    let mut unpacked = 0;
+    for outcome in outcomes {
+        let dir_name = extension_dir_name(
+            &outcome.id,
+            &outcome.version,
+            outcome.target_platform.as_ref(),
+        );
+        if Path::new(destination)
+            .join(&dir_name)
+            .join("package.json")
+            .exists()
+        {
+            continue;
+        }
+        unpack_vsix(&outcome.file_path, destination)?;
+        unpacked += 1;
+    }
+    info!("{} package(s) unpacked in {}", unpacked, destination);
+    Ok(unpacked)
+}
+
+/// Register unpacked extensions in the `extensions.json` of an extensions directory
+///
+/// The entries of the same extensions are replaced, the other entries are kept.
+/// Extensions are located relatively to the directory, so it can be shared on a
+/// network path mounted anywhere.
+///
+/// # Arguments
+///
+/// * `extensions_dir` - The extensions directory
+/// * `unpacked` - The unpacked extensions
+///
+/// # Returns
+///
+/// A Result containing the directory names of the replaced entries or an error that occurred
+pub fn register_extensions(
+    extensions_dir: &str,
+    unpacked: &[UnpackedExtension],
+) -> Result<Vec<String>> {
+    let path = Path::new(extensions_dir).join(EXTENSIONS_JSON_FILE_NAME);
+    let mut entries: Vec<Value> = if path.exists() {
+        serde_json::from_str(&fs::read_to_string(&path)?)?
+    } else {
+        Vec::new()
+    };
+    let mut replaced = Vec::new();
+    entries.retain(|entry| {
+        let id = entry["identifier"]["id"].as_str().unwrap_or_default();
+        let kept = !unpacked
+            .iter()
+            .any(|extension| extension.id.eq_ignore_ascii_case(id));
+        if !kept {
+            if let Some(dir_name) = entry["relativeLocation"].as_str() {
+                replaced.push(dir_name.to_string());
+            }
+        }
+        kept
+    });
+    let installed_timestamp = Utc::now().timestamp_millis();
+    for extension in unpacked {
+        replaced.retain(|dir_name| *dir_name != extension.dir_name);
+        entries.push(json!({
+            "identifier": {"id": extension.id.to_lowercase()},
+            "version": extension.version,
+            "relativeLocation": extension.dir_name,
+            "metadata": {
+                "installedTimestamp": installed_timestamp,
+                "source": "vsix",
+                "targetPlatform": target_platform_name(extension.target_platform.as_ref()),
+            },
+        }));
+    }
+    write_atomic(
+        Path::new(&path),
+        serde_json::to_string_pretty(&entries)?.as_bytes(),
+    )?;
+    Ok(replaced)
+}
+
+/// Select the package a host of a target platform installs for each extension
+///
+/// The newest version wins, and its build for the target platform wins over its
+/// universal package. The builds for other platforms are never selected.
+///
+/// # Arguments
+///
+/// * `packages` - The packages of the mirror
+/// * `target_platform` - The target platform of the host (e.g., "linux-x64")
+///
+/// # Returns
+///
+/// The selected packages, one per extension
+pub fn select_packages<'a>(
+    packages: &'a [MirroredPackage],
+    target_platform: &TargetPlatform,
+) -> Vec<&'a MirroredPackage> {
+    let mut selected: BTreeMap<String, &MirroredPackage> = BTreeMap::new();
+    for package in packages {
+        if package
+            .target_platform
+            .as_ref()
+            .is_some_and(|platform| platform != target_platform)
+        {
+            continue;
+        }
+        let current = selected.entry(package.id.to_lowercase()).or_insert(package);
+        let newer = compare_versions(&package.version, &current.version);
+        if newer > 0 || (newer == 0 && package.target_platform.is_some()) {
+            *current = package;
+        }
+    }
+    selected.into_values().collect()
+}
+
+/// Lay out the packages of a mirror as the extensions directory of a VS Code Server
+///
+/// The directory can be shared on a network path and used as the extensions
+/// directory of the VS Code Servers of Remote-SSH and tunnels hosts (e.g., with
+/// `--extensions-dir`), without any marketplace access.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `output` - The extensions directory to write
+/// * `target_platform` - The target platform of the hosts (e.g., "linux-x64")
+///
+/// # Returns
+///
+/// A Result containing the number of unpacked extensions or an error that occurred
+pub fn export_server_extensions(
+    destination: &str,
+    output: &str,
+    target_platform: &TargetPlatform,
+) -> Result<usize> {
+    create_directory_if_not_exists(output)?;
+    let packages = scan_mirror(destination)?;
+    let mut unpacked = Vec::new();
+    for package in select_packages(&packages, target_platform) {
+        match unpack_vsix(&format!("{}/{}", destination, package.file_name), output) {
+            Ok(extension) => unpacked.push(extension),
+            Err(e) => warn!("Not unpacking {}: {}", package.file_name, e),
+        }
+    }
+    for dir_name in register_extensions(output, &unpacked)? {
+        let path = Path::new(output).join(&dir_name);
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+    }
+    info!(
+        "{} extension(s) for {} unpacked in {}",
+        unpacked.len(),
+        target_platform,
+        output
+    );
+    Ok(unpacked.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn vsix(version: &str, target_platform: Option<&str>) -> Vec<u8> {
+        test_support::vsix_with(
+            "rust-lang.rust-analyzer",
+            version,
+            target_platform,
+            json!({}),
+            &[
+                ("extension/server/rust-analyzer", b"binary"),
+                ("[Content_Types].xml", b""),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_export_server_extensions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("mirror");
+        let destination = destination.to_str().unwrap();
+        let output = temp_dir.path().join("extensions");
+        let output = output.to_str().unwrap();
+        fs::create_dir(destination).unwrap();
+        for (file_name, version, target_platform) in [
+            (
+                "rust-lang.rust-analyzer-0.3.1@linux-x64.vsix",
+                "0.3.1",
+                Some("linux-x64"),
+            ),
+            ("rust-lang.rust-analyzer-0.3.2.vsix", "0.3.2", None),
+            (
+                "rust-lang.rust-analyzer-0.3.2@linux-x64.vsix",
+                "0.3.2",
+                Some("linux-x64"),
+            ),
+            (
+                "rust-lang.rust-analyzer-0.3.2@win32-x64.vsix",
+                "0.3.2",
+                Some("win32-x64"),
+            ),
+        ] {
+            fs::write(
+                format!("{}/{}", destination, file_name),
+                vsix(version, target_platform),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            export_server_extensions(destination, output, &"linux-x64".into()).unwrap(),
+            1
+        );
+        let dir = format!("{}/rust-lang.rust-analyzer-0.3.2-linux-x64", output);
+        assert_eq!(
+            fs::read_to_string(format!("{}/server/rust-analyzer", dir)).unwrap(),
+            "binary"
+        );
+        assert!(Path::new(&format!("{}/package.json", dir)).exists());
+        assert!(Path::new(&format!("{}/.vsixmanifest", dir)).exists());
+        assert!(!Path::new(&format!("{}/[Content_Types].xml", dir)).exists());
+
+        let entries: Value = serde_json::from_str(
+            &fs::read_to_string(format!("{}/extensions.json", output)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(entries.as_array().unwrap().len(), 1);
+        assert_eq!(entries[0]["identifier"]["id"], "rust-lang.rust-analyzer");
+        assert_eq!(
+            entries[0]["relativeLocation"],
+            "rust-lang.rust-analyzer-0.3.2-linux-x64"
+        );
+        assert_eq!(entries[0]["metadata"]["targetPlatform"], "linux-x64");
+
+        // The universal package is selected for the platforms without a build
+        assert_eq!(
+            export_server_extensions(destination, output, &"darwin-arm64".into()).unwrap(),
+            1
+        );
+        assert!(!Path::new(&dir).exists());
+        assert!(Path::new(&format!("{}/rust-lang.rust-analyzer-0.3.2", output)).exists());
+
+        let outcome = DownloadOutcome {
+            id: "rust-lang.rust-analyzer".to_string(),
+            version: "0.3.2".to_string(),
+            target_platform: Some("win32-x64".into()),
+            file_path: format!(
+                "{}/rust-lang.rust-analyzer-0.3.2@win32-x64.vsix",
+                destination
+            ),
+            download_url: String::new(),
+            sha256: None,
+            digest: None,
+        };
+        assert_eq!(
+            unpack_outcomes(destination, std::slice::from_ref(&outcome)).unwrap(),
+            1
+        );
+        assert!(Path::new(&format!(
+            "{}/rust-lang.rust-analyzer-0.3.2-win32-x64/server/rust-analyzer",
+            destination
+        ))
+        .exists());
+        // Already unpacked
+        assert_eq!(unpack_outcomes(destination, &[outcome]).unwrap(), 0);
+    }
+}
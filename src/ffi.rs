@@ -0,0 +1,214 @@
+//! C ABI of the harvesting library
+//!
+//! Built into the `vsixharvester` cdylib with the `ffi` feature, declared in
+//! `include/vsixharvester.h`. Every function blocks on a runtime of its own and
+//! reports failures through `vsixharvester_last_error` of the calling thread.
+
+use crate::architecture::TargetPlatform;
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::marketplace::{download_resolved_extension, resolve_extension, DownloadOptions};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::future::Future;
+use std::path::Path;
+use std::ptr;
+
+thread_local! {
+    /// The error of the last failed call of the thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record the error of a failed call
+fn set_last_error(error: &VsixHarvesterError) {
+    let message = CString::new(error.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Read an optional string argument, a NULL pointer being None
+///
+/// # Safety
+///
+/// `value` must be NULL or a valid NUL-terminated string
+unsafe fn optional_str<'a>(value: *const c_char) -> Result<Option<&'a str>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(Some)
+        .map_err(|e| VsixHarvesterError::InvalidArgument(e.to_string()))
+}
+
+/// Read a mandatory string argument
+///
+/// # Safety
+///
+/// `value` must be NULL or a valid NUL-terminated string
+unsafe fn required_str<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    optional_str(value)?
+        .ok_or_else(|| VsixHarvesterError::InvalidArgument(format!("{} is NULL", name)))
+}
+
+/// Run a future of the library on a runtime of its own
+fn block_on<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Runtime::new()?.block_on(future)
+}
+
+/// Build the download options of a call
+fn download_options(engine_version: Option<&str>) -> DownloadOptions {
+    DownloadOptions {
+        engine_version: engine_version.map(str::to_string),
+        pin_hashes: true,
+        ..Default::default()
+    }
+}
+
+/// Resolve the version of an extension to download
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `target_platform` - Optional target platform (e.g., "linux-x64"), NULL for universal
+/// * `engine_version` - Optional engine version the version must be compatible with, NULL for any
+///
+/// # Returns
+///
+/// The version, to release with `vsixharvester_string_free`, or NULL on failure
+///
+/// # Safety
+///
+/// The arguments must be NULL or valid NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn vsixharvester_resolve_version(
+    id: *const c_char,
+    target_platform: *const c_char,
+    engine_version: *const c_char,
+) -> *mut c_char {
+    let resolved = (|| {
+        let extension = Extension::from_id(required_str(id, "id")?)?;
+        let target_platform = optional_str(target_platform)?.map(TargetPlatform::new);
+        let options = download_options(optional_str(engine_version)?);
+        block_on(resolve_extension(
+            extension,
+            &options,
+            target_platform.as_ref(),
+        ))
+    })();
+    match resolved.and_then(|resolved| {
+        CString::new(resolved.version)
+            .map_err(|e| VsixHarvesterError::InvalidArgument(e.to_string()))
+    }) {
+        Ok(version) => version.into_raw(),
+        Err(e) => {
+            set_last_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Download the latest compatible version of an extension to a file
+///
+/// The package is validated before being written, a partial download never
+/// replaces the file.
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `target_platform` - Optional target platform (e.g., "linux-x64"), NULL for universal
+/// * `engine_version` - Optional engine version the version must be compatible with, NULL for any
+/// * `file_path` - The path of the VSIX file to write, its directory is created if needed
+///
+/// # Returns
+///
+/// 0 on success, -1 on failure
+///
+/// # Safety
+///
+/// The arguments must be NULL or valid NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn vsixharvester_download(
+    id: *const c_char,
+    target_platform: *const c_char,
+    engine_version: *const c_char,
+    file_path: *const c_char,
+) -> c_int {
+    let downloaded = (|| {
+        let extension = Extension::from_id(required_str(id, "id")?)?;
+        let target_platform = optional_str(target_platform)?.map(TargetPlatform::new);
+        let file_path = required_str(file_path, "file_path")?;
+        let mut options = download_options(optional_str(engine_version)?);
+        // The destination is the directory of the file, where quarantined packages are kept
+        options.destination = Path::new(file_path)
+            .parent()
+            .map(|parent| parent.to_string_lossy().into_owned())
+            .filter(|parent| !parent.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        options.no_cache = true;
+        crate::create_directory_if_not_exists(&options.destination)?;
+        block_on(async {
+            let mut resolved =
+                resolve_extension(extension, &options, target_platform.as_ref()).await?;
+            resolved.file_path = file_path.to_string();
+            download_resolved_extension(&resolved, &options, None).await
+        })
+    })();
+    match downloaded {
+        Ok(_) => 0,
+        Err(e) => {
+            set_last_error(&e);
+            -1
+        }
+    }
+}
+
+/// Get the error of the last failed call of the thread
+///
+/// # Returns
+///
+/// The error message, NULL if no call failed. It is owned by the library and
+/// valid until the next failed call of the thread.
+#[no_mangle]
+pub extern "C" fn vsixharvester_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Release a string returned by the library
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned by the library, released only once
+#[no_mangle]
+pub unsafe extern "C" fn vsixharvester_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_last_error() {
+        let id = CString::new("not-an-identifier").unwrap();
+        let version =
+            unsafe { vsixharvester_resolve_version(id.as_ptr(), ptr::null(), ptr::null()) };
+        assert!(version.is_null());
+        let error = unsafe { CStr::from_ptr(vsixharvester_last_error()) };
+        assert_eq!(
+            error.to_str().unwrap(),
+            "Invalid extension identifier: not-an-identifier"
+        );
+
+        let status =
+            unsafe { vsixharvester_download(id.as_ptr(), ptr::null(), ptr::null(), ptr::null()) };
+        assert_eq!(status, -1);
+        unsafe { vsixharvester_string_free(ptr::null_mut()) };
+    }
+}
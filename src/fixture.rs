@@ -0,0 +1,415 @@
+use crate::config::{
+    FIXTURE_INDEX_FILE_NAME, FIXTURE_MANIFEST_FILE_NAME, FIXTURE_RESPONSES_DIR,
+    FIXTURE_STUB_ENTRIES, MARKETPLACE_ORIGIN,
+};
+use crate::error::{Result, VsixHarvesterError};
+use crate::http_server::{serve_connections, Request, Response};
+use crate::marketplace::{build_client, sha256_hex, DownloadOptions};
+use crate::temp::{copy_atomic, write_atomic};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// A marketplace response recorded in a fixture bundle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Exchange {
+    pub method: String,
+    /// The path and query of the request (e.g., "/_apis/public/gallery/extensionquery")
+    pub path: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// The file of the response body, relative to the responses directory
+    pub file: String,
+}
+
+/// The index of a fixture bundle, the exchanges keyed by request digest
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FixtureIndex {
+    pub exchanges: BTreeMap<String, Exchange>,
+}
+
+/// Get the digest identifying a request in the bundle, from its method, path and body
+fn request_key(request: &Request) -> String {
+    let mut content = format!("{} {}\n", request.method, request.path).into_bytes();
+    content.extend_from_slice(&request.body);
+    sha256_hex(&content)
+}
+
+/// How the fixture server answers the requests
+#[derive(Debug)]
+enum Mode {
+    /// Forward the requests to the upstream origin and record the responses
+    Capture {
+        upstream: String,
+        client: reqwest::Client,
+        index: Mutex<FixtureIndex>,
+    },
+    /// Answer the requests with the recorded responses
+    Replay { index: FixtureIndex },
+}
+
+#[derive(Debug)]
+struct Fixture {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+/// A marketplace stand-in on localhost, capturing or replaying a fixture bundle
+///
+/// The harvest runs against its origin like in bench mode. A bundle holds the
+/// responses of the marketplace API in `responses/`, the packages reduced to stubs
+/// keeping only their manifests, the index `fixture.json` and the harvested manifest.
+#[derive(Debug)]
+pub struct FixtureServer {
+    origin: String,
+    fixture: Arc<Fixture>,
+    shutdown: CancellationToken,
+}
+
+impl FixtureServer {
+    /// Start recording the marketplace responses of a harvest in a bundle
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory of the bundle, created if needed
+    /// * `upstream` - The origin the requests are forwarded to (e.g., "https://marketplace.visualstudio.com")
+    /// * `options` - The download options (proxy, connection tuning)
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the server or an error that occurred
+    pub async fn capture(dir: &str, upstream: &str, options: &DownloadOptions) -> Result<Self> {
+        fs::create_dir_all(Path::new(dir).join(FIXTURE_RESPONSES_DIR))?;
+        let mode = Mode::Capture {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            client: build_client(options)?,
+            index: Mutex::new(FixtureIndex::default()),
+        };
+        Self::start(dir, mode).await
+    }
+
+    /// Start replaying a bundle
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory of the bundle
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the server or an error if the bundle cannot be read
+    pub async fn replay(dir: &str) -> Result<Self> {
+        let index_path = Path::new(dir).join(FIXTURE_INDEX_FILE_NAME);
+        let index: FixtureIndex = serde_json::from_str(&fs::read_to_string(&index_path)?)?;
+        info!(
+            "Replaying {} recorded responses from {}",
+            index.exchanges.len(),
+            dir
+        );
+        Self::start(dir, Mode::Replay { index }).await
+    }
+
+    async fn start(dir: &str, mode: Mode) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let origin = format!("http://{}", listener.local_addr()?);
+        let fixture = Arc::new(Fixture {
+            dir: PathBuf::from(dir),
+            mode,
+        });
+        let shutdown = CancellationToken::new();
+        let answering = fixture.clone();
+        tokio::spawn(serve_connections(
+            listener,
+            "fixture server",
+            shutdown.clone(),
+            move |request| {
+                let fixture = answering.clone();
+                async move {
+                    match respond(&fixture, &request).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            warn!(
+                                "Failed to answer {} {}: {}",
+                                request.method, request.path, e
+                            );
+                            Response::new(502, "text/plain", e.to_string().into_bytes())
+                        }
+                    }
+                }
+            },
+        ));
+        Ok(Self {
+            origin,
+            fixture,
+            shutdown,
+        })
+    }
+
+    /// Get the origin the harvest is rebased on
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Stop the server, saving the index and the manifest of a captured bundle
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - Optional path of the harvested manifest, copied into the bundle
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error that occurred
+    pub fn finish(self, manifest: Option<&str>) -> Result<()> {
+        self.shutdown.cancel();
+        let Mode::Capture { index, .. } = &self.fixture.mode else {
+            return Ok(());
+        };
+        let index = index.lock().unwrap();
+        write_atomic(
+            &self.fixture.dir.join(FIXTURE_INDEX_FILE_NAME),
+            serde_json::to_string_pretty(&*index)?.as_bytes(),
+        )?;
+        if let Some(manifest) = manifest.filter(|manifest| Path::new(manifest).exists()) {
+            copy_atomic(
+                Path::new(manifest),
+                &self.fixture.dir.join(FIXTURE_MANIFEST_FILE_NAME),
+            )?;
+        }
+        info!(
+            "{} responses captured in {}",
+            index.exchanges.len(),
+            self.fixture.dir.display()
+        );
+        Ok(())
+    }
+}
+
+/// Reduce a package to a stub keeping only its manifests, still a valid VSIX package
+///
+/// # Arguments
+///
+/// * `package` - The content of the package
+///
+/// # Returns
+///
+/// The stub, or the package itself if it is not a ZIP archive
+pub fn stub_package(package: &[u8]) -> Vec<u8> {
+    let stub = || -> Result<Vec<u8>> {
+        let mut archive = ZipArchive::new(Cursor::new(package))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in FIXTURE_STUB_ENTRIES {
+            let Ok(mut entry) = archive.by_name(name) else {
+                continue;
+            };
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(&content)?;
+        }
+        Ok(writer.finish()?.into_inner())
+    };
+    stub().unwrap_or_else(|_| package.to_vec())
+}
+
+/// Answer a request, forwarding it upstream or from the bundle
+async fn respond(fixture: &Fixture, request: &Request) -> Result<Response> {
+    let key = request_key(request);
+    let responses = fixture.dir.join(FIXTURE_RESPONSES_DIR);
+    match &fixture.mode {
+        Mode::Replay { index } => match index.exchanges.get(&key) {
+            Some(exchange) => Ok(Response {
+                status: exchange.status,
+                content_type: exchange.content_type.clone(),
+                body: fs::read(responses.join(&exchange.file))?,
+            }),
+            None => {
+                warn!(
+                    "No recorded response for {} {}",
+                    request.method, request.path
+                );
+                Ok(Response {
+                    status: 404,
+                    content_type: None,
+                    body: Vec::new(),
+                })
+            }
+        },
+        Mode::Capture {
+            upstream,
+            client,
+            index,
+        } => {
+            let method = reqwest::Method::from_bytes(request.method.as_bytes())
+                .map_err(|e| VsixHarvesterError::InvalidArgument(e.to_string()))?;
+            let mut forwarded = client.request(method, format!("{}{}", upstream, request.path));
+            if let Some(content_type) = request.header("content-type") {
+                forwarded = forwarded.header("Content-Type", content_type);
+            }
+            if let Some(accept) = request.header("accept") {
+                forwarded = forwarded.header("Accept", accept);
+            }
+            let resp = forwarded.body(request.body.clone()).send().await?;
+            let status = resp.status().as_u16();
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let mut body = resp.bytes().await?.to_vec();
+            if request
+                .path
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .ends_with("/vspackage")
+            {
+                body = stub_package(&body);
+            }
+            let file = format!("{}.bin", key);
+            write_atomic(&responses.join(&file), &body)?;
+            index.lock().unwrap().exchanges.insert(
+                key,
+                Exchange {
+                    method: request.method.clone(),
+                    path: request.path.clone(),
+                    status,
+                    content_type: content_type.clone(),
+                    file,
+                },
+            );
+            Ok(Response {
+                status,
+                content_type,
+                body,
+            })
+        }
+    }
+}
+
+/// Start the fixture server of a harvest run with `--capture-fixture` or `--replay-fixture`
+///
+/// # Arguments
+///
+/// * `capture` - Optional directory of the bundle to capture
+/// * `replay` - Optional directory of the bundle to replay
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the server, None without a fixture, or an error that occurred
+pub async fn start_fixture(
+    capture: Option<&str>,
+    replay: Option<&str>,
+    options: &DownloadOptions,
+) -> Result<Option<FixtureServer>> {
+    match (capture, replay) {
+        (Some(dir), _) => Ok(Some(
+            FixtureServer::capture(dir, MARKETPLACE_ORIGIN, options).await?,
+        )),
+        (None, Some(dir)) => Ok(Some(FixtureServer::replay(dir).await?)),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use crate::vsix::validate_vsix_reader;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_capture_and_replay() {
+        let package = test_support::zip(&[
+            ("extension.vsixmanifest", b"<PackageManifest/>"),
+            ("extension/package.json", b"{}"),
+            ("extension/dist/main.js", b"large bundle"),
+        ]);
+        let stub = stub_package(&package);
+        validate_vsix_reader(Cursor::new(&stub)).unwrap();
+        let archive = ZipArchive::new(Cursor::new(&stub)).unwrap();
+        assert_eq!(archive.len(), 2);
+
+        // A hand-made bundle serves as the upstream of a capture
+        let upstream_dir = TempDir::new().unwrap();
+        let upstream_path = upstream_dir.path().to_str().unwrap();
+        let package_path = "/_apis/public/gallery/publisher/golang/extension/Go/0.41.2/assetbyname/Microsoft.VisualStudio.Services.VSIXPackage/vspackage";
+        let request = Request {
+            method: "GET".to_string(),
+            path: package_path.to_string(),
+            ..Default::default()
+        };
+        fs::create_dir_all(upstream_dir.path().join(FIXTURE_RESPONSES_DIR)).unwrap();
+        fs::write(
+            upstream_dir
+                .path()
+                .join(FIXTURE_RESPONSES_DIR)
+                .join("package.bin"),
+            &package,
+        )
+        .unwrap();
+        let upstream_index = FixtureIndex {
+            exchanges: BTreeMap::from([(
+                request_key(&request),
+                Exchange {
+                    method: "GET".to_string(),
+                    path: package_path.to_string(),
+                    status: 200,
+                    content_type: Some("application/octet-stream".to_string()),
+                    file: "package.bin".to_string(),
+                },
+            )]),
+        };
+        fs::write(
+            upstream_dir.path().join(FIXTURE_INDEX_FILE_NAME),
+            serde_json::to_string(&upstream_index).unwrap(),
+        )
+        .unwrap();
+
+        let capture_dir = TempDir::new().unwrap();
+        let capture_path = capture_dir.path().to_str().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let upstream = FixtureServer::replay(upstream_path).await.unwrap();
+            let capture = FixtureServer::capture(
+                capture_path,
+                upstream.origin(),
+                &DownloadOptions::default(),
+            )
+            .await
+            .unwrap();
+            let client = reqwest::Client::new();
+            let captured = client
+                .get(format!("{}{}", capture.origin(), package_path))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(captured.status(), 200);
+            assert_eq!(captured.bytes().await.unwrap().to_vec(), stub);
+            capture.finish(None).unwrap();
+            upstream.finish(None).unwrap();
+
+            let replay = FixtureServer::replay(capture_path).await.unwrap();
+            let replayed = client
+                .get(format!("{}{}", replay.origin(), package_path))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(replayed.bytes().await.unwrap().to_vec(), stub);
+            let missing = client
+                .get(format!("{}/_apis/unknown", replay.origin()))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(missing.status(), 404);
+            replay.finish(None).unwrap();
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use crate::architecture::{Architecture, PlatformSelector};
+use crate::args::Args;
+use crate::download_extensions_from_json;
+use crate::error::{Result, VsixHarvesterError};
+use log::{error, info};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Harvest profile of a machine or a role of the fleet
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetProfile {
+    /// The platform of the machines (e.g., "linux_x64")
+    pub platform: Architecture,
+    /// Optional engine version of the machines, the one of the command line by default
+    pub engine_version: Option<String>,
+    /// Optional group, exposed to the manifest as `${MIRROR_GROUP}`
+    pub group: Option<String>,
+}
+
+/// Fleet inventory file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fleet {
+    /// The profiles keyed by hostname or role
+    pub profiles: BTreeMap<String, FleetProfile>,
+}
+
+impl Fleet {
+    /// Load a fleet file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the fleet file
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the fleet or an error that occurred
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Build the arguments of the harvest of a fleet profile
+///
+/// The packages are downloaded once into the destination, shared by all the profiles,
+/// and copied to the `<destination>/<name>` sub-directory of the profile.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `name` - The hostname or role of the profile
+/// * `profile` - The fleet profile
+///
+/// # Returns
+///
+/// A Result containing the arguments or an error if the profile is invalid
+pub fn profile_args(args: &Args, name: &str, profile: &FleetProfile) -> Result<Args> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(VsixHarvesterError::InvalidFleetProfile(name.to_string()));
+    }
+    let primary = args.primary_destination();
+    let mut profile_args = args.clone();
+    profile_args.fleet = None;
+    profile_args.destination = vec![primary.clone(), format!("{}/{}", primary, name)];
+    profile_args.only_platforms = vec![
+        PlatformSelector::Universal,
+        profile.platform.target_platform().into(),
+    ];
+    if let Some(engine_version) = &profile.engine_version {
+        profile_args.engine_version = Some(engine_version.clone());
+    }
+    if let Some(engine_version) = &profile_args.engine_version {
+        profile_args
+            .manifest_variables
+            .insert("ENGINE_VERSION".to_string(), engine_version.clone());
+    }
+    if let Some(group) = &profile.group {
+        profile_args
+            .manifest_variables
+            .insert("MIRROR_GROUP".to_string(), group.clone());
+    }
+    Ok(profile_args)
+}
+
+/// Harvest the manifest for every profile of a fleet
+///
+/// # Arguments
+///
+/// * `fleet_path` - The path of the fleet file
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error if a profile failed
+pub async fn harvest_fleet(fleet_path: &str, args: &Args) -> Result<()> {
+    let fleet = Fleet::load(fleet_path)?;
+    let mut failed = Vec::new();
+    for (name, profile) in &fleet.profiles {
+        info!(
+            "Harvesting fleet profile {} ({}, engine {})",
+            name,
+            profile.platform,
+            profile
+                .engine_version
+                .as_deref()
+                .or(args.engine_version.as_deref())
+                .unwrap_or("any")
+        );
+        let result = match profile_args(args, name, profile) {
+            Ok(profile_args) => download_extensions_from_json(&profile_args).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!(
+                "Error occurred when harvesting fleet profile {}: {}",
+                name, e
+            );
+            failed.push(name.clone());
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(VsixHarvesterError::InvalidFleetProfile(failed.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_args() {
+        let fleet: Fleet = serde_json::from_str(
+            r#"{"profiles": {
+                "build-01": {"platform": "linux_x64", "engineVersion": "1.97.0", "group": "backend"},
+                "designers": {"platform": "darwin_arm64"}
+            }}"#,
+        )
+        .unwrap();
+        assert!(serde_json::from_str::<Fleet>(
+            r#"{"profiles": {"build-02": {"platform": "haiku_x64"}}}"#
+        )
+        .is_err());
+        let args = Args {
+            destination: vec!["/srv/mirror".to_string()],
+            engine_version: Some("1.96.0".to_string()),
+            ..Default::default()
+        };
+
+        let build = profile_args(&args, "build-01", &fleet.profiles["build-01"]).unwrap();
+        assert_eq!(build.primary_destination(), "/srv/mirror");
+        assert_eq!(build.mirror_destinations()[0].path, "/srv/mirror/build-01");
+        assert_eq!(
+            build.only_platforms,
+            vec![
+                PlatformSelector::Universal,
+                PlatformSelector::Target("linux-x64".into())
+            ]
+        );
+        assert_eq!(build.engine_version.as_deref(), Some("1.97.0"));
+        assert_eq!(build.manifest_variables["MIRROR_GROUP"], "backend");
+
+        let designers = profile_args(&args, "designers", &fleet.profiles["designers"]).unwrap();
+        assert_eq!(designers.engine_version.as_deref(), Some("1.96.0"));
+        assert_eq!(designers.manifest_variables["ENGINE_VERSION"], "1.96.0");
+        assert!(!designers.manifest_variables.contains_key("MIRROR_GROUP"));
+
+        let invalid = FleetProfile {
+            platform: Architecture::LinuxX64,
+            engine_version: None,
+            group: None,
+        };
+        assert!(profile_args(&args, "../etc", &invalid).is_err());
+    }
+}
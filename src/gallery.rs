@@ -0,0 +1,621 @@
+use crate::architecture::TargetPlatform;
+use crate::assets::{asset_cache_path, icon_cache_path, ICON_ASSET_TYPE};
+use crate::config::GALLERY_DEFAULT_PAGE_SIZE;
+use crate::error::Result;
+use crate::http_server::{serve_connections, Request, Response};
+use crate::marketplace::{sha256_hex, Flags};
+use crate::status::scan_mirror;
+use crate::types::{
+    compare_versions, Extension as MarketplaceExtension, File, MarketplaceResponse, MetadataItem,
+    Property, Publisher, ResultItem, ResultMetadata, Version,
+};
+use crate::vsix::read_vsix_info;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use zip::ZipArchive;
+
+/// The package asset type of the gallery API
+const VSIX_ASSET_TYPE: &str = "Microsoft.VisualStudio.Services.VSIXPackage";
+
+/// The entries of a package served for the detail asset types not cached by `prewarm`
+const PACKAGE_ASSET_ENTRIES: [(&str, &str); 3] = [
+    (
+        "Microsoft.VisualStudio.Code.Manifest",
+        "extension/package.json",
+    ),
+    (
+        "Microsoft.VisualStudio.Services.Content.Details",
+        "extension/README.md",
+    ),
+    (
+        "Microsoft.VisualStudio.Services.Content.Changelog",
+        "extension/CHANGELOG.md",
+    ),
+];
+
+/// Check that an asset type of a request is one the gallery serves
+///
+/// The asset type is joined to the path of the assets cache, so anything else than a
+/// known type, such as "../../secret", is rejected.
+fn is_served_asset_type(asset_type: &str) -> bool {
+    !asset_type.contains("..")
+        && !asset_type.contains(['/', '\\'])
+        && (asset_type == VSIX_ASSET_TYPE
+            || asset_type == ICON_ASSET_TYPE
+            || PACKAGE_ASSET_ENTRIES
+                .iter()
+                .any(|(kind, _)| *kind == asset_type))
+}
+
+/// A package of the served directory
+#[derive(Debug, Clone)]
+struct GalleryPackage {
+    file_name: String,
+    version: String,
+    target_platform: Option<TargetPlatform>,
+    engine: Option<String>,
+    extension_dependencies: Vec<String>,
+    extension_pack: Vec<String>,
+    last_updated: DateTime<Utc>,
+}
+
+/// An extension of the served directory with its packages, the newest version first
+#[derive(Debug, Clone)]
+struct GalleryExtension {
+    publisher: String,
+    name: String,
+    packages: Vec<GalleryPackage>,
+}
+
+impl GalleryExtension {
+    fn id(&self) -> String {
+        format!("{}.{}", self.publisher, self.name)
+    }
+
+    /// Get a stable GUID standing for the `extensionId` of the marketplace
+    fn extension_id(&self) -> String {
+        let digest = sha256_hex(self.id().to_lowercase().as_bytes());
+        format!(
+            "{}-{}-{}-{}-{}",
+            &digest[..8],
+            &digest[8..12],
+            &digest[12..16],
+            &digest[16..20],
+            &digest[20..32]
+        )
+    }
+
+    /// Build the marketplace metadata of the extension
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The origin of the gallery the asset URLs point to
+    /// * `latest_only` - Whether to keep the packages of the newest version only
+    fn to_marketplace(&self, origin: &str, latest_only: bool) -> MarketplaceExtension {
+        let newest = self.packages[0].version.as_str();
+        let versions: Vec<Version> = self
+            .packages
+            .iter()
+            .filter(|package| !latest_only || package.version == newest)
+            .map(|package| {
+                let asset_uri = format!("{}/assets/{}", origin, package.file_name);
+                let mut properties = Vec::new();
+                if let Some(engine) = &package.engine {
+                    properties.push(Property {
+                        key: "Microsoft.VisualStudio.Code.Engine".to_string(),
+                        value: engine.clone(),
+                    });
+                }
+                properties.push(Property {
+                    key: "Microsoft.VisualStudio.Code.ExtensionDependencies".to_string(),
+                    value: package.extension_dependencies.join(","),
+                });
+                properties.push(Property {
+                    key: "Microsoft.VisualStudio.Code.ExtensionPack".to_string(),
+                    value: package.extension_pack.join(","),
+                });
+                let files = std::iter::once(VSIX_ASSET_TYPE)
+                    .chain(
+                        PACKAGE_ASSET_ENTRIES
+                            .iter()
+                            .map(|(asset_type, _)| *asset_type),
+                    )
+                    .map(|asset_type| File {
+                        asset_type: asset_type.to_string(),
+                        source: format!("{}/{}", asset_uri, asset_type),
+                    })
+                    .collect();
+                Version {
+                    version: package.version.clone(),
+                    flags: "validated".to_string(),
+                    last_updated: package.last_updated.to_rfc3339(),
+                    files,
+                    properties: Some(properties),
+                    fallback_asset_uri: asset_uri.clone(),
+                    asset_uri,
+                    target_platform: package.target_platform.clone(),
+                }
+            })
+            .collect();
+        let last_updated = self
+            .packages
+            .iter()
+            .map(|package| package.last_updated)
+            .max()
+            .unwrap_or_default()
+            .to_rfc3339();
+        MarketplaceExtension {
+            publisher: Publisher {
+                publisher_id: self.publisher.to_lowercase(),
+                publisher_name: self.publisher.clone(),
+                display_name: self.publisher.clone(),
+                flags: String::new(),
+                domain: None,
+                is_domain_verified: false,
+            },
+            extension_id: self.extension_id(),
+            extension_name: self.name.clone(),
+            display_name: self.name.clone(),
+            flags: "validated, public".to_string(),
+            last_updated: last_updated.clone(),
+            published_date: last_updated.clone(),
+            release_date: last_updated,
+            short_description: String::new(),
+            versions,
+            deployment_type: 0,
+            statistics: Vec::new(),
+        }
+    }
+}
+
+/// The harvested packages served as an offline gallery
+#[derive(Debug, Default)]
+pub struct Gallery {
+    dir: String,
+    /// The extensions keyed by lowercase identifier
+    extensions: BTreeMap<String, GalleryExtension>,
+}
+
+impl Gallery {
+    /// Index the packages of a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory of the harvested packages
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the gallery or an error if the directory cannot be read
+    pub fn load(dir: &str) -> Result<Self> {
+        let mut extensions: BTreeMap<String, GalleryExtension> = BTreeMap::new();
+        for package in scan_mirror(dir)? {
+            let path = format!("{}/{}", dir, package.file_name);
+            let info = match read_vsix_info(&path) {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!("Not serving {}: {}", package.file_name, e);
+                    continue;
+                }
+            };
+            let last_updated = fs::metadata(&path)?
+                .modified()
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_default();
+            extensions
+                .entry(info.id().to_lowercase())
+                .or_insert_with(|| GalleryExtension {
+                    publisher: info.publisher.clone(),
+                    name: info.name.clone(),
+                    packages: Vec::new(),
+                })
+                .packages
+                .push(GalleryPackage {
+                    file_name: package.file_name,
+                    version: info.version,
+                    target_platform: info.target_platform,
+                    engine: info.engine,
+                    extension_dependencies: info.extension_dependencies,
+                    extension_pack: info.extension_pack,
+                    last_updated,
+                });
+        }
+        for extension in extensions.values_mut() {
+            extension
+                .packages
+                .sort_by(|a, b| compare_versions(&b.version, &a.version).cmp(&0));
+        }
+        Ok(Self {
+            dir: dir.to_string(),
+            extensions,
+        })
+    }
+
+    /// Answer an `extensionquery` request
+    ///
+    /// The extensions are matched by name (filter type 7), by `extensionId` (filter
+    /// type 4) or by search text (filter type 10), the other criteria are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The JSON payload of the query
+    /// * `origin` - The origin of the gallery the asset URLs point to
+    ///
+    /// # Returns
+    ///
+    /// The response in the marketplace format
+    pub fn query(&self, query: &Value, origin: &str) -> MarketplaceResponse {
+        let filter = &query["filters"][0];
+        let criteria: Vec<(u64, String)> = filter["criteria"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|criterion| {
+                Some((
+                    criterion["filterType"].as_u64()?,
+                    criterion["value"].as_str()?.to_lowercase(),
+                ))
+            })
+            .collect();
+        let values = |filter_type: u64| -> Vec<&str> {
+            criteria
+                .iter()
+                .filter(|(kind, _)| *kind == filter_type)
+                .map(|(_, value)| value.as_str())
+                .collect()
+        };
+        let (names, guids, texts) = (values(7), values(4), values(10));
+        let matches = |key: &String, extension: &GalleryExtension| {
+            if !names.is_empty() || !guids.is_empty() {
+                return names.contains(&key.as_str())
+                    || guids.contains(&extension.extension_id().as_str());
+            }
+            texts.iter().all(|text| key.contains(text))
+        };
+        let found: Vec<&GalleryExtension> = self
+            .extensions
+            .iter()
+            .filter(|(key, extension)| matches(key, extension))
+            .map(|(_, extension)| extension)
+            .collect();
+
+        let page_size = filter["pageSize"]
+            .as_u64()
+            .map_or(GALLERY_DEFAULT_PAGE_SIZE, |size| size as usize);
+        let page_number = filter["pageNumber"].as_u64().unwrap_or(1).max(1) as usize;
+        let latest_only = query["flags"].as_u64().is_some_and(|flags| {
+            Flags::from_bits_truncate(flags as u32).contains(Flags::INCLUDE_LATEST_VERSION_ONLY)
+        });
+        MarketplaceResponse {
+            results: vec![ResultItem {
+                extensions: found
+                    .iter()
+                    .skip((page_number - 1) * page_size)
+                    .take(page_size)
+                    .map(|extension| extension.to_marketplace(origin, latest_only))
+                    .collect(),
+                paging_token: None,
+                result_metadata: vec![ResultMetadata {
+                    metadata_type: "ResultCount".to_string(),
+                    metadata_items: vec![MetadataItem {
+                        name: "TotalCount".to_string(),
+                        count: found.len() as i32,
+                    }],
+                }],
+            }],
+        }
+    }
+
+    /// Find a served package by file name
+    fn find_package(&self, file_name: &str) -> Option<(&GalleryExtension, &GalleryPackage)> {
+        self.extensions.values().find_map(|extension| {
+            extension
+                .packages
+                .iter()
+                .find(|package| package.file_name == file_name)
+                .map(|package| (extension, package))
+        })
+    }
+
+    /// Read an asset of a served package, preferring the assets cached by `prewarm`
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The file name of the package
+    /// * `asset_type` - The asset type (e.g., "Microsoft.VisualStudio.Code.Manifest")
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the asset, None if the package or the asset does not exist
+    /// or if the asset type is not served
+    pub fn asset(&self, file_name: &str, asset_type: &str) -> Result<Option<Vec<u8>>> {
+        if !is_served_asset_type(asset_type) {
+            return Ok(None);
+        }
+        let Some((extension, package)) = self.find_package(file_name) else {
+            return Ok(None);
+        };
+        let path = format!("{}/{}", self.dir, package.file_name);
+        if asset_type == VSIX_ASSET_TYPE {
+            return Ok(Some(fs::read(path)?));
+        }
+        let cached = if asset_type == ICON_ASSET_TYPE {
+            icon_cache_path(&self.dir, &extension.id(), &package.version)
+        } else {
+            asset_cache_path(&self.dir, &extension.id(), &package.version, asset_type)
+        };
+        if let Ok(content) = fs::read(cached) {
+            return Ok(Some(content));
+        }
+        let Some((_, entry_name)) = PACKAGE_ASSET_ENTRIES
+            .iter()
+            .find(|(kind, _)| *kind == asset_type)
+        else {
+            return Ok(None);
+        };
+        let mut archive = ZipArchive::new(fs::File::open(path)?)?;
+        let mut entry = match archive.by_name(entry_name) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        Ok(Some(content))
+    }
+
+    /// Find the file name of a package from the download URL of the marketplace
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the request, "/_apis/public/gallery/publishers/<publisher>/vsextensions/<name>/<version>/vspackage"
+    ///   with an optional `targetPlatform` query parameter
+    ///
+    /// # Returns
+    ///
+    /// The file name of the package, None if it is not served
+    fn vspackage_file_name(&self, path: &str) -> Option<String> {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let parts: Vec<&str> = path
+            .strip_prefix("/_apis/public/gallery/publishers/")?
+            .split('/')
+            .collect();
+        let [publisher, "vsextensions", name, version, "vspackage"] = parts[..] else {
+            return None;
+        };
+        let target_platform = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("targetPlatform="))
+            .map(TargetPlatform::new);
+        let extension = self
+            .extensions
+            .get(&format!("{}.{}", publisher, name).to_lowercase())?;
+        // Universal packages are served for any target platform
+        extension
+            .packages
+            .iter()
+            .filter(|package| package.version == version)
+            .find(|package| package.target_platform == target_platform)
+            .or_else(|| {
+                extension
+                    .packages
+                    .iter()
+                    .find(|package| package.version == version && package.target_platform.is_none())
+            })
+            .map(|package| package.file_name.clone())
+    }
+}
+
+/// Answer a request of the gallery
+///
+/// # Returns
+///
+/// The status, content type and body of the response
+fn handle_request(gallery: &Gallery, request: &Request, origin: &str) -> Result<Response> {
+    let not_found = Ok(Response::new(404, "text/plain", b"Not Found".to_vec()));
+    let path = request.path.as_str();
+    match request.method.as_str() {
+        "POST" if path.starts_with("/_apis/public/gallery/extensionquery") => {
+            let query: Value = serde_json::from_slice(&request.body)?;
+            let response = gallery.query(&query, origin);
+            Ok(Response::new(
+                200,
+                "application/json",
+                serde_json::to_vec(&response)?,
+            ))
+        }
+        "GET" => {
+            let asset = if let Some(file_name) = path.strip_prefix("/files/") {
+                gallery.asset(file_name, VSIX_ASSET_TYPE)?
+            } else if let Some(asset) = path.strip_prefix("/assets/") {
+                let Some((file_name, asset_type)) = asset.split_once('/') else {
+                    return not_found;
+                };
+                if !is_served_asset_type(asset_type) {
+                    return not_found;
+                }
+                gallery.asset(file_name, asset_type)?
+            } else if let Some(file_name) = gallery.vspackage_file_name(path) {
+                gallery.asset(&file_name, VSIX_ASSET_TYPE)?
+            } else {
+                None
+            };
+            match asset {
+                Some(content) if path.ends_with(".json") || path.ends_with("Manifest") => {
+                    Ok(Response::new(200, "application/json", content))
+                }
+                Some(content) => Ok(Response::new(200, "application/octet-stream", content)),
+                None => not_found,
+            }
+        }
+        _ => not_found,
+    }
+}
+
+/// Answer a request of the gallery, logging the failures
+fn respond(gallery: &Gallery, request: &Request) -> Response {
+    let origin = format!("http://{}", request.header("host").unwrap_or("localhost"));
+    handle_request(gallery, request, &origin).unwrap_or_else(|e| {
+        warn!(
+            "Failed to answer {} {}: {}",
+            request.method, request.path, e
+        );
+        Response::new(400, "text/plain", e.to_string().into_bytes())
+    })
+}
+
+/// Serve the harvested packages as an offline gallery until the shutdown token is cancelled
+///
+/// VS Code instances use it by setting the `serviceUrl` of the `extensionsGallery`
+/// of their `product.json` to `http://<host>:<port>/_apis/public/gallery`.
+///
+/// # Arguments
+///
+/// * `dir` - The directory of the harvested packages
+/// * `listen` - The address to listen on (e.g., "127.0.0.1:8080")
+/// * `shutdown` - The token stopping the gallery
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn serve_gallery(dir: &str, listen: &str, shutdown: CancellationToken) -> Result<()> {
+    let gallery = Arc::new(Gallery::load(dir)?);
+    let listener = TcpListener::bind(listen).await?;
+    let address = listener.local_addr()?;
+    info!(
+        "Serving {} extension(s) of {} on http://{}, serviceUrl http://{}/_apis/public/gallery",
+        gallery.extensions.len(),
+        dir,
+        address,
+        address
+    );
+    serve_connections(listener, "gallery", shutdown, move |request| {
+        let response = respond(&gallery, &request);
+        async move { response }
+    })
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    fn package(version: &str, target_platform: Option<&str>) -> Vec<u8> {
+        test_support::vsix("golang.Go", version, target_platform)
+    }
+
+    #[test]
+    fn test_gallery() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.1.vsix", dir),
+            package("0.41.1", None),
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.2@linux-x64.vsix", dir),
+            package("0.41.2", Some("linux-x64")),
+        )
+        .unwrap();
+        let gallery = Gallery::load(dir).unwrap();
+
+        let origin = "http://gallery:8080";
+        let response = gallery.query(
+            &json!({
+                "filters": [{"criteria": [
+                    {"filterType": 8, "value": "Microsoft.VisualStudio.Code"},
+                    {"filterType": 7, "value": "golang.go"}
+                ]}],
+                "flags": Flags::all_versions().bits()
+            }),
+            origin,
+        );
+        // The response is read back like a marketplace one
+        let response: MarketplaceResponse =
+            serde_json::from_value(serde_json::to_value(response).unwrap()).unwrap();
+        let extension = &response.results[0].extensions[0];
+        assert_eq!(extension.get_identifier(), "golang.Go");
+        assert_eq!(extension.versions.len(), 2);
+        assert_eq!(extension.versions[0].version, "0.41.2");
+        assert_eq!(
+            extension.versions[0].get_vsix_url().as_deref(),
+            Some("http://gallery:8080/assets/golang.Go-0.41.2@linux-x64.vsix/Microsoft.VisualStudio.Services.VSIXPackage")
+        );
+        assert_eq!(
+            extension.versions[1].get_vscode_engine_version().as_deref(),
+            Some("^1.90.0")
+        );
+
+        let latest = gallery.query(
+            &json!({
+                "filters": [{"criteria": [{"filterType": 10, "value": "go"}]}],
+                "flags": Flags::standard().bits()
+            }),
+            origin,
+        );
+        assert_eq!(latest.results[0].extensions[0].versions.len(), 1);
+
+        let manifest = gallery
+            .asset(
+                "golang.Go-0.41.1.vsix",
+                "Microsoft.VisualStudio.Code.Manifest",
+            )
+            .unwrap()
+            .unwrap();
+        assert!(String::from_utf8(manifest).unwrap().contains("0.41.1"));
+        assert_eq!(
+            gallery
+                .vspackage_file_name("/_apis/public/gallery/publishers/golang/vsextensions/Go/0.41.1/vspackage?targetPlatform=win32-x64")
+                .as_deref(),
+            Some("golang.Go-0.41.1.vsix")
+        );
+        assert!(gallery
+            .asset("../secret.vsix", VSIX_ASSET_TYPE)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_gallery_asset_traversal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.1.vsix", dir),
+            package("0.41.1", None),
+        )
+        .unwrap();
+        // Resolved from the assets cache of the package, "../../secret.txt" is the mirror root
+        fs::create_dir_all(asset_cache_path(dir, "golang.Go", "0.41.1", "")).unwrap();
+        fs::write(format!("{}/secret.txt", dir), "secret").unwrap();
+        let gallery = Gallery::load(dir).unwrap();
+
+        for asset_type in [
+            "../../secret.txt",
+            "..",
+            "Microsoft.VisualStudio.Code.Manifest/..",
+        ] {
+            assert!(gallery
+                .asset("golang.Go-0.41.1.vsix", asset_type)
+                .unwrap()
+                .is_none());
+        }
+        let request = Request {
+            method: "GET".to_string(),
+            path: "/assets/golang.Go-0.41.1.vsix/../../secret.txt".to_string(),
+            ..Default::default()
+        };
+        let response = handle_request(&gallery, &request, "http://gallery").unwrap();
+        assert_eq!(response.status, 404);
+        assert_ne!(response.body, b"secret");
+    }
+}
@@ -0,0 +1,182 @@
+use crate::args::Args;
+use crate::config::{
+    ATTESTATION_PREDICATE_FILE_NAME, CHECKSUMS_FILE_NAME, GIT_PUBLISH_DIR, INVENTORY_FILE_NAME,
+};
+use crate::error::{Result, VsixHarvesterError};
+use crate::temp::copy_atomic;
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Check if a publish target is a Git repository (e.g., "git@host:mirror.git")
+pub fn is_git_target(target: &str) -> bool {
+    target.starts_with("git@")
+        || target.starts_with("git+")
+        || target.starts_with("ssh://")
+        || target.ends_with(".git")
+}
+
+/// Expand the placeholders of the commit message template
+///
+/// # Arguments
+///
+/// * `template` - The template, with `{date}`, `{packages}` and `{input}` placeholders
+/// * `date` - The date of the harvest
+/// * `packages` - The number of packages of the mirror
+/// * `input` - The path of the manifest
+///
+/// # Returns
+///
+/// The commit message
+pub fn render_commit_message(
+    template: &str,
+    date: DateTime<Utc>,
+    packages: usize,
+    input: &str,
+) -> String {
+    template
+        .replace("{date}", &date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .replace("{packages}", &packages.to_string())
+        .replace("{input}", input)
+}
+
+/// Run a git command in a directory
+///
+/// # Arguments
+///
+/// * `directory` - The working directory
+/// * `args` - The arguments of the git command
+///
+/// # Returns
+///
+/// A Result containing the standard output or an error if the command failed
+fn git(directory: &Path, args: &[&str]) -> Result<String> {
+    debug!("git {}", args.join(" "));
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(VsixHarvesterError::PublishError(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Copy a file into the working tree if it exists
+fn copy_if_exists(source: &str, directory: &Path) -> Result<bool> {
+    let source = Path::new(source);
+    let Some(file_name) = source.file_name() else {
+        return Ok(false);
+    };
+    if !source.is_file() {
+        return Ok(false);
+    }
+    copy_atomic(source, &directory.join(file_name))?;
+    Ok(true)
+}
+
+/// Commit the state of the mirror to a Git repository and push it
+///
+/// The repository is cloned once in the destination and updated by each publication.
+/// The manifest, lockfile, checksums, attestation predicate and inventory are committed,
+/// and the packages too through Git LFS with `--publish-lfs`.
+///
+/// # Arguments
+///
+/// * `target` - The URL of the repository (e.g., "git@host:mirror.git")
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn publish_to_git(target: &str, args: &Args) -> Result<()> {
+    let url = target.strip_prefix("git+").unwrap_or(target);
+    let destination = args.primary_destination();
+    let directory = Path::new(&destination).join(GIT_PUBLISH_DIR);
+    if directory.join(".git").is_dir() {
+        git(&directory, &["pull", "--ff-only"])?;
+    } else {
+        info!("Cloning {} into {}", url, directory.display());
+        git(
+            Path::new(&destination),
+            &["clone", url, &directory.to_string_lossy()],
+        )?;
+    }
+
+    for path in [
+        args.input.clone(),
+        args.lockfile_path(),
+        format!("{}/{}", destination, CHECKSUMS_FILE_NAME),
+        format!("{}/{}", destination, ATTESTATION_PREDICATE_FILE_NAME),
+        format!("{}/{}", destination, INVENTORY_FILE_NAME),
+    ]
+    .into_iter()
+    .chain(args.drift_report.clone())
+    {
+        copy_if_exists(&path, &directory)?;
+    }
+
+    let mut packages = 0;
+    for entry in fs::read_dir(&destination)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "vsix")
+        {
+            packages += 1;
+            if args.publish_lfs {
+                copy_if_exists(&path.to_string_lossy(), &directory)?;
+            }
+        }
+    }
+    if args.publish_lfs {
+        git(&directory, &["lfs", "track", "*.vsix"])?;
+    }
+
+    git(&directory, &["add", "--all"])?;
+    if git(&directory, &["status", "--porcelain"])?
+        .trim()
+        .is_empty()
+    {
+        info!("Nothing changed since the last publication to {}", url);
+        return Ok(());
+    }
+    let message = render_commit_message(&args.publish_message, Utc::now(), packages, &args.input);
+    git(&directory, &["commit", "--quiet", "-m", &message])?;
+    git(&directory, &["push", "--quiet"])?;
+    info!("Published the harvest to {}: {}", url, message);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_git_target() {
+        assert!(is_git_target("git@example.com:team/mirror.git"));
+        assert!(is_git_target("https://example.com/team/mirror.git"));
+        assert!(is_git_target("git+https://example.com/team/mirror"));
+        assert!(!is_git_target(
+            "oci://registry.local/vscode/extensions:latest"
+        ));
+
+        let date = Utc.with_ymd_and_hms(2025, 3, 1, 12, 30, 0).unwrap();
+        assert_eq!(
+            render_commit_message(
+                "Harvest of {date} ({packages} packages, {input})",
+                date,
+                42,
+                "extensions.json"
+            ),
+            "Harvest of 2025-03-01 12:30 UTC (42 packages, extensions.json)"
+        );
+    }
+}
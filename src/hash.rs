@@ -0,0 +1,320 @@
+use crate::config::BLAKE3_CHECKSUMS_FILE_NAME;
+use crate::config::{CHECKSUMS_FILE_NAME, SHA512_CHECKSUMS_FILE_NAME};
+use crate::crypto::{blake3, Blake3Hasher};
+use crate::crypto::{sha256, sha512, Sha256Hasher, Sha512Hasher};
+use clap::ValueEnum;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+/// Hash algorithm of the checksums of the packages
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ValueEnum,
+)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum HashAlgorithm {
+    /// SHA-256, the default
+    #[default]
+    Sha256,
+    /// SHA-512, where a policy demands it
+    Sha512,
+    /// BLAKE3, much faster on huge mirrors
+    Blake3,
+}
+
+impl TryFrom<String> for HashAlgorithm {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match name.as_str() {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(format!("unknown hash algorithm {}", name)),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    /// Every supported algorithm
+    pub const ALL: &'static [HashAlgorithm] = &[
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake3,
+    ];
+
+    /// Get the name of the algorithm, as recorded in the lockfile
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Get the name of the checksums file listing every package
+    pub fn sums_file_name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => CHECKSUMS_FILE_NAME,
+            HashAlgorithm::Sha512 => SHA512_CHECKSUMS_FILE_NAME,
+            HashAlgorithm::Blake3 => BLAKE3_CHECKSUMS_FILE_NAME,
+        }
+    }
+
+    /// Get the extension of the checksum file written next to a package
+    pub fn sidecar_extension(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "b3",
+        }
+    }
+
+    /// Hash a content
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content to hash
+    ///
+    /// # Returns
+    ///
+    /// The digest of the content, hex encoded
+    pub fn digest(self, content: &[u8]) -> Digest {
+        let value = match self {
+            HashAlgorithm::Sha256 => hex::encode(sha256(content)),
+            HashAlgorithm::Sha512 => hex::encode(sha512(content)),
+            HashAlgorithm::Blake3 => hex::encode(blake3(content)),
+        };
+        Digest {
+            algorithm: self,
+            value,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A hex encoded digest with its algorithm
+///
+/// It is serialized as a single entry map keyed by the algorithm (e.g.,
+/// `{"blake3": "..."}`), the format of the in-toto digest sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub algorithm: HashAlgorithm,
+    pub value: String,
+}
+
+impl Digest {
+    /// Create a digest from a stored hex value, compared case-insensitively
+    pub fn new(algorithm: HashAlgorithm, value: &str) -> Self {
+        Self {
+            algorithm,
+            value: value.to_lowercase(),
+        }
+    }
+
+    /// Check that a content has this digest
+    pub fn matches(&self, content: &[u8]) -> bool {
+        self.algorithm.digest(content).value == self.value
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.value)
+    }
+}
+
+impl Serialize for Digest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.algorithm.name(), &self.value)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct DigestVisitor;
+
+        impl<'de> Visitor<'de> for DigestVisitor {
+            type Value = Digest;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of a hash algorithm to its hex digest")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Digest, A::Error> {
+                let (algorithm, value): (HashAlgorithm, String) = map
+                    .next_entry()?
+                    .ok_or_else(|| de::Error::custom("empty digest"))?;
+                if map.next_key::<de::IgnoredAny>()?.is_some() {
+                    return Err(de::Error::custom("a digest has a single algorithm"));
+                }
+                Ok(Digest::new(algorithm, &value))
+            }
+        }
+
+        deserializer.deserialize_map(DigestVisitor)
+    }
+}
+
+/// The hasher of another algorithm than SHA-256
+#[derive(Debug, Clone)]
+enum OtherHasher {
+    Sha512(Box<Sha512Hasher>),
+    Blake3(Box<Blake3Hasher>),
+}
+
+/// The hashes of a package computed in a single pass over its content
+///
+/// The SHA-256 identifies the package in the lockfile and its provenance, the digest
+/// with the `--hash` algorithm is also computed when it is another one.
+#[derive(Debug, Clone)]
+pub struct PackageHasher {
+    sha256: Sha256Hasher,
+    other: Option<OtherHasher>,
+}
+
+impl PackageHasher {
+    /// Create a hasher of empty content
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The algorithm of the digest recorded next to the SHA-256
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        let other = match algorithm {
+            HashAlgorithm::Sha256 => None,
+            HashAlgorithm::Sha512 => Some(OtherHasher::Sha512(Box::default())),
+            HashAlgorithm::Blake3 => Some(OtherHasher::Blake3(Box::default())),
+        };
+        Self {
+            sha256: Sha256Hasher::new(),
+            other,
+        }
+    }
+
+    /// Hash a whole content
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The algorithm of the digest recorded next to the SHA-256
+    /// * `content` - The content to hash
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded SHA-256, and the digest with the other algorithm if any
+    pub fn hash(algorithm: HashAlgorithm, content: &[u8]) -> (String, Option<Digest>) {
+        let mut hasher = Self::new(algorithm);
+        hasher.update(content);
+        hasher.finalize()
+    }
+
+    /// Hash the next bytes of the content
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        match &mut self.other {
+            Some(OtherHasher::Sha512(hasher)) => hasher.update(data),
+            Some(OtherHasher::Blake3(hasher)) => hasher.update(data),
+            None => {}
+        }
+    }
+
+    /// Get the hashes of the hashed content
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded SHA-256, and the digest with the other algorithm if any
+    pub fn finalize(self) -> (String, Option<Digest>) {
+        let other = self.other.map(|other| match other {
+            OtherHasher::Sha512(hasher) => Digest {
+                algorithm: HashAlgorithm::Sha512,
+                value: hex::encode(hasher.finalize()),
+            },
+            OtherHasher::Blake3(hasher) => Digest {
+                algorithm: HashAlgorithm::Blake3,
+                value: hex::encode(hasher.finalize()),
+            },
+        });
+        (hex::encode(self.sha256.finalize()), other)
+    }
+}
+
+impl io::Write for PackageHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        let content = b"package";
+        let sha256 = HashAlgorithm::Sha256.digest(content);
+        assert_eq!(sha256.value, crate::marketplace::sha256_hex(content));
+        let sha512 = HashAlgorithm::Sha512.digest(content);
+        assert_eq!(sha512.value.len(), 128);
+        let json = serde_json::to_string(&sha512).unwrap();
+        assert_eq!(serde_json::from_str::<Digest>(&json).unwrap(), sha512);
+        assert!(serde_json::from_str::<Digest>(r#"{"md5":"00"}"#).is_err());
+        assert!(serde_json::from_str::<Digest>(r#"{"sha256":"00","sha512":"00"}"#).is_err());
+    }
+
+    #[test]
+    fn test_package_hasher() {
+        for &algorithm in HashAlgorithm::ALL {
+            let mut hasher = PackageHasher::new(algorithm);
+            hasher.update(b"pack");
+            hasher.update(b"age");
+            let (sha256, other) = hasher.finalize();
+            assert_eq!(sha256, HashAlgorithm::Sha256.digest(b"package").value);
+            match algorithm {
+                HashAlgorithm::Sha256 => assert_eq!(other, None),
+                _ => assert_eq!(other, Some(algorithm.digest(b"package"))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_blake3_digest() {
+        let content = b"package";
+        let sha256 = HashAlgorithm::Sha256.digest(content);
+        let blake3 = HashAlgorithm::Blake3.digest(content);
+        assert_eq!(blake3.value.len(), 64);
+        assert_ne!(blake3.value, sha256.value);
+        assert!(blake3.matches(content));
+        assert!(!blake3.matches(b"other"));
+
+        let json = serde_json::to_string(&blake3).unwrap();
+        assert_eq!(json, format!(r#"{{"blake3":"{}"}}"#, blake3.value));
+        assert_eq!(serde_json::from_str::<Digest>(&json).unwrap(), blake3);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::config::{HTTP_MAX_BODY_SIZE, HTTP_MAX_HEAD_SIZE};
+use log::warn;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+/// A request received by the local HTTP/1.1 servers (gallery, fixture, service)
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    /// The path and query of the request (e.g., "/jobs?status=running")
+    pub path: String,
+    /// The headers, keyed by lowercase name
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Get the value of a header
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The lowercase name of the header (e.g., "content-type")
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// A response of the local HTTP/1.1 servers
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Create a response with a content type
+    pub fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type: Some(content_type.to_string()),
+            body,
+        }
+    }
+
+    /// Create a JSON response
+    pub fn json(status: u16, body: &Value) -> Self {
+        Self::new(status, "application/json", body.to_string().into_bytes())
+    }
+}
+
+/// Get the reason phrase of a status code
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Content Too Large",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// Read a request from a connection
+///
+/// # Arguments
+///
+/// * `stream` - The connection
+///
+/// # Returns
+///
+/// A Result containing the request, None if the connection closed before a complete
+/// request, or an `InvalidData` error if its head or body exceeds the size limits
+pub async fn read_request<S: AsyncBufRead + Unpin>(stream: &mut S) -> io::Result<Option<Request>> {
+    let too_large =
+        |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} too large", what));
+    let mut head = (&mut *stream).take(HTTP_MAX_HEAD_SIZE as u64);
+    let mut request_line = String::new();
+    if head.read_line(&mut request_line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        ..Default::default()
+    };
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header).await? == 0 {
+            if head.limit() == 0 {
+                return Err(too_large("request head"));
+            }
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            request
+                .headers
+                .insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let content_length = request
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    if content_length > HTTP_MAX_BODY_SIZE {
+        return Err(too_large("request body"));
+    }
+    request.body = vec![0; content_length];
+    stream.read_exact(&mut request.body).await?;
+    Ok(Some(request))
+}
+
+/// Answer the request of a connection, then close it
+async fn serve_connection<H, F>(stream: TcpStream, handler: &H) -> io::Result<()>
+where
+    H: Fn(Request) -> F,
+    F: Future<Output = Response>,
+{
+    let mut stream = BufReader::new(stream);
+    let response = match read_request(&mut stream).await {
+        Ok(Some(request)) => handler(request).await,
+        Ok(None) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            Response::new(413, "text/plain", e.to_string().into_bytes())
+        }
+        Err(e) => return Err(e),
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len()
+    );
+    if let Some(content_type) = &response.content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    head.push_str("\r\n");
+    stream.get_mut().write_all(head.as_bytes()).await?;
+    stream.get_mut().write_all(&response.body).await?;
+    stream.get_mut().shutdown().await
+}
+
+/// Answer the connections of a listener, one request per connection, until the shutdown
+/// token is cancelled
+///
+/// # Arguments
+///
+/// * `listener` - The listener of the server
+/// * `name` - The name of the server in the logs (e.g., "gallery")
+/// * `shutdown` - The token stopping the server
+/// * `handler` - The function answering a request
+pub async fn serve_connections<H, F>(
+    listener: TcpListener,
+    name: &'static str,
+    shutdown: CancellationToken,
+    handler: H,
+) where
+    H: Fn(Request) -> F + Send + Sync + 'static,
+    F: Future<Output = Response> + Send,
+{
+    let handler = Arc::new(handler);
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.cancelled() => return,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("The {} failed to accept a connection: {}", name, e);
+                    continue;
+                }
+            },
+        };
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, handler.as_ref()).await {
+                warn!("The {} failed to answer a request: {}", name, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(raw: &[u8]) -> io::Result<Option<Request>> {
+        let mut stream = raw;
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(read_request(&mut stream))
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request =
+            read(b"POST /jobs HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}")
+                .unwrap()
+                .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/jobs");
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.body, b"{}");
+        assert!(read(b"").unwrap().is_none());
+        assert!(read(b"GET / HTTP/1.1\r\nHost: local").unwrap().is_none());
+
+        // Oversized requests are refused before their body is allocated
+        let oversized = format!(
+            "POST /jobs HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            HTTP_MAX_BODY_SIZE + 1
+        );
+        let error = read(oversized.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let endless_header = format!(
+            "GET / HTTP/1.1\r\nX-Padding: {}",
+            "a".repeat(HTTP_MAX_HEAD_SIZE)
+        );
+        let error = read(endless_header.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::architecture::{host_target_platform, TargetPlatform};
+use crate::config::OBSOLETE_FILE_NAME;
+use crate::create_directory_if_not_exists;
+use crate::error::{Result, VsixHarvesterError};
+use crate::extensions_dir::{register_extensions, select_packages, unpack_vsix};
+use crate::install_script::{install_order, InstallPackage};
+use crate::status::scan_mirror;
+use crate::temp::write_atomic;
+use crate::vsix::read_vsix_info;
+use log::{error, info, warn};
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How the packages are installed into the local VS Code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallMethod {
+    /// Run `<code> --install-extension` for every package
+    Cli(PathBuf),
+    /// Unpack every package into an extensions directory
+    Unpack(String),
+}
+
+/// Find the `code` command of the local VS Code
+///
+/// The directories of the PATH are searched first, then the default install
+/// locations of VS Code on macOS and Windows.
+///
+/// # Returns
+///
+/// The path of the command, None if VS Code was not found
+pub fn locate_code() -> Option<PathBuf> {
+    let names: &[&str] = if cfg!(windows) {
+        &["code.cmd", "code.exe"]
+    } else {
+        &["code"]
+    };
+    let mut candidates: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+                .collect()
+        })
+        .unwrap_or_default();
+    candidates.push(PathBuf::from(
+        "/Applications/Visual Studio Code.app/Contents/Resources/app/bin/code",
+    ));
+    if let Some(local_app_data) = env::var_os("LOCALAPPDATA") {
+        candidates.push(
+            Path::new(&local_app_data)
+                .join("Programs")
+                .join("Microsoft VS Code")
+                .join("bin")
+                .join("code.cmd"),
+        );
+    }
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Choose how to install the packages
+///
+/// # Arguments
+///
+/// * `code_binary` - Optional `code` command to run
+/// * `extensions_dir` - Optional extensions directory to unpack the packages into
+///
+/// # Returns
+///
+/// A Result containing the install method, or an error if VS Code was not found
+pub fn install_method(
+    code_binary: Option<&str>,
+    extensions_dir: Option<&str>,
+) -> Result<InstallMethod> {
+    if let Some(extensions_dir) = extensions_dir {
+        return Ok(InstallMethod::Unpack(extensions_dir.to_string()));
+    }
+    code_binary
+        .map(PathBuf::from)
+        .or_else(locate_code)
+        .map(InstallMethod::Cli)
+        .ok_or_else(|| {
+            VsixHarvesterError::InvalidArgument(
+                "VS Code was not found, use --code-binary or --extensions-dir".to_string(),
+            )
+        })
+}
+
+/// Mark the directories of replaced extensions as obsolete, VS Code removes them at startup
+///
+/// # Arguments
+///
+/// * `extensions_dir` - The extensions directory
+/// * `dir_names` - The directories of the replaced extensions
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn mark_obsolete(extensions_dir: &str, dir_names: &[String]) -> Result<()> {
+    if dir_names.is_empty() {
+        return Ok(());
+    }
+    let path = Path::new(extensions_dir).join(OBSOLETE_FILE_NAME);
+    let mut obsolete: Map<String, Value> = if path.exists() {
+        serde_json::from_str(&fs::read_to_string(&path)?)?
+    } else {
+        Map::new()
+    };
+    for dir_name in dir_names {
+        obsolete.insert(dir_name.clone(), Value::Bool(true));
+    }
+    write_atomic(&path, serde_json::to_string(&obsolete)?.as_bytes())?;
+    Ok(())
+}
+
+/// Install VSIX packages into the local VS Code, dependencies first
+///
+/// Without packages, the newest package of every extension of the destination
+/// directory is installed, the build for the target platform being preferred.
+///
+/// # Arguments
+///
+/// * `files` - The packages to install
+/// * `destination` - The directory where the extensions are saved
+/// * `target_platform` - Optional target platform, the one of the host by default
+/// * `method` - How to install the packages
+///
+/// # Returns
+///
+/// A Result containing the number of installed packages or an error if any failed
+pub fn install_packages(
+    files: &[String],
+    destination: &str,
+    target_platform: Option<&TargetPlatform>,
+    method: &InstallMethod,
+) -> Result<usize> {
+    let files = if files.is_empty() {
+        let target_platform = target_platform
+            .cloned()
+            .or_else(host_target_platform)
+            .ok_or_else(|| {
+                VsixHarvesterError::InvalidArgument(
+                    "Unknown host platform, use --target-platform".to_string(),
+                )
+            })?;
+        let packages = scan_mirror(destination)?;
+        select_packages(&packages, &target_platform)
+            .into_iter()
+            .map(|package| format!("{}/{}", destination, package.file_name))
+            .collect()
+    } else {
+        files.to_vec()
+    };
+    let packages: Vec<InstallPackage> = files
+        .iter()
+        .map(|file| {
+            let (id, requires) = match read_vsix_info(file) {
+                Ok(info) => (
+                    info.id(),
+                    [info.extension_dependencies, info.extension_pack].concat(),
+                ),
+                Err(e) => {
+                    warn!("Could not read the dependencies of {}: {}", file, e);
+                    (file.clone(), Vec::new())
+                }
+            };
+            InstallPackage {
+                id,
+                file: file.clone(),
+                requires,
+            }
+        })
+        .collect();
+
+    let mut installed = 0;
+    let mut failed = Vec::new();
+    let mut unpacked = Vec::new();
+    for package in install_order(&packages) {
+        let result = match method {
+            InstallMethod::Cli(code) => Command::new(code)
+                .arg("--install-extension")
+                .arg(&package.file)
+                .arg("--force")
+                .status()
+                .map_err(VsixHarvesterError::from)
+                .and_then(|status| {
+                    if status.success() {
+                        Ok(())
+                    } else {
+                        Err(VsixHarvesterError::InstallError(format!(
+                            "{} --install-extension exited with {}",
+                            code.display(),
+                            status
+                        )))
+                    }
+                }),
+            InstallMethod::Unpack(extensions_dir) => create_directory_if_not_exists(extensions_dir)
+                .and_then(|_| unpack_vsix(&package.file, extensions_dir))
+                .map(|extension| unpacked.push(extension)),
+        };
+        match result {
+            Ok(()) => {
+                info!("Installed {}", package.file);
+                installed += 1;
+            }
+            Err(e) => {
+                error!("Could not install {}: {}", package.file, e);
+                failed.push(package.file.clone());
+            }
+        }
+    }
+    if let InstallMethod::Unpack(extensions_dir) = method {
+        let replaced = register_extensions(extensions_dir, &unpacked)?;
+        mark_obsolete(extensions_dir, &replaced)?;
+    }
+    if !failed.is_empty() {
+        return Err(VsixHarvesterError::InstallError(format!(
+            "{} package(s) could not be installed: {}",
+            failed.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::vsix_with;
+
+    fn vsix(name: &str, version: &str, dependencies: &[&str]) -> Vec<u8> {
+        vsix_with(
+            &format!("golang.{}", name),
+            version,
+            None,
+            serde_json::json!({"extensionDependencies": dependencies}),
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_install_packages_unpack() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("mirror");
+        let destination = destination.to_str().unwrap();
+        let extensions_dir = temp_dir.path().join("extensions");
+        let extensions_dir = extensions_dir.to_str().unwrap().to_string();
+        fs::create_dir(destination).unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.1.vsix", destination),
+            vsix("Go", "0.41.1", &["golang.tools"]),
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/golang.tools-1.0.0.vsix", destination),
+            vsix("tools", "1.0.0", &[]),
+        )
+        .unwrap();
+        let method = install_method(None, Some(&extensions_dir)).unwrap();
+        assert_eq!(method, InstallMethod::Unpack(extensions_dir.clone()));
+        assert_eq!(
+            install_packages(&[], destination, Some(&"linux-x64".into()), &method).unwrap(),
+            2
+        );
+        let entries: Value = serde_json::from_str(
+            &fs::read_to_string(format!("{}/extensions.json", extensions_dir)).unwrap(),
+        )
+        .unwrap();
+        // The dependency is registered first
+        assert_eq!(entries[0]["identifier"]["id"], "golang.tools");
+        assert_eq!(entries[1]["identifier"]["id"], "golang.go");
+
+        // Installing a newer version marks the previous one obsolete
+        let newer = format!("{}/golang.Go-0.41.2.vsix", destination);
+        fs::write(&newer, vsix("Go", "0.41.2", &[])).unwrap();
+        install_packages(&[newer], destination, None, &method).unwrap();
+        let obsolete: Value = serde_json::from_str(
+            &fs::read_to_string(format!("{}/.obsolete", extensions_dir)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(obsolete, serde_json::json!({"golang.go-0.41.1": true}));
+        assert!(Path::new(&format!("{}/golang.go-0.41.2/package.json", extensions_dir)).exists());
+    }
+}
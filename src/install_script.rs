@@ -0,0 +1,234 @@
+use crate::config::{INSTALL_SCRIPT_FILE_NAME, VERSION};
+use crate::error::Result;
+use crate::marketplace::DownloadOutcome;
+use crate::temp::write_atomic;
+use crate::vsix::read_vsix_info;
+use clap::ValueEnum;
+use log::{info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Shells the install script can be written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InstallScript {
+    /// `install-extensions.sh`, for Linux and macOS
+    Bash,
+    /// `install-extensions.ps1`, for Windows
+    Powershell,
+}
+
+impl InstallScript {
+    /// Get the extension of the script file
+    pub fn extension(self) -> &'static str {
+        match self {
+            InstallScript::Bash => "sh",
+            InstallScript::Powershell => "ps1",
+        }
+    }
+}
+
+/// A harvested package to install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallPackage {
+    pub id: String,
+    /// The path of the package, relative to the directory of the script
+    pub file: String,
+    /// The extensions to install before this one, dependencies and pack members
+    pub requires: Vec<String>,
+}
+
+/// Append the packages of an extension after the ones they require, depth first
+fn visit_requirements<'a>(
+    id: &str,
+    by_id: &BTreeMap<String, Vec<&'a InstallPackage>>,
+    visited: &mut HashSet<String>,
+    ordered: &mut Vec<&'a InstallPackage>,
+) {
+    let Some(id_packages) = by_id.get(id) else {
+        return;
+    };
+    if !visited.insert(id.to_string()) {
+        return;
+    }
+    for package in id_packages {
+        for required in &package.requires {
+            visit_requirements(&required.to_lowercase(), by_id, visited, ordered);
+        }
+    }
+    ordered.extend(id_packages);
+}
+
+/// Order the packages so every extension comes after the ones it requires
+///
+/// Requirements missing from the packages are ignored, and a dependency cycle is cut
+/// where it is found. The order is otherwise alphabetical, to keep the script stable.
+///
+/// # Arguments
+///
+/// * `packages` - The packages to install
+///
+/// # Returns
+///
+/// The packages in install order
+pub fn install_order(packages: &[InstallPackage]) -> Vec<&InstallPackage> {
+    let mut by_id: BTreeMap<String, Vec<&InstallPackage>> = BTreeMap::new();
+    for package in packages {
+        by_id
+            .entry(package.id.to_lowercase())
+            .or_default()
+            .push(package);
+    }
+    let mut visited = HashSet::new();
+    let mut ordered = Vec::new();
+    for id in by_id.keys() {
+        visit_requirements(id, &by_id, &mut visited, &mut ordered);
+    }
+    ordered
+}
+
+/// Render the install script of packages already in install order
+///
+/// The packages are found next to the script, and the `CODE` environment variable
+/// selects another VS Code command (e.g., `code-insiders` or `codium`).
+///
+/// # Arguments
+///
+/// * `shell` - The shell of the script
+/// * `packages` - The packages in install order
+///
+/// # Returns
+///
+/// The content of the script
+pub fn render_install_script(shell: InstallScript, packages: &[&InstallPackage]) -> String {
+    let header = format!(
+        "Install the {} package(s) harvested by vsixHarvester {}, dependencies first",
+        packages.len(),
+        VERSION
+    );
+    match shell {
+        InstallScript::Bash => {
+            let mut script = format!(
+                "#!/usr/bin/env bash\n# {}\nset -euo pipefail\nCODE=\"${{CODE:-code}}\"\nDIR=\"$(cd \"$(dirname \"${{BASH_SOURCE[0]}}\")\" && pwd)\"\n\n",
+                header
+            );
+            for package in packages {
+                script.push_str(&format!(
+                    "\"$CODE\" --install-extension \"$DIR\"/'{}'\n",
+                    package.file.replace('\'', "'\\''")
+                ));
+            }
+            script
+        }
+        InstallScript::Powershell => {
+            let mut script = format!(
+                "# {}\n$ErrorActionPreference = 'Stop'\n$Code = if ($env:CODE) {{ $env:CODE }} else {{ 'code' }}\n\n",
+                header
+            );
+            for package in packages {
+                script.push_str(&format!(
+                    "& $Code --install-extension (Join-Path $PSScriptRoot '{}')\nif ($LASTEXITCODE -ne 0) {{ exit $LASTEXITCODE }}\n",
+                    package.file.replace('\'', "''")
+                ));
+            }
+            script
+        }
+    }
+}
+
+/// Write the install script of the packages downloaded by the run in the destination
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `shell` - The shell of the script
+/// * `outcomes` - The outcomes of the downloads of the run
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_install_script(
+    destination: &str,
+    shell: InstallScript,
+    outcomes: &[DownloadOutcome],
+) -> Result<()> {
+    let packages: Vec<InstallPackage> = outcomes
+        .iter()
+        .map(|outcome| {
+            let requires = match read_vsix_info(&outcome.file_path) {
+                Ok(info) => [info.extension_dependencies, info.extension_pack].concat(),
+                Err(e) => {
+                    warn!(
+                        "Could not read the dependencies of {}: {}",
+                        outcome.file_path, e
+                    );
+                    Vec::new()
+                }
+            };
+            let path = Path::new(&outcome.file_path);
+            let file = path
+                .strip_prefix(destination)
+                .unwrap_or_else(|_| Path::new(path.file_name().unwrap_or_default()))
+                .to_string_lossy()
+                .replace('\\', "/");
+            InstallPackage {
+                id: outcome.id.clone(),
+                file,
+                requires,
+            }
+        })
+        .collect();
+    let script = render_install_script(shell, &install_order(&packages));
+    let path = format!(
+        "{}/{}.{}",
+        destination,
+        INSTALL_SCRIPT_FILE_NAME,
+        shell.extension()
+    );
+    write_atomic(Path::new(&path), script.as_bytes())?;
+    #[cfg(unix)]
+    if shell == InstallScript::Bash {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    info!("Install script saved in {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(id: &str, requires: &[&str]) -> InstallPackage {
+        InstallPackage {
+            id: id.to_string(),
+            file: format!("{}-1.0.0.vsix", id),
+            requires: requires.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_install_order() {
+        let packages = [
+            package("a.pack", &["c.member", "b.member"]),
+            package("b.member", &["D.base", "ms-vscode.missing"]),
+            package("c.member", &[]),
+            package("d.base", &["b.member"]),
+        ];
+        let ordered: Vec<&str> = install_order(&packages)
+            .iter()
+            .map(|package| package.id.as_str())
+            .collect();
+        // The cycle between b.member and d.base is cut at b.member
+        assert_eq!(ordered, vec!["c.member", "d.base", "b.member", "a.pack"]);
+
+        let script = render_install_script(InstallScript::Bash, &install_order(&packages[2..3]));
+        assert!(script.starts_with("#!/usr/bin/env bash\n"));
+        assert!(script.ends_with("\"$CODE\" --install-extension \"$DIR\"/'c.member-1.0.0.vsix'\n"));
+        let script =
+            render_install_script(InstallScript::Powershell, &install_order(&packages[2..3]));
+        assert!(script.contains(
+            "& $Code --install-extension (Join-Path $PSScriptRoot 'c.member-1.0.0.vsix')"
+        ));
+    }
+}
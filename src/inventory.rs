@@ -0,0 +1,459 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::config::{DRIFT_INSTALL_DROP_RATIO, DRIFT_RATING_DROP, INVENTORY_FILE_NAME};
+use crate::error::Result;
+use crate::marketplace::ResolvedExtension;
+use crate::temp::write_atomic;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Popularity statistics of an extension recorded at a harvest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatisticsSnapshot {
+    pub installs: u64,
+    #[serde(rename = "averageRating")]
+    pub average_rating: Option<f64>,
+    #[serde(rename = "ratingCount")]
+    pub rating_count: Option<u64>,
+    #[serde(rename = "recordedAt")]
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Evolution of the popularity of an extension since the previous harvest
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PopularityDrift {
+    pub id: String,
+    pub previous: StatisticsSnapshot,
+    pub current: StatisticsSnapshot,
+    #[serde(rename = "installChange")]
+    pub install_change: i64,
+    /// The install change relative to the previous install count
+    #[serde(rename = "installChangeRatio")]
+    pub install_change_ratio: f64,
+    #[serde(rename = "ratingChange")]
+    pub rating_change: Option<f64>,
+    /// Whether the extension is losing users or its rating is collapsing
+    pub alarming: bool,
+}
+
+impl PopularityDrift {
+    /// Compute the drift between two statistics snapshots of an extension
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `previous` - The statistics of the previous harvest
+    /// * `current` - The statistics of the current harvest
+    ///
+    /// # Returns
+    ///
+    /// The popularity drift
+    pub fn new(id: &str, previous: StatisticsSnapshot, current: StatisticsSnapshot) -> Self {
+        let install_change = current.installs as i64 - previous.installs as i64;
+        let install_change_ratio = if previous.installs == 0 {
+            0.0
+        } else {
+            install_change as f64 / previous.installs as f64
+        };
+        let rating_change = previous
+            .average_rating
+            .zip(current.average_rating)
+            .map(|(previous, current)| current - previous);
+        let alarming = install_change_ratio <= -DRIFT_INSTALL_DROP_RATIO
+            || rating_change.is_some_and(|change| change <= -DRIFT_RATING_DROP);
+        Self {
+            id: id.to_string(),
+            previous,
+            current,
+            install_change,
+            install_change_ratio,
+            rating_change,
+            alarming,
+        }
+    }
+}
+
+/// What is known about an extension from the previous harvests
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InventoryEntry {
+    /// The extension identifier in the format "publisher.name"
+    pub id: String,
+    /// The marketplace identifier of the publisher
+    #[serde(rename = "publisherId")]
+    pub publisher_id: String,
+    /// The marketplace identifier of the extension
+    #[serde(rename = "extensionId")]
+    pub extension_id: String,
+    /// The publisher identifiers seen before a publisher change
+    #[serde(
+        rename = "previousPublisherIds",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub previous_publisher_ids: Vec<String>,
+    #[serde(rename = "firstSeen")]
+    pub first_seen: DateTime<Utc>,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: DateTime<Utc>,
+    /// The popularity statistics of the last harvest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<StatisticsSnapshot>,
+    /// The marketplace `lastUpdated` of the extension when its packages were last downloaded
+    #[serde(
+        rename = "lastUpdated",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_updated: Option<String>,
+    /// The target platforms downloaded since `lastUpdated`, "universal" for universal packages
+    #[serde(
+        rename = "syncedPlatforms",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub synced_platforms: Vec<String>,
+}
+
+/// A publisher change detected between two harvests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublisherChange {
+    pub id: String,
+    pub previous_publisher_id: String,
+    pub publisher_id: String,
+}
+
+/// Inventory of the harvested extensions, stored in the destination directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    /// The entries keyed by lowercase extension identifier
+    pub extensions: BTreeMap<String, InventoryEntry>,
+}
+
+impl Inventory {
+    /// Get the path of the inventory of a destination directory
+    pub fn path(destination: &str) -> String {
+        format!("{}/{}", destination, INVENTORY_FILE_NAME)
+    }
+
+    /// Load the inventory of a destination directory, returning an empty one if it does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - The directory where the extensions are saved
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the inventory or an error that occurred
+    pub fn load(destination: &str) -> Result<Self> {
+        let path = Self::path(destination);
+        if !Path::new(&path).exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save the inventory in a destination directory
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - The directory where the extensions are saved
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error that occurred
+    pub fn save(&self, destination: &str) -> Result<()> {
+        write_atomic(
+            Path::new(&Self::path(destination)),
+            serde_json::to_string_pretty(self)?.as_bytes(),
+        )
+    }
+
+    /// Record the marketplace identity of an extension seen during a harvest
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `publisher_id` - The marketplace identifier of the publisher
+    /// * `extension_id` - The marketplace identifier of the extension
+    /// * `now` - The time of the harvest
+    ///
+    /// # Returns
+    ///
+    /// The publisher change if the extension was previously seen under another publisher
+    pub fn record(
+        &mut self,
+        id: &str,
+        publisher_id: &str,
+        extension_id: &str,
+        now: DateTime<Utc>,
+    ) -> Option<PublisherChange> {
+        let entry = self
+            .extensions
+            .entry(id.to_lowercase())
+            .or_insert_with(|| InventoryEntry {
+                id: id.to_string(),
+                publisher_id: publisher_id.to_string(),
+                extension_id: extension_id.to_string(),
+                previous_publisher_ids: Vec::new(),
+                first_seen: now,
+                last_seen: now,
+                statistics: None,
+                last_updated: None,
+                synced_platforms: Vec::new(),
+            });
+        entry.last_seen = now;
+        entry.extension_id = extension_id.to_string();
+        if entry.publisher_id == publisher_id {
+            return None;
+        }
+        let change = PublisherChange {
+            id: id.to_string(),
+            previous_publisher_id: entry.publisher_id.clone(),
+            publisher_id: publisher_id.to_string(),
+        };
+        entry.previous_publisher_ids.push(std::mem::replace(
+            &mut entry.publisher_id,
+            publisher_id.to_string(),
+        ));
+        Some(change)
+    }
+
+    /// Record the resolved extensions of a harvest, warning about publisher changes
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved extensions
+    ///
+    /// # Returns
+    ///
+    /// The detected publisher changes
+    pub fn record_resolved(&mut self, resolved: &[ResolvedExtension]) -> Vec<PublisherChange> {
+        let now = Utc::now();
+        let mut changes = Vec::new();
+        for resolved in resolved {
+            let Some(metadata) = &resolved.metadata else {
+                continue;
+            };
+            if let Some(change) = self.record(
+                &resolved.id,
+                &metadata.publisher.publisher_id,
+                &metadata.extension_id,
+                now,
+            ) {
+                warn!(
+                    "!!! PUBLISHER CHANGED for {}: previously published by {}, now by {}. \
+                     This may be an account takeover or a dependency confusion, review it before installing !!!",
+                    change.id, change.previous_publisher_id, change.publisher_id
+                );
+                changes.push(change);
+            }
+        }
+        changes
+    }
+
+    /// Check if the package of an extension was downloaded since its last update
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `target_platform` - The target platform of the package, None for universal packages
+    /// * `last_updated` - The current marketplace `lastUpdated` of the extension
+    pub fn is_synced(
+        &self,
+        id: &str,
+        target_platform: Option<&TargetPlatform>,
+        last_updated: &str,
+    ) -> bool {
+        self.extensions
+            .get(&id.to_lowercase())
+            .filter(|entry| entry.last_updated.as_deref() == Some(last_updated))
+            .is_some_and(|entry| {
+                entry
+                    .synced_platforms
+                    .iter()
+                    .any(|platform| platform == target_platform_name(target_platform))
+            })
+    }
+
+    /// Record the download of the package of an extension at its last update
+    ///
+    /// The platforms downloaded at a previous update of the extension are forgotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `target_platform` - The target platform of the package, None for universal packages
+    /// * `last_updated` - The marketplace `lastUpdated` of the extension
+    pub fn record_synced(
+        &mut self,
+        id: &str,
+        target_platform: Option<&TargetPlatform>,
+        last_updated: &str,
+    ) {
+        let Some(entry) = self.extensions.get_mut(&id.to_lowercase()) else {
+            return;
+        };
+        if entry.last_updated.as_deref() != Some(last_updated) {
+            entry.last_updated = Some(last_updated.to_string());
+            entry.synced_platforms.clear();
+        }
+        let platform = target_platform_name(target_platform).to_string();
+        if !entry.synced_platforms.contains(&platform) {
+            entry.synced_platforms.push(platform);
+        }
+    }
+
+    /// Record the popularity statistics of the resolved extensions of a harvest
+    ///
+    /// The resolved extensions must have been recorded with `record_resolved` first.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved extensions
+    ///
+    /// # Returns
+    ///
+    /// The popularity drift of the extensions with statistics recorded at a previous harvest
+    pub fn record_statistics(&mut self, resolved: &[ResolvedExtension]) -> Vec<PopularityDrift> {
+        let now = Utc::now();
+        let mut drifts = Vec::new();
+        for resolved in resolved {
+            let Some(metadata) = &resolved.metadata else {
+                continue;
+            };
+            let Some(entry) = self.extensions.get_mut(&resolved.id.to_lowercase()) else {
+                continue;
+            };
+            // Skip the extensions without statistics or already recorded for another platform
+            if metadata.statistics.is_empty()
+                || entry
+                    .statistics
+                    .as_ref()
+                    .is_some_and(|statistics| statistics.recorded_at == now)
+            {
+                continue;
+            }
+            let current = StatisticsSnapshot {
+                installs: metadata.get_install_count(),
+                average_rating: metadata.get_statistic("averagerating"),
+                rating_count: metadata
+                    .get_statistic("ratingcount")
+                    .map(|count| count as u64),
+                recorded_at: now,
+            };
+            if let Some(previous) = entry.statistics.replace(current.clone()) {
+                let drift = PopularityDrift::new(&entry.id, previous, current);
+                if drift.alarming {
+                    warn!(
+                        "Popularity drift of {}: {:+} installs ({:+.1}%), rating change {}",
+                        drift.id,
+                        drift.install_change,
+                        drift.install_change_ratio * 100.0,
+                        drift
+                            .rating_change
+                            .map_or("unknown".to_string(), |change| format!("{:+.2}", change))
+                    );
+                }
+                drifts.push(drift);
+            }
+        }
+        drifts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_publisher_change() {
+        let mut inventory = Inventory::default();
+        let now = Utc::now();
+        assert!(inventory
+            .record("golang.Go", "publisher-a", "ext", now)
+            .is_none());
+        assert!(inventory
+            .record("Golang.go", "publisher-a", "ext", now)
+            .is_none());
+
+        let change = inventory
+            .record("golang.Go", "publisher-b", "ext", now)
+            .unwrap();
+        assert_eq!(change.previous_publisher_id, "publisher-a");
+        assert_eq!(change.publisher_id, "publisher-b");
+
+        let entry = &inventory.extensions["golang.go"];
+        assert_eq!(entry.publisher_id, "publisher-b");
+        assert_eq!(entry.previous_publisher_ids, vec!["publisher-a"]);
+    }
+
+    #[test]
+    fn test_synced_platforms() {
+        let mut inventory = Inventory::default();
+        inventory.record("golang.Go", "publisher-a", "ext", Utc::now());
+        assert!(!inventory.is_synced("golang.Go", None, "2025-01-01T00:00:00Z"));
+
+        inventory.record_synced("golang.go", None, "2025-01-01T00:00:00Z");
+        inventory.record_synced(
+            "golang.go",
+            Some(&"linux-x64".into()),
+            "2025-01-01T00:00:00Z",
+        );
+        assert!(inventory.is_synced("golang.Go", None, "2025-01-01T00:00:00Z"));
+        assert!(inventory.is_synced(
+            "golang.Go",
+            Some(&"linux-x64".into()),
+            "2025-01-01T00:00:00Z"
+        ));
+        assert!(!inventory.is_synced(
+            "golang.Go",
+            Some(&"win32-x64".into()),
+            "2025-01-01T00:00:00Z"
+        ));
+        assert!(!inventory.is_synced("golang.Go", None, "2025-02-01T00:00:00Z"));
+
+        // An update forgets the platforms downloaded before it
+        inventory.record_synced("golang.Go", None, "2025-02-01T00:00:00Z");
+        assert!(!inventory.is_synced(
+            "golang.Go",
+            Some(&"linux-x64".into()),
+            "2025-02-01T00:00:00Z"
+        ));
+        assert_eq!(
+            inventory.extensions["golang.go"].synced_platforms,
+            ["universal"]
+        );
+    }
+
+    #[test]
+    fn test_popularity_drift() {
+        let snapshot = |installs, average_rating| StatisticsSnapshot {
+            installs,
+            average_rating,
+            rating_count: None,
+            recorded_at: Utc::now(),
+        };
+        let drift = PopularityDrift::new(
+            "golang.Go",
+            snapshot(1000, Some(4.5)),
+            snapshot(1010, Some(4.4)),
+        );
+        assert_eq!(drift.install_change, 10);
+        assert!(!drift.alarming);
+
+        let drift = PopularityDrift::new("golang.Go", snapshot(1000, None), snapshot(900, None));
+        assert!((drift.install_change_ratio + 0.1).abs() < f64::EPSILON);
+        assert!(drift.alarming);
+
+        let drift = PopularityDrift::new(
+            "golang.Go",
+            snapshot(1000, Some(4.5)),
+            snapshot(1000, Some(3.2)),
+        );
+        assert!(drift.alarming);
+    }
+}
@@ -0,0 +1,2077 @@
+//! Harvest VSCode extensions from the Visual Studio Marketplace or Open VSX
+//!
+//! The `vsixHarvester` binary is a thin command line over this library. Embedding
+//! applications use [`Client`] to resolve and download single extensions, or
+//! [`run_command`] to run a whole harvest from command line arguments:
+//!
+//! ```no_run
+//! use vsixharvester::args::{Args, Parser};
+//!
+//! # async fn provision() -> vsixharvester::error::Result<()> {
+//! let args = Args::parse_from(["vsixHarvester", "--input", "extensions.json", "--destination", "/srv/mirror"]);
+//! vsixharvester::run_command(&args).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The main modules are [`marketplace`] (resolution and downloads), [`extension`]
+//! (manifest entries), [`architecture`] (target platforms), [`registry`] and
+//! [`storage`] (extension points) and [`types`] (marketplace responses).
+
+pub mod architecture;
+pub mod args;
+mod assets;
+mod budget;
+mod checksums;
+mod client;
+mod collection;
+mod compression;
+mod config;
+mod credentials;
+mod crypto;
+mod curate;
+mod destination;
+mod diff;
+pub mod error;
+mod export;
+pub mod extension;
+mod extensions_dir;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fixture;
+mod fleet;
+mod gallery;
+mod git;
+mod hash;
+mod http_server;
+mod install;
+mod install_script;
+mod inventory;
+mod locales;
+mod lockfile;
+pub mod marketplace;
+mod naming;
+mod oci;
+mod openvsx;
+mod outdated;
+mod paths;
+mod policy;
+mod pool;
+mod prefetch;
+mod progress;
+mod provenance;
+mod publish;
+#[cfg(feature = "python")]
+mod python;
+mod quarantine;
+mod reconcile;
+mod registries;
+pub mod registry;
+mod renames;
+mod sbom;
+mod schedule;
+mod search;
+mod server;
+mod service;
+mod signature;
+mod static_mirror;
+mod stats;
+mod status;
+pub mod storage;
+mod temp;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod tests;
+mod timezone;
+pub mod types;
+mod update;
+mod verify;
+mod versions;
+mod vsix;
+mod workspaces;
+mod yank;
+
+pub use architecture::TargetPlatform;
+pub use budget::SizeBudgetPolicy;
+pub use checksums::ChecksumOutputs;
+pub use client::Client;
+pub use compression::StoreCompression;
+pub use curate::CurationSort;
+pub use destination::Destination;
+pub use diff::DiffFormat;
+pub use export::ExportFormat;
+pub use hash::{Digest, HashAlgorithm};
+pub use install_script::InstallScript;
+pub use lockfile::{LockEntry, Lockfile, UpdatePolicy, VersionConstraint};
+pub use marketplace::{DownloadOptions, DownloadOutcome, ResolvedExtension};
+pub use naming::NamingPreset;
+pub use policy::PolicyMode;
+pub use pool::{HarvestProgress, ProgressSnapshot};
+pub use progress::{DownloadBars, PackageBar};
+pub use provenance::ProvenanceStorage;
+pub use sbom::SbomFormat;
+pub use search::SearchSort;
+pub use signature::SignaturePolicy;
+pub use timezone::ReportTimezone;
+
+use architecture::{join_target_platforms, Architecture, PlatformFilter, PlatformRegistry};
+use args::{Args, CollectionCommand, Command, RegistriesCommand};
+use assets::mirror_icon;
+use budget::{apply_size_budget, parse_size};
+use checksums::write_checksums;
+use compression::write_compressed_copies;
+use config::{
+    ALL_PLATFORMS_FIELD, COLLECTION_FIELD, MANIFEST_SIDECAR_SUFFIX, NORMALIZED_DIR,
+    QUERY_WINDOW_THRESHOLD,
+};
+
+use chrono::Utc;
+use destination::copy_to_destinations;
+use diff::MirrorDiff;
+use error::{Result, VsixHarvesterError};
+use extensions_dir::unpack_outcomes;
+use futures::stream::{self, StreamExt};
+use install_script::write_install_script;
+use inventory::Inventory;
+use locales::language_pack_entries;
+use lockfile::UpgradeFilter;
+use marketplace::{
+    download_resolved_extension, fetch_download_size, find_extension_by_guid,
+    query_marketplace_extension, resolve_constrained_extension, resolve_extension,
+    resolve_pinned_extension, NoCompatiblePolicy,
+};
+use policy::apply_policy;
+use pool::{TaskMeta, TaskPool};
+use reconcile::{LockfileChange, Reconciler};
+use registry::registry_target_platform;
+use renames::RenameMap;
+use sbom::write_sbom;
+use server::harvest_vscode_server;
+use status::scan_mirror;
+use temp::write_atomic;
+use workspaces::scan_workspaces;
+
+use log::{error, info, warn};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use extension::{
+    interpolate_env, is_extension_guid, move_to_platforms, split_version, Extension, Extensions,
+    ManifestEntry, PackageOverride,
+};
+
+/// Create a directory if it does not exist
+///
+/// # Arguments
+///
+/// * `path` - The path of the directory to create
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub(crate) fn create_directory_if_not_exists(path: &str) -> Result<()> {
+    let path = Path::new(path);
+    if !path.exists() {
+        fs::create_dir_all(path)?;
+    }
+    Ok(())
+}
+
+/// Process extensions based on the provided arguments
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub(crate) async fn process_extensions(args: &Args) -> Result<()> {
+    let args = &args.with_policy()?;
+    // Run against the fixture server of a captured or replayed bundle like in bench mode
+    let fixture = fixture::start_fixture(
+        args.capture_fixture.as_deref(),
+        args.replay_fixture.as_deref(),
+        &DownloadOptions::from(args),
+    )
+    .await?;
+    let Some(fixture) = fixture else {
+        return harvest_extensions(args).await;
+    };
+    let mut fixture_args = args.clone();
+    fixture_args.bench_mode = Some(fixture.origin().to_string());
+    let result = harvest_extensions(&fixture_args).await;
+    let manifest = Some(args.input.as_str()).filter(|_| args.download.is_none());
+    fixture.finish(manifest)?;
+    result
+}
+
+/// Download the requested extensions, then publish them
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn harvest_extensions(args: &Args) -> Result<()> {
+    // Handle direct extension download if specified
+    if let Some(str_extension) = &args.download {
+        let str_extension = match split_version(str_extension) {
+            (guid, version) if is_extension_guid(guid) => {
+                let id = find_extension_by_guid(guid, &DownloadOptions::from(args)).await?;
+                match version {
+                    Some(version) => format!("{}@{}", id, version),
+                    None => id,
+                }
+            }
+            _ => str_extension.clone(),
+        };
+        let extension = Extension::from_id(&str_extension)?;
+        download_single_extension(extension, args).await?;
+    } else if let Some(fleet) = &args.fleet {
+        fleet::harvest_fleet(fleet, args).await?;
+    } else {
+        download_extensions_from_json(args).await?;
+    }
+    if let Some(target) = &args.publish {
+        publish::publish(target, args).await?;
+    }
+    Ok(())
+}
+
+/// Download a single extension
+///
+/// # Arguments
+/// * `extension` - The extension to download
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn download_single_extension(extension: Extension<'_>, args: &Args) -> Result<()> {
+    info!("Direct download mode for extension: {}", extension.to_id());
+    // Map architecture to a target platform of the registry
+    let options = DownloadOptions::from(args);
+    // Without --arch, the build for the host is preferred, when the registry supports it
+    let host_platform = match args.arch {
+        Some(_) => None,
+        None if args.all_archs => None,
+        None => Architecture::host()
+            .and_then(Architecture::target_platform)
+            .and_then(|host| registry_target_platform(Some(host), options.registry.as_ref()).ok())
+            .flatten(),
+    };
+    let target_platform = match &args.arch {
+        Some(arch) => {
+            registry_target_platform(arch.target_platform().cloned(), options.registry.as_ref())?
+        }
+        None => None,
+    };
+    let target_platform = target_platform.as_ref();
+
+    if args.all_archs {
+        info!("Using every architecture the extension publishes");
+    } else if let Some(target_platform) = target_platform {
+        info!("Using architecture: {}", target_platform);
+    } else if let Some(host_platform) = &host_platform {
+        info!("Using host architecture: {}", host_platform);
+    } else {
+        info!("Using universal architecture");
+    }
+
+    // Ensure the destination directory exists
+    create_directory_if_not_exists(&args.primary_destination())?;
+
+    // Download the extension
+    let pins = load_pins(args)?;
+    let resolved = if args.all_archs {
+        resolve_all_platforms(extension.clone(), &options).await
+    } else {
+        match &host_platform {
+            Some(host_platform) => {
+                resolve_for_platform(extension.clone(), Some(host_platform), &options).await
+            }
+            None => resolve_extension(extension.clone(), &options, target_platform).await,
+        }
+        .map(|resolved| vec![resolved])
+    };
+    let resolved = match resolved {
+        Ok(resolved) => resolved,
+        Err(e @ VsixHarvesterError::NoCompatibleVersion(..))
+            if options.on_no_compatible == NoCompatiblePolicy::Skip =>
+        {
+            warn!("Skipping: {}", e);
+            return Ok(());
+        }
+        Err(e) => {
+            error!("Error occurred when resolving {}: {}", extension.to_id(), e);
+            return Err(e);
+        }
+    };
+    let resolved = apply_locked(args, pins.as_ref(), resolved)?;
+    let resolved = enforce_policy(args, resolved, &options, 1).await?;
+    let Some(first) = resolved.first() else {
+        return Ok(());
+    };
+    record_inventory(args, &resolved)?;
+    if args.mirror_icons {
+        if let Err(e) = mirror_icon(first, &options).await {
+            error!("Error occurred when downloading icon: {}", e);
+        }
+    }
+    let downloads = resolved
+        .iter()
+        .map(|resolved| download_resolved_extension(resolved, &options, pins.as_ref()));
+    let results: Vec<_> = stream::iter(downloads)
+        .buffered(args.concurrency())
+        .collect()
+        .await;
+    let mut outcomes = match results.into_iter().collect::<Result<Vec<_>>>() {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            error!(
+                "Error occurred when downloading {}: {}",
+                extension.to_id(),
+                e
+            );
+            return Err(e);
+        }
+    };
+    if args.with_dependencies || args.expand_packs {
+        let linked = download_linked_extensions(
+            args,
+            &outcomes,
+            &options,
+            pins.as_ref(),
+            args.concurrency(),
+        )
+        .await?;
+        outcomes.extend(linked);
+    }
+    if args.delta_copies {
+        write_delta_copies(&args.primary_destination(), &outcomes)?;
+    }
+    if args.save_manifest {
+        write_manifest_sidecars(&outcomes)?;
+    }
+    if let Some(compression) = args.store_compressed {
+        let paths: Vec<&str> = outcomes
+            .iter()
+            .map(|outcome| outcome.file_path.as_str())
+            .collect();
+        write_compressed_copies(&paths, compression)?;
+    }
+    if args.checksum_outputs().any() {
+        write_checksums(
+            &args.primary_destination(),
+            args.checksum_outputs(),
+            &outcomes,
+        )?;
+    }
+    if let Some(shell) = args.emit_install_script {
+        write_install_script(&args.primary_destination(), shell, &outcomes)?;
+    }
+    if args.unpack {
+        unpack_outcomes(&args.primary_destination(), &outcomes)?;
+    }
+    if let Some(format) = args.sbom {
+        write_sbom(&args.primary_destination(), format, &outcomes)?;
+    }
+    copy_to_destinations(&args.mirror_destinations(), &outcomes, &options).await?;
+    record_pins(args, pins, &outcomes)
+}
+
+/// Load the lockfile when hash pinning, a partial upgrade or a locked run is requested
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result containing the lockfile if it is used by this run or an error that occurred
+fn load_pins(args: &Args) -> Result<Option<Lockfile>> {
+    if !args.pin_hashes && !args.locked && args.upgrade_only.is_empty() {
+        return Ok(None);
+    }
+    let path = args.lockfile_path();
+    if args.locked && !Path::new(&path).exists() {
+        return Err(VsixHarvesterError::LockfileMismatch(format!(
+            "{} not found",
+            path
+        )));
+    }
+    info!("Using lockfile {}", path);
+    Lockfile::load(&path).map(Some)
+}
+
+/// Check the resolved extensions against the lockfile in `--locked` runs
+///
+/// The mismatches accepted on the terminal or by `--accept-new` are kept, their
+/// lockfile entries are updated after the download. Every other mismatch is reported
+/// at once. The packages are downloaded from the URLs recorded in the lockfile.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `pins` - The lockfile loaded by `load_pins`
+/// * `resolved` - The resolved extensions
+///
+/// # Returns
+///
+/// A Result containing the resolved extensions or the mismatches
+fn apply_locked(
+    args: &Args,
+    pins: Option<&Lockfile>,
+    resolved: Vec<ResolvedExtension>,
+) -> Result<Vec<ResolvedExtension>> {
+    let Some(lockfile) = pins.filter(|_| args.locked) else {
+        return Ok(resolved);
+    };
+    let mut reconciler = Reconciler::new(args.accept_new, !args.no_prompt);
+    let mut mismatches = Vec::new();
+    let mut locked = Vec::with_capacity(resolved.len());
+    for mut resolved in resolved {
+        let target_platform = resolved.target_platform.as_ref();
+        match lockfile.check_locked(&resolved.id, &resolved.version, target_platform) {
+            Ok(entry) => {
+                if let Some(download_url) = &entry.download_url {
+                    resolved.download_url = download_url.clone();
+                }
+                locked.push(resolved);
+            }
+            Err(VsixHarvesterError::LockfileMismatch(mismatch)) => {
+                let package = LockfileChange::package(&resolved.id, target_platform);
+                let change = match lockfile.find_current(&resolved.id, target_platform) {
+                    Some(entry) => LockfileChange::Changed {
+                        package,
+                        locked: entry.version.clone(),
+                        resolved: resolved.version.clone(),
+                    },
+                    None => LockfileChange::Added {
+                        package,
+                        version: resolved.version.clone(),
+                    },
+                };
+                if reconciler.accept(&change)? {
+                    info!("Accepted the lockfile change: {}", change);
+                    locked.push(resolved);
+                } else {
+                    mismatches.push(mismatch);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if !mismatches.is_empty() {
+        return Err(VsixHarvesterError::LockfileMismatch(mismatches.join("; ")));
+    }
+    Ok(locked)
+}
+
+/// Drop the lockfile entries of the extensions removed from the manifest in `--locked` runs
+///
+/// Only the removals accepted on the terminal or by `--accept-new` are dropped, the
+/// others are reported and kept. The linked extensions of `--with-dependencies` and
+/// `--expand-packs` are not in the manifest, so removals are not detected with them.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `lockfile` - The lockfile loaded by `load_pins`
+/// * `extensions` - The extensions of the manifest
+/// * `renames` - The renamed extensions
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn reconcile_removed(
+    args: &Args,
+    lockfile: &mut Lockfile,
+    extensions: &Extensions,
+    renames: &RenameMap,
+) -> Result<()> {
+    if args.with_dependencies || args.expand_packs {
+        return Ok(());
+    }
+    let listed: HashSet<String> = extensions
+        .entries()
+        .flat_map(|entry| [Some(entry.id()), renames.successor(entry.id())])
+        .flatten()
+        .map(str::to_lowercase)
+        .collect();
+    let mut reconciler = Reconciler::new(args.accept_new, !args.no_prompt);
+    let mut removed = Vec::new();
+    for entry in &lockfile.extensions {
+        if listed.contains(&entry.id.to_lowercase()) {
+            continue;
+        }
+        let change = LockfileChange::Removed {
+            package: LockfileChange::package(&entry.id, entry.target_platform.as_ref()),
+            version: entry.version.clone(),
+        };
+        if reconciler.accept(&change)? {
+            info!("Accepted the lockfile change: {}", change);
+            removed.push((entry.id.clone(), entry.target_platform.clone()));
+        } else {
+            warn!("Keeping the lockfile entry not in the manifest: {}", change);
+        }
+    }
+    lockfile
+        .extensions
+        .retain(|entry| !removed.contains(&(entry.id.clone(), entry.target_platform.clone())));
+    Ok(())
+}
+
+/// Record the hashes of the downloaded extensions in the lockfile
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `pins` - The lockfile loaded by `load_pins`
+/// * `outcomes` - The successful downloads
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn record_pins(args: &Args, pins: Option<Lockfile>, outcomes: &[DownloadOutcome]) -> Result<()> {
+    let Some(mut lockfile) = pins else {
+        return Ok(());
+    };
+    // Locked runs only update the reconciled entries, packages already present must match it too
+    if args.locked {
+        let path = args.lockfile_path();
+        let mut reconciled = Lockfile::load(&path)?.extensions != lockfile.extensions;
+        for outcome in outcomes {
+            let target_platform = outcome.target_platform.as_ref();
+            let Some(sha256) = &outcome.sha256 else {
+                continue;
+            };
+            match lockfile.find(&outcome.id, &outcome.version, target_platform) {
+                Some(entry) if *sha256 != entry.sha256 => {
+                    return Err(VsixHarvesterError::HashMismatch(
+                        format!("{}@{}", outcome.id, outcome.version),
+                        entry.sha256.clone(),
+                        sha256.clone(),
+                    ));
+                }
+                Some(_) => {}
+                // Accepted by the reconciliation of apply_locked
+                None => {
+                    reconciled = true;
+                    lockfile.upsert(LockEntry {
+                        id: outcome.id.clone(),
+                        version: outcome.version.clone(),
+                        target_platform: outcome.target_platform.clone(),
+                        download_url: Some(outcome.download_url.clone()),
+                        sha256: sha256.clone(),
+                        digest: outcome.digest.clone(),
+                        locked_at: Some(Utc::now()),
+                    });
+                }
+            }
+        }
+        if reconciled {
+            lockfile.save(&path)?;
+            info!("Reconciled lockfile saved in {}", path);
+        }
+        return Ok(());
+    }
+    for outcome in outcomes {
+        if let Some(sha256) = &outcome.sha256 {
+            lockfile.upsert(LockEntry {
+                id: outcome.id.clone(),
+                version: outcome.version.clone(),
+                target_platform: outcome.target_platform.clone(),
+                download_url: Some(outcome.download_url.clone()),
+                sha256: sha256.clone(),
+                digest: outcome.digest.clone(),
+                locked_at: Some(Utc::now()),
+            });
+        }
+    }
+    let path = args.lockfile_path();
+    lockfile.save(&path)?;
+    info!("Pinned hashes saved in {}", path);
+    Ok(())
+}
+
+/// Leave out the requested packages already downloaded at the last update of their extension
+///
+/// The last update of every extension is queried in batches, so the unchanged
+/// extensions are not resolved one by one.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `requested` - The requested packages
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the packages to resolve and the `lastUpdated` timestamps keyed
+/// by lowercase identifier, or an error that occurred
+async fn skip_synced_extensions<'a>(
+    args: &Args,
+    requested: Vec<ResolutionRequest<'a>>,
+    options: &DownloadOptions,
+) -> Result<(Vec<ResolutionRequest<'a>>, HashMap<String, String>)> {
+    let inventory = Inventory::load(&args.primary_destination())?;
+    let mut ids: Vec<String> = requested
+        .iter()
+        .map(|request| request.extension.to_id())
+        .collect();
+    ids.sort_by_key(|id| id.to_lowercase());
+    ids.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    let last_updated = options.registry.last_updated(&ids, options).await?;
+    // The registry does not tell when the package of an override changes
+    let (skipped, requested): (Vec<_>, Vec<_>) = requested.into_iter().partition(|request| {
+        let id = request.extension.to_id();
+        request.package_override.is_none()
+            && last_updated
+                .get(&id.to_lowercase())
+                .is_some_and(|last_updated| {
+                    inventory.is_synced(&id, request.target_platform.as_ref(), last_updated)
+                })
+    });
+    info!(
+        "{} package(s) unchanged since the last run, {} to resolve",
+        skipped.len(),
+        requested.len()
+    );
+    Ok((requested, last_updated))
+}
+
+/// Record in the inventory the packages downloaded at the current update of their extension
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `resolved` - The resolved extensions
+/// * `outcomes` - The successful downloads
+/// * `last_updated` - The `lastUpdated` timestamps queried by `--since-last-run`
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn record_synced(
+    args: &Args,
+    resolved: &[ResolvedExtension],
+    outcomes: &[DownloadOutcome],
+    last_updated: &HashMap<String, String>,
+) -> Result<()> {
+    let mut inventory = Inventory::load(&args.primary_destination())?;
+    for outcome in outcomes {
+        let current = resolved
+            .iter()
+            .find(|resolved| {
+                resolved.id == outcome.id && resolved.target_platform == outcome.target_platform
+            })
+            .and_then(|resolved| resolved.metadata.as_ref())
+            .map(|metadata| metadata.last_updated.clone())
+            .or_else(|| last_updated.get(&outcome.id.to_lowercase()).cloned())
+            .filter(|current| !current.is_empty());
+        if let Some(current) = current {
+            inventory.record_synced(&outcome.id, outcome.target_platform.as_ref(), &current);
+        }
+    }
+    inventory.save(&args.primary_destination())
+}
+
+/// Record the resolved extensions in the inventory of the destination directory
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `resolved` - The resolved extensions
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn record_inventory(args: &Args, resolved: &[ResolvedExtension]) -> Result<()> {
+    let mut inventory = Inventory::load(&args.primary_destination())?;
+    let changes = inventory.record_resolved(resolved);
+    if !changes.is_empty() {
+        error!(
+            "{} extension(s) changed publisher since the previous harvest",
+            changes.len()
+        );
+    }
+    let drifts = inventory.record_statistics(resolved);
+    if let Some(drift_report) = &args.drift_report {
+        write_atomic(
+            Path::new(drift_report),
+            serde_json::to_string_pretty(&drifts)?.as_bytes(),
+        )?;
+        info!("Popularity drift report saved in {}", drift_report);
+    }
+    inventory.save(&args.primary_destination())
+}
+
+/// Download extensions from extensions.json
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn download_extensions_from_json(args: &Args) -> Result<()> {
+    // Read extensions.json, optional when the workspaces give the extensions
+    info!("Attempting to read file: {}", &args.input);
+    let file_content = match fs::read_to_string(&args.input) {
+        Err(_) if args.scan_workspaces.is_some() && !Path::new(&args.input).exists() => {
+            info!(
+                "No {}, harvesting the workspace recommendations only",
+                &args.input
+            );
+            "{}".to_string()
+        }
+        Ok(content) => content,
+        Err(e) => {
+            error!("Failed to read file {}: {}", &args.input, e);
+            return Err(VsixHarvesterError::IoError(e));
+        }
+    };
+    let mut manifest: serde_json::Value = match serde_json::from_str(&file_content) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!("Failed to parse file {}: {}", &args.input, e);
+            return Err(VsixHarvesterError::JsonError(e));
+        }
+    };
+    // Expand the environment variable placeholders of the template manifest
+    interpolate_env(&mut manifest, &args.manifest_variables)?;
+    // A collection file is harvested as a manifest
+    if let Some(info) = manifest.get(COLLECTION_FIELD) {
+        info!(
+            "{} is the collection {} of {}",
+            &args.input,
+            info["name"].as_str().unwrap_or_default(),
+            info["owner"].as_str().unwrap_or_default()
+        );
+    }
+    let mut extensions: Extensions = match serde_json::from_value(manifest) {
+        Ok(extensions) => extensions,
+        Err(e) => {
+            error!("Failed to parse file {}: {}", &args.input, e);
+            return Err(VsixHarvesterError::JsonError(e));
+        }
+    };
+
+    collection::merge_collections(&mut extensions, &args.collections, &args.manifest_variables)?;
+
+    // Language packs of the requested locales are universal extensions
+    let language_packs = language_pack_entries(&args.locales)?;
+    if !language_packs.is_empty() {
+        extensions
+            .section_mut(Architecture::Universal.to_field_name())
+            .extend(language_packs);
+    }
+
+    // The workspace recommendations are universal extensions
+    if let Some(root) = &args.scan_workspaces {
+        let universal = extensions.section_mut(Architecture::Universal.to_field_name());
+        for entry in scan_workspaces(root)? {
+            if !universal
+                .iter()
+                .any(|listed| listed.id().eq_ignore_ascii_case(entry.id()))
+            {
+                universal.push(entry);
+            }
+        }
+    }
+
+    // Ensure the destination directory exists
+    create_directory_if_not_exists(&args.primary_destination())?;
+
+    // Define all platform categories with their target platform identifiers
+    let registry = PlatformRegistry::with_custom(&args.custom_platforms)?;
+    let options = DownloadOptions::from(args);
+    let concurrent_downloads = args.concurrency();
+    resolve_extension_guids(&mut extensions, &options).await?;
+    let all_platforms = extensions.take_section(ALL_PLATFORMS_FIELD);
+    if !all_platforms.is_empty() {
+        expand_all_platforms(
+            all_platforms,
+            &mut extensions,
+            &registry,
+            &options,
+            concurrent_downloads,
+        )
+        .await;
+    }
+    for field_name in extensions.field_names() {
+        if !registry.contains(field_name) {
+            warn!(
+                "Ignoring the {} list of the manifest, not a known platform (see --custom-platforms)",
+                field_name
+            );
+        }
+    }
+    let mut pins = load_pins(args)?;
+
+    let upgrade_filters = args
+        .upgrade_only
+        .iter()
+        .map(|filter| filter.parse::<UpgradeFilter>())
+        .collect::<Result<Vec<_>>>()?;
+    let now = Utc::now();
+    let default_platforms = &args.platform_default;
+    let platform_filter =
+        PlatformFilter::new(&args.only_platforms, &args.skip_platforms, &registry)?;
+    let renames = RenameMap::load(args.rename_map.as_deref())?;
+    if let Some(lockfile) = pins.as_mut().filter(|_| args.locked) {
+        reconcile_removed(args, lockfile, &extensions, &renames)?;
+    }
+
+    // Update policies are relative to the locked versions
+    let policy_lockfile = if pins.is_none()
+        && extensions
+            .entries()
+            .any(|entry| entry.update_policy() != UpdatePolicy::Latest)
+    {
+        Some(Lockfile::load(&args.lockfile_path())?)
+    } else {
+        None
+    };
+    let locked = pins.as_ref().or(policy_lockfile.as_ref());
+
+    // Collect the extensions of every platform, keeping the locked versions not selected for upgrade
+    let mut prioritized = Vec::new();
+    let mut seen = HashSet::new();
+    for platform in registry.platforms() {
+        let platform_field = platform.field_name.as_str();
+        let target_platform = &platform.target_platform;
+        if !platform_filter.matches(platform_field) {
+            info!("Skipping the {} platform", platform_field);
+            continue;
+        }
+        // Universal entries may be downloaded for the default platforms instead
+        let target_platforms = if target_platform.is_none() && !default_platforms.is_empty() {
+            default_platforms
+                .iter()
+                .map(|architecture| architecture.target_platform())
+                .collect()
+        } else {
+            vec![target_platform.clone()]
+        };
+        if let Some(platform_extensions) = extensions.section(platform_field) {
+            for entry in platform_extensions {
+                for target_platform in &target_platforms {
+                    let extension =
+                        Extension::from_id(renames.apply(entry.id(), args.apply_renames))?;
+                    if !seen.insert((extension.to_id().to_lowercase(), target_platform.clone())) {
+                        continue;
+                    }
+                    let kept_entry = pins
+                        .as_ref()
+                        .filter(|_| !upgrade_filters.is_empty())
+                        .and_then(|lockfile| {
+                            lockfile.find_current(&extension.to_id(), target_platform.as_ref())
+                        })
+                        .filter(|entry| {
+                            !upgrade_filters
+                                .iter()
+                                .any(|filter| filter.matches(entry, now))
+                        });
+                    // A version pinned in the manifest wins over the locked one
+                    let pinned_version = entry
+                        .version()
+                        .map(str::to_string)
+                        .or_else(|| kept_entry.map(|entry| entry.version.clone()));
+                    let constraint = Some(entry.update_policy())
+                        .filter(|policy| *policy != UpdatePolicy::Latest)
+                        .zip(locked.and_then(|lockfile| {
+                            lockfile.find_current(&extension.to_id(), target_platform.as_ref())
+                        }))
+                        .map(|(policy, locked_entry)| VersionConstraint {
+                            policy,
+                            locked_version: locked_entry.version.clone(),
+                        });
+                    prioritized.push((
+                        entry.priority(),
+                        ResolutionRequest {
+                            extension,
+                            target_platform: target_platform.clone(),
+                            pinned_version,
+                            constraint,
+                            package_override: entry.package_override()?,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    // Higher priorities first, keeping the manifest order between equal priorities
+    prioritized.sort_by_key(|(priority, _)| Reverse(*priority));
+    let requested: Vec<ResolutionRequest> = prioritized
+        .into_iter()
+        .map(|(_, requested)| requested)
+        .collect();
+    let (requested, last_updated) = if args.since_last_run && !args.no_cache {
+        skip_synced_extensions(args, requested, &options).await?
+    } else {
+        (requested, HashMap::new())
+    };
+
+    // Resolve every version before downloading anything
+    let resolve_options = prefetch_requests(args, &requested, &options).await;
+    let resolved = resolve_extensions(requested, &resolve_options, concurrent_downloads).await?;
+    let resolved = check_platform_specific(args, resolved, &options)?;
+    let resolved = apply_locked(args, pins.as_ref(), resolved)?;
+    let resolved = enforce_policy(args, resolved, &options, concurrent_downloads).await?;
+    if args.dry_run {
+        return print_dry_run(args, resolved, &options, concurrent_downloads).await;
+    }
+    record_inventory(args, &resolved)?;
+
+    // Keep the downloads within the size budget
+    let resolved = match &args.max_total_size {
+        Some(max_total_size) => {
+            let budget = parse_size(max_total_size)?;
+            let sized = size_extensions(resolved, &options, concurrent_downloads).await;
+            apply_size_budget(sized, budget, args.size_budget_policy)?
+        }
+        None => resolved,
+    };
+
+    // Download the resolved extensions
+    let pool = TaskPool::new(concurrent_downloads)
+        .timeout(args.task_timeout.map(Duration::from_secs))
+        .retries(args.task_retries)
+        .cancellation(options.cancellation.clone());
+    let inputs = resolved
+        .iter()
+        .map(|resolved| {
+            (
+                TaskMeta::new(&resolved.id, resolved.target_platform.as_ref()),
+                resolved.clone(),
+            )
+        })
+        .collect();
+    let bars = args
+        .progress_bars()
+        .then(|| DownloadBars::new(resolved.len()));
+    let shared_options = Arc::new(DownloadOptions {
+        progress_bars: bars.clone(),
+        ..options.clone()
+    });
+    let shared_pins = Arc::new(pins.clone());
+    args.progress.add_total(resolved.len());
+    let reports = pool
+        .run(inputs, |resolved: ResolvedExtension| {
+            let options = shared_options.clone();
+            let pins = shared_pins.clone();
+            let progress = args.progress.clone();
+            async move {
+                let result =
+                    download_resolved_extension(&resolved, &options, pins.as_ref().as_ref()).await;
+                if result.is_ok() {
+                    progress.record(true);
+                }
+                if let Some(bars) = &options.progress_bars {
+                    bars.finish_package();
+                }
+                result
+            }
+        })
+        .await;
+    if let Some(bars) = bars {
+        bars.finish();
+    }
+    let mut outcomes = Vec::new();
+    let mut quarantined = Vec::new();
+    for report in reports {
+        match report.result {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(VsixHarvesterError::Quarantined(path, reason)) => {
+                args.progress.record(false);
+                quarantined.push((path, reason))
+            }
+            Err(VsixHarvesterError::Cancelled) => {}
+            Err(e) => {
+                args.progress.record(false);
+                error!(
+                    "Error occurred when downloading {} (attempt {}): {}",
+                    report.meta, report.meta.attempt, e
+                )
+            }
+        }
+    }
+    record_synced(args, &resolved, &outcomes, &last_updated)?;
+    if !quarantined.is_empty() {
+        error!(
+            "{} package(s) failed verification and were quarantined:",
+            quarantined.len()
+        );
+        for (path, reason) in &quarantined {
+            error!("  {}: {}", path, reason);
+        }
+    }
+    if (args.with_dependencies || args.expand_packs) && !options.cancellation.is_cancelled() {
+        let linked = download_linked_extensions(
+            args,
+            &outcomes,
+            &options,
+            pins.as_ref(),
+            concurrent_downloads,
+        )
+        .await?;
+        outcomes.extend(linked);
+    }
+    if options.cancellation.is_cancelled() {
+        // Keep the pins of the packages downloaded before the cancellation
+        record_pins(args, pins, &outcomes)?;
+        return Err(VsixHarvesterError::Cancelled);
+    }
+    if args.mirror_icons {
+        mirror_icons(&resolved, &options, concurrent_downloads).await;
+    }
+    if args.vscode_server {
+        let target_platforms: Vec<TargetPlatform> = registry
+            .platforms()
+            .iter()
+            .filter(|platform| platform_filter.matches(&platform.field_name))
+            .filter_map(|platform| platform.target_platform.clone())
+            .collect();
+        harvest_vscode_server(&target_platforms, &options, concurrent_downloads).await?;
+    }
+    if args.delta_copies {
+        write_delta_copies(&args.primary_destination(), &outcomes)?;
+    }
+    if args.save_manifest {
+        write_manifest_sidecars(&outcomes)?;
+    }
+    if let Some(compression) = args.store_compressed {
+        let paths: Vec<&str> = outcomes
+            .iter()
+            .map(|outcome| outcome.file_path.as_str())
+            .collect();
+        write_compressed_copies(&paths, compression)?;
+    }
+    if args.checksum_outputs().any() {
+        write_checksums(
+            &args.primary_destination(),
+            args.checksum_outputs(),
+            &outcomes,
+        )?;
+    }
+    if let Some(shell) = args.emit_install_script {
+        write_install_script(&args.primary_destination(), shell, &outcomes)?;
+    }
+    if args.unpack {
+        unpack_outcomes(&args.primary_destination(), &outcomes)?;
+    }
+    if let Some(format) = args.sbom {
+        write_sbom(&args.primary_destination(), format, &outcomes)?;
+    }
+    copy_to_destinations(&args.mirror_destinations(), &outcomes, &options).await?;
+    record_pins(args, pins, &outcomes)
+}
+
+/// Replace the extension GUIDs of the manifest entries with their `publisher.name` identifiers
+///
+/// # Arguments
+///
+/// * `extensions` - The manifest
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result indicating success or an error if a GUID is not found in the Marketplace
+async fn resolve_extension_guids(
+    extensions: &mut Extensions,
+    options: &DownloadOptions,
+) -> Result<()> {
+    let mut ids: HashMap<String, String> = HashMap::new();
+    for entry in extensions.entries_mut() {
+        let guid = entry.id().to_lowercase();
+        if !is_extension_guid(&guid) {
+            continue;
+        }
+        if !ids.contains_key(&guid) {
+            let id = find_extension_by_guid(&guid, options).await?;
+            ids.insert(guid.clone(), id);
+        }
+        entry.set_id(&ids[&guid]);
+    }
+    Ok(())
+}
+
+/// Expand the entries of the `allPlatforms` list to the platforms each extension publishes
+///
+/// An extension publishing platform-specific builds is added to the list of each
+/// registered platform it has a build for, one publishing a single build is universal.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of the `allPlatforms` list
+/// * `extensions` - The manifest lists receiving the expanded entries
+/// * `registry` - The known platforms
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+async fn expand_all_platforms(
+    entries: Vec<ManifestEntry>,
+    extensions: &mut Extensions,
+    registry: &PlatformRegistry,
+    options: &DownloadOptions,
+    concurrency: usize,
+) {
+    let queries = entries.into_iter().map(|entry| async move {
+        let result = match Extension::from_id(entry.id()) {
+            // Every version is needed to find the platforms of a pinned one
+            Ok(extension) => {
+                query_marketplace_extension(&extension, options, entry.version().is_some()).await
+            }
+            Err(e) => Err(e),
+        };
+        (entry, result)
+    });
+    let results: Vec<_> = stream::iter(queries).buffered(concurrency).collect().await;
+    for (entry, result) in results {
+        let metadata = match result {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("Could not expand {} to its platforms: {}", entry.id(), e);
+                continue;
+            }
+        };
+        let Some(version) = entry.version().or(metadata
+            .versions
+            .first()
+            .map(|latest| latest.version.as_str()))
+        else {
+            let e =
+                VsixHarvesterError::ApiError(format!("{} has no published version", entry.id()));
+            error!("Could not expand {} to its platforms: {}", entry.id(), e);
+            continue;
+        };
+        let target_platforms = metadata.get_target_platforms(version);
+        let fields = registry.field_names_of(&target_platforms);
+        if target_platforms.is_empty() {
+            info!("{} is universal", entry.id());
+            extensions
+                .section_mut(Architecture::Universal.to_field_name())
+                .push(entry);
+            continue;
+        }
+        if fields.len() < target_platforms.len() {
+            warn!(
+                "{} also publishes builds for platforms without a manifest list ({}), see --custom-platforms",
+                entry.id(),
+                join_target_platforms(target_platforms.iter().copied(), ", ")
+            );
+        }
+        info!("Expanding {} to {}", entry.id(), fields.join(", "));
+        for field in fields {
+            extensions.section_mut(field).push(entry.clone());
+        }
+    }
+}
+
+/// Detect the universal entries whose resolved version publishes platform-specific builds
+///
+/// The universal build of such extensions is often broken offline. With `--fix-manifest`
+/// the entry is moved to the platform lists of the manifest and the same version is
+/// downloaded for each of these platforms instead.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `resolved` - The resolved extensions
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the extensions to download or an error that occurred
+fn check_platform_specific(
+    args: &Args,
+    resolved: Vec<ResolvedExtension>,
+    options: &DownloadOptions,
+) -> Result<Vec<ResolvedExtension>> {
+    let mut requested: HashSet<(String, Option<TargetPlatform>)> = resolved
+        .iter()
+        .map(|resolved| (resolved.id.to_lowercase(), resolved.target_platform.clone()))
+        .collect();
+    let mut checked = Vec::with_capacity(resolved.len());
+    for resolved in resolved {
+        let architectures: Vec<Architecture> = match (&resolved.target_platform, &resolved.metadata)
+        {
+            (None, Some(metadata)) => metadata
+                .get_target_platforms(&resolved.version)
+                .into_iter()
+                .filter_map(TargetPlatform::architecture)
+                .collect(),
+            _ => Vec::new(),
+        };
+        if architectures.is_empty() {
+            checked.push(resolved);
+            continue;
+        }
+        let fields: Vec<&str> = architectures
+            .iter()
+            .map(|architecture| architecture.to_field_name())
+            .collect();
+        if !args.fix_manifest {
+            warn!(
+                "{} is listed as universal but {} publishes platform-specific builds ({}), \
+                 move it to the platform lists or run with --fix-manifest",
+                resolved.id,
+                resolved.version,
+                fields.join(", ")
+            );
+            checked.push(resolved);
+            continue;
+        }
+        warn!(
+            "Moving {} from universal to {} in {}",
+            resolved.id,
+            fields.join(", "),
+            args.input
+        );
+        move_to_platforms(&args.input, &resolved.id, &fields)?;
+        let extension = Extension::from_id(&resolved.id)?;
+        for architecture in architectures {
+            let target_platform = architecture.target_platform();
+            if requested.insert((resolved.id.to_lowercase(), target_platform.clone())) {
+                checked.push(ResolvedExtension {
+                    metadata: resolved.metadata.clone(),
+                    ..resolve_pinned_extension(
+                        extension.clone(),
+                        &resolved.version,
+                        options,
+                        target_platform.as_ref(),
+                    )
+                });
+            }
+        }
+    }
+    Ok(checked)
+}
+
+/// Download the extensions linked to the downloaded packages, recursively
+///
+/// The `extensionDependencies` (with `--with-dependencies`) and the `extensionPack`
+/// members (with `--expand-packs`) of each package.json are resolved for the target
+/// platform of the linking package, falling back to the universal package when the
+/// linked extension publishes no build for it. Failures are logged without failing the harvest.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `outcomes` - The downloaded packages
+/// * `options` - The download options
+/// * `pins` - Optional lockfile with the pinned hashes
+/// * `concurrency` - The maximum number of concurrent downloads
+///
+/// # Returns
+///
+/// A Result containing the downloaded linked extensions or an error that occurred
+async fn download_linked_extensions(
+    args: &Args,
+    outcomes: &[DownloadOutcome],
+    options: &DownloadOptions,
+    pins: Option<&Lockfile>,
+    concurrency: usize,
+) -> Result<Vec<DownloadOutcome>> {
+    let mut harvested: HashSet<(String, Option<TargetPlatform>)> = outcomes
+        .iter()
+        .map(|outcome| (outcome.id.to_lowercase(), outcome.target_platform.clone()))
+        .collect();
+    let mut requested = harvested.clone();
+    let mut linked = Vec::new();
+    let mut linking: Vec<DownloadOutcome> = outcomes.to_vec();
+    while !linking.is_empty() && !options.cancellation.is_cancelled() {
+        // The extensions linked in this round not already requested for the same platform
+        let mut pending = Vec::new();
+        for package in &linking {
+            let info = match vsix::read_vsix_info(&package.file_path) {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!(
+                        "Could not read the linked extensions of {}: {}",
+                        package.file_path, e
+                    );
+                    continue;
+                }
+            };
+            let dependencies = info
+                .extension_dependencies
+                .into_iter()
+                .filter(|_| args.with_dependencies)
+                .map(|id| (id, "depends on"));
+            let members = info
+                .extension_pack
+                .into_iter()
+                .filter(|_| args.expand_packs)
+                .map(|id| (id, "packs"));
+            for (id, link) in dependencies.chain(members) {
+                if requested.insert((id.to_lowercase(), package.target_platform.clone())) {
+                    info!("{} {} {}", package.id, link, id);
+                    pending.push((id, package.target_platform.clone()));
+                }
+            }
+        }
+
+        let resolutions = pending.into_iter().map(|(id, target_platform)| async move {
+            let resolved = resolve_linked_extension(&id, target_platform.as_ref(), options).await;
+            (id, resolved)
+        });
+        let results: Vec<_> = stream::iter(resolutions)
+            .buffered(concurrency)
+            .collect()
+            .await;
+        let mut resolved = Vec::new();
+        for (id, result) in results {
+            match result {
+                Ok(extension) => {
+                    if harvested.insert((
+                        extension.id.to_lowercase(),
+                        extension.target_platform.clone(),
+                    )) {
+                        resolved.push(extension);
+                    }
+                }
+                Err(e) => error!(
+                    "Error occurred when resolving the linked extension {}: {}",
+                    id, e
+                ),
+            }
+        }
+        let resolved = apply_locked(args, pins, resolved)?;
+        let resolved = enforce_policy(args, resolved, options, concurrency).await?;
+
+        let downloads = resolved
+            .iter()
+            .map(|resolved| download_resolved_extension(resolved, options, pins));
+        let results: Vec<_> = stream::iter(downloads)
+            .buffered(concurrency)
+            .collect()
+            .await;
+        linking = Vec::new();
+        for (resolved, result) in resolved.iter().zip(results) {
+            match result {
+                Ok(outcome) => linking.push(outcome),
+                Err(e) => error!(
+                    "Error occurred when downloading the linked extension {}: {}",
+                    resolved.id, e
+                ),
+            }
+        }
+        linked.extend(linking.iter().cloned());
+    }
+    Ok(linked)
+}
+
+/// Resolve a dependency or pack member for the target platform of the package linking it
+///
+/// # Arguments
+///
+/// * `id` - The identifier of the linked extension
+/// * `target_platform` - The target platform of the linking package
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the platform-specific package when the extension publishes one
+/// for the target platform, the universal package otherwise
+async fn resolve_linked_extension(
+    id: &str,
+    target_platform: Option<&TargetPlatform>,
+    options: &DownloadOptions,
+) -> Result<ResolvedExtension> {
+    resolve_for_platform(Extension::from_id(id)?, target_platform, options).await
+}
+
+/// Resolve an extension, preferring its package for a target platform
+///
+/// The platforms of a version pinned in the identifier are looked up in the marketplace
+/// metadata of every version.
+///
+/// # Arguments
+///
+/// * `extension` - The extension to resolve
+/// * `target_platform` - The preferred target platform
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the platform-specific package when the extension publishes one
+/// for the target platform, the universal package otherwise
+async fn resolve_for_platform(
+    extension: Extension<'_>,
+    target_platform: Option<&TargetPlatform>,
+    options: &DownloadOptions,
+) -> Result<ResolvedExtension> {
+    let mut resolved = resolve_extension(extension.clone(), options, None).await?;
+    let Some(target_platform) = target_platform else {
+        return Ok(resolved);
+    };
+    if resolved.metadata.is_none() {
+        resolved.metadata = Some(query_marketplace_extension(&extension, options, true).await?);
+    }
+    let platform_specific = resolved.metadata.as_ref().is_some_and(|metadata| {
+        metadata
+            .get_target_platforms(&resolved.version)
+            .contains(&target_platform)
+    });
+    if !platform_specific {
+        return Ok(resolved);
+    }
+    Ok(ResolvedExtension {
+        metadata: resolved.metadata.clone(),
+        ..resolve_pinned_extension(extension, &resolved.version, options, Some(target_platform))
+    })
+}
+
+/// Resolve an extension to its package for every built-in target platform it publishes
+///
+/// # Arguments
+///
+/// * `extension` - The extension to resolve
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the platform-specific packages of the resolved version, its
+/// universal package when it publishes no platform-specific build
+async fn resolve_all_platforms(
+    extension: Extension<'_>,
+    options: &DownloadOptions,
+) -> Result<Vec<ResolvedExtension>> {
+    let resolved = resolve_extension(extension.clone(), options, None).await?;
+    // Every version is needed to find the platforms of a pinned one
+    let metadata = match &resolved.metadata {
+        Some(metadata) => metadata.clone(),
+        None => query_marketplace_extension(&extension, options, true).await?,
+    };
+    let published = metadata.get_target_platforms(&resolved.version);
+    if published.is_empty() {
+        info!("{} {} is universal", resolved.id, resolved.version);
+        return Ok(vec![resolved]);
+    }
+    let mut packages = Vec::new();
+    for architecture in Architecture::all() {
+        let Some(target_platform) = architecture.target_platform() else {
+            continue;
+        };
+        if !published.contains(&&target_platform) {
+            info!(
+                "Skipping {}: {} publishes no build for it",
+                target_platform, resolved.id
+            );
+            continue;
+        }
+        packages.push(ResolvedExtension {
+            metadata: Some(metadata.clone()),
+            ..resolve_pinned_extension(
+                extension.clone(),
+                &resolved.version,
+                options,
+                Some(&target_platform),
+            )
+        });
+    }
+    if packages.is_empty() {
+        info!(
+            "{} {} has no build for a built-in architecture, using its universal package",
+            resolved.id, resolved.version
+        );
+        return Ok(vec![resolved]);
+    }
+    if packages.len() < published.len() {
+        warn!(
+            "{} also publishes builds for platforms without a built-in architecture ({})",
+            resolved.id,
+            join_target_platforms(published.iter().copied(), ", ")
+        );
+    }
+    Ok(packages)
+}
+
+/// Write the normalized copies of the downloaded packages used for delta transfers
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `outcomes` - The successful downloads
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn write_delta_copies(destination: &str, outcomes: &[DownloadOutcome]) -> Result<()> {
+    let normalized_dir = format!("{}/{}", destination, NORMALIZED_DIR);
+    create_directory_if_not_exists(&normalized_dir)?;
+    for outcome in outcomes {
+        let Some(file_name) = Path::new(&outcome.file_path).file_name() else {
+            continue;
+        };
+        let output_path = format!("{}/{}", normalized_dir, file_name.to_string_lossy());
+        if Path::new(&output_path).exists() {
+            continue;
+        }
+        if let Err(e) = vsix::write_normalized_copy(&outcome.file_path, &output_path) {
+            error!("Failed to normalize {}: {}", outcome.file_path, e);
+        } else {
+            info!("Normalized copy saved in {}", output_path);
+        }
+    }
+    Ok(())
+}
+
+/// Write the `package.json` manifest of each downloaded package next to it
+///
+/// The manifest of `publisher.name-version.vsix` is saved as
+/// `publisher.name-version.manifest.json`, so tooling can read its contributions and
+/// engine requirement without unzipping the package.
+///
+/// # Arguments
+///
+/// * `outcomes` - The successful downloads
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+fn write_manifest_sidecars(outcomes: &[DownloadOutcome]) -> Result<()> {
+    for outcome in outcomes {
+        let stem = outcome
+            .file_path
+            .strip_suffix(".vsix")
+            .unwrap_or(&outcome.file_path);
+        let output_path = format!("{}{}", stem, MANIFEST_SIDECAR_SUFFIX);
+        if Path::new(&output_path).exists() {
+            continue;
+        }
+        match vsix::read_package_json(&outcome.file_path) {
+            Ok(manifest) => {
+                write_atomic(Path::new(&output_path), manifest.as_bytes())?;
+                info!("Manifest saved in {}", output_path);
+            }
+            Err(e) => error!(
+                "Failed to extract the manifest of {}: {}",
+                outcome.file_path, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Download the icons of the resolved extensions into the icons cache
+///
+/// # Arguments
+///
+/// * `resolved` - The resolved extensions
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent downloads
+async fn mirror_icons(
+    resolved: &[ResolvedExtension],
+    options: &DownloadOptions,
+    concurrency: usize,
+) {
+    let mut mirrored = HashSet::new();
+    let inputs = resolved
+        .iter()
+        .filter(|resolved| mirrored.insert((resolved.id.clone(), resolved.version.clone())))
+        .map(|resolved| (TaskMeta::new(&resolved.id, None), resolved.clone()))
+        .collect();
+    let options = Arc::new(options.clone());
+    let reports = TaskPool::new(concurrency)
+        .cancellation(options.cancellation.clone())
+        .run(inputs, |resolved: ResolvedExtension| {
+            let options = options.clone();
+            async move { mirror_icon(&resolved, &options).await }
+        })
+        .await;
+    for report in reports {
+        if let Err(e) = report.result {
+            error!(
+                "Error occurred when downloading icon of {}: {}",
+                report.meta, e
+            );
+        }
+    }
+}
+
+/// Print the changes a harvest would make to the mirror, without downloading anything
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `resolved` - The resolved extensions
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent requests
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn print_dry_run(
+    args: &Args,
+    resolved: Vec<ResolvedExtension>,
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<()> {
+    let destination = args.primary_destination();
+    let packages = if Path::new(&destination).exists() {
+        scan_mirror(&destination)?
+    } else {
+        Vec::new()
+    };
+    let sized = size_extensions(resolved, options, concurrency).await;
+    print!(
+        "{}",
+        MirrorDiff::new(&packages, &sized).render(args.diff_format)
+    );
+    Ok(())
+}
+
+/// Check the resolved extensions against the policy file of `--policy`, if any
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `resolved` - The resolved extensions
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent requests
+///
+/// # Returns
+///
+/// A Result containing the extensions to download or an error that occurred
+async fn enforce_policy(
+    args: &Args,
+    resolved: Vec<ResolvedExtension>,
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<Vec<ResolvedExtension>> {
+    match &args.policy {
+        Some(path) => apply_policy(path, args.policy_mode(), resolved, options, concurrency).await,
+        None => Ok(resolved),
+    }
+}
+
+/// Get the number of bytes to download for each resolved extension
+///
+/// Packages already present in the destination directory do not count, unless
+/// the cache is disabled.
+///
+/// # Arguments
+///
+/// * `resolved` - The resolved extensions
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent requests
+///
+/// # Returns
+///
+/// The resolved extensions in the same order with their size
+async fn size_extensions(
+    resolved: Vec<ResolvedExtension>,
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Vec<(ResolvedExtension, u64)> {
+    let tasks = resolved.into_iter().map(|resolved| async move {
+        if !options.no_cache && Path::new(&resolved.file_path).exists() {
+            return (resolved, 0);
+        }
+        let size = match fetch_download_size(&resolved, options).await {
+            Ok(Some(size)) => size,
+            Ok(None) => {
+                warn!("Size of {} is unknown, counting it as 0", resolved.id);
+                0
+            }
+            Err(e) => {
+                warn!("Could not get the size of {}: {}", resolved.id, e);
+                0
+            }
+        };
+        (resolved, size)
+    });
+    stream::iter(tasks).buffered(concurrency).collect().await
+}
+
+/// An extension of the manifest to resolve
+struct ResolutionRequest<'a> {
+    extension: Extension<'a>,
+    target_platform: Option<TargetPlatform>,
+    /// The locked version kept instead of querying the marketplace
+    pinned_version: Option<String>,
+    /// The versions allowed by the update policy of the manifest entry
+    constraint: Option<VersionConstraint>,
+    /// The URL and hash of a patched build replacing the registry package
+    package_override: Option<PackageOverride>,
+}
+
+/// Prefetch the metadata of the extensions of large manifests in batched query windows
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `requested` - The extensions to resolve
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// The download options to resolve the extensions with, answering from the prefetched
+/// metadata when the manifest is large enough
+async fn prefetch_requests(
+    args: &Args,
+    requested: &[ResolutionRequest<'_>],
+    options: &DownloadOptions,
+) -> DownloadOptions {
+    // Every version is needed to filter them by engine or by constraint
+    let ids: Vec<(String, bool)> = requested
+        .iter()
+        .filter(|request| request.pinned_version.is_none())
+        .map(|request| {
+            (
+                request.extension.to_id(),
+                options.engine_version.is_some() || request.constraint.is_some(),
+            )
+        })
+        .collect();
+    if args.query_window == 0 || ids.len() <= QUERY_WINDOW_THRESHOLD {
+        return options.clone();
+    }
+    let registry = prefetch::prefetch_metadata(
+        &ids,
+        options,
+        args.query_window,
+        Duration::from_millis(args.query_window_delay),
+    )
+    .await;
+    DownloadOptions {
+        registry: Arc::new(registry),
+        ..options.clone()
+    }
+}
+
+/// Resolve the versions of all the requested extensions
+///
+/// All the resolution errors are reported before returning, so a large manifest
+/// is validated at once instead of failing extension by extension during the downloads.
+///
+/// # Arguments
+///
+/// * `requested` - The extensions to resolve with their target platform and optional pinned version
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+///
+/// # Returns
+///
+/// A Result containing the successfully resolved extensions, in the order of the manifest,
+/// or an error if an extension has no compatible version and the policy is to fail
+async fn resolve_extensions(
+    requested: Vec<ResolutionRequest<'_>>,
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<Vec<ResolvedExtension>> {
+    let tasks = requested.into_iter().map(|request| {
+        let id = request.extension.to_id();
+        async move {
+            let result = match (request.package_override, request.pinned_version) {
+                // The version of an override is pinned, its package does not come from the registry
+                (Some(package_override), Some(version)) => Ok(ResolvedExtension {
+                    download_url: package_override.url,
+                    override_sha256: Some(package_override.sha256),
+                    ..resolve_pinned_extension(
+                        request.extension,
+                        &version,
+                        options,
+                        request.target_platform.as_ref(),
+                    )
+                }),
+                (_, Some(version)) => Ok(resolve_pinned_extension(
+                    request.extension,
+                    &version,
+                    options,
+                    request.target_platform.as_ref(),
+                )),
+                (_, None) => {
+                    resolve_constrained_extension(
+                        request.extension,
+                        options,
+                        request.target_platform.as_ref(),
+                        request.constraint.as_ref(),
+                    )
+                    .await
+                }
+            };
+            (id, result)
+        }
+    });
+    let results: Vec<_> = stream::iter(tasks).buffered(concurrency).collect().await;
+
+    let mut resolved = Vec::new();
+    let mut failures = 0;
+    let mut incompatible = 0;
+    for (id, result) in results {
+        match result {
+            Ok(extension) => resolved.push(extension),
+            Err(e @ VsixHarvesterError::NoCompatibleVersion(..)) => {
+                incompatible += 1;
+                match options.on_no_compatible {
+                    NoCompatiblePolicy::Skip => warn!("Skipping: {}", e),
+                    _ => error!("{}", e),
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                error!("Error occurred when resolving {}: {}", id, e);
+            }
+        }
+    }
+    info!(
+        "Resolved {} extensions, {} resolution errors, {} without compatible version",
+        resolved.len(),
+        failures,
+        incompatible
+    );
+    if incompatible > 0 && options.on_no_compatible == NoCompatiblePolicy::Fail {
+        return Err(VsixHarvesterError::ApiError(format!(
+            "{} extension(s) have no version compatible with engine {}",
+            incompatible,
+            options.engine_version.as_deref().unwrap_or_default()
+        )));
+    }
+    Ok(resolved)
+}
+
+/// Cache the detail assets of every package of a mirror, so a gallery serving it never
+/// makes origin requests
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+/// * `dir` - The directory of the mirror
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn prewarm_mirror(args: &Args, dir: &str) -> Result<()> {
+    let options = DownloadOptions {
+        destination: dir.to_string(),
+        ..DownloadOptions::from(args)
+    };
+    let cached = assets::prewarm_assets(&options, args.concurrency()).await?;
+    info!("{} asset(s) cached in {}", cached, dir);
+    Ok(())
+}
+
+/// Run the subcommand of the command line, the harvest when there is none
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn run_command(args: &Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Registries {
+            action: RegistriesCommand::Status,
+        }) => registries::print_registries_status(&DownloadOptions::from(args)).await,
+        Some(Command::Collection {
+            action: CollectionCommand::Verify { path, offline },
+        }) => {
+            collection::verify_collection(
+                path,
+                &DownloadOptions::from(args),
+                &PlatformRegistry::with_custom(&args.custom_platforms)?,
+                *offline,
+            )
+            .await
+        }
+        Some(Command::Export { format, output }) => export::export_lockfile(
+            &args.lockfile_path(),
+            *format,
+            output.as_deref(),
+            &args.primary_destination(),
+            args.naming_preset,
+        ),
+        Some(Command::ExportServerExtensions {
+            output,
+            target_platform,
+        }) => extensions_dir::export_server_extensions(
+            &args.primary_destination(),
+            output,
+            target_platform,
+        )
+        .map(|_| ()),
+        Some(Command::Install {
+            files,
+            code_binary,
+            extensions_dir,
+            target_platform,
+        }) => {
+            let method =
+                install::install_method(code_binary.as_deref(), extensions_dir.as_deref())?;
+            install::install_packages(
+                files,
+                &args.primary_destination(),
+                target_platform.as_ref(),
+                &method,
+            )
+            .map(|installed| info!("{} package(s) installed", installed))
+        }
+        Some(Command::Clean { max_age }) => temp::clean_temp_files(
+            &args.primary_destination(),
+            schedule::parse_interval(max_age)?,
+        )
+        .map(|_| ()),
+        Some(Command::ExportMirror { output }) => static_mirror::export_static_mirror(
+            &args.primary_destination(),
+            output,
+            args.hash,
+            args.timezone.unwrap_or_default(),
+        )
+        .map(|_| ()),
+        Some(Command::Status {
+            stale_days,
+            offline,
+            json,
+        }) => {
+            status::print_mirror_status(
+                &DownloadOptions::from(args),
+                *stale_days,
+                *offline,
+                args.concurrency(),
+                *json,
+                args.timezone.unwrap_or_default(),
+            )
+            .await
+        }
+        Some(Command::Outdated { json }) => {
+            outdated::print_outdated(&DownloadOptions::from(args), args.concurrency(), *json).await
+        }
+        Some(Command::Stats { top, json }) => {
+            stats::print_mirror_stats(&args.primary_destination(), *top, *json)
+        }
+        Some(Command::Verify {
+            quarantine,
+            offline,
+            checksums,
+            json,
+        }) => {
+            let lockfile = if *checksums {
+                Some(Lockfile::load(&args.lockfile_path())?)
+            } else {
+                None
+            };
+            verify::print_verification(
+                &DownloadOptions::from(args),
+                args.concurrency(),
+                *quarantine,
+                *offline,
+                lockfile.as_ref(),
+                *json,
+            )
+            .await
+        }
+        Some(Command::Provenance { path, json }) => {
+            provenance::print_provenance(path, *json, args.timezone.unwrap_or_default())
+        }
+        Some(Command::Yank {
+            target,
+            quarantine,
+            reason,
+        }) => yank::print_yank(
+            &args.primary_destination(),
+            &args.lockfile_path(),
+            target,
+            *quarantine,
+            reason,
+        ),
+        Some(Command::CheckUpdate { path, download }) => {
+            update::print_vsix_update(path, &DownloadOptions::from(args), *download).await
+        }
+        Some(Command::Curate {
+            sort,
+            min_installs,
+            since,
+            search,
+            limit,
+            download,
+            json,
+        }) => {
+            let query = curate::CurationQuery {
+                sort: *sort,
+                min_installs: *min_installs,
+                since: *since,
+                search: search.clone(),
+                limit: *limit,
+            };
+            curate::print_candidates(
+                &query,
+                &args.input,
+                &DownloadOptions::from(args),
+                args.concurrency(),
+                *download,
+                *json,
+            )
+            .await
+        }
+        Some(Command::Search {
+            text,
+            category,
+            sort,
+            limit,
+            json,
+        }) => {
+            let query = search::SearchQuery {
+                text: text.clone(),
+                category: category.clone(),
+                sort: *sort,
+                limit: *limit,
+            };
+            search::print_search(&query, &DownloadOptions::from(args), *json).await
+        }
+        Some(Command::Prewarm) => prewarm_mirror(args, &args.primary_destination()).await,
+        Some(Command::Serve {
+            dir,
+            host,
+            port,
+            prewarm,
+        }) => {
+            let dir = dir.clone().unwrap_or_else(|| args.primary_destination());
+            if *prewarm {
+                prewarm_mirror(args, &dir).await?;
+            }
+            gallery::serve_gallery(
+                &dir,
+                &format!("{}:{}", host, port),
+                args.cancellation.clone(),
+            )
+            .await
+        }
+        Some(Command::Service {
+            listen,
+            token,
+            schedules,
+            jitter,
+            window,
+        }) => {
+            service::serve(
+                listen,
+                token.as_deref(),
+                schedules.as_deref(),
+                jitter.as_deref(),
+                window.as_deref(),
+                args.timezone,
+                args.cancellation.clone(),
+            )
+            .await
+        }
+        Some(Command::Versions { id, json }) => {
+            versions::print_versions(id, &DownloadOptions::from(args), *json).await
+        }
+        None => process_extensions(args).await,
+    }
+}
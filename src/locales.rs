@@ -0,0 +1,94 @@
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::ManifestEntry;
+
+/// Publisher of the VS Code language packs
+const LANGUAGE_PACK_PUBLISHER: &str = "MS-CEINTL";
+
+/// Locales with a VS Code language pack, as they appear in the extension identifiers
+const LANGUAGE_PACK_LOCALES: &[&str] = &[
+    "cs", "de", "es", "fr", "it", "ja", "ko", "pl", "pt-BR", "qps-ploc", "ru", "tr", "zh-hans",
+    "zh-hant",
+];
+
+/// Get the identifier of the language pack of a locale
+///
+/// # Arguments
+///
+/// * `locale` - The locale, case insensitive (e.g., "fr", "pt-br", "zh-cn")
+///
+/// # Returns
+///
+/// A Result containing the extension identifier (e.g., "MS-CEINTL.vscode-language-pack-fr")
+/// or an error if no language pack is published for the locale
+pub fn language_pack_id(locale: &str) -> Result<String> {
+    let normalized = match locale.trim().to_lowercase().replace('_', "-").as_str() {
+        "zh-cn" | "zh-sg" => "zh-hans".to_string(),
+        "zh-tw" | "zh-hk" => "zh-hant".to_string(),
+        "pt" => "pt-br".to_string(),
+        other => other.to_string(),
+    };
+    // Regional variants fall back to the language (e.g., "fr-CA" -> "fr")
+    let language = normalized.split('-').next().unwrap_or_default();
+    LANGUAGE_PACK_LOCALES
+        .iter()
+        .find(|pack_locale| pack_locale.eq_ignore_ascii_case(&normalized))
+        .or_else(|| {
+            LANGUAGE_PACK_LOCALES
+                .iter()
+                .find(|pack_locale| pack_locale.eq_ignore_ascii_case(language))
+        })
+        .map(|pack_locale| {
+            format!(
+                "{}.vscode-language-pack-{}",
+                LANGUAGE_PACK_PUBLISHER, pack_locale
+            )
+        })
+        .ok_or_else(|| VsixHarvesterError::InvalidLocale(locale.to_string()))
+}
+
+/// Get the manifest entries of the language packs of the locales
+///
+/// # Arguments
+///
+/// * `locales` - The locales (e.g., ["fr", "de", "ja"])
+///
+/// # Returns
+///
+/// A Result containing the universal entries to harvest or an error if a locale is unknown
+pub fn language_pack_entries(locales: &[String]) -> Result<Vec<ManifestEntry>> {
+    locales
+        .iter()
+        .filter(|locale| !locale.trim().is_empty())
+        .map(|locale| language_pack_id(locale).map(ManifestEntry::Id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_pack_id() {
+        assert_eq!(
+            language_pack_id("fr").unwrap(),
+            "MS-CEINTL.vscode-language-pack-fr"
+        );
+        assert_eq!(
+            language_pack_id("fr-CA").unwrap(),
+            "MS-CEINTL.vscode-language-pack-fr"
+        );
+        assert_eq!(
+            language_pack_id("pt_br").unwrap(),
+            "MS-CEINTL.vscode-language-pack-pt-BR"
+        );
+        assert_eq!(
+            language_pack_id("zh-CN").unwrap(),
+            "MS-CEINTL.vscode-language-pack-zh-hans"
+        );
+        assert!(language_pack_id("tlh").is_err());
+
+        let entries = language_pack_entries(&["de".to_string(), "ja".to_string()]).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].id(), "MS-CEINTL.vscode-language-pack-ja");
+    }
+}
@@ -0,0 +1,441 @@
+use crate::architecture::TargetPlatform;
+use crate::error::{Result, VsixHarvesterError};
+use crate::hash::{Digest, HashAlgorithm};
+use crate::temp::write_atomic;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A resolved extension artifact recorded in the lockfile
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockEntry {
+    /// The extension identifier in the format "publisher.name"
+    pub id: String,
+    /// The resolved version of the extension
+    pub version: String,
+    /// The target platform of the artifact, None for universal packages
+    #[serde(
+        rename = "targetPlatform",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub target_platform: Option<TargetPlatform>,
+    /// The URL the package was downloaded from
+    #[serde(
+        rename = "downloadUrl",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub download_url: Option<String>,
+    /// The SHA-256 of the downloaded VSIX package
+    pub sha256: String,
+    /// The digest of the package with the algorithm of `--hash`, when it is not SHA-256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<Digest>,
+    /// When this version was first locked
+    #[serde(rename = "lockedAt", default, skip_serializing_if = "Option::is_none")]
+    pub locked_at: Option<DateTime<Utc>>,
+}
+
+impl LockEntry {
+    /// Get the digest to verify the package with, the recorded one or the SHA-256
+    ///
+    /// Entries locked with different `--hash` algorithms can be mixed in a lockfile,
+    /// each one is verified with its own algorithm.
+    pub fn stored_digest(&self) -> Digest {
+        self.digest
+            .clone()
+            .unwrap_or_else(|| Digest::new(HashAlgorithm::Sha256, &self.sha256))
+    }
+}
+
+/// Lockfile pinning the artifacts of a harvest
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub extensions: Vec<LockEntry>,
+}
+
+impl Lockfile {
+    /// Load a lockfile, returning an empty one if the file does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the lockfile
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the lockfile or an error that occurred
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Sort the entries by id, target platform and version
+    pub fn sort(&mut self) {
+        self.extensions.sort_by(|a, b| {
+            (&a.id, &a.target_platform, &a.version).cmp(&(&b.id, &b.target_platform, &b.version))
+        });
+    }
+
+    /// Save the lockfile, entries are sorted to keep the file diffable
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the lockfile
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error that occurred
+    pub fn save(&mut self, path: &str) -> Result<()> {
+        self.sort();
+        write_atomic(
+            Path::new(path),
+            serde_json::to_string_pretty(self)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Find the entry pinned for an extension version and target platform
+    pub fn find(
+        &self,
+        id: &str,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> Option<&LockEntry> {
+        self.extensions.iter().find(|entry| {
+            entry.id == id
+                && entry.version == version
+                && entry.target_platform.as_ref() == target_platform
+        })
+    }
+
+    /// Find the entry locked for an extension and target platform, whatever its version
+    pub fn find_current(
+        &self,
+        id: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> Option<&LockEntry> {
+        self.extensions
+            .iter()
+            .find(|entry| entry.id == id && entry.target_platform.as_ref() == target_platform)
+    }
+
+    /// Check that a resolved version is the locked one, for `--locked` runs
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `version` - The resolved version
+    /// * `target_platform` - The target platform, None for universal packages
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the locked entry, or a mismatch error when the extension is
+    /// not locked or is locked to another version
+    pub fn check_locked(
+        &self,
+        id: &str,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> Result<&LockEntry> {
+        let package = match target_platform {
+            Some(target_platform) => format!("{} ({})", id, target_platform),
+            None => id.to_string(),
+        };
+        match self.find_current(id, target_platform) {
+            Some(entry) if entry.version == version => Ok(entry),
+            Some(entry) => Err(VsixHarvesterError::LockfileMismatch(format!(
+                "{} is locked to {}, resolved {}",
+                package, entry.version, version
+            ))),
+            None => Err(VsixHarvesterError::LockfileMismatch(format!(
+                "{} is not locked, resolved {}",
+                package, version
+            ))),
+        }
+    }
+
+    /// Insert an entry, replacing the one with the same id and target platform
+    ///
+    /// The lock date of the replaced entry is kept when the version did not change.
+    pub fn upsert(&mut self, mut new_entry: LockEntry) {
+        if let Some(position) = self.extensions.iter().position(|entry| {
+            entry.id == new_entry.id && entry.target_platform == new_entry.target_platform
+        }) {
+            let old_entry = self.extensions.remove(position);
+            if old_entry.version == new_entry.version && old_entry.locked_at.is_some() {
+                new_entry.locked_at = old_entry.locked_at;
+            }
+        }
+        self.extensions.push(new_entry);
+    }
+}
+
+/// Get the lockfile path associated with an extensions.json file
+///
+/// # Arguments
+///
+/// * `input` - The path of the extensions.json file
+///
+/// # Returns
+///
+/// The path of the lockfile (e.g., "./extensions.lock" for "./extensions.json")
+pub fn lockfile_path(input: &str) -> String {
+    Path::new(input)
+        .with_extension("lock")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Selects the locked entries that may be upgraded by a partial refresh
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeFilter {
+    /// A single extension (e.g., "golang.Go")
+    Extension(String),
+    /// Every extension of a publisher (e.g., "golang.*")
+    Publisher(String),
+    /// Entries locked more than N days ago (e.g., "older-than:30d")
+    OlderThanDays(i64),
+}
+
+impl UpgradeFilter {
+    /// Check if a locked entry is selected for upgrade
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The locked entry
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// `true` if the entry must be resolved again, `false` if its pinned version is kept
+    pub fn matches(&self, entry: &LockEntry, now: DateTime<Utc>) -> bool {
+        match self {
+            Self::Extension(id) => entry.id.eq_ignore_ascii_case(id),
+            Self::Publisher(publisher) => {
+                entry
+                    .id
+                    .split_once('.')
+                    .is_some_and(|(entry_publisher, _)| {
+                        entry_publisher.eq_ignore_ascii_case(publisher)
+                    })
+            }
+            // Entries without a lock date predate the date tracking, they are considered old
+            Self::OlderThanDays(days) => entry
+                .locked_at
+                .is_none_or(|locked_at| now - locked_at > Duration::days(*days)),
+        }
+    }
+}
+
+impl FromStr for UpgradeFilter {
+    type Err = VsixHarvesterError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(age) = s.strip_prefix("older-than:") {
+            return age
+                .trim_end_matches('d')
+                .parse::<i64>()
+                .map(Self::OlderThanDays)
+                .map_err(|_| VsixHarvesterError::InvalidUpgradeFilter(s.to_string()));
+        }
+        if let Some(publisher) = s.strip_suffix(".*") {
+            return Ok(Self::Publisher(publisher.to_string()));
+        }
+        if s.split('.').count() == 2 {
+            return Ok(Self::Extension(s.to_string()));
+        }
+        Err(VsixHarvesterError::InvalidUpgradeFilter(s.to_string()))
+    }
+}
+
+/// How far a locked extension may be updated by a sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdatePolicy {
+    /// Only versions with the same major and minor version
+    Patch,
+    /// Only versions with the same major version
+    Minor,
+    /// Any version
+    #[default]
+    Latest,
+}
+
+impl UpdatePolicy {
+    /// Check if the policy allows updating a locked version to a candidate version
+    ///
+    /// # Arguments
+    ///
+    /// * `locked_version` - The locked version (e.g., "1.2.3")
+    /// * `candidate` - The candidate version (e.g., "1.2.4")
+    ///
+    /// # Returns
+    ///
+    /// `true` if the candidate is allowed
+    pub fn allows(&self, locked_version: &str, candidate: &str) -> bool {
+        let same_components = |count: usize| {
+            locked_version
+                .split('.')
+                .take(count)
+                .eq(candidate.split('.').take(count))
+        };
+        match self {
+            Self::Patch => same_components(2),
+            Self::Minor => same_components(1),
+            Self::Latest => true,
+        }
+    }
+}
+
+/// Versions an extension may be resolved to, relative to its locked version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConstraint {
+    pub policy: UpdatePolicy,
+    pub locked_version: String,
+}
+
+impl VersionConstraint {
+    /// Check if a candidate version is allowed
+    pub fn allows(&self, candidate: &str) -> bool {
+        self.policy.allows(&self.locked_version, candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_policy() {
+        assert!(UpdatePolicy::Patch.allows("1.2.3", "1.2.10"));
+        assert!(!UpdatePolicy::Patch.allows("1.2.3", "1.3.0"));
+        assert!(UpdatePolicy::Minor.allows("1.2.3", "1.9.0"));
+        assert!(!UpdatePolicy::Minor.allows("1.2.3", "2.0.0"));
+        assert!(!UpdatePolicy::Minor.allows("1.2.3", "11.0.0"));
+        assert!(UpdatePolicy::Latest.allows("1.2.3", "2.0.0"));
+        let policy: UpdatePolicy = serde_json::from_str(r#""patch""#).unwrap();
+        assert_eq!(policy, UpdatePolicy::Patch);
+    }
+
+    fn entry(version: &str, sha256: &str) -> LockEntry {
+        LockEntry {
+            id: "golang.Go".to_string(),
+            version: version.to_string(),
+            target_platform: None,
+            download_url: None,
+            sha256: sha256.to_string(),
+            digest: None,
+            locked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_lockfile_path() {
+        assert_eq!(lockfile_path("./extensions.json"), "./extensions.lock");
+        assert_eq!(lockfile_path("extensions"), "extensions.lock");
+    }
+
+    #[test]
+    fn test_upsert_and_find() {
+        let mut lockfile = Lockfile::default();
+        lockfile.upsert(entry("1.0.0", "aaa"));
+        lockfile.upsert(entry("1.0.1", "bbb"));
+        assert_eq!(lockfile.extensions.len(), 1);
+        assert!(lockfile.find("golang.Go", "1.0.0", None).is_none());
+        assert_eq!(
+            lockfile.find("golang.Go", "1.0.1", None).unwrap().sha256,
+            "bbb"
+        );
+        assert!(lockfile
+            .find("golang.Go", "1.0.1", Some(&"linux-x64".into()))
+            .is_none());
+        assert_eq!(
+            lockfile.find_current("golang.Go", None).unwrap().version,
+            "1.0.1"
+        );
+    }
+
+    #[test]
+    fn test_upsert_keeps_lock_date_of_same_version() {
+        let mut lockfile = Lockfile::default();
+        let locked_at = Utc::now() - Duration::days(10);
+        lockfile.upsert(LockEntry {
+            locked_at: Some(locked_at),
+            ..entry("1.0.0", "aaa")
+        });
+        lockfile.upsert(LockEntry {
+            locked_at: Some(Utc::now()),
+            ..entry("1.0.0", "aaa")
+        });
+        assert_eq!(lockfile.extensions[0].locked_at, Some(locked_at));
+    }
+
+    #[test]
+    fn test_upgrade_filter() {
+        let now = Utc::now();
+        let old_entry = LockEntry {
+            locked_at: Some(now - Duration::days(40)),
+            ..entry("1.0.0", "aaa")
+        };
+        let recent_entry = LockEntry {
+            locked_at: Some(now - Duration::days(2)),
+            ..entry("1.0.0", "aaa")
+        };
+
+        let filter: UpgradeFilter = "older-than:30d".parse().unwrap();
+        assert!(filter.matches(&old_entry, now));
+        assert!(!filter.matches(&recent_entry, now));
+
+        let filter: UpgradeFilter = "golang.*".parse().unwrap();
+        assert_eq!(filter, UpgradeFilter::Publisher("golang".to_string()));
+        assert!(filter.matches(&recent_entry, now));
+
+        let filter: UpgradeFilter = "ms-python.python".parse().unwrap();
+        assert!(!filter.matches(&recent_entry, now));
+
+        assert!("older-than:soon".parse::<UpgradeFilter>().is_err());
+        assert!("invalid".parse::<UpgradeFilter>().is_err());
+    }
+
+    #[test]
+    fn test_check_locked() {
+        let mut lockfile = Lockfile::default();
+        lockfile.upsert(LockEntry {
+            download_url: Some("https://example.com/golang.Go-1.0.0.vsix".to_string()),
+            ..entry("1.0.0", "aaa")
+        });
+        let locked = lockfile.check_locked("golang.Go", "1.0.0", None).unwrap();
+        assert_eq!(
+            locked.download_url.as_deref(),
+            Some("https://example.com/golang.Go-1.0.0.vsix")
+        );
+        assert_eq!(
+            lockfile
+                .check_locked("golang.Go", "1.0.1", None)
+                .unwrap_err()
+                .to_string(),
+            "Resolution differs from the lockfile: golang.Go is locked to 1.0.0, resolved 1.0.1"
+        );
+        assert_eq!(
+            lockfile
+                .check_locked("golang.Go", "1.0.0", Some(&"linux-x64".into()))
+                .unwrap_err()
+                .to_string(),
+            "Resolution differs from the lockfile: golang.Go (linux-x64) is not locked, resolved 1.0.0"
+        );
+        let serialized = serde_json::to_string(&lockfile).unwrap();
+        assert!(serialized.contains(r#""downloadUrl":"https://example.com/golang.Go-1.0.0.vsix""#));
+    }
+
+    #[test]
+    fn test_load_missing_lockfile() {
+        let lockfile = Lockfile::load("./does-not-exist.lock").unwrap();
+        assert!(lockfile.extensions.is_empty());
+    }
+}
@@ -1,15 +1,42 @@
-use crate::config::{API_URL, MARKETPLACE_API_VERSION, MARKETPLACE_URL, USER_AGENT};
+use crate::architecture::TargetPlatform;
+use crate::config::{
+    API_URL, ASSET_URL, LAST_UPDATED_BATCH_SIZE, MARKETPLACE_API_VERSION, MARKETPLACE_ORIGIN,
+    MARKETPLACE_TARGET_PLATFORMS, MARKETPLACE_URL, POLITE_MAX_RETRIES, POLITE_REQUEST_DELAY_MS,
+    POLITE_RETRY_DELAY_MS, RETRY_DELAY_MS, USER_AGENT,
+};
+use crate::credentials::resolve_credentials;
+use crate::crypto::sha256;
 use crate::error::Result;
 use crate::error::VsixHarvesterError;
 use crate::extension::Extension;
-use crate::types::MarketplaceResponse;
-use log::{debug, error, info};
-use serde::de;
+use crate::hash::{Digest, HashAlgorithm, PackageHasher};
+use crate::lockfile::{Lockfile, VersionConstraint};
+use crate::naming::NamingPreset;
+use crate::progress::{DownloadBars, PackageBar};
+use crate::provenance::{record_provenance, Provenance, ProvenanceStorage, Verification};
+use crate::quarantine::{package_file_name, quarantine_package, quarantine_package_file};
+use crate::registry::Registry;
+use crate::signature::{verify_package_signature, SignaturePolicy};
+use crate::storage::{LocalStorage, Storage};
+use crate::temp::{partial_path, temp_workspace, write_atomic};
+use crate::types::{
+    compare_versions, Extension as MarketplaceExtension, MarketplaceResponse, Version,
+};
+use crate::vsix::validate_vsix_reader;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use log::{debug, error, info, warn};
 use serde_json::json;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::{self, BufReader, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 use bitflags::bitflags;
+use clap::ValueEnum;
 
 bitflags! {
     /// Flags that control what data is included in the marketplace API response
@@ -80,85 +107,1214 @@ impl Flags {
             | Flags::INCLUDE_VERSION_PROPERTIES
     }
     pub fn all_versions() -> Self {
-        Flags::INCLUDE_VERSIONS | Flags::INCLUDE_FILES | Flags::INCLUDE_VERSION_PROPERTIES
+        Flags::INCLUDE_VERSIONS
+            | Flags::INCLUDE_FILES
+            | Flags::INCLUDE_VERSION_PROPERTIES
+            | Flags::INCLUDE_STATISTICS
+    }
+    /// Creates the smallest flags combination resolving a version, without files nor statistics
+    ///
+    /// The version properties hold the engine requirement and the pre-release flag.
+    pub fn minimal(all_versions: bool) -> Self {
+        let flags = Flags::INCLUDE_VERSIONS | Flags::INCLUDE_VERSION_PROPERTIES;
+        if all_versions {
+            flags
+        } else {
+            flags | Flags::INCLUDE_LATEST_VERSION_ONLY
+        }
+    }
+    /// Creates the flags combination of the searches, ranking the extensions by their statistics
+    ///
+    /// With `minimal`, the statistics are only added to the latest version and its properties.
+    pub fn search(minimal: bool) -> Self {
+        if minimal {
+            Flags::minimal(false) | Flags::INCLUDE_STATISTICS
+        } else {
+            Flags::standard()
+        }
     }
 }
-/// Downloads a VSCode extension by its identifier
+/// What to do when no version of an extension is compatible with the requested engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NoCompatiblePolicy {
+    /// Fail before downloading anything
+    Fail,
+    /// Download the latest version anyway
+    #[default]
+    Latest,
+    /// Skip the extension
+    Skip,
+}
+
+/// How to pick among the versions equally compatible with the engine and the update policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum VersionPreference {
+    /// The most recently released version, then the highest version number
+    #[default]
+    ReleaseDate,
+    /// The highest version number, then the most recently released version
+    VersionNumber,
+}
+
+impl VersionPreference {
+    /// Sort versions from the preferred one
+    ///
+    /// The sort is stable, versions released at the same time with the same number
+    /// (e.g., the builds of each platform) keep the order of the registry response.
+    ///
+    /// # Arguments
+    ///
+    /// * `versions` - The candidate versions
+    pub fn sort(self, versions: &mut [&Version]) {
+        let released = |version: &Version| {
+            DateTime::parse_from_rfc3339(&version.last_updated)
+                .map(|date| date.with_timezone(&Utc))
+                .ok()
+        };
+        versions.sort_by(|a, b| {
+            let by_date = released(b).cmp(&released(a));
+            let by_number = compare_versions(&b.version, &a.version).cmp(&0);
+            match self {
+                VersionPreference::ReleaseDate => by_date.then(by_number),
+                VersionPreference::VersionNumber => by_number.then(by_date),
+            }
+        });
+    }
+}
+
+/// Settings shared by every download of a harvest run
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// The directory where the extensions will be saved
+    pub destination: String,
+    /// Whether to force redownload even if the extension already exists
+    pub no_cache: bool,
+    /// Optional proxy URL
+    pub proxy: Option<String>,
+    /// Optional reference to the credentials of the proxy (e.g., "keyring:corp-proxy")
+    pub proxy_credentials: Option<String>,
+    /// Optional engine version the extensions must be compatible with
+    pub engine_version: Option<String>,
+    /// Whether versions marked as pre-release may be downloaded
+    pub allow_pre_release: bool,
+    /// Whether the SHA-256 of already downloaded files must be computed
+    pub pin_hashes: bool,
+    /// The algorithm of the digests computed next to the SHA-256 of the packages
+    pub hash: HashAlgorithm,
+    /// Whether the requests are spaced out and retried when throttled
+    pub polite: bool,
+    /// The number of times a request failing with a transient error is retried
+    pub retries: u32,
+    /// The delay before the first retry, doubled at each retry
+    pub retry_delay: Duration,
+    /// What to do when no version is compatible with the engine version
+    pub on_no_compatible: NoCompatiblePolicy,
+    /// How to pick among equally compatible versions
+    pub prefer: VersionPreference,
+    /// Whether the Marketplace signatures of the downloaded packages are checked
+    pub signature_policy: SignaturePolicy,
+    /// Whether to speak HTTP/2 without negotiation
+    pub http2_prior_knowledge: bool,
+    /// Optional timeout after which idle pooled connections are closed
+    pub pool_idle_timeout: Option<Duration>,
+    /// Optional interval of the TCP keepalive probes
+    pub tcp_keepalive: Option<Duration>,
+    /// Optional base URL of a store of previously harvested packages tried before the marketplace
+    pub source_store: Option<String>,
+    /// Token aborting the downloads of the run when cancelled
+    pub cancellation: CancellationToken,
+    /// Optional origin replacing the marketplace one, a fixture server in bench mode
+    pub marketplace_origin: Option<String>,
+    /// The file naming convention of the packages
+    pub naming: NamingPreset,
+    /// Where to record the provenance of the downloaded packages, None to skip it
+    pub provenance: Option<ProvenanceStorage>,
+    /// The registry the extensions are queried and downloaded from
+    pub registry: Arc<dyn Registry>,
+    /// Where the packages are written, the local file system by default
+    pub storage: Arc<dyn Storage>,
+    /// Optional progress bars of the batch, advanced as the packages are received
+    pub progress_bars: Option<DownloadBars>,
+    /// Whether the queries ask for the smallest payloads and no response is dumped
+    pub minimal_requests: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            destination: String::new(),
+            no_cache: false,
+            proxy: None,
+            proxy_credentials: None,
+            engine_version: None,
+            allow_pre_release: false,
+            pin_hashes: false,
+            hash: HashAlgorithm::default(),
+            polite: false,
+            retries: 0,
+            retry_delay: Duration::from_millis(RETRY_DELAY_MS),
+            on_no_compatible: NoCompatiblePolicy::default(),
+            prefer: VersionPreference::default(),
+            signature_policy: SignaturePolicy::default(),
+            http2_prior_knowledge: false,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
+            source_store: None,
+            cancellation: CancellationToken::new(),
+            marketplace_origin: None,
+            naming: NamingPreset::default(),
+            provenance: None,
+            registry: Arc::new(Marketplace),
+            storage: Arc::new(LocalStorage::default()),
+            progress_bars: None,
+            minimal_requests: false,
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// Get a marketplace URL, rebased on the origin of the fixture server in bench mode
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The marketplace URL
+    ///
+    /// # Returns
+    ///
+    /// The URL to request
+    pub fn marketplace_url(&self, url: &str) -> String {
+        match (
+            &self.marketplace_origin,
+            url.strip_prefix(MARKETPLACE_ORIGIN),
+        ) {
+            (Some(origin), Some(path)) => format!("{}{}", origin.trim_end_matches('/'), path),
+            _ => url.to_string(),
+        }
+    }
+}
+
+/// Where the packages are downloaded from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SourcePreference {
+    /// Always download from the marketplace
+    #[default]
+    Marketplace,
+    /// Download the pinned packages from the store when it holds the same content
+    Store,
+}
+
+/// Result of a successful extension download
+#[derive(Debug, Clone)]
+pub struct DownloadOutcome {
+    /// The extension identifier in the format "publisher.name"
+    pub id: String,
+    /// The downloaded version
+    pub version: String,
+    /// The target platform of the downloaded package
+    pub target_platform: Option<TargetPlatform>,
+    /// The path of the VSIX file
+    pub file_path: String,
+    /// The URL of the package
+    pub download_url: String,
+    /// The SHA-256 of the VSIX file, None if it was skipped without hashing
+    pub sha256: Option<String>,
+    /// The digest of the VSIX file with the `hash` algorithm of the options when it is
+    /// not SHA-256, None otherwise or if it was skipped without hashing
+    pub digest: Option<Digest>,
+}
+
+impl DownloadOutcome {
+    /// Get the digest of the VSIX file computed during the download
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The hash algorithm
+    ///
+    /// # Returns
+    ///
+    /// The digest, None if the file was not hashed with this algorithm
+    pub fn digest(&self, algorithm: HashAlgorithm) -> Option<Digest> {
+        match algorithm {
+            HashAlgorithm::Sha256 => self
+                .sha256
+                .as_deref()
+                .map(|sha256| Digest::new(algorithm, sha256)),
+            _ => self
+                .digest
+                .clone()
+                .filter(|digest| digest.algorithm == algorithm),
+        }
+    }
+}
+
+/// Create an HTTP client, optionally going through a proxy
 ///
 /// # Arguments
 ///
-/// * `extension` - The extension to downloads
-/// * `destination` - The directory where the extension will be saved
-/// * `no_cache` - Whether to force redownload even if the extension already exists
-/// * `proxy` - Optional proxy URL
-/// * `verbose` - Whether to print verbose output
-/// * `os_arch` - Optional target platform
-/// * `engine_version` - Optional, the engine to be compatible with
+/// * `options` - The download options (proxy and connection tuning)
 ///
 /// # Returns
 ///
-/// A Result indicating success or an error that occurred
-pub async fn download_extension(
+/// A Result containing the client or an error that occurred
+pub fn build_client(options: &DownloadOptions) -> Result<reqwest::Client> {
+    let mut client_builder = reqwest::Client::builder().gzip(true);
+    if let Some(tcp_keepalive) = options.tcp_keepalive {
+        client_builder = client_builder.tcp_keepalive(tcp_keepalive);
+    }
+    if let Some(pool_idle_timeout) = options.pool_idle_timeout {
+        client_builder = client_builder.pool_idle_timeout(pool_idle_timeout);
+    }
+    if options.http2_prior_knowledge {
+        client_builder = client_builder.http2_prior_knowledge();
+    }
+    let client = if let Some(proxy_url) = options.proxy.as_deref() {
+        info!("Using proxy: {}", proxy_url);
+        let mut proxy = reqwest::Proxy::all(proxy_url)?;
+        if let Some(reference) = options.proxy_credentials.as_deref() {
+            let credentials = resolve_credentials(reference)?;
+            proxy = proxy.basic_auth(&credentials.username, &credentials.password);
+        }
+        client_builder.proxy(proxy).build()?
+    } else {
+        client_builder.build()?
+    };
+    Ok(client)
+}
+
+/// Check if a response status is worth retrying in polite mode
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Get the delay requested by the Retry-After header of a response, in seconds only
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Check if a request failed with a transient network error (unreachable host,
+/// timeout or connection reset)
+fn is_transient_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() || error.is_timeout() {
+        return true;
+    }
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_error.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Compute the delay before a retry, doubling the base delay at each retry with up to 50% of jitter
+///
+/// # Arguments
+///
+/// * `base` - The delay before the first retry
+/// * `retry` - The number of retries already made
+///
+/// # Returns
+///
+/// The delay to wait
+pub fn backoff_delay(base: Duration, retry: u32) -> Duration {
+    let delay = base.saturating_mul(1 << retry.min(16));
+    // The sub-second part of the clock is random enough to spread the retries of concurrent tasks
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 2000.0)
+}
+
+/// Send a request to the marketplace
+///
+/// Throttled (429), failed (5xx) or unreachable requests and connection resets are
+/// retried `options.retries` times with an exponential backoff and jitter, honouring
+/// Retry-After. In polite mode every request is also delayed, and retried at least 3
+/// times.
+///
+/// # Arguments
+///
+/// * `request` - The request to send
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the response or an error that occurred
+pub async fn send_request(
+    request: reqwest::RequestBuilder,
+    options: &DownloadOptions,
+) -> Result<reqwest::Response> {
+    let (max_retries, base_delay) = if options.polite {
+        (
+            options.retries.max(POLITE_MAX_RETRIES),
+            options
+                .retry_delay
+                .max(Duration::from_millis(POLITE_RETRY_DELAY_MS)),
+        )
+    } else {
+        (options.retries, options.retry_delay)
+    };
+    if max_retries == 0 && !options.polite {
+        return Ok(request.send().await?);
+    }
+    let mut retries = 0;
+    loop {
+        if options.polite {
+            tokio::time::sleep(Duration::from_millis(POLITE_REQUEST_DELAY_MS)).await;
+        }
+        let Some(attempt) = request.try_clone() else {
+            return Ok(request.send().await?);
+        };
+        let requested_delay = match attempt.send().await {
+            Ok(resp) if retries < max_retries && is_retryable_status(resp.status()) => {
+                warn!("{} answered {}", resp.url(), resp.status());
+                retry_after(&resp)
+            }
+            Ok(resp) => return Ok(resp),
+            Err(e) if retries < max_retries && is_transient_error(&e) => {
+                warn!("Request failed: {}", e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let delay = requested_delay.unwrap_or_else(|| backoff_delay(base_delay, retries));
+        retries += 1;
+        warn!(
+            "Retrying in {}ms ({}/{})",
+            delay.as_millis(),
+            retries,
+            max_retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Compute the hex encoded SHA-256 of some content
+pub fn sha256_hex(content: &[u8]) -> String {
+    hex::encode(sha256(content))
+}
+
+/// An extension version resolved against the marketplace, ready to be downloaded
+#[derive(Debug, Clone)]
+pub struct ResolvedExtension {
+    /// The extension identifier in the format "publisher.name"
+    pub id: String,
+    /// The resolved version
+    pub version: String,
+    /// The target platform of the package
+    pub target_platform: Option<TargetPlatform>,
+    /// The URL of the VSIX package
+    pub download_url: String,
+    /// The path where the VSIX package is saved
+    pub file_path: String,
+    /// The marketplace metadata of the extension, None when resolved from a pinned version
+    pub metadata: Option<MarketplaceExtension>,
+    /// The SHA-256 required by the `overrideUrl` of the manifest entry, whose package
+    /// is downloaded from `download_url` instead of the registry
+    pub override_sha256: Option<String>,
+}
+
+impl ResolvedExtension {
+    /// Get when the resolved version was published on the marketplace
+    ///
+    /// # Returns
+    ///
+    /// The `lastUpdated` of the version for the target platform, None when resolved
+    /// from a pinned version
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        let versions: Vec<&Version> = self
+            .metadata
+            .as_ref()?
+            .versions
+            .iter()
+            .filter(|version| version.version == self.version)
+            .collect();
+        versions
+            .iter()
+            .find(|version| version.target_platform == self.target_platform)
+            .or(versions.first())
+            .and_then(|version| version.last_updated.parse().ok())
+    }
+}
+
+/// Resolves the version of a VSCode extension to download
+///
+/// # Arguments
+///
+/// * `extension` - The extension to resolve
+/// * `options` - The download options
+/// * `target_platform` - Optional target platform
+///
+/// # Returns
+///
+/// A Result containing the resolved extension or an error that occurred
+pub async fn resolve_extension(
     extension: Extension<'_>,
-    destination: &str,
-    no_cache: bool,
-    proxy: Option<&str>,
-    os_arch: Option<&str>,
-    engine_version: Option<&str>,
-    allow_pre_release: bool,
-) -> Result<()> {
-    info!("Progress in extension: {}", extension.to_id());
+    options: &DownloadOptions,
+    target_platform: Option<&TargetPlatform>,
+) -> Result<ResolvedExtension> {
+    resolve_constrained_extension(extension, options, target_platform, None).await
+}
+
+/// Resolves a VSCode extension to the latest version allowed by a version constraint
+///
+/// The version pinned in the extension, if any, is used as is.
+///
+/// # Arguments
+///
+/// * `extension` - The extension to resolve
+/// * `options` - The download options
+/// * `target_platform` - Optional target platform
+/// * `constraint` - Optional constraint relative to the locked version
+///
+/// # Returns
+///
+/// A Result containing the resolved extension or an error that occurred
+pub async fn resolve_constrained_extension(
+    extension: Extension<'_>,
+    options: &DownloadOptions,
+    target_platform: Option<&TargetPlatform>,
+    constraint: Option<&VersionConstraint>,
+) -> Result<ResolvedExtension> {
+    // A version pinned in the identifier is downloaded without querying the marketplace
+    if let Some(version) = extension.version {
+        return Ok(resolve_pinned_extension(
+            extension.clone(),
+            version,
+            options,
+            target_platform,
+        ));
+    }
+    info!("Resolving extension: {}", extension.to_id());
 
     // Get latest version
-    let version =
-        get_extension_version(extension.clone(), proxy, engine_version, allow_pre_release).await?;
+    let (metadata, version) =
+        query_constrained_extension_version(extension.clone(), options, constraint).await?;
     info!("Latest version of {}: {}", extension.to_id(), version);
 
-    let (download_url, file_path) =
-        build_download_url_and_file_path(extension.clone(), &version, destination, os_arch);
+    let (_, file_path) = build_download_url_and_file_path(
+        extension.clone(),
+        &version,
+        &options.destination,
+        target_platform,
+        options.naming,
+    );
+    let download_url = options.registry.package_url(
+        &extension,
+        &version,
+        target_platform,
+        Some(&metadata),
+        options,
+    );
 
+    Ok(ResolvedExtension {
+        id: extension.to_id(),
+        version,
+        target_platform: target_platform.cloned(),
+        download_url,
+        file_path,
+        metadata: Some(metadata),
+        override_sha256: None,
+    })
+}
+
+/// Resolves a VSCode extension to an already known version without querying the marketplace
+///
+/// # Arguments
+///
+/// * `extension` - The extension to resolve
+/// * `version` - The pinned version
+/// * `options` - The download options
+/// * `target_platform` - Optional target platform
+///
+/// # Returns
+///
+/// The resolved extension
+pub fn resolve_pinned_extension(
+    extension: Extension<'_>,
+    version: &str,
+    options: &DownloadOptions,
+    target_platform: Option<&TargetPlatform>,
+) -> ResolvedExtension {
+    info!("Using pinned version of {}: {}", extension.to_id(), version);
+    let (_, file_path) = build_download_url_and_file_path(
+        extension.clone(),
+        version,
+        &options.destination,
+        target_platform,
+        options.naming,
+    );
+    let download_url =
+        options
+            .registry
+            .package_url(&extension, version, target_platform, None, options);
+    ResolvedExtension {
+        id: extension.to_id(),
+        version: version.to_string(),
+        target_platform: target_platform.cloned(),
+        download_url,
+        file_path,
+        metadata: None,
+        override_sha256: None,
+    }
+}
+
+/// Get the size of the package of a resolved extension without downloading it
+///
+/// # Arguments
+///
+/// * `resolved` - The resolved extension
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the size in bytes, None if the server does not announce it
+pub async fn fetch_download_size(
+    resolved: &ResolvedExtension,
+    options: &DownloadOptions,
+) -> Result<Option<u64>> {
+    let client = build_client(options)?;
+    let resp = send_request(
+        client
+            .head(&resolved.download_url)
+            .header("User-Agent", USER_AGENT),
+        options,
+    )
+    .await?;
+    if !resp.status().is_success() {
+        return Err(VsixHarvesterError::DownloadError(format!(
+            "size of {} ({})",
+            resolved.id,
+            resp.status()
+        )));
+    }
+    Ok(resp
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse().ok()))
+}
+
+/// Downloads a VSCode extension by its identifier
+///
+/// # Arguments
+///
+/// * `extension` - The extension to downloads, its pinned version instead of the latest one if set
+/// * `options` - The download options
+/// * `target_platform` - Optional target platform
+/// * `pins` - Optional lockfile with the pinned hashes, a download whose hash differs is refused
+///
+/// # Returns
+///
+/// A Result containing the download outcome or an error that occurred
+pub async fn download_extension(
+    extension: Extension<'_>,
+    options: &DownloadOptions,
+    target_platform: Option<&TargetPlatform>,
+    pins: Option<&Lockfile>,
+) -> Result<DownloadOutcome> {
+    let resolved = resolve_extension(extension, options, target_platform).await?;
+    download_resolved_extension(&resolved, options, pins).await
+}
+
+/// Downloads an already resolved VSCode extension
+///
+/// # Arguments
+///
+/// * `resolved` - The resolved extension to download
+/// * `options` - The download options
+/// * `pins` - Optional lockfile with the pinned hashes, a download whose hash differs is refused
+///
+/// # Returns
+///
+/// A Result containing the download outcome or an error that occurred
+pub async fn download_resolved_extension(
+    resolved: &ResolvedExtension,
+    options: &DownloadOptions,
+    pins: Option<&Lockfile>,
+) -> Result<DownloadOutcome> {
+    info!("Progress in extension: {}", resolved.id);
+    let download_url = &resolved.download_url;
+    let file_path = &resolved.file_path;
+    let version = &resolved.version;
+    let target_platform = resolved.target_platform.as_ref();
     debug!("Download URL: {}", download_url);
 
-    // Make file path
+    let mut outcome = DownloadOutcome {
+        id: resolved.id.clone(),
+        version: version.clone(),
+        target_platform: resolved.target_platform.clone(),
+        file_path: file_path.clone(),
+        download_url: download_url.clone(),
+        sha256: None,
+        digest: None,
+    };
 
     // Check file already exists
-    if !no_cache && Path::new(&file_path).exists() {
+    if !options.no_cache && options.storage.exists(file_path).await? {
         info!(
             "Skip download: File is already exists. File Name {}.",
             file_path
         );
-        return Ok(());
+        if options.pin_hashes {
+            let content = options.storage.read(file_path).await?;
+            let (sha256, digest) = PackageHasher::hash(options.hash, &content);
+            outcome.sha256 = Some(sha256);
+            outcome.digest = digest;
+        }
+        return Ok(outcome);
     }
 
     // Create http client
-    let client_builder = reqwest::Client::builder();
-    let client = if let Some(proxy_url) = proxy {
-        info!("Using proxy: {}", proxy_url);
-        let proxy = reqwest::Proxy::all(proxy_url)?;
-        client_builder.gzip(true).proxy(proxy).build()?
-    } else {
-        client_builder.gzip(true).build()?
-    };
+    let client = build_client(options)?;
 
-    // Download VSIX file
+    // Pull the pinned content through the store when it holds it
+    let pinned = pins.and_then(|pins| pins.find(&outcome.id, version, target_platform));
+    if let (Some(store), Some(pinned)) = (&options.source_store, pinned) {
+        if let Some(downloaded) =
+            fetch_from_store(&client, store, file_path, &pinned.sha256, options).await
+        {
+            save_package(&downloaded.content, file_path, options).await?;
+            info!(
+                "Saved in {} from the store",
+                options.storage.location(file_path)
+            );
+            if let (Some(storage), Some(local_path)) =
+                (options.provenance, options.storage.local_path(file_path))
+            {
+                let provenance = Provenance {
+                    published_at: resolved.published_at(),
+                    ..Provenance::new(
+                        &build_store_url(store, file_path),
+                        &pinned.sha256,
+                        Verification::Pinned,
+                    )
+                };
+                record_provenance(&local_path.to_string_lossy(), &provenance, storage)?;
+            }
+            outcome.sha256 = Some(downloaded.sha256);
+            outcome.digest = downloaded.digest;
+            return Ok(outcome);
+        }
+    }
+
+    // Download VSIX file, unless the run is cancelled meanwhile
+    // Overridden packages come from their own URL instead of the registry
     info!("Download form {}", download_url);
-    let resp = client
-        .get(&download_url)
-        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
-        .send()
+    let DownloadedPackage {
+        content,
+        sha256,
+        digest,
+    } = tokio::select! {
+        _ = options.cancellation.cancelled() => {
+            // Only the network failures keep the partial file, to be resumed
+            PartialPackage::new(file_path, options).discard();
+            return Err(VsixHarvesterError::Cancelled);
+        }
+        downloaded = async {
+            match resolved.override_sha256 {
+                Some(_) => download_package(&client, resolved, options).await,
+                None => options.registry.download(&client, resolved, options).await,
+            }
+        } => downloaded?,
+    };
+
+    // Quarantine content that differs from the pinned or overridden hash of the same version
+    // or is not a valid package
+    let expected_sha256 = resolved
+        .override_sha256
+        .as_ref()
+        .or(pinned.map(|pinned| &pinned.sha256));
+    let failure = match expected_sha256 {
+        Some(expected) if *expected != sha256 => Some(VsixHarvesterError::HashMismatch(
+            format!("{}@{}", outcome.id, version),
+            expected.clone(),
+            sha256.clone(),
+        )),
+        _ => content.reader().and_then(validate_vsix_reader).err(),
+    };
+    let mut digests_matched = false;
+    let failure = match failure {
+        Some(failure) => Some(failure),
+        // The signature of the registry does not cover a patched build
+        None if resolved.override_sha256.is_some() => None,
+        None => match verify_package_signature(&client, resolved, &content, options).await {
+            Ok(verified) => {
+                digests_matched = verified;
+                None
+            }
+            Err(e @ VsixHarvesterError::SignatureMismatch(..)) => Some(e),
+            Err(e) => return Err(e),
+        },
+    };
+    if let Some(failure) = failure {
+        error!("Refusing {} {}: {}", outcome.id, version, failure);
+        let file_name = package_file_name(file_path);
+        let quarantined_path = match &content {
+            PackageContent::Memory(content) => quarantine_package(
+                &options.destination,
+                &file_name,
+                content,
+                &failure.to_string(),
+            )?,
+            PackageContent::File(path) => quarantine_package_file(
+                &options.destination,
+                &file_name,
+                path,
+                &sha256,
+                &failure.to_string(),
+            )?,
+        };
+        return Err(VsixHarvesterError::Quarantined(
+            quarantined_path,
+            failure.to_string(),
+        ));
+    }
+
+    save_package(&content, file_path, options).await?;
+    info!("Saved in {}", options.storage.location(file_path));
+    if let (Some(storage), Some(local_path)) =
+        (options.provenance, options.storage.local_path(file_path))
+    {
+        let verification = if expected_sha256.is_some() {
+            Verification::Pinned
+        } else if digests_matched {
+            Verification::DigestsMatched
+        } else {
+            Verification::Validated
+        };
+        let provenance = Provenance {
+            published_at: resolved.published_at(),
+            ..Provenance::new(download_url, &sha256, verification)
+        };
+        record_provenance(&local_path.to_string_lossy(), &provenance, storage)?;
+    }
+
+    outcome.sha256 = Some(sha256);
+    outcome.digest = digest;
+    Ok(outcome)
+}
+
+/// Save a checked package, the partial file of a local download is renamed to the package
+///
+/// # Arguments
+///
+/// * `content` - The content of the package
+/// * `file_path` - The path of the package
+/// * `options` - The download options, with the storage of the package
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+async fn save_package(
+    content: &PackageContent,
+    file_path: &str,
+    options: &DownloadOptions,
+) -> Result<()> {
+    match content {
+        PackageContent::Memory(content) => options.storage.write(file_path, content).await,
+        PackageContent::File(path) => Ok(fs::rename(
+            path,
+            options
+                .storage
+                .local_path(file_path)
+                .unwrap_or_else(|| file_path.into()),
+        )?),
+    }
+}
+
+/// Download a package from its resolved URL, streamed into its partial file when it is saved locally
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client
+/// * `resolved` - The resolved extension
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// A Result containing the downloaded package or an error that occurred
+pub async fn download_package(
+    client: &reqwest::Client,
+    resolved: &ResolvedExtension,
+    options: &DownloadOptions,
+) -> Result<DownloadedPackage> {
+    let mut partial = PartialPackage::new(&resolved.file_path, options);
+    let resp = partial
+        .request(client, &resolved.download_url, options)
         .await?;
     if !resp.status().is_success() {
-        error!("Fail download of {}", extension.to_id());
-        return Err(VsixHarvesterError::DownloadError(extension.to_id()));
+        error!("Fail download of {}", resolved.id);
+        return Err(VsixHarvesterError::DownloadError(resolved.id.clone()));
     }
+    partial.receive(resp).await
+}
 
-    let vsix_raw_content = resp.bytes().await?;
+/// Send the request of a package download
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client
+/// * `url` - The URL of the package
+/// * `options` - The download options (polite mode)
+///
+/// # Returns
+///
+/// A Result containing the response, whatever its status, or an error that occurred
+pub async fn request_package(
+    client: &reqwest::Client,
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<reqwest::Response> {
+    send_request(
+        client
+            .get(url)
+            .header(reqwest::header::ACCEPT_ENCODING, "gzip"),
+        options,
+    )
+    .await
+}
+
+/// The partial file of a package download, resumed with HTTP range requests
+///
+/// When the package is saved on the local file system, the package is received in its
+/// partial file of the temp workspace, renamed to the package once checked. The bytes
+/// received before a download fails are kept in the partial file, and the next
+/// download only requests the missing bytes.
+#[derive(Debug, Clone, Default)]
+pub struct PartialPackage {
+    /// The path of the partial file, None for remote storages
+    path: Option<PathBuf>,
+    /// The number of bytes of the partial file the download continues
+    offset: u64,
+    /// The file name of the package, shown by the progress bars
+    name: String,
+    /// Optional progress bars of the batch
+    bars: Option<DownloadBars>,
+    /// The algorithm of the digest computed next to the SHA-256
+    algorithm: HashAlgorithm,
+}
+
+impl PartialPackage {
+    /// Find the partial file of a package
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - The path where the package is saved
+    /// * `options` - The download options, with the storage of the package
+    ///
+    /// # Returns
+    ///
+    /// The partial file, empty if no previous download was interrupted
+    pub fn new(file_path: &str, options: &DownloadOptions) -> Self {
+        let path = options
+            .storage
+            .local_path(file_path)
+            .map(|path| partial_path(&path));
+        let offset = path
+            .as_ref()
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len());
+        Self {
+            path,
+            offset,
+            name: package_file_name(file_path),
+            bars: options.progress_bars.clone(),
+            algorithm: options.hash,
+        }
+    }
+
+    /// Send the request of the package, only for the missing bytes when a partial file exists
+    ///
+    /// The requests of resumable downloads ask for the identity encoding, so the byte
+    /// ranges match the saved bytes. The partial file is dropped when the server does
+    /// not honour the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The HTTP client
+    /// * `url` - The URL of the package
+    /// * `options` - The download options (polite mode)
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the response, whatever its status, or an error that occurred
+    pub async fn request(
+        &mut self,
+        client: &reqwest::Client,
+        url: &str,
+        options: &DownloadOptions,
+    ) -> Result<reqwest::Response> {
+        let Some(path) = &self.path else {
+            return request_package(client, url, options).await;
+        };
+        let request = || {
+            client
+                .get(url)
+                .header(reqwest::header::ACCEPT_ENCODING, "identity")
+        };
+        if self.offset == 0 {
+            return send_request(request(), options).await;
+        }
+        let resp = send_request(
+            request().header(reqwest::header::RANGE, format!("bytes={}-", self.offset)),
+            options,
+        )
+        .await?;
+        let resumed = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT
+            && resp
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .is_some_and(|range| range.starts_with(&format!("bytes {}-", self.offset)));
+        if resumed {
+            info!("Resume download from byte {} of {}", self.offset, url);
+            return Ok(resp);
+        }
+        debug!("Range of {} not honoured ({})", url, resp.status());
+        self.offset = 0;
+        let _ = fs::remove_file(path);
+        if resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return send_request(request(), options).await;
+        }
+        Ok(resp)
+    }
+
+    /// Remove the partial file, when the download is abandoned instead of interrupted
+    pub fn discard(&self) {
+        if let Some(path) = self.path.as_ref().filter(|path| path.exists()) {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Receive the body of the package after the bytes of the partial file
+    ///
+    /// The body is streamed chunk by chunk into the partial file and hashed on the
+    /// way, so the package is never held in memory. A failure leaves the received
+    /// bytes in the partial file for the next download.
+    ///
+    /// # Arguments
+    ///
+    /// * `resp` - The successful response of `request`
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the downloaded package or an error that occurred
+    pub async fn receive(&self, mut resp: reqwest::Response) -> Result<DownloadedPackage> {
+        let mut hasher = PackageHasher::new(self.algorithm);
+        let bar = match &self.bars {
+            Some(bars) => {
+                let size = resp.content_length().map(|length| length + self.offset);
+                bars.package(&self.name, size, self.offset)
+            }
+            None => PackageBar::default(),
+        };
+        let Some(path) = &self.path else {
+            let mut content = Vec::new();
+            while let Some(chunk) = resp.chunk().await? {
+                hasher.update(&chunk);
+                content.extend_from_slice(&chunk);
+                bar.inc(chunk.len() as u64);
+            }
+            let (sha256, digest) = hasher.finalize();
+            return Ok(DownloadedPackage {
+                content: PackageContent::Memory(content),
+                sha256,
+                digest,
+            });
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = if self.offset > 0 {
+            let mut file = fs::OpenOptions::new().read(true).append(true).open(path)?;
+            io::copy(&mut (&mut file).take(self.offset), &mut hasher)?;
+            file
+        } else {
+            fs::File::create(path)?
+        };
+        let mut received = self.offset;
+        loop {
+            match resp.chunk().await {
+                Ok(Some(chunk)) => {
+                    hasher.update(&chunk);
+                    file.write_all(&chunk)?;
+                    received += chunk.len() as u64;
+                    bar.inc(chunk.len() as u64);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(
+                        "Download interrupted, {} bytes kept in {}",
+                        received,
+                        path.display()
+                    );
+                    return Err(e.into());
+                }
+            }
+        }
+        file.sync_all()?;
+        let (sha256, digest) = hasher.finalize();
+        Ok(DownloadedPackage {
+            content: PackageContent::File(path.clone()),
+            sha256,
+            digest,
+        })
+    }
+}
+
+/// The content of a downloaded package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageContent {
+    /// The content in memory, for packages saved on remote storages
+    Memory(Vec<u8>),
+    /// The complete partial file of the package in the temp workspace, renamed to the
+    /// package once checked
+    File(PathBuf),
+}
+
+/// A reader of a package, from memory or from a file
+pub trait PackageReader: Read + Seek {}
+
+impl<T: Read + Seek> PackageReader for T {}
+
+impl PackageContent {
+    /// Open a reader of the content
+    pub fn reader(&self) -> Result<Box<dyn PackageReader + '_>> {
+        Ok(match self {
+            PackageContent::Memory(content) => Box::new(Cursor::new(content.as_slice())),
+            PackageContent::File(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+        })
+    }
+}
+
+/// A downloaded package with its hashes, computed while it was received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedPackage {
+    pub content: PackageContent,
+    pub sha256: String,
+    /// The digest with the `hash` algorithm of the options when it is not SHA-256
+    pub digest: Option<Digest>,
+}
+
+/// Save a package through a temporary file of the temp workspace renamed once complete
+///
+/// An interrupted write never leaves a truncated package under the final name,
+/// the temporary file is removed when the write fails or is cancelled.
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the package
+/// * `content` - The content of the package
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_package(file_path: &str, content: &[u8]) -> Result<()> {
+    write_atomic(Path::new(file_path), content)
+}
 
-    // Save file
-    fs::write(&file_path, &vsix_raw_content)?;
-    info!("Saved in {}", file_path);
+/// Build the URL of a package in a store of previously harvested packages
+///
+/// # Arguments
+///
+/// * `store` - The base URL of the store (e.g., `https://s3.example.com/mirror/extensions`)
+/// * `file_path` - The path where the package is saved, its file name is the key in the store
+///
+/// # Returns
+///
+/// The URL of the package
+pub fn build_store_url(store: &str, file_path: &str) -> String {
+    format!(
+        "{}/{}",
+        store.trim_end_matches('/'),
+        package_file_name(file_path)
+    )
+}
 
-    Ok(())
+/// Download a package from the store if it holds the expected content
+///
+/// Any failure falls back to the marketplace, the store is only an accelerator. The
+/// package is streamed into its partial file like a marketplace download, a content
+/// differing from the pinned hash is dropped.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client
+/// * `store` - The base URL of the store
+/// * `file_path` - The path where the package is saved
+/// * `expected_sha256` - The pinned SHA-256 of the package
+/// * `options` - The download options
+///
+/// # Returns
+///
+/// The package, None if the store does not hold the expected content
+async fn fetch_from_store(
+    client: &reqwest::Client,
+    store: &str,
+    file_path: &str,
+    expected_sha256: &str,
+    options: &DownloadOptions,
+) -> Option<DownloadedPackage> {
+    let store_url = build_store_url(store, file_path);
+    info!("Download from store {}", store_url);
+    let mut partial = PartialPackage::new(file_path, options);
+    let resp = match partial.request(client, &store_url, options).await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            debug!("Store miss for {} ({})", store_url, resp.status());
+            return None;
+        }
+        Err(e) => {
+            warn!("Store unavailable for {}: {}", store_url, e);
+            return None;
+        }
+    };
+    let downloaded = match partial.receive(resp).await {
+        Ok(downloaded) => downloaded,
+        Err(e) => {
+            warn!("Failed to read {} from the store: {}", store_url, e);
+            return None;
+        }
+    };
+    let problem = if downloaded.sha256 != expected_sha256 {
+        Some("differs from the pinned hash".to_string())
+    } else {
+        downloaded
+            .content
+            .reader()
+            .and_then(validate_vsix_reader)
+            .err()
+            .map(|e| format!("is invalid: {}", e))
+    };
+    let Some(problem) = problem else {
+        return Some(downloaded);
+    };
+    warn!(
+        "{} in the store {}, using the marketplace",
+        store_url, problem
+    );
+    if let PackageContent::File(path) = &downloaded.content {
+        let _ = fs::remove_file(path);
+    }
+    None
 }
 
 /// Get the latest version of a VSCode extension
@@ -173,23 +1329,436 @@ pub async fn download_extension(
 /// # Returns
 ///
 /// A Result containing the version or an error that occurreds
+#[allow(dead_code)]
 pub async fn get_extension_version(
     extension: Extension<'_>,
     proxy: Option<&str>,
     engine_version: Option<&str>,
     allow_pre_release: bool,
 ) -> std::result::Result<String, VsixHarvesterError> {
-    let api_url = API_URL;
+    let options = DownloadOptions {
+        proxy: proxy.map(str::to_string),
+        engine_version: engine_version.map(str::to_string),
+        allow_pre_release,
+        ..Default::default()
+    };
+    query_extension_version(extension, &options)
+        .await
+        .map(|(_, version)| version)
+}
+
+/// Query the marketplace metadata of a VSCode extension and select the version to download
+///
+/// # Arguments
+///
+/// * `extension` - The extension to query
+/// * `options` - The download options (proxy, engine version, pre-release, polite mode)
+///
+/// # Returns
+///
+/// A Result containing the marketplace metadata and the selected version or an error that occurred
+pub async fn query_extension_version(
+    extension: Extension<'_>,
+    options: &DownloadOptions,
+) -> Result<(MarketplaceExtension, String)> {
+    query_constrained_extension_version(extension, options, None).await
+}
+
+/// Query the marketplace metadata of a VSCode extension and select the latest version
+/// allowed by a version constraint
+///
+/// # Arguments
+///
+/// * `extension` - The extension to query
+/// * `options` - The download options (proxy, engine version, pre-release, polite mode)
+/// * `constraint` - Optional constraint relative to the locked version
+///
+/// # Returns
+///
+/// A Result containing the marketplace metadata and the selected version or an error that occurred
+pub async fn query_constrained_extension_version(
+    extension: Extension<'_>,
+    options: &DownloadOptions,
+    constraint: Option<&VersionConstraint>,
+) -> Result<(MarketplaceExtension, String)> {
+    let engine_version = options.engine_version.as_deref();
+    let allow_pre_release = options.allow_pre_release;
 
-    let (flags, str_engine_version) = if engine_version.is_some() {
-        (Flags::all_versions().bits(), engine_version.unwrap())
+    // Every version is needed to filter them by engine or by constraint
+    let marketplace_extension = query_marketplace_extension(
+        &extension,
+        options,
+        engine_version.is_some() || constraint.is_some(),
+    )
+    .await?;
+    let str_engine_version = engine_version.unwrap_or_default();
+
+    // Keep the versions allowed by the update policy of the locked version, before the
+    // engine filter and its fallback to the latest version
+    let mut allowed: Vec<&Version> = marketplace_extension.versions.iter().collect();
+    if let Some(constraint) = constraint {
+        allowed.retain(|version| {
+            (allow_pre_release || !version.is_prerelease()) && constraint.allows(&version.version)
+        });
+        if allowed.is_empty() {
+            error!(
+                "No version of {} is allowed by the {:?} update policy of {}",
+                extension.to_id(),
+                constraint.policy,
+                constraint.locked_version
+            );
+            return Err(VsixHarvesterError::ApiError(format!(
+                "no version of {} allowed from {}",
+                extension.to_id(),
+                constraint.locked_version
+            )));
+        }
+    }
+    let Some(latest) = allowed.first().copied() else {
+        return Err(VsixHarvesterError::ApiError(format!(
+            "{} has no published version",
+            extension.to_id()
+        )));
+    };
+
+    let mut versions = if engine_version.is_some() {
+        allowed
+            .iter()
+            .copied()
+            .filter(|version| version.is_compatible_with(str_engine_version, allow_pre_release))
+            .collect()
     } else {
-        (Flags::standard().bits(), "")
+        allowed
+    };
+    options.prefer.sort(&mut versions);
+
+    let version = if engine_version.is_some() && !versions.is_empty() {
+        // Debug the versions
+        debug!(
+            "Got {} version compatible with engine {}",
+            versions.len(),
+            str_engine_version
+        );
+        for current_version in versions.iter() {
+            debug!(
+                " - Version: {} Engine: {} PreRelease: {}",
+                current_version.version,
+                current_version
+                    .get_vscode_engine_version()
+                    .unwrap_or("None".to_string()),
+                current_version
+                    .get_vscode_prerelease()
+                    .unwrap_or("false".to_string())
+            );
+        }
+
+        versions[0].version.clone()
+    } else if let Some(engine_version) = engine_version {
+        let requirement = latest
+            .get_vscode_engine_version()
+            .unwrap_or("unknown".to_string());
+        if options.on_no_compatible != NoCompatiblePolicy::Latest {
+            return Err(VsixHarvesterError::NoCompatibleVersion(
+                extension.to_id(),
+                engine_version.to_string(),
+                requirement,
+            ));
+        }
+        warn!(
+            "No version of {} is compatible with engine {}, using latest {} (requires {})",
+            extension.to_id(),
+            engine_version,
+            latest.version,
+            requirement
+        );
+        latest.version.clone()
+    } else {
+        debug!("No engine version, using latest");
+        versions.first().unwrap_or(&latest).version.clone()
+    };
+
+    Ok((marketplace_extension, version))
+}
+
+/// Query the marketplace metadata of a VSCode extension on the registry of the options
+///
+/// # Arguments
+///
+/// * `extension` - The extension to query
+/// * `options` - The download options (registry, proxy, polite mode)
+/// * `all_versions` - Whether to get every version instead of the latest one
+///
+/// # Returns
+///
+/// A Result containing the marketplace metadata or an error that occurred
+pub async fn query_marketplace_extension(
+    extension: &Extension<'_>,
+    options: &DownloadOptions,
+    all_versions: bool,
+) -> Result<MarketplaceExtension> {
+    options
+        .registry
+        .query_extension(extension, options, all_versions)
+        .await
+}
+
+/// The Visual Studio Marketplace
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Marketplace;
+
+impl Registry for Marketplace {
+    fn name(&self) -> &str {
+        "marketplace"
+    }
+
+    fn target_platforms(&self) -> Option<&'static [&'static str]> {
+        Some(&MARKETPLACE_TARGET_PLATFORMS)
+    }
+
+    fn query_extension<'a>(
+        &'a self,
+        extension: &'a Extension<'_>,
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+        Box::pin(query_gallery_extension(extension, options, all_versions))
+    }
+
+    fn query_extensions<'a>(
+        &'a self,
+        ids: &'a [String],
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<HashMap<String, MarketplaceExtension>>> {
+        Box::pin(query_gallery_window(ids, options, all_versions))
+    }
+
+    fn last_updated<'a>(
+        &'a self,
+        ids: &'a [String],
+        options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<HashMap<String, String>>> {
+        Box::pin(query_last_updated(ids, options))
+    }
+
+    fn package_url(
+        &self,
+        extension: &Extension<'_>,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+        _metadata: Option<&MarketplaceExtension>,
+        options: &DownloadOptions,
+    ) -> String {
+        let (download_url, _) = build_download_url_and_file_path(
+            extension.clone(),
+            version,
+            &options.destination,
+            target_platform,
+            options.naming,
+        );
+        options.marketplace_url(&download_url)
+    }
+
+    fn signature_url(
+        &self,
+        resolved: &ResolvedExtension,
+        options: &DownloadOptions,
+    ) -> Option<String> {
+        let extension = Extension::from_id(&resolved.id).ok()?;
+        let mut url = format!(
+            "{}/{}/extension/{}/{}/assetbyname/Microsoft.VisualStudio.Services.VsixSignature",
+            ASSET_URL, extension.publisher, extension.name, resolved.version
+        );
+        if let Some(target_platform) = &resolved.target_platform {
+            url.push_str(&format!("?targetPlatform={}", target_platform));
+        }
+        Some(options.marketplace_url(&url))
+    }
+}
+
+/// Query the metadata of a VSCode extension on the Marketplace gallery API
+async fn query_gallery_extension(
+    extension: &Extension<'_>,
+    options: &DownloadOptions,
+    all_versions: bool,
+) -> Result<MarketplaceExtension> {
+    // Filter type 7 is the extension name
+    query_gallery(7, &extension.to_id(), options, all_versions).await
+}
+
+/// Resolve a Marketplace extension GUID (`extensionId`) to its `publisher.name` identifier
+///
+/// # Arguments
+///
+/// * `guid` - The extension GUID (e.g., "d6f6cfea-4b6f-41f4-b571-6ad2ab7918da")
+/// * `options` - The download options (proxy, marketplace URL)
+///
+/// # Returns
+///
+/// A Result containing the identifier of the extension (e.g., "golang.Go") or an error
+/// if no extension has this GUID
+pub async fn find_extension_by_guid(guid: &str, options: &DownloadOptions) -> Result<String> {
+    // Filter type 4 is the extension GUID
+    let marketplace_extension = query_gallery(4, guid, options, false).await?;
+    let id = format!(
+        "{}.{}",
+        marketplace_extension.publisher.publisher_name, marketplace_extension.extension_name
+    );
+    info!("Extension {} is {}", guid, id);
+    Ok(id)
+}
+
+/// Query the metadata and versions of several extensions in a single gallery query
+///
+/// # Arguments
+///
+/// * `ids` - The extension identifiers in the format "publisher.name"
+/// * `options` - The download options (proxy, marketplace URL)
+/// * `all_versions` - Whether to get every version instead of the latest one
+///
+/// # Returns
+///
+/// A Result containing the metadata keyed by lowercase identifier, the extensions
+/// not found in the Marketplace are missing
+pub async fn query_gallery_window(
+    ids: &[String],
+    options: &DownloadOptions,
+    all_versions: bool,
+) -> Result<HashMap<String, MarketplaceExtension>> {
+    let flags = if options.minimal_requests {
+        Flags::minimal(all_versions)
+    } else if all_versions {
+        Flags::all_versions()
+    } else {
+        Flags::standard()
+    };
+    // Filter type 8 is the target, an extension matches any of the filter type 7 names
+    let mut criteria = vec![json!({"filterType": 8, "value": "Microsoft.VisualStudio.Code"})];
+    criteria.extend(ids.iter().map(|id| json!({"filterType": 7, "value": id})));
+    let payload = json!({
+        "filters": [{
+            "criteria": criteria,
+            "pageNumber": 1,
+            "pageSize": ids.len()
+        }],
+        "flags": flags.bits()
+    });
+    let client = build_client(options)?;
+    let resp = send_request(
+        client
+            .post(options.marketplace_url(API_URL))
+            .header("Content-Type", "application/json")
+            .header(
+                "Accept",
+                format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
+            )
+            .header("User-Agent", USER_AGENT)
+            .json(&payload),
+        options,
+    )
+    .await?;
+    if !resp.status().is_success() {
+        return Err(VsixHarvesterError::ApiError(format!(
+            "batch query failed ({})",
+            resp.status()
+        )));
+    }
+    let response: MarketplaceResponse = resp.json().await?;
+    Ok(response
+        .results
+        .into_iter()
+        .flat_map(|result| result.extensions)
+        .filter(|extension| !extension.versions.is_empty())
+        .map(|extension| (extension.get_identifier().to_lowercase(), extension))
+        .collect())
+}
+
+/// Query the `lastUpdated` timestamp of extensions in batches, without their versions
+///
+/// # Arguments
+///
+/// * `ids` - The extension identifiers in the format "publisher.name"
+/// * `options` - The download options (proxy, marketplace URL)
+///
+/// # Returns
+///
+/// A Result containing the timestamps keyed by lowercase identifier, the extensions
+/// not found in the Marketplace are missing
+pub async fn query_last_updated(
+    ids: &[String],
+    options: &DownloadOptions,
+) -> Result<HashMap<String, String>> {
+    let api_url = options.marketplace_url(API_URL);
+    let client = build_client(options)?;
+    let mut last_updated = HashMap::new();
+    for batch in ids.chunks(LAST_UPDATED_BATCH_SIZE) {
+        // Filter type 8 is the target, an extension matches any of the filter type 7 names
+        let mut criteria = vec![json!({"filterType": 8, "value": "Microsoft.VisualStudio.Code"})];
+        criteria.extend(batch.iter().map(|id| json!({"filterType": 7, "value": id})));
+        let payload = json!({
+            "filters": [{
+                "criteria": criteria,
+                "pageNumber": 1,
+                "pageSize": batch.len()
+            }],
+            "flags": Flags::NONE.bits()
+        });
+        let resp = send_request(
+            client
+                .post(&api_url)
+                .header("Content-Type", "application/json")
+                .header(
+                    "Accept",
+                    format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
+                )
+                .header("User-Agent", USER_AGENT)
+                .json(&payload),
+            options,
+        )
+        .await?;
+        if !resp.status().is_success() {
+            return Err(VsixHarvesterError::ApiError(format!(
+                "lastUpdated query failed ({})",
+                resp.status()
+            )));
+        }
+        let body: serde_json::Value = resp.json().await?;
+        let extensions = body["results"][0]["extensions"].as_array();
+        for extension in extensions.into_iter().flatten() {
+            if let (Some(publisher), Some(name), Some(updated)) = (
+                extension["publisher"]["publisherName"].as_str(),
+                extension["extensionName"].as_str(),
+                extension["lastUpdated"].as_str(),
+            ) {
+                last_updated.insert(
+                    format!("{}.{}", publisher, name).to_lowercase(),
+                    updated.to_string(),
+                );
+            }
+        }
+    }
+    Ok(last_updated)
+}
+
+/// Query the gallery API for the first extension matching a criterion
+async fn query_gallery(
+    filter_type: u32,
+    value: &str,
+    options: &DownloadOptions,
+    all_versions: bool,
+) -> Result<MarketplaceExtension> {
+    let api_url = options.marketplace_url(API_URL);
+    let flags = if options.minimal_requests {
+        Flags::minimal(all_versions).bits()
+    } else if all_versions {
+        Flags::all_versions().bits()
+    } else {
+        Flags::standard().bits()
     };
     let payload = json!({
         "filters": [{
             "criteria": [
-                {"filterType": 7, "value": format!("{}.{}", extension.publisher, extension.name)}
+                {"filterType": filter_type, "value": value}
             ]
         }],
         "flags": flags
@@ -197,31 +1766,23 @@ pub async fn get_extension_version(
     debug!("Using search payload: {}", payload);
 
     // Create http client
-    let client_builder = reqwest::Client::builder();
-    let client = if let Some(proxy_url) = proxy {
-        info!("Using proxy for API request: {}", proxy_url);
-        let proxy = reqwest::Proxy::all(proxy_url)?;
-        client_builder.proxy(proxy).build()?
-    } else {
-        client_builder.build()?
-    };
+    let client = build_client(options)?;
 
     // Send POST request
-    debug!(
-        "Sending query for Marketplace API: {}.{}",
-        extension.publisher, extension.name
-    );
-    let resp = client
-        .post(api_url)
-        .header("Content-Type", "application/json")
-        .header(
-            "Accept",
-            format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
-        )
-        .header("User-Agent", USER_AGENT)
-        .json(&payload)
-        .send()
-        .await?;
+    debug!("Sending query for Marketplace API: {}", value);
+    let resp = send_request(
+        client
+            .post(&api_url)
+            .header("Content-Type", "application/json")
+            .header(
+                "Accept",
+                format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
+            )
+            .header("User-Agent", USER_AGENT)
+            .json(&payload),
+        options,
+    )
+    .await?;
     if !resp.status().is_success() {
         error!("Failed query for Marketplace API");
         return Err(VsixHarvesterError::ApiError(
@@ -233,11 +1794,14 @@ pub async fn get_extension_version(
 
     let resp_json_result: std::result::Result<MarketplaceResponse, serde_json::Error> =
         serde_json::from_str(json_body.as_str());
-    // If RUST_LOG is set to debug save the JSON response to a temporary file and display the path
-    if std::env::var("RUST_LOG").is_ok_and(|v| v == "debug") {
-        let temp_file_path = format!("./vsix_harvester_{}.json", extension.to_id());
+    // If RUST_LOG is set to debug save the JSON response in the temp workspace and display the path,
+    // the dumps are kept until `clean` removes them
+    if !options.minimal_requests && std::env::var("RUST_LOG").is_ok_and(|v| v == "debug") {
+        let workspace = temp_workspace(Path::new(&options.destination));
+        fs::create_dir_all(&workspace)?;
+        let temp_file_path = workspace.join(format!("vsix_harvester_{}.json", value));
         fs::write(&temp_file_path, &json_body)?;
-        debug!("Saved JSON response to {}", temp_file_path);
+        debug!("Saved JSON response to {}", temp_file_path.display());
     }
     if resp_json_result.is_err() {
         error!("Failed to parse JSON response");
@@ -247,47 +1811,25 @@ pub async fn get_extension_version(
         ));
     }
     let resp_json = resp_json_result.unwrap();
+    let Some(marketplace_extension) = resp_json
+        .results
+        .into_iter()
+        .next()
+        .and_then(|result| result.extensions.into_iter().next())
+        .filter(|marketplace_extension| !marketplace_extension.versions.is_empty())
+    else {
+        error!("{} was not found in the Marketplace", value);
+        return Err(VsixHarvesterError::ApiError(format!(
+            "{} was not found",
+            value
+        )));
+    };
     debug!(
         "Got {} version results",
-        resp_json.results[0].extensions[0].versions.len()
+        marketplace_extension.versions.len()
     );
 
-    let versions = if engine_version.is_some() {
-        resp_json.results[0].extensions[0]
-            .get_compatible_versions(str_engine_version, allow_pre_release)
-    } else {
-        resp_json.results[0].extensions[0].versions.iter().collect()
-    };
-
-    let version = if engine_version.is_some() && !versions.is_empty() {
-        // Debug the versions
-        debug!(
-            "Got {} version compatible with engine {}",
-            versions.len(),
-            str_engine_version
-        );
-        for current_version in versions.iter() {
-            debug!(
-                " - Version: {} Engine: {} PreRelease: {}",
-                current_version.version,
-                current_version
-                    .get_vscode_engine_version()
-                    .unwrap_or("None".to_string()),
-                current_version
-                    .get_vscode_prerelease()
-                    .unwrap_or("false".to_string())
-            );
-        }
-
-        versions[0].version.clone()
-    } else {
-        debug!("Could not find compatible version, using latest");
-        resp_json.results[0].extensions[0].versions[0]
-            .version
-            .clone()
-    };
-
-    Ok(version)
+    Ok(marketplace_extension)
 }
 
 /// Build the download URL and file path for a VSCode extension
@@ -297,7 +1839,8 @@ pub async fn get_extension_version(
 /// * `extension` - The extension to build the URL and file path for
 /// * `version` - The version of the extension
 /// * `destination` - The directory where the extension will be saved
-/// * `os_arch` - Optional target platform
+/// * `target_platform` - Optional target platform
+/// * `naming` - The file naming convention
 ///
 /// # Returns
 ///
@@ -306,33 +1849,318 @@ pub fn build_download_url_and_file_path(
     extension: Extension<'_>,
     version: &str,
     destination: &str,
-    os_arch: Option<&str>,
+    target_platform: Option<&TargetPlatform>,
+    naming: NamingPreset,
 ) -> (String, String) {
-    let file_name: String;
-    let file_path: String;
-    let download_url: String;
-
-    if let Some(target_platform) = os_arch {
-        file_name = format!(
-            "{}.{}-{version}@{}.vsix",
-            extension.publisher, extension.name, target_platform
-        );
-        file_path = format!("{}/{}", destination, file_name);
-        download_url = format!(
+    let file_path = format!(
+        "{}/{}",
+        destination,
+        naming.file_name(&extension, version, target_platform)
+    );
+    let download_url = if let Some(target_platform) = target_platform {
+        format!(
             "{}/{}/vsextensions/{}/{}/vspackage?targetPlatform={}",
             MARKETPLACE_URL, extension.publisher, extension.name, version, target_platform
-        );
+        )
     } else {
-        file_name = format!(
-            "{}.{}-{}.vsix",
-            extension.publisher, extension.name, version
-        );
-        file_path = format!("{}/{}", destination, file_name);
-        download_url = format!(
+        format!(
             "{}/{}/vsextensions/{}/{}/vspackage",
             MARKETPLACE_URL, extension.publisher, extension.name, version
+        )
+    };
+
+    (download_url, file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::UpdatePolicy;
+    use crate::test_support::{self, FakeRegistry};
+
+    #[test]
+    fn test_constrained_version_without_compatible_engine() {
+        let engine = json!({"properties": [
+            {"key": "Microsoft.VisualStudio.Code.Engine", "value": "^1.99.0"}
+        ]});
+        let options = DownloadOptions {
+            engine_version: Some("1.90.0".to_string()),
+            registry: Arc::new(FakeRegistry {
+                versions: ["2.0.0", "1.3.0", "1.2.5"]
+                    .into_iter()
+                    .map(|version| test_support::version(version, engine.clone()))
+                    .collect(),
+            }),
+            ..Default::default()
+        };
+        let constraint = |locked_version: &str| VersionConstraint {
+            policy: UpdatePolicy::Patch,
+            locked_version: locked_version.to_string(),
+        };
+        let resolve = |options: &DownloadOptions, locked_version: &str| {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(query_constrained_extension_version(
+                    Extension::from_id("golang.Go").unwrap(),
+                    options,
+                    Some(&constraint(locked_version)),
+                ))
+                .map(|(_, version)| version)
+        };
+
+        // The fallback to the latest version stays within the update policy
+        assert_eq!(resolve(&options, "1.2.3").unwrap(), "1.2.5");
+        assert!(resolve(&options, "3.0.0").is_err());
+        let options = DownloadOptions {
+            on_no_compatible: NoCompatiblePolicy::Fail,
+            ..options
+        };
+        assert!(matches!(
+            resolve(&options, "1.2.3"),
+            Err(VsixHarvesterError::NoCompatibleVersion(..))
+        ));
+    }
+
+    #[test]
+    fn test_build_store_url() {
+        assert_eq!(
+            build_store_url(
+                "https://s3.example.com/mirror/",
+                "./extensions/golang.Go-0.41.2@linux-x64.vsix"
+            ),
+            "https://s3.example.com/mirror/golang.Go-0.41.2@linux-x64.vsix"
         );
     }
 
-    (download_url, file_path)
+    #[test]
+    fn test_fetch_from_store() {
+        use crate::http_server::{serve_connections, Response};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir
+            .path()
+            .join("golang.Go-0.41.2.vsix")
+            .display()
+            .to_string();
+        let partial_path = partial_path(Path::new(&file_path));
+        let package = test_support::vsix("golang.Go", "0.41.2", None);
+        let sha256 = sha256_hex(&package);
+        let options = DownloadOptions::default();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (fetched, differing) = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let store = format!("http://{}", listener.local_addr().unwrap());
+            let shutdown = CancellationToken::new();
+            tokio::spawn(serve_connections(
+                listener,
+                "store",
+                shutdown.clone(),
+                move |_| {
+                    let package = package.clone();
+                    async move { Response::new(200, "application/octet-stream", package) }
+                },
+            ));
+            let client = reqwest::Client::new();
+            let fetched = fetch_from_store(&client, &store, &file_path, &sha256, &options).await;
+            let differing = fetch_from_store(&client, &store, &file_path, "c0ffee", &options).await;
+            shutdown.cancel();
+            (fetched, differing)
+        });
+        // The package is streamed into its partial file instead of memory
+        let fetched = fetched.unwrap();
+        assert_eq!(fetched.content, PackageContent::File(partial_path.clone()));
+        assert_eq!(fetched.sha256, sha256);
+        // A content differing from the pinned hash is dropped
+        assert!(differing.is_none());
+        assert!(!partial_path.exists());
+    }
+
+    #[test]
+    fn test_resume_partial_package() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("pkg.vsix").display().to_string();
+        let partial_path = partial_path(Path::new(&file_path));
+        fs::create_dir_all(partial_path.parent().unwrap()).unwrap();
+        fs::write(&partial_path, b"PK\x03").unwrap();
+        let options = DownloadOptions {
+            hash: HashAlgorithm::Sha512,
+            ..Default::default()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (request, downloaded) = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/pkg.vsix", listener.local_addr().unwrap());
+            let server = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                stream
+                    .write_all(
+                        b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-6/7\r\nContent-Length: 4\r\nConnection: close\r\n\r\n\x04end",
+                    )
+                    .await
+                    .unwrap();
+                String::from_utf8_lossy(&request[..read]).to_lowercase()
+            });
+            let mut partial = PartialPackage::new(&file_path, &options);
+            let resp = partial
+                .request(&reqwest::Client::new(), &url, &options)
+                .await
+                .unwrap();
+            let downloaded = partial.receive(resp).await.unwrap();
+            (server.await.unwrap(), downloaded)
+        });
+        assert!(request.contains("range: bytes=3-"));
+        assert!(request.contains("accept-encoding: identity"));
+        // The package is streamed into the partial file, hashed from its first byte
+        assert_eq!(
+            downloaded.content,
+            PackageContent::File(partial_path.clone())
+        );
+        assert_eq!(fs::read(&partial_path).unwrap(), b"PK\x03\x04end");
+        assert_eq!(downloaded.sha256, sha256_hex(b"PK\x03\x04end"));
+        // The digest of --hash is computed in the same pass
+        assert_eq!(
+            downloaded.digest,
+            Some(HashAlgorithm::Sha512.digest(b"PK\x03\x04end"))
+        );
+    }
+
+    #[test]
+    fn test_cancel_download_removes_partial_package() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("pkg.vsix").display().to_string();
+        let partial_path = partial_path(Path::new(&file_path));
+        let options = DownloadOptions::default();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let resolved = ResolvedExtension {
+                download_url: format!("http://{}/pkg.vsix", listener.local_addr().unwrap()),
+                file_path: file_path.clone(),
+                override_sha256: Some("c0ffee".to_string()),
+                ..test_support::resolved("publisher.pkg", "1.0.0")
+            };
+            // Send the first bytes of the package, then stall
+            tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\nPK\x03\x04")
+                    .await
+                    .unwrap();
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                drop(stream);
+            });
+            let download = download_resolved_extension(&resolved, &options, None);
+            let cancel = async {
+                while fs::metadata(&partial_path).map_or(0, |metadata| metadata.len()) == 0 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                options.cancellation.cancel();
+            };
+            tokio::join!(download, cancel).0
+        });
+        assert!(matches!(result, Err(VsixHarvesterError::Cancelled)));
+        assert!(!partial_path.exists());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let base = Duration::from_millis(200);
+        for (retry, expected) in [(0, 200), (1, 400), (3, 1600)] {
+            let delay = backoff_delay(base, retry);
+            assert!(delay >= Duration::from_millis(expected));
+            assert!(delay <= Duration::from_millis(expected * 3 / 2));
+        }
+        assert!(backoff_delay(base, 40) > Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_version_preference() {
+        let version = |number: &str, last_updated: &str, target_platform: &str| Version {
+            version: number.to_string(),
+            flags: "validated".to_string(),
+            last_updated: last_updated.to_string(),
+            files: Vec::new(),
+            properties: None,
+            asset_uri: String::new(),
+            fallback_asset_uri: String::new(),
+            target_platform: Some(target_platform.into()),
+        };
+        // A hotfix of an older line released after the newest version
+        let candidates = [
+            version("2.0.0", "2025-03-01T10:00:00.5Z", "linux-x64"),
+            version("1.9.1", "2025-03-02T08:00:00Z", "linux-x64"),
+            version("2.0.0", "2025-03-01T10:00:00.5Z", "win32-x64"),
+            version("2.0.0-beta", "2025-03-01T10:00:00Z", "linux-x64"),
+        ];
+        let sorted = |preference: VersionPreference| {
+            let mut versions: Vec<&Version> = candidates.iter().collect();
+            preference.sort(&mut versions);
+            versions
+                .iter()
+                .map(|v| format!("{}@{}", v.version, v.target_platform.as_ref().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sorted(VersionPreference::ReleaseDate),
+            [
+                "1.9.1@linux-x64",
+                "2.0.0@linux-x64",
+                "2.0.0@win32-x64",
+                "2.0.0-beta@linux-x64"
+            ]
+        );
+        assert_eq!(
+            sorted(VersionPreference::VersionNumber),
+            [
+                "2.0.0@linux-x64",
+                "2.0.0@win32-x64",
+                "2.0.0-beta@linux-x64",
+                "1.9.1@linux-x64"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_marketplace_url() {
+        let mut options = DownloadOptions::default();
+        assert_eq!(options.marketplace_url(API_URL), API_URL);
+        options.marketplace_origin = Some("http://127.0.0.1:8080/".to_string());
+        assert_eq!(
+            options.marketplace_url(API_URL),
+            "http://127.0.0.1:8080/_apis/public/gallery/extensionquery"
+        );
+        // Other hosts are left untouched
+        assert_eq!(
+            options.marketplace_url("https://s3.example.com/mirror"),
+            "https://s3.example.com/mirror"
+        );
+    }
+
+    #[test]
+    fn test_minimal_flags() {
+        assert_eq!(Flags::minimal(true).bits(), 0x11);
+        assert_eq!(Flags::minimal(false).bits(), 0x211);
+        assert!(!Flags::minimal(false).contains(Flags::INCLUDE_STATISTICS));
+    }
+
+    #[test]
+    fn test_is_retryable_status() {
+        assert!(is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(!is_retryable_status(reqwest::StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(reqwest::StatusCode::OK));
+    }
 }
@@ -0,0 +1,154 @@
+use crate::architecture::TargetPlatform;
+use crate::extension::Extension;
+use clap::ValueEnum;
+
+/// File naming convention of the downloaded packages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NamingPreset {
+    /// `publisher.name-version@platform.vsix`, understood by every command of the harvester
+    #[default]
+    Harvester,
+    /// `name-platform-version.vsix`, the default output of `vsce package`
+    Vsce,
+    /// `name-version@platform.vsix`, as installed by code-server
+    Codeserver,
+    /// `publisher-name-version-platform.vsix`, for tools rejecting dots and `@` in file names
+    Flat,
+}
+
+impl NamingPreset {
+    /// Get the file name template of the preset
+    ///
+    /// Placeholders are `{publisher}`, `{name}`, `{version}` and `{platform}`, a
+    /// `[...]` group is only rendered when every placeholder inside it has a value.
+    pub fn template(self) -> &'static str {
+        match self {
+            NamingPreset::Harvester => "{publisher}.{name}-{version}[@{platform}].vsix",
+            NamingPreset::Vsce => "{name}-[{platform}-]{version}.vsix",
+            NamingPreset::Codeserver => "{name}-{version}[@{platform}].vsix",
+            NamingPreset::Flat => "{publisher}-{name}-{version}[-{platform}].vsix",
+        }
+    }
+
+    /// Get the file name of a package
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension of the package
+    /// * `version` - The version of the package
+    /// * `target_platform` - Optional target platform of the package
+    ///
+    /// # Returns
+    ///
+    /// The file name of the package
+    pub fn file_name(
+        self,
+        extension: &Extension<'_>,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+    ) -> String {
+        render_file_name(self.template(), &|placeholder| match placeholder {
+            "publisher" => Some(extension.publisher),
+            "name" => Some(extension.name),
+            "version" => Some(version),
+            "platform" => target_platform.map(TargetPlatform::as_str),
+            _ => None,
+        })
+    }
+}
+
+/// Render a file name template
+///
+/// # Arguments
+///
+/// * `template` - The template, with `{placeholder}` values and `[...]` optional groups
+/// * `lookup` - The function giving the value of a placeholder, None when it has no value
+///
+/// # Returns
+///
+/// The rendered file name, placeholders without value are left empty
+pub fn render_file_name<'a>(template: &str, lookup: &dyn Fn(&str) -> Option<&'a str>) -> String {
+    let mut rendered = String::new();
+    // The optional group being rendered and whether all its placeholders have a value
+    let mut group: Option<(String, bool)> = None;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        let value = match c {
+            '[' => {
+                group = Some((String::new(), true));
+                continue;
+            }
+            ']' => {
+                if let Some((content, true)) = group.take() {
+                    rendered.push_str(&content);
+                }
+                continue;
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let value = lookup(&placeholder);
+                if value.is_none() {
+                    if let Some((_, complete)) = &mut group {
+                        *complete = false;
+                    }
+                }
+                value.unwrap_or_default().to_string()
+            }
+            c => c.to_string(),
+        };
+        match &mut group {
+            Some((content, _)) => content.push_str(&value),
+            None => rendered.push_str(&value),
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming_presets() {
+        let extension = Extension {
+            publisher: "rust-lang",
+            name: "rust-analyzer",
+            version: None,
+        };
+        let cases = [
+            (
+                NamingPreset::Harvester,
+                "rust-lang.rust-analyzer-0.3.2@linux-x64.vsix",
+                "rust-lang.rust-analyzer-0.3.2.vsix",
+            ),
+            (
+                NamingPreset::Vsce,
+                "rust-analyzer-linux-x64-0.3.2.vsix",
+                "rust-analyzer-0.3.2.vsix",
+            ),
+            (
+                NamingPreset::Codeserver,
+                "rust-analyzer-0.3.2@linux-x64.vsix",
+                "rust-analyzer-0.3.2.vsix",
+            ),
+            (
+                NamingPreset::Flat,
+                "rust-lang-rust-analyzer-0.3.2-linux-x64.vsix",
+                "rust-lang-rust-analyzer-0.3.2.vsix",
+            ),
+        ];
+        for (preset, targeted, universal) in cases {
+            assert_eq!(
+                preset.file_name(&extension, "0.3.2", Some(&"linux-x64".into())),
+                targeted
+            );
+            assert_eq!(preset.file_name(&extension, "0.3.2", None), universal);
+        }
+        assert_eq!(
+            render_file_name("{name}[-{unknown}].vsix", &|placeholder| {
+                (placeholder == "name").then_some("go")
+            }),
+            "go.vsix"
+        );
+    }
+}
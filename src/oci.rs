@@ -0,0 +1,419 @@
+use crate::compression::{package_file_name, read_package};
+use crate::config::USER_AGENT;
+use crate::credentials::secret_from_env;
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{build_client, sha256_hex, DownloadOptions};
+use log::{debug, info};
+use reqwest::header::{CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+/// Artifact type of a harvest bundle
+pub const BUNDLE_ARTIFACT_TYPE: &str = "application/vnd.vsixharvester.bundle.v1";
+/// Media type of a VSIX package layer
+pub const VSIX_MEDIA_TYPE: &str = "application/vnd.microsoft.vscode.vsix";
+/// Media type of a JSON layer (manifest, lockfile)
+pub const JSON_MEDIA_TYPE: &str = "application/json";
+/// Media type of the layers holding the VS Code server archives
+pub const ARCHIVE_MEDIA_TYPE: &str = "application/octet-stream";
+const EMPTY_CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// A reference to an OCI repository tag (e.g., "oci://registry.local/vscode/extensions:2025-05")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OciReference {
+    /// The scheme used to reach the registry, "https" or "http" for oci+http:// references
+    pub scheme: String,
+    pub registry: String,
+    pub repository: String,
+    pub tag: String,
+}
+
+impl OciReference {
+    /// Parse an OCI reference
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The reference, `oci://registry/repository:tag` or `oci+http://...` for plain HTTP registries
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the reference or an error if it is invalid
+    pub fn parse(reference: &str) -> Result<Self> {
+        let invalid = || VsixHarvesterError::InvalidPublishTarget(reference.to_string());
+        let (scheme, rest) = if let Some(rest) = reference.strip_prefix("oci://") {
+            ("https", rest)
+        } else if let Some(rest) = reference.strip_prefix("oci+http://") {
+            ("http", rest)
+        } else {
+            return Err(invalid());
+        };
+        let (registry, path) = rest.split_once('/').ok_or_else(invalid)?;
+        // The tag separator is the last colon after the last slash
+        let (repository, tag) = match path.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+            _ => (path, "latest"),
+        };
+        if registry.is_empty() || repository.is_empty() || tag.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            scheme: scheme.to_string(),
+            registry: registry.to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+        })
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!(
+            "{}://{}/v2/{}/{}",
+            self.scheme, self.registry, self.repository, path
+        )
+    }
+}
+
+/// A file pushed as a layer of the bundle
+#[derive(Debug, Clone)]
+pub struct BundleLayer {
+    pub path: String,
+    pub media_type: &'static str,
+}
+
+/// Descriptor of a blob stored in the registry
+#[derive(Debug, Clone)]
+struct Descriptor {
+    media_type: &'static str,
+    digest: String,
+    size: usize,
+    title: Option<String>,
+}
+
+impl Descriptor {
+    fn to_json(&self) -> Value {
+        let mut descriptor = json!({
+            "mediaType": self.media_type,
+            "digest": self.digest,
+            "size": self.size,
+        });
+        if let Some(title) = &self.title {
+            descriptor["annotations"] = json!({ "org.opencontainers.image.title": title });
+        }
+        descriptor
+    }
+}
+
+/// Build the OCI image manifest of a bundle
+fn build_manifest(config: &Descriptor, layers: &[Descriptor]) -> Value {
+    json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_MEDIA_TYPE,
+        "artifactType": BUNDLE_ARTIFACT_TYPE,
+        "config": config.to_json(),
+        "layers": layers.iter().map(Descriptor::to_json).collect::<Vec<_>>(),
+        "annotations": {
+            "org.opencontainers.image.created": chrono::Utc::now().to_rfc3339(),
+        },
+    })
+}
+
+/// Credentials sent to the registry
+enum Authorization {
+    Basic(String, String),
+    Bearer(String),
+}
+
+/// Client of the OCI distribution API for one repository
+struct OciPusher {
+    client: Client,
+    reference: OciReference,
+    authorization: Option<Authorization>,
+}
+
+impl OciPusher {
+    /// Connect to the registry, negotiating a bearer token when the registry requires one
+    async fn connect(reference: OciReference, options: &DownloadOptions) -> Result<Self> {
+        let client = build_client(options)?;
+        let basic = match (
+            std::env::var("OCI_USERNAME"),
+            secret_from_env("OCI_PASSWORD")?,
+        ) {
+            (Ok(username), Some(password)) => Some((username, password)),
+            _ => None,
+        };
+        let mut pusher = Self {
+            client,
+            reference,
+            authorization: None,
+        };
+        let resp = pusher
+            .client
+            .get(format!(
+                "{}://{}/v2/",
+                pusher.reference.scheme, pusher.reference.registry
+            ))
+            .header("User-Agent", USER_AGENT)
+            .send()
+            .await?;
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            return Ok(pusher);
+        }
+        let challenge = resp
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if let Some(params) = challenge.strip_prefix("Bearer ") {
+            let realm = challenge_param(params, "realm").ok_or_else(|| {
+                VsixHarvesterError::PublishError(format!("invalid challenge: {}", challenge))
+            })?;
+            let mut request = pusher.client.get(realm).query(&[(
+                "scope",
+                format!("repository:{}:pull,push", pusher.reference.repository),
+            )]);
+            if let Some(service) = challenge_param(params, "service") {
+                request = request.query(&[("service", service)]);
+            }
+            if let Some((username, password)) = &basic {
+                request = request.basic_auth(username, Some(password));
+            }
+            let token: Value = request.send().await?.error_for_status()?.json().await?;
+            let token = token["token"]
+                .as_str()
+                .or_else(|| token["access_token"].as_str())
+                .ok_or_else(|| {
+                    VsixHarvesterError::PublishError("no token returned by the registry".into())
+                })?;
+            pusher.authorization = Some(Authorization::Bearer(token.to_string()));
+        } else if let Some((username, password)) = basic {
+            pusher.authorization = Some(Authorization::Basic(username, password));
+        } else {
+            return Err(VsixHarvesterError::PublishError(
+                "registry requires authentication, set OCI_USERNAME and OCI_PASSWORD".into(),
+            ));
+        }
+        Ok(pusher)
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, url)
+            .header("User-Agent", USER_AGENT);
+        match &self.authorization {
+            Some(Authorization::Basic(username, password)) => {
+                request.basic_auth(username, Some(password))
+            }
+            Some(Authorization::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Upload a blob unless the registry already has it
+    async fn push_blob(
+        &self,
+        content: Vec<u8>,
+        media_type: &'static str,
+        title: Option<String>,
+    ) -> Result<Descriptor> {
+        let descriptor = Descriptor {
+            media_type,
+            digest: format!("sha256:{}", sha256_hex(&content)),
+            size: content.len(),
+            title,
+        };
+        let blob_url = self
+            .reference
+            .api_url(&format!("blobs/{}", descriptor.digest));
+        let resp = self
+            .request(reqwest::Method::HEAD, &blob_url)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            debug!("Blob {} already exists", descriptor.digest);
+            return Ok(descriptor);
+        }
+
+        let resp = self
+            .request(
+                reqwest::Method::POST,
+                &self.reference.api_url("blobs/uploads/"),
+            )
+            .send()
+            .await?;
+        if resp.status() != StatusCode::ACCEPTED {
+            return Err(VsixHarvesterError::PublishError(format!(
+                "upload of {} refused ({})",
+                descriptor.digest,
+                resp.status()
+            )));
+        }
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| VsixHarvesterError::PublishError("no upload location".into()))?;
+        let location = if location.starts_with('/') {
+            format!(
+                "{}://{}{}",
+                self.reference.scheme, self.reference.registry, location
+            )
+        } else {
+            location.to_string()
+        };
+        let separator = if location.contains('?') { '&' } else { '?' };
+        let resp = self
+            .request(
+                reqwest::Method::PUT,
+                &format!("{}{}digest={}", location, separator, descriptor.digest),
+            )
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(content)
+            .send()
+            .await?;
+        if resp.status() != StatusCode::CREATED {
+            return Err(VsixHarvesterError::PublishError(format!(
+                "upload of {} failed ({})",
+                descriptor.digest,
+                resp.status()
+            )));
+        }
+        Ok(descriptor)
+    }
+}
+
+/// Extract a parameter of a WWW-Authenticate challenge
+fn challenge_param<'a>(params: &'a str, name: &str) -> Option<&'a str> {
+    params.split(',').find_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        (key == name).then(|| value.trim_matches('"'))
+    })
+}
+
+/// Push a set of files as an OCI artifact
+///
+/// # Arguments
+///
+/// * `target` - The OCI reference (e.g., "oci://registry.local/vscode/extensions:latest")
+/// * `layers` - The files of the bundle
+/// * `options` - The download options (proxy and connection tuning)
+///
+/// # Returns
+///
+/// A Result containing the digest of the pushed manifest or an error that occurred
+pub async fn push_bundle(
+    target: &str,
+    layers: &[BundleLayer],
+    options: &DownloadOptions,
+) -> Result<String> {
+    let reference = OciReference::parse(target)?;
+    let pusher = OciPusher::connect(reference, options).await?;
+
+    let config = pusher
+        .push_blob(b"{}".to_vec(), EMPTY_CONFIG_MEDIA_TYPE, None)
+        .await?;
+    let mut descriptors = Vec::new();
+    for layer in layers {
+        // Stored copies are pushed decompressed, under the name of the package
+        let title = package_file_name(&layer.path);
+        info!("Pushing {}", layer.path);
+        let content = read_package(&layer.path)?;
+        descriptors.push(pusher.push_blob(content, layer.media_type, title).await?);
+    }
+
+    let manifest = serde_json::to_vec(&build_manifest(&config, &descriptors))?;
+    let digest = format!("sha256:{}", sha256_hex(&manifest));
+    let resp = pusher
+        .request(
+            reqwest::Method::PUT,
+            &pusher
+                .reference
+                .api_url(&format!("manifests/{}", pusher.reference.tag)),
+        )
+        .header(CONTENT_TYPE, MANIFEST_MEDIA_TYPE)
+        .body(manifest)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(VsixHarvesterError::PublishError(format!(
+            "manifest push failed ({})",
+            resp.status()
+        )));
+    }
+    info!(
+        "Pushed {} layers to {}/{}:{} ({})",
+        descriptors.len(),
+        pusher.reference.registry,
+        pusher.reference.repository,
+        pusher.reference.tag,
+        digest
+    );
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oci_reference() {
+        let reference =
+            OciReference::parse("oci://registry.local:5000/vscode/extensions:2025-05").unwrap();
+        assert_eq!(reference.scheme, "https");
+        assert_eq!(reference.registry, "registry.local:5000");
+        assert_eq!(reference.repository, "vscode/extensions");
+        assert_eq!(reference.tag, "2025-05");
+        assert_eq!(
+            reference.api_url("manifests/2025-05"),
+            "https://registry.local:5000/v2/vscode/extensions/manifests/2025-05"
+        );
+
+        let reference = OciReference::parse("oci+http://localhost:5000/extensions").unwrap();
+        assert_eq!(reference.scheme, "http");
+        assert_eq!(reference.tag, "latest");
+
+        assert!(OciReference::parse("https://registry.local/extensions").is_err());
+        assert!(OciReference::parse("oci://registry.local").is_err());
+    }
+
+    #[test]
+    fn test_challenge_param() {
+        let params = r#"realm="https://auth.docker.io/token",service="registry.docker.io""#;
+        assert_eq!(
+            challenge_param(params, "realm"),
+            Some("https://auth.docker.io/token")
+        );
+        assert_eq!(
+            challenge_param(params, "service"),
+            Some("registry.docker.io")
+        );
+        assert_eq!(challenge_param(params, "scope"), None);
+    }
+
+    #[test]
+    fn test_build_manifest() {
+        let config = Descriptor {
+            media_type: EMPTY_CONFIG_MEDIA_TYPE,
+            digest: format!("sha256:{}", sha256_hex(b"{}")),
+            size: 2,
+            title: None,
+        };
+        let layer = Descriptor {
+            media_type: VSIX_MEDIA_TYPE,
+            digest: "sha256:abc".to_string(),
+            size: 3,
+            title: Some("golang.Go-0.41.2.vsix".to_string()),
+        };
+        let manifest = build_manifest(&config, &[layer]);
+        assert_eq!(manifest["artifactType"], BUNDLE_ARTIFACT_TYPE);
+        assert_eq!(
+            manifest["config"]["digest"],
+            "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        assert_eq!(
+            manifest["layers"][0]["annotations"]["org.opencontainers.image.title"],
+            "golang.Go-0.41.2.vsix"
+        );
+    }
+}
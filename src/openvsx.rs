@@ -0,0 +1,455 @@
+use crate::architecture::TargetPlatform;
+use crate::config::{OPENVSX_API_URL, OPENVSX_TARGET_PLATFORMS, USER_AGENT};
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::marketplace::{
+    build_client, send_request, DownloadOptions, DownloadedPackage, PartialPackage,
+    ResolvedExtension,
+};
+use crate::registry::Registry;
+use crate::types::{
+    compare_versions, Extension as MarketplaceExtension, File, Property, Publisher, Statistic,
+    Version,
+};
+use futures::future::BoxFuture;
+use log::{debug, error};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Marketplace asset types of the files listed by Open VSX
+const ASSET_TYPES: [(&str, &str); 6] = [
+    ("download", "Microsoft.VisualStudio.Services.VSIXPackage"),
+    ("manifest", "Microsoft.VisualStudio.Code.Manifest"),
+    ("icon", "Microsoft.VisualStudio.Services.Icons.Default"),
+    ("readme", "Microsoft.VisualStudio.Services.Content.Details"),
+    (
+        "changelog",
+        "Microsoft.VisualStudio.Services.Content.Changelog",
+    ),
+    ("license", "Microsoft.VisualStudio.Services.Content.License"),
+];
+
+/// Response of the Open VSX query API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenVsxQueryResult {
+    #[serde(default)]
+    pub total_size: usize,
+    #[serde(default)]
+    pub extensions: Vec<OpenVsxExtension>,
+}
+
+/// A reference to another extension in the Open VSX API
+#[derive(Debug, Deserialize)]
+pub struct OpenVsxReference {
+    pub namespace: String,
+    pub extension: String,
+}
+
+/// An extension version published on Open VSX, one per target platform
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenVsxExtension {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    /// The target platform of the package, "universal" for universal packages
+    pub target_platform: Option<TargetPlatform>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub timestamp: Option<String>,
+    pub pre_release: Option<bool>,
+    /// Whether the namespace is owned by a verified publisher
+    #[serde(default)]
+    pub verified: bool,
+    /// The engine requirements (e.g., "vscode": "^1.90.0")
+    pub engines: Option<HashMap<String, String>>,
+    /// The URLs of the files of the package by kind (e.g., "download", "icon")
+    #[serde(default)]
+    pub files: HashMap<String, String>,
+    pub download_count: Option<f64>,
+    pub average_rating: Option<f64>,
+    #[serde(default)]
+    pub dependencies: Vec<OpenVsxReference>,
+    #[serde(default)]
+    pub bundled_extensions: Vec<OpenVsxReference>,
+}
+
+/// Join the identifiers of extension references, as in the marketplace properties
+fn join_references(references: &[OpenVsxReference]) -> String {
+    references
+        .iter()
+        .map(|reference| format!("{}.{}", reference.namespace, reference.extension))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl OpenVsxExtension {
+    /// Convert the package to a marketplace version, so that the version selection is shared
+    fn to_version(&self) -> Version {
+        let mut properties = Vec::new();
+        if let Some(engine) = self
+            .engines
+            .as_ref()
+            .and_then(|engines| engines.get("vscode"))
+        {
+            properties.push(Property {
+                key: "Microsoft.VisualStudio.Code.Engine".to_string(),
+                value: engine.clone(),
+            });
+        }
+        properties.push(Property {
+            key: "Microsoft.VisualStudio.Code.PreRelease".to_string(),
+            value: self.pre_release.unwrap_or_default().to_string(),
+        });
+        properties.push(Property {
+            key: "Microsoft.VisualStudio.Code.ExtensionDependencies".to_string(),
+            value: join_references(&self.dependencies),
+        });
+        properties.push(Property {
+            key: "Microsoft.VisualStudio.Code.ExtensionPack".to_string(),
+            value: join_references(&self.bundled_extensions),
+        });
+        Version {
+            version: self.version.clone(),
+            flags: "validated".to_string(),
+            last_updated: self.timestamp.clone().unwrap_or_default(),
+            files: ASSET_TYPES
+                .iter()
+                .filter_map(|(kind, asset_type)| {
+                    self.files.get(*kind).map(|source| File {
+                        asset_type: asset_type.to_string(),
+                        source: source.clone(),
+                    })
+                })
+                .collect(),
+            properties: Some(properties),
+            asset_uri: String::new(),
+            fallback_asset_uri: String::new(),
+            target_platform: self.target_platform.clone(),
+        }
+    }
+}
+
+/// Convert the packages of an extension published on Open VSX to marketplace metadata
+///
+/// # Arguments
+///
+/// * `published` - The packages of the extension, in any order
+///
+/// # Returns
+///
+/// The marketplace metadata with the versions from the latest, None without package
+pub fn to_marketplace_extension(
+    mut published: Vec<OpenVsxExtension>,
+) -> Option<MarketplaceExtension> {
+    published.sort_by(|a, b| compare_versions(&b.version, &a.version).cmp(&0));
+    let latest = published.first()?;
+    let mut statistics = Vec::new();
+    if let Some(download_count) = latest.download_count {
+        statistics.push(Statistic {
+            statistic_name: "install".to_string(),
+            value: download_count,
+        });
+    }
+    if let Some(average_rating) = latest.average_rating {
+        statistics.push(Statistic {
+            statistic_name: "averagerating".to_string(),
+            value: average_rating,
+        });
+    }
+    let timestamp = latest.timestamp.clone().unwrap_or_default();
+    Some(MarketplaceExtension {
+        publisher: Publisher {
+            // Open VSX has no publisher identifier, the namespace is the stable one
+            publisher_id: latest.namespace.clone(),
+            publisher_name: latest.namespace.clone(),
+            display_name: latest.namespace.clone(),
+            flags: String::new(),
+            domain: None,
+            is_domain_verified: latest.verified,
+        },
+        extension_id: format!("{}.{}", latest.namespace, latest.name),
+        extension_name: latest.name.clone(),
+        display_name: latest.display_name.clone().unwrap_or_default(),
+        flags: String::new(),
+        last_updated: timestamp.clone(),
+        published_date: timestamp.clone(),
+        release_date: timestamp,
+        short_description: latest.description.clone().unwrap_or_default(),
+        versions: published.iter().map(OpenVsxExtension::to_version).collect(),
+        deployment_type: 0,
+        statistics,
+    })
+}
+
+/// Query the metadata of an extension on Open VSX
+///
+/// # Arguments
+///
+/// * `extension` - The extension to query
+/// * `options` - The download options (proxy, polite mode)
+/// * `all_versions` - Whether to get every version instead of the latest one
+///
+/// # Returns
+///
+/// A Result containing the extension metadata, in the marketplace format, or an error that occurred
+pub async fn query_openvsx_extension(
+    extension: &Extension<'_>,
+    options: &DownloadOptions,
+    all_versions: bool,
+) -> Result<MarketplaceExtension> {
+    let client = build_client(options)?;
+    let mut published = Vec::new();
+    // The results are paginated, one entry per version and target platform
+    loop {
+        let query_url = format!(
+            "{}/-/query?extensionId={}&includeAllVersions={}&offset={}",
+            OPENVSX_API_URL,
+            extension.to_id(),
+            all_versions,
+            published.len()
+        );
+        debug!("Sending query for Open VSX API: {}", query_url);
+        let resp = send_request(
+            client
+                .get(&query_url)
+                .header("Accept", "application/json")
+                .header("User-Agent", USER_AGENT),
+            options,
+        )
+        .await?;
+        if !resp.status().is_success() {
+            error!("Failed query for Open VSX API");
+            return Err(VsixHarvesterError::ApiError(
+                "Failed query for Open VSX API".to_string(),
+            ));
+        }
+        let page: OpenVsxQueryResult = serde_json::from_str(&resp.text().await?)?;
+        let received = page.extensions.len();
+        published.extend(page.extensions);
+        if received == 0 || published.len() >= page.total_size {
+            break;
+        }
+    }
+    debug!("Got {} Open VSX package results", published.len());
+
+    to_marketplace_extension(published).ok_or_else(|| {
+        error!("{} was not found in Open VSX", extension.to_id());
+        VsixHarvesterError::ApiError(format!("{} was not found", extension.to_id()))
+    })
+}
+
+/// Get the package URL of a version from the Open VSX metadata
+///
+/// Universal packages are used for the target platforms without a package of their own.
+///
+/// # Arguments
+///
+/// * `metadata` - The metadata of the extension
+/// * `version` - The version of the package
+/// * `target_platform` - Optional target platform
+///
+/// # Returns
+///
+/// An Option containing the URL of the package, None if the version is not listed
+pub fn find_openvsx_download_url(
+    metadata: &MarketplaceExtension,
+    version: &str,
+    target_platform: Option<&TargetPlatform>,
+) -> Option<String> {
+    let packages: Vec<&Version> = metadata
+        .versions
+        .iter()
+        .filter(|candidate| candidate.version == version)
+        .collect();
+    packages
+        .iter()
+        .find(|package| {
+            target_platform.is_some() && package.target_platform.as_ref() == target_platform
+        })
+        .or_else(|| {
+            packages.iter().find(|package| {
+                package
+                    .target_platform
+                    .as_ref()
+                    .is_none_or(|target_platform| target_platform == "universal")
+            })
+        })
+        .and_then(|package| package.get_vsix_url())
+}
+
+/// Build the URL of a package on Open VSX
+///
+/// # Arguments
+///
+/// * `extension` - The extension of the package
+/// * `version` - The version of the package
+/// * `target_platform` - Optional target platform, None for the universal package
+///
+/// # Returns
+///
+/// The URL of the package
+pub fn build_openvsx_download_url(
+    extension: &Extension<'_>,
+    version: &str,
+    target_platform: Option<&TargetPlatform>,
+) -> String {
+    let (publisher, name) = (extension.publisher, extension.name);
+    match target_platform {
+        Some(target_platform) => format!(
+            "{}/{}/{}/{}/{}/file/{}.{}-{}@{}.vsix",
+            OPENVSX_API_URL,
+            publisher,
+            name,
+            target_platform,
+            version,
+            publisher,
+            name,
+            version,
+            target_platform
+        ),
+        None => format!(
+            "{}/{}/{}/{}/file/{}.{}-{}.vsix",
+            OPENVSX_API_URL, publisher, name, version, publisher, name, version
+        ),
+    }
+}
+
+/// The Open VSX registry (open-vsx.org)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenVsx;
+
+impl Registry for OpenVsx {
+    fn name(&self) -> &str {
+        "openvsx"
+    }
+
+    fn target_platforms(&self) -> Option<&'static [&'static str]> {
+        Some(&OPENVSX_TARGET_PLATFORMS)
+    }
+
+    fn query_extension<'a>(
+        &'a self,
+        extension: &'a Extension<'_>,
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+        Box::pin(query_openvsx_extension(extension, options, all_versions))
+    }
+
+    fn package_url(
+        &self,
+        extension: &Extension<'_>,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+        metadata: Option<&MarketplaceExtension>,
+        _options: &DownloadOptions,
+    ) -> String {
+        metadata
+            .and_then(|metadata| find_openvsx_download_url(metadata, version, target_platform))
+            .unwrap_or_else(|| build_openvsx_download_url(extension, version, target_platform))
+    }
+
+    fn download<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        resolved: &'a ResolvedExtension,
+        options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<DownloadedPackage>> {
+        Box::pin(async move {
+            let mut partial = PartialPackage::new(&resolved.file_path, options);
+            let mut resp = partial
+                .request(client, &resolved.download_url, options)
+                .await?;
+            // Universal packages have no target platform in their URL, pinned versions are not checked
+            if resp.status() == reqwest::StatusCode::NOT_FOUND && resolved.target_platform.is_some()
+            {
+                let extension = Extension::from_id(&resolved.id)?;
+                let universal_url = build_openvsx_download_url(&extension, &resolved.version, None);
+                resp = partial.request(client, &universal_url, options).await?;
+            }
+            if !resp.status().is_success() {
+                error!("Fail download of {}", resolved.id);
+                return Err(VsixHarvesterError::DownloadError(resolved.id.clone()));
+            }
+            partial.receive(resp).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openvsx_query_result() {
+        let json = r#"{
+            "offset": 0,
+            "totalSize": 3,
+            "extensions": [
+                {
+                    "namespace": "rust-lang", "name": "rust-analyzer", "version": "0.3.2",
+                    "targetPlatform": "universal", "preRelease": false,
+                    "engines": {"vscode": "^1.78.0"},
+                    "files": {"download": "https://open-vsx.org/api/rust-lang/rust-analyzer/0.3.2/file/rust-lang.rust-analyzer-0.3.2.vsix"}
+                },
+                {
+                    "namespace": "rust-lang", "name": "rust-analyzer", "version": "0.4.1",
+                    "targetPlatform": "linux-x64", "preRelease": true, "downloadCount": 1200,
+                    "engines": {"vscode": "^1.93.0"},
+                    "dependencies": [{"namespace": "vadimcn", "extension": "vscode-lldb"}],
+                    "files": {"download": "https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.4.1/file/rust-lang.rust-analyzer-0.4.1@linux-x64.vsix"}
+                },
+                {
+                    "namespace": "rust-lang", "name": "rust-analyzer", "version": "0.4.1",
+                    "targetPlatform": "darwin-arm64", "preRelease": true, "downloadCount": 1200,
+                    "files": {}
+                }
+            ]
+        }"#;
+        let result: OpenVsxQueryResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.total_size, 3);
+        let metadata = to_marketplace_extension(result.extensions).unwrap();
+        assert_eq!(metadata.get_identifier(), "rust-lang.rust-analyzer");
+        assert_eq!(metadata.get_install_count(), 1200);
+        assert_eq!(metadata.versions[0].version, "0.4.1");
+        assert_eq!(
+            metadata.versions[0].get_vscode_engine_version().as_deref(),
+            Some("^1.93.0")
+        );
+        assert_eq!(
+            metadata.versions[0].get_extension_dependencies(),
+            vec!["vadimcn.vscode-lldb"]
+        );
+        assert_eq!(
+            metadata.get_target_platforms("0.4.1"),
+            vec!["linux-x64", "darwin-arm64"]
+        );
+        assert!(metadata.get_target_platforms("0.3.2").is_empty());
+        // Only the stable version is compatible without pre-releases
+        let compatible = metadata.get_compatible_versions("1.78.5", false);
+        assert_eq!(compatible.len(), 1);
+        assert_eq!(compatible[0].version, "0.3.2");
+
+        assert_eq!(
+            find_openvsx_download_url(&metadata, "0.4.1", Some(&"linux-x64".into())).as_deref(),
+            Some("https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.4.1/file/rust-lang.rust-analyzer-0.4.1@linux-x64.vsix")
+        );
+        // The universal package serves every target platform
+        assert_eq!(
+            find_openvsx_download_url(&metadata, "0.3.2", Some(&"win32-x64".into())).as_deref(),
+            Some("https://open-vsx.org/api/rust-lang/rust-analyzer/0.3.2/file/rust-lang.rust-analyzer-0.3.2.vsix")
+        );
+
+        let extension = Extension::from_id("golang.Go").unwrap();
+        assert_eq!(
+            build_openvsx_download_url(&extension, "0.41.2", Some(&"linux-x64".into())),
+            "https://open-vsx.org/api/golang/Go/linux-x64/0.41.2/file/golang.Go-0.41.2@linux-x64.vsix"
+        );
+        assert_eq!(
+            build_openvsx_download_url(&extension, "0.41.2", None),
+            "https://open-vsx.org/api/golang/Go/0.41.2/file/golang.Go-0.41.2.vsix"
+        );
+    }
+}
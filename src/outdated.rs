@@ -0,0 +1,186 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{resolve_extension, DownloadOptions};
+use crate::status::{scan_mirror, MirroredPackage};
+use crate::types::compare_versions;
+use futures::stream::{self, StreamExt};
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A mirrored package behind the latest version of the marketplace
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutdatedPackage {
+    pub id: String,
+    pub target_platform: Option<TargetPlatform>,
+    pub local_version: String,
+    pub latest_version: String,
+}
+
+/// Result of the comparison of a mirror with the marketplace
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutdatedReport {
+    /// Number of packages compared, one per extension and target platform
+    pub checked: usize,
+    pub outdated: Vec<OutdatedPackage>,
+    /// Packages whose latest version could not be checked
+    pub unchecked: Vec<String>,
+}
+
+/// Get the newest mirrored version of each extension and target platform
+///
+/// # Arguments
+///
+/// * `packages` - The packages of the mirror
+///
+/// # Returns
+///
+/// The newest packages, keyed by lowercase identifier and target platform
+pub fn newest_packages(
+    packages: &[MirroredPackage],
+) -> BTreeMap<(String, Option<TargetPlatform>), &MirroredPackage> {
+    let mut newest: BTreeMap<(String, Option<TargetPlatform>), &MirroredPackage> = BTreeMap::new();
+    for package in packages {
+        let current = newest
+            .entry((package.id.to_lowercase(), package.target_platform.clone()))
+            .or_insert(package);
+        if compare_versions(&package.version, &current.version) > 0 {
+            *current = package;
+        }
+    }
+    newest
+}
+
+/// Compare the packages of the destination directory with the latest versions of the marketplace
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+///
+/// # Returns
+///
+/// A Result containing the outdated packages or an error that occurred
+pub async fn find_outdated(
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<OutdatedReport> {
+    let packages = scan_mirror(&options.destination)?;
+    let newest = newest_packages(&packages);
+    let checks = newest.into_values().map(|package| async move {
+        let result = match Extension::from_id(&package.id) {
+            Ok(extension) => {
+                resolve_extension(extension, options, package.target_platform.as_ref()).await
+            }
+            Err(e) => Err(e),
+        };
+        (package, result)
+    });
+    let results: Vec<_> = stream::iter(checks).buffered(concurrency).collect().await;
+
+    let mut report = OutdatedReport {
+        checked: results.len(),
+        ..Default::default()
+    };
+    for (package, result) in results {
+        let latest = match result {
+            Ok(latest) => latest,
+            Err(e) => {
+                warn!(
+                    "Could not check the latest version of {}: {}",
+                    package.file_name, e
+                );
+                report.unchecked.push(package.file_name.clone());
+                continue;
+            }
+        };
+        if compare_versions(&latest.version, &package.version) > 0 {
+            report.outdated.push(OutdatedPackage {
+                id: package.id.clone(),
+                target_platform: package.target_platform.clone(),
+                local_version: package.version.clone(),
+                latest_version: latest.version,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Print the packages of the destination directory behind the marketplace
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+/// * `json` - Whether to print JSON instead of a table
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_outdated(
+    options: &DownloadOptions,
+    concurrency: usize,
+    json: bool,
+) -> Result<()> {
+    let report = find_outdated(options, concurrency).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!(
+        "{:<50} {:<14} {:<14} {:<14}",
+        "EXTENSION", "PLATFORM", "LOCAL", "LATEST"
+    );
+    for outdated in &report.outdated {
+        println!(
+            "{:<50} {:<14} {:<14} {:<14}",
+            outdated.id,
+            target_platform_name(outdated.target_platform.as_ref()),
+            outdated.local_version,
+            outdated.latest_version
+        );
+    }
+    println!();
+    println!(
+        "{} of {} package(s) outdated",
+        report.outdated.len(),
+        report.checked
+    );
+    if !report.unchecked.is_empty() {
+        println!("Unchecked: {}", report.unchecked.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mirrored_package;
+
+    #[test]
+    fn test_newest_packages() {
+        let package = |id: &str, version: &str, target_platform: Option<&str>| {
+            mirrored_package(id, version, target_platform, 10)
+        };
+        let packages = [
+            package("golang.Go", "0.41.2", None),
+            package("golang.Go", "0.41.10", None),
+            package("rust-lang.rust-analyzer", "0.3.2", Some("linux-x64")),
+            package("rust-lang.rust-analyzer", "0.3.1", Some("win32-x64")),
+        ];
+        let newest = newest_packages(&packages);
+        assert_eq!(newest.len(), 3);
+        assert_eq!(newest[&("golang.go".to_string(), None)].version, "0.41.10");
+        assert_eq!(
+            newest[&(
+                "rust-lang.rust-analyzer".to_string(),
+                Some("win32-x64".into())
+            )]
+                .version,
+            "0.3.1"
+        );
+    }
+}
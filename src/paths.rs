@@ -0,0 +1,142 @@
+use crate::architecture::Architecture;
+use crate::storage::is_local_location;
+use std::convert::Infallible;
+use std::path::Path;
+
+/// Expand the leading `~` and the Windows-style `%NAME%` environment variables of a path
+///
+/// The home directory is `HOME`, or `USERPROFILE` on Windows. Like cmd.exe, a
+/// `%NAME%` of a variable that is not set is kept as is.
+///
+/// # Arguments
+///
+/// * `path` - The path to expand (e.g., "~/mirror", "%USERPROFILE%\\mirror")
+/// * `lookup` - The function returning the value of an environment variable
+///
+/// # Returns
+///
+/// The expanded path
+pub fn expand_path_with(path: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        let Some(end) = rest[start + 1..].find('%').map(|end| start + 1 + end) else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        let name = &rest[start + 1..end];
+        match lookup(name).filter(|_| !name.is_empty()) {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                // The closing `%` may open the next variable
+                expanded.push_str(&rest[start..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    let tilde = expanded
+        .strip_prefix('~')
+        .filter(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\'));
+    match (tilde, lookup("HOME").or_else(|| lookup("USERPROFILE"))) {
+        (Some(rest), Some(home)) => format!("{}{}", home, rest),
+        _ => expanded,
+    }
+}
+
+/// Expand the leading `~` and the `%NAME%` environment variables of a path
+///
+/// # Arguments
+///
+/// * `path` - The path to expand
+///
+/// # Returns
+///
+/// The expanded path
+pub fn expand_path(path: &str) -> String {
+    expand_path_with(path, &|name| {
+        std::env::var(name).ok().filter(|value| !value.is_empty())
+    })
+}
+
+/// Resolve a path read from a config file against the directory of that file
+///
+/// Remote storages (`s3://`, `webdav://`) and absolute paths are kept as is.
+///
+/// # Arguments
+///
+/// * `path` - The path read from the config file
+/// * `config_path` - The path of the config file
+///
+/// # Returns
+///
+/// The expanded path, relative paths joined to the directory of the config file
+pub fn resolve_path(path: &str, config_path: &str) -> String {
+    let expanded = expand_path(path);
+    if !is_local_location(&expanded) || Path::new(&expanded).is_absolute() {
+        return expanded;
+    }
+    match Path::new(config_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(&expanded).display().to_string(),
+        _ => expanded,
+    }
+}
+
+/// Parse a path of the command line, expanding `~` and `%NAME%`
+pub fn parse_path(value: &str) -> std::result::Result<String, Infallible> {
+    Ok(expand_path(value))
+}
+
+/// Parse a destination of the command line, expanding the directory of `platform=directory`
+pub fn parse_destination(value: &str) -> std::result::Result<String, Infallible> {
+    Ok(match value.split_once('=') {
+        Some((platform, path)) if Architecture::from_cli_arg(platform).is_some() => {
+            format!("{}={}", platform, expand_path(path))
+        }
+        _ => expand_path(value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_path() {
+        let lookup = |name: &str| match name {
+            "USERPROFILE" => Some("C:\\Users\\ci".to_string()),
+            "MIRROR" => Some("/srv/mirror".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_path_with("%USERPROFILE%\\mirror", &lookup),
+            "C:\\Users\\ci\\mirror"
+        );
+        assert_eq!(
+            expand_path_with("~/mirror", &lookup),
+            "C:\\Users\\ci/mirror"
+        );
+        assert_eq!(expand_path_with("~", &lookup), "C:\\Users\\ci");
+        assert_eq!(expand_path_with("~user/mirror", &lookup), "~user/mirror");
+        assert_eq!(
+            expand_path_with("50%UNSET%MIRROR%/x", &lookup),
+            "50%UNSET/srv/mirror/x"
+        );
+        assert_eq!(expand_path_with("100%", &lookup), "100%");
+
+        assert_eq!(
+            resolve_path("team-a.json", "/etc/vsixharvester/schedules.json"),
+            "/etc/vsixharvester/team-a.json"
+        );
+        assert_eq!(resolve_path("/srv/a", "/etc/schedules.json"), "/srv/a");
+        assert_eq!(
+            resolve_path("s3://bucket/a", "/etc/schedules.json"),
+            "s3://bucket/a"
+        );
+        assert_eq!(resolve_path("team-a.json", "schedules.json"), "team-a.json");
+    }
+}
@@ -0,0 +1,554 @@
+use crate::assets::build_asset_url;
+use crate::budget::parse_size;
+use crate::budget::SizeBudgetPolicy;
+use crate::config::USER_AGENT;
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::marketplace::{
+    build_client, fetch_download_size, query_marketplace_extension, send_request, DownloadOptions,
+    ResolvedExtension,
+};
+use crate::types::Version;
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use futures::stream::{self, StreamExt};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Asset type of the package.json of an extension version
+const MANIFEST_ASSET_TYPE: &str = "Microsoft.VisualStudio.Code.Manifest";
+
+/// What to do with the packages violating the harvest policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PolicyMode {
+    /// Fail the harvest before downloading anything
+    #[default]
+    Enforce,
+    /// Log the violations and download the packages anyway
+    Warn,
+}
+
+/// Rules every resolved package of a harvest must follow
+///
+/// Every rule is optional, an empty policy allows everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HarvestPolicy {
+    /// Publishers allowed, every publisher when empty
+    #[serde(default)]
+    pub allow_publishers: Vec<String>,
+    /// Publishers refused
+    #[serde(default)]
+    pub deny_publishers: Vec<String>,
+    /// Refuse the publishers without a verified domain
+    #[serde(default)]
+    pub require_verified_domain: bool,
+    /// Maximum size of a package (e.g., "50M")
+    #[serde(default)]
+    pub max_size: Option<String>,
+    /// SPDX identifiers of the licenses allowed, every license when empty
+    #[serde(default)]
+    pub allow_licenses: Vec<String>,
+    /// Refuse the pre-release versions
+    #[serde(default)]
+    pub no_pre_release: bool,
+    /// Refuse the versions published more than this number of days ago
+    #[serde(default)]
+    pub max_age_days: Option<i64>,
+    /// Refuse the packages without a Marketplace signature, like `--require-signature`
+    #[serde(default)]
+    pub require_signature: bool,
+    /// Maximum total size of the downloads (e.g., "10G"), unless `--max-total-size` is given
+    #[serde(default)]
+    pub max_total_size: Option<String>,
+    /// What to do when the downloads exceed `maxTotalSize`
+    #[serde(default)]
+    pub size_budget_policy: SizeBudgetPolicy,
+}
+
+/// A package breaking a rule of the harvest policy
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct PolicyViolation {
+    /// The package, as "publisher.name@version" with its target platform
+    pub package: String,
+    /// The rule of the policy file
+    pub rule: &'static str,
+    pub reason: String,
+}
+
+/// Get the description of a resolved package used in the violations
+fn package_name(resolved: &ResolvedExtension) -> String {
+    match &resolved.target_platform {
+        Some(target_platform) => {
+            format!("{}@{} ({})", resolved.id, resolved.version, target_platform)
+        }
+        None => format!("{}@{}", resolved.id, resolved.version),
+    }
+}
+
+/// Check if a license, possibly an SPDX `OR` expression, is allowed
+///
+/// # Arguments
+///
+/// * `license` - The license of the package.json (e.g., "MIT" or "(MIT OR Apache-2.0)")
+/// * `allowed` - The SPDX identifiers allowed
+///
+/// # Returns
+///
+/// `true` if the license or one of its alternatives is allowed
+pub fn license_allowed(license: &str, allowed: &[String]) -> bool {
+    license
+        .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+        .split(" OR ")
+        .map(|alternative| {
+            alternative.trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+        })
+        .any(|alternative| {
+            allowed
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(alternative))
+        })
+}
+
+impl HarvestPolicy {
+    /// Load a policy file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the JSON policy file
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the policy or an error that occurred
+    pub fn load(path: &str) -> Result<Self> {
+        let policy: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        for size in [&policy.max_size, &policy.max_total_size]
+            .into_iter()
+            .flatten()
+        {
+            parse_size(size)?;
+        }
+        Ok(policy)
+    }
+
+    /// Check if a rule needs the marketplace metadata of the packages
+    fn needs_metadata(&self) -> bool {
+        self.require_verified_domain || self.no_pre_release || self.max_age_days.is_some()
+    }
+
+    /// Find the resolved version in the marketplace metadata of a package
+    fn find_version(resolved: &ResolvedExtension) -> Option<&Version> {
+        resolved.metadata.as_ref()?.versions.iter().find(|version| {
+            version.version == resolved.version
+                && (version.target_platform.is_none()
+                    || version.target_platform == resolved.target_platform)
+        })
+    }
+
+    /// Query the marketplace metadata of a package resolved without its version, such as
+    /// a locked or pinned version, when a rule needs it
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved package
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the package with every version in its metadata, None when the
+    /// metadata of the package is enough, or an error that occurred
+    async fn query_metadata(
+        &self,
+        resolved: &ResolvedExtension,
+        options: &DownloadOptions,
+    ) -> Result<Option<ResolvedExtension>> {
+        if !self.needs_metadata() || Self::find_version(resolved).is_some() {
+            return Ok(None);
+        }
+        let extension = Extension::from_id(&resolved.id)?;
+        let metadata = query_marketplace_extension(&extension, options, true).await?;
+        Ok(Some(ResolvedExtension {
+            metadata: Some(metadata),
+            ..resolved.clone()
+        }))
+    }
+
+    /// Check the rules answered by the marketplace metadata of a resolved package
+    ///
+    /// The publisher rules only need the identifier. The other rules are reported as
+    /// unchecked for the packages whose metadata does not list the resolved version.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved package
+    /// * `now` - The current date, for the age of the version
+    ///
+    /// # Returns
+    ///
+    /// The violated rules
+    pub fn check_metadata(
+        &self,
+        resolved: &ResolvedExtension,
+        now: DateTime<Utc>,
+    ) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let mut violation = |rule: &'static str, reason: String| {
+            violations.push(PolicyViolation {
+                package: package_name(resolved),
+                rule,
+                reason,
+            })
+        };
+        let publisher = resolved
+            .id
+            .split_once('.')
+            .map_or(resolved.id.as_str(), |(publisher, _)| publisher);
+        if !self.allow_publishers.is_empty()
+            && !self
+                .allow_publishers
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(publisher))
+        {
+            violation(
+                "allowPublishers",
+                format!("publisher {} is not allowed", publisher),
+            );
+        }
+        if self
+            .deny_publishers
+            .iter()
+            .any(|denied| denied.eq_ignore_ascii_case(publisher))
+        {
+            violation(
+                "denyPublishers",
+                format!("publisher {} is denied", publisher),
+            );
+        }
+
+        if !self.needs_metadata() {
+            return violations;
+        }
+        let (Some(metadata), Some(version)) = (&resolved.metadata, Self::find_version(resolved))
+        else {
+            violation(
+                "unchecked",
+                "the marketplace metadata of the version is unknown".to_string(),
+            );
+            return violations;
+        };
+        if self.require_verified_domain && !metadata.publisher.is_domain_verified {
+            violation(
+                "requireVerifiedDomain",
+                format!("publisher {} has no verified domain", publisher),
+            );
+        }
+        if self.no_pre_release
+            && version
+                .get_vscode_prerelease()
+                .is_some_and(|pre_release| pre_release.contains("true"))
+        {
+            violation("noPreRelease", "the version is a pre-release".to_string());
+        }
+        if let Some(max_age_days) = self.max_age_days {
+            match DateTime::parse_from_rfc3339(&version.last_updated) {
+                Ok(last_updated) => {
+                    let age = now - last_updated.with_timezone(&Utc);
+                    if age > Duration::days(max_age_days) {
+                        violation(
+                            "maxAgeDays",
+                            format!(
+                                "the version was published {} days ago, more than {}",
+                                age.num_days(),
+                                max_age_days
+                            ),
+                        );
+                    }
+                }
+                Err(_) => violation(
+                    "unchecked",
+                    format!("the publication date {} is invalid", version.last_updated),
+                ),
+            }
+        }
+        violations
+    }
+
+    /// Check the rules needing a request for a resolved package: its size and license
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved package
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the violated rules or an error that occurred
+    async fn check_remote(
+        &self,
+        resolved: &ResolvedExtension,
+        options: &DownloadOptions,
+    ) -> Result<Vec<PolicyViolation>> {
+        let mut violations = Vec::new();
+        if let Some(max_size) = &self.max_size {
+            let max_size = parse_size(max_size)?;
+            if let Some(size) = fetch_download_size(resolved, options).await? {
+                if size > max_size {
+                    violations.push(PolicyViolation {
+                        package: package_name(resolved),
+                        rule: "maxSize",
+                        reason: format!("the package has {} bytes, more than {}", size, max_size),
+                    });
+                }
+            }
+        }
+        if !self.allow_licenses.is_empty() {
+            let client = build_client(options)?;
+            let url = build_asset_url(&resolved.id, &resolved.version, MANIFEST_ASSET_TYPE);
+            let resp = send_request(client.get(&url).header("User-Agent", USER_AGENT), options)
+                .await?
+                .error_for_status()?;
+            let manifest: serde_json::Value = resp.json().await?;
+            let reason = match manifest["license"].as_str() {
+                Some(license) if license_allowed(license, &self.allow_licenses) => None,
+                Some(license) => Some(format!("license {} is not allowed", license)),
+                None => Some("the package declares no license".to_string()),
+            };
+            if let Some(reason) = reason {
+                violations.push(PolicyViolation {
+                    package: package_name(resolved),
+                    rule: "allowLicenses",
+                    reason,
+                });
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Evaluate the policy on the resolved packages of a harvest
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved packages
+    /// * `options` - The download options
+    /// * `concurrency` - The maximum number of concurrent requests
+    ///
+    /// # Returns
+    ///
+    /// The violations, a package whose metadata, size or license could not be checked is
+    /// a violation
+    pub async fn evaluate(
+        &self,
+        resolved: &[ResolvedExtension],
+        options: &DownloadOptions,
+        concurrency: usize,
+    ) -> Vec<PolicyViolation> {
+        let now = Utc::now();
+        let checks = resolved.iter().map(|resolved| async move {
+            let queried = match self.query_metadata(resolved, options).await {
+                Ok(queried) => queried,
+                Err(e) => {
+                    warn!("Could not query the metadata of {}: {}", resolved.id, e);
+                    None
+                }
+            };
+            let mut violations = self.check_metadata(queried.as_ref().unwrap_or(resolved), now);
+            match self.check_remote(resolved, options).await {
+                Ok(remote) => violations.extend(remote),
+                Err(e) => violations.push(PolicyViolation {
+                    package: package_name(resolved),
+                    rule: "unchecked",
+                    reason: format!("could not be checked: {}", e),
+                }),
+            }
+            violations
+        });
+        stream::iter(checks)
+            .buffered(concurrency)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Check the resolved packages of a harvest against a policy file
+///
+/// # Arguments
+///
+/// * `path` - The path of the policy file
+/// * `mode` - What to do with the violations
+/// * `resolved` - The resolved packages
+/// * `options` - The download options
+/// * `concurrency` - The maximum number of concurrent requests
+///
+/// # Returns
+///
+/// A Result containing the packages to download, or a policy violation error in enforce mode
+pub async fn apply_policy(
+    path: &str,
+    mode: PolicyMode,
+    resolved: Vec<ResolvedExtension>,
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<Vec<ResolvedExtension>> {
+    let policy = HarvestPolicy::load(path)?;
+    let violations = policy.evaluate(&resolved, options, concurrency).await;
+    if violations.is_empty() {
+        info!(
+            "The {} resolved package(s) follow the policy {}",
+            resolved.len(),
+            path
+        );
+        return Ok(resolved);
+    }
+    for violation in &violations {
+        match mode {
+            PolicyMode::Enforce => error!(
+                "Policy {}: {} violates {}: {}",
+                path, violation.package, violation.rule, violation.reason
+            ),
+            PolicyMode::Warn => warn!(
+                "Policy {}: {} violates {}: {}",
+                path, violation.package, violation.rule, violation.reason
+            ),
+        }
+    }
+    if mode == PolicyMode::Enforce {
+        return Err(VsixHarvesterError::PolicyViolation(format!(
+            "{} violation(s) of {}",
+            violations.len(),
+            path
+        )));
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn resolved(id: &str, pre_release: &str, last_updated: &str) -> ResolvedExtension {
+        let properties = json!([
+            {"key": "Microsoft.VisualStudio.Code.PreRelease", "value": pre_release}
+        ]);
+        let metadata = test_support::metadata(
+            id,
+            json!({
+                "lastUpdated": last_updated,
+                "versions": [test_support::version(
+                    "1.0.0",
+                    json!({"lastUpdated": last_updated, "properties": properties})
+                )]
+            }),
+        );
+        ResolvedExtension {
+            metadata: Some(metadata),
+            ..test_support::resolved(id, "1.0.0")
+        }
+    }
+
+    #[test]
+    fn test_check_metadata() {
+        let policy: HarvestPolicy = serde_json::from_str(
+            r#"{"allowPublishers": ["golang", "evil"], "denyPublishers": ["Evil"],
+                "requireVerifiedDomain": true, "noPreRelease": true, "maxAgeDays": 365}"#,
+        )
+        .unwrap();
+        let now = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let rules = |resolved: &ResolvedExtension| -> Vec<&'static str> {
+            policy
+                .check_metadata(resolved, now)
+                .into_iter()
+                .map(|violation| violation.rule)
+                .collect()
+        };
+        assert_eq!(
+            rules(&resolved("golang.Go", "false", "2025-12-01T00:00:00Z")),
+            vec!["requireVerifiedDomain"]
+        );
+        assert_eq!(
+            rules(&resolved("evil.Ext", "true", "2020-01-01T00:00:00Z")),
+            vec![
+                "denyPublishers",
+                "requireVerifiedDomain",
+                "noPreRelease",
+                "maxAgeDays"
+            ]
+        );
+        let mut locked = resolved("other.Ext", "true", "2020-01-01T00:00:00Z");
+        locked.metadata = None;
+        assert_eq!(rules(&locked), vec!["allowPublishers", "unchecked"]);
+
+        assert!(serde_json::from_str::<HarvestPolicy>(r#"{"maxsize": "1M"}"#).is_err());
+        let allowed = vec!["MIT".to_string(), "Apache-2.0".to_string()];
+        assert!(license_allowed("mit", &allowed));
+        assert!(license_allowed("(GPL-3.0 OR Apache-2.0)", &allowed));
+        assert!(!license_allowed("GPL-3.0", &allowed));
+    }
+
+    #[test]
+    fn test_evaluate_queries_missing_metadata() {
+        // A locked or pinned version resolved without metadata is checked against the
+        // metadata of the registry
+        let policy: HarvestPolicy = serde_json::from_str(
+            r#"{"noPreRelease": true, "requireSignature": true, "maxTotalSize": "1G",
+                "sizeBudgetPolicy": "installs"}"#,
+        )
+        .unwrap();
+        assert!(policy.require_signature);
+        assert_eq!(policy.size_budget_policy, SizeBudgetPolicy::Installs);
+        let properties = json!([
+            {"key": "Microsoft.VisualStudio.Code.PreRelease", "value": "true"}
+        ]);
+        let options = DownloadOptions {
+            registry: Arc::new(test_support::FakeRegistry {
+                versions: vec![test_support::version(
+                    "1.0.0",
+                    json!({"properties": properties}),
+                )],
+            }),
+            ..Default::default()
+        };
+        let evaluate = |resolved: ResolvedExtension| -> Vec<&'static str> {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(policy.evaluate(&[resolved], &options, 1))
+                .into_iter()
+                .map(|violation| violation.rule)
+                .collect()
+        };
+        assert_eq!(
+            evaluate(test_support::resolved("golang.Go", "1.0.0")),
+            vec!["noPreRelease"]
+        );
+        // A version the registry does not list cannot be checked
+        assert_eq!(
+            evaluate(test_support::resolved("golang.Go", "0.9.0")),
+            vec!["unchecked"]
+        );
+    }
+
+    #[test]
+    fn test_policy_mode_flags() {
+        use crate::args::{Args, Parser};
+
+        let parse = |flags: &[&str]| {
+            Args::try_parse_from(std::iter::once("vsixHarvester").chain(flags.iter().copied()))
+        };
+        assert_eq!(parse(&[]).unwrap().policy_mode(), PolicyMode::Enforce);
+        assert_eq!(
+            parse(&["--enforce"]).unwrap().policy_mode(),
+            PolicyMode::Enforce
+        );
+        assert_eq!(parse(&["--warn"]).unwrap().policy_mode(), PolicyMode::Warn);
+        assert!(parse(&["--enforce", "--warn"]).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::architecture::TargetPlatform;
+use crate::config::TASK_RETRY_DELAY_MS;
+use crate::error::{Result, VsixHarvesterError};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::{Id, JoinSet};
+use tokio_util::sync::CancellationToken;
+
+/// Metadata of a task of the pool, reported with its result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskMeta {
+    /// The extension identifier in the format "publisher.name"
+    pub id: String,
+    /// The target platform of the task, None for universal packages
+    pub target_platform: Option<TargetPlatform>,
+    /// The attempt number, starting at 1
+    pub attempt: u32,
+}
+
+impl TaskMeta {
+    /// Create the metadata of the first attempt of a task
+    pub fn new(id: &str, target_platform: Option<&TargetPlatform>) -> Self {
+        Self {
+            id: id.to_string(),
+            target_platform: target_platform.cloned(),
+            attempt: 1,
+        }
+    }
+}
+
+impl fmt::Display for TaskMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(target_platform) = &self.target_platform {
+            write!(f, "@{}", target_platform)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of a task of the pool
+#[derive(Debug)]
+pub struct TaskReport<T> {
+    /// The metadata of the last attempt
+    pub meta: TaskMeta,
+    /// The result of the last attempt
+    pub result: Result<T>,
+}
+
+/// Bounded pool running the tasks of a harvest on the tokio runtime
+///
+/// Each task carries its metadata, may be limited in time and is retried on
+/// transient failures. Dropping the pool future or cancelling its token aborts
+/// the running tasks.
+#[derive(Debug, Clone)]
+pub struct TaskPool {
+    concurrency: usize,
+    timeout: Option<Duration>,
+    retries: u32,
+    cancellation: CancellationToken,
+}
+
+impl TaskPool {
+    /// Create a pool running at most `concurrency` tasks at once
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            timeout: None,
+            retries: 0,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// Limit the duration of each attempt of a task
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry the tasks failing with a transient error this number of times
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Abort the running tasks and skip the pending ones once this token is cancelled
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Run the tasks of the inputs and report their results
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The inputs of the tasks with their metadata
+    /// * `task` - The function creating the future of an attempt from its input
+    ///
+    /// # Returns
+    ///
+    /// The reports of the tasks in the order of the inputs
+    pub async fn run<I, T, F, Fut>(&self, inputs: Vec<(TaskMeta, I)>, task: F) -> Vec<TaskReport<T>>
+    where
+        I: Clone + Send + 'static,
+        T: Send + 'static,
+        F: Fn(I) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let total = inputs.len();
+        let mut pending: VecDeque<(usize, TaskMeta, I)> = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, (meta, input))| (index, meta, input))
+            .collect();
+        let mut running: HashMap<Id, (usize, TaskMeta, I)> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let mut reports: Vec<Option<TaskReport<T>>> = (0..total).map(|_| None).collect();
+        let mut completed = 0;
+
+        loop {
+            if self.cancellation.is_cancelled() {
+                tasks.abort_all();
+                for (index, meta, _) in pending.drain(..) {
+                    let result = Err(VsixHarvesterError::Cancelled);
+                    reports[index] = Some(TaskReport { meta, result });
+                }
+            }
+            while tasks.len() < self.concurrency {
+                let Some((index, meta, input)) = pending.pop_front() else {
+                    break;
+                };
+                let future = task(input.clone());
+                let timeout = self.timeout;
+                let attempt = meta.attempt;
+                let handle = tasks.spawn(async move {
+                    if attempt > 1 {
+                        let delay = TASK_RETRY_DELAY_MS * u64::from(attempt - 1);
+                        tokio::time::sleep(Duration::from_millis(delay)).await;
+                    }
+                    match timeout {
+                        Some(timeout) => tokio::time::timeout(timeout, future)
+                            .await
+                            .unwrap_or(Err(VsixHarvesterError::Timeout(timeout.as_secs()))),
+                        None => future.await,
+                    }
+                });
+                running.insert(handle.id(), (index, meta, input));
+            }
+
+            let joined = tokio::select! {
+                joined = tasks.join_next_with_id() => joined,
+                _ = self.cancellation.cancelled(), if !self.cancellation.is_cancelled() => continue,
+            };
+            let Some(joined) = joined else {
+                break;
+            };
+            let (id, result) = match joined {
+                Ok((id, result)) => (id, result),
+                Err(e) if e.is_cancelled() => (e.id(), Err(VsixHarvesterError::Cancelled)),
+                Err(e) => (
+                    e.id(),
+                    Err(VsixHarvesterError::DownloadError(format!(
+                        "task failed: {}",
+                        e
+                    ))),
+                ),
+            };
+            let Some((index, mut meta, input)) = running.remove(&id) else {
+                continue;
+            };
+            if let Err(e) = &result {
+                if e.is_retryable() && meta.attempt <= self.retries {
+                    warn!(
+                        "{} failed (attempt {}), retrying: {}",
+                        meta, meta.attempt, e
+                    );
+                    meta.attempt += 1;
+                    pending.push_back((index, meta, input));
+                    continue;
+                }
+            }
+            completed += 1;
+            info!("[{}/{}] {} done", completed, total, meta);
+            reports[index] = Some(TaskReport { meta, result });
+        }
+
+        reports.into_iter().flatten().collect()
+    }
+}
+
+/// Counters of the packages of a harvest, shared with the embedding application
+///
+/// Clones share the same counters, so a service can read the progress of a harvest
+/// it passed the progress to through `Args::progress`.
+#[derive(Debug, Clone, Default)]
+pub struct HarvestProgress {
+    total: Arc<AtomicUsize>,
+    downloaded: Arc<AtomicUsize>,
+    failed: Arc<AtomicUsize>,
+}
+
+/// The progress of a harvest at a point in time
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// The packages to download
+    pub total: usize,
+    /// The packages downloaded or already present
+    pub downloaded: usize,
+    /// The packages whose download failed
+    pub failed: usize,
+}
+
+impl HarvestProgress {
+    /// Add packages to download
+    pub fn add_total(&self, packages: usize) {
+        self.total.fetch_add(packages, Ordering::Relaxed);
+    }
+
+    /// Record the end of the download of a package
+    pub fn record(&self, success: bool) {
+        let counter = if success {
+            &self.downloaded
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the counters
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            total: self.total.load(Ordering::Relaxed),
+            downloaded: self.downloaded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_task_pool() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let inputs = (0..5)
+            .map(|value| (TaskMeta::new(&format!("test.ext{}", value), None), value))
+            .collect();
+        let pool = TaskPool::new(2)
+            .retries(1)
+            .timeout(Some(Duration::from_secs(5)));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let reports = runtime.block_on(pool.run(inputs, |value: u32| {
+            let calls = calls.clone();
+            async move {
+                // The third task fails on its first attempt only
+                if value == 2 && calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(VsixHarvesterError::DownloadError("reset".to_string()));
+                }
+                if value == 4 {
+                    return Err(VsixHarvesterError::InvalidVsix("broken".to_string()));
+                }
+                Ok(value * 10)
+            }
+        }));
+
+        assert_eq!(reports.len(), 5);
+        assert_eq!(reports[0].meta.id, "test.ext0");
+        assert_eq!(reports[2].result.as_ref().unwrap(), &20);
+        assert_eq!(reports[2].meta.attempt, 2);
+        // Permanent failures are not retried
+        assert!(reports[4].result.is_err());
+        assert_eq!(reports[4].meta.attempt, 1);
+    }
+
+    #[test]
+    fn test_task_pool_cancellation() {
+        let cancellation = CancellationToken::new();
+        let inputs = (0..3)
+            .map(|value| (TaskMeta::new(&format!("test.ext{}", value), None), value))
+            .collect();
+        let pool = TaskPool::new(1).cancellation(cancellation.clone());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let reports = runtime.block_on(pool.run(inputs, move |value: u32| {
+            let cancellation = cancellation.clone();
+            async move {
+                // The first task cancels the run and never completes
+                if value == 0 {
+                    cancellation.cancel();
+                    std::future::pending::<()>().await;
+                }
+                Ok(value)
+            }
+        }));
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports
+            .iter()
+            .all(|report| matches!(report.result, Err(VsixHarvesterError::Cancelled))));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::architecture::TargetPlatform;
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{DownloadOptions, DownloadedPackage, ResolvedExtension};
+use crate::registry::Registry;
+use crate::types::Extension as MarketplaceExtension;
+use futures::future::BoxFuture;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A registry answering the queries of the extensions prefetched in batched windows
+///
+/// The extensions missing from the prefetched metadata, because their window failed or
+/// they were not found, are queried one by one from the wrapped registry.
+#[derive(Debug)]
+pub struct PrefetchedRegistry {
+    inner: Arc<dyn Registry>,
+    /// The metadata keyed by lowercase identifier and whether every version was queried
+    metadata: HashMap<(String, bool), MarketplaceExtension>,
+}
+
+impl Registry for PrefetchedRegistry {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn query_extension<'a>(
+        &'a self,
+        extension: &'a Extension<'_>,
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+        match self
+            .metadata
+            .get(&(extension.to_id().to_lowercase(), all_versions))
+        {
+            Some(metadata) => {
+                let metadata = metadata.clone();
+                Box::pin(async move { Ok(metadata) })
+            }
+            None => self.inner.query_extension(extension, options, all_versions),
+        }
+    }
+
+    fn query_extensions<'a>(
+        &'a self,
+        ids: &'a [String],
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<HashMap<String, MarketplaceExtension>>> {
+        self.inner.query_extensions(ids, options, all_versions)
+    }
+
+    fn target_platforms(&self) -> Option<&'static [&'static str]> {
+        self.inner.target_platforms()
+    }
+
+    fn package_url(
+        &self,
+        extension: &Extension<'_>,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+        metadata: Option<&MarketplaceExtension>,
+        options: &DownloadOptions,
+    ) -> String {
+        self.inner
+            .package_url(extension, version, target_platform, metadata, options)
+    }
+
+    fn signature_url(
+        &self,
+        resolved: &ResolvedExtension,
+        options: &DownloadOptions,
+    ) -> Option<String> {
+        self.inner.signature_url(resolved, options)
+    }
+
+    fn last_updated<'a>(
+        &'a self,
+        ids: &'a [String],
+        options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<HashMap<String, String>>> {
+        self.inner.last_updated(ids, options)
+    }
+
+    fn download<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        resolved: &'a ResolvedExtension,
+        options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<DownloadedPackage>> {
+        self.inner.download(client, resolved, options)
+    }
+}
+
+/// Prefetch the metadata of many extensions in batched query windows
+///
+/// Each window is a single registry query of at most `window_size` extensions, and
+/// the windows are sent one after the other, `delay` apart. A failed window is logged
+/// and its extensions are left to the individual queries of the resolution.
+///
+/// # Arguments
+///
+/// * `ids` - The identifiers of the extensions to query, with whether every version is needed
+/// * `options` - The download options, with the registry to query
+/// * `window_size` - The maximum number of extensions of a query
+/// * `delay` - The pause between two windows
+///
+/// # Returns
+///
+/// The registry answering from the prefetched metadata, wrapping the registry of the options
+pub async fn prefetch_metadata(
+    ids: &[(String, bool)],
+    options: &DownloadOptions,
+    window_size: usize,
+    delay: Duration,
+) -> PrefetchedRegistry {
+    let mut metadata = HashMap::new();
+    for all_versions in [false, true] {
+        let mut group: Vec<String> = ids
+            .iter()
+            .filter(|(_, all)| *all == all_versions)
+            .map(|(id, _)| id.clone())
+            .collect();
+        group.sort_by_key(|id| id.to_lowercase());
+        group.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        for (index, window) in group.chunks(window_size.max(1)).enumerate() {
+            if index > 0 && !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            match options
+                .registry
+                .query_extensions(window, options, all_versions)
+                .await
+            {
+                Ok(found) => metadata.extend(
+                    found
+                        .into_iter()
+                        .map(|(id, extension)| ((id, all_versions), extension)),
+                ),
+                Err(e) => warn!(
+                    "Query window {} of {} extension(s) failed, querying them one by one: {}",
+                    index + 1,
+                    window.len(),
+                    e
+                ),
+            }
+        }
+    }
+    info!(
+        "Prefetched the metadata of {} of {} extension(s)",
+        metadata.len(),
+        ids.len()
+    );
+    PrefetchedRegistry {
+        inner: options.registry.clone(),
+        metadata,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A registry whose second window fails
+    #[derive(Debug, Default)]
+    struct FlakyRegistry {
+        windows: AtomicUsize,
+        single_queries: AtomicUsize,
+    }
+
+    fn metadata(id: &str) -> MarketplaceExtension {
+        test_support::metadata(id, serde_json::json!({}))
+    }
+
+    impl Registry for FlakyRegistry {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn query_extension<'a>(
+            &'a self,
+            extension: &'a Extension<'_>,
+            _options: &'a DownloadOptions,
+            _all_versions: bool,
+        ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+            self.single_queries.fetch_add(1, Ordering::SeqCst);
+            let id = extension.to_id();
+            Box::pin(async move { Ok(metadata(&id)) })
+        }
+
+        fn query_extensions<'a>(
+            &'a self,
+            ids: &'a [String],
+            _options: &'a DownloadOptions,
+            _all_versions: bool,
+        ) -> BoxFuture<'a, Result<HashMap<String, MarketplaceExtension>>> {
+            let window = self.windows.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                if window == 1 {
+                    return Err(crate::error::VsixHarvesterError::ApiError(
+                        "throttled".to_string(),
+                    ));
+                }
+                Ok(ids
+                    .iter()
+                    .map(|id| (id.to_lowercase(), metadata(id)))
+                    .collect())
+            })
+        }
+
+        fn package_url(
+            &self,
+            extension: &Extension<'_>,
+            version: &str,
+            _target_platform: Option<&TargetPlatform>,
+            _metadata: Option<&MarketplaceExtension>,
+            _options: &DownloadOptions,
+        ) -> String {
+            format!(
+                "https://flaky.example.com/{}/{}",
+                extension.to_id(),
+                version
+            )
+        }
+    }
+
+    #[test]
+    fn test_prefetch_metadata() {
+        let registry = Arc::new(FlakyRegistry::default());
+        let options = DownloadOptions {
+            registry: registry.clone(),
+            ..Default::default()
+        };
+        let ids: Vec<(String, bool)> = ["a.one", "b.two", "c.three", "d.four", "e.five"]
+            .iter()
+            .map(|id| (id.to_string(), false))
+            .collect();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let prefetched = runtime.block_on(prefetch_metadata(&ids, &options, 2, Duration::ZERO));
+        // The windows are [a, b], [c, d] which fails, and [e]
+        assert_eq!(registry.windows.load(Ordering::SeqCst), 3);
+        assert_eq!(prefetched.metadata.len(), 3);
+
+        let found = runtime
+            .block_on(prefetched.query_extension(
+                &Extension::from_id("c.three").unwrap(),
+                &options,
+                false,
+            ))
+            .unwrap();
+        assert_eq!(found.get_identifier(), "c.three");
+        runtime
+            .block_on(prefetched.query_extension(
+                &Extension::from_id("a.one").unwrap(),
+                &options,
+                false,
+            ))
+            .unwrap();
+        // Only the extension of the failed window was queried on its own
+        assert_eq!(registry.single_queries.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,132 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fmt;
+
+/// The template of the bar of a package download
+const PACKAGE_TEMPLATE: &str =
+    "{msg:40!} [{bar:30}] {bytes:>10}/{total_bytes:<10} {bytes_per_sec:>12} ETA {eta}";
+/// The template of the bar of a package download of unknown size
+const PACKAGE_SPINNER_TEMPLATE: &str = "{msg:40!} {spinner} {bytes:>10} {bytes_per_sec:>12}";
+/// The template of the overall counter
+const OVERALL_TEMPLATE: &str = "Packages [{bar:30}] {pos}/{len} ({elapsed})";
+
+/// Progress bars of a batch download, drawn on stderr
+///
+/// An overall counter of the finished packages is followed by one bar per
+/// in-flight download with its bytes, speed and ETA.
+#[derive(Clone)]
+pub struct DownloadBars {
+    bars: MultiProgress,
+    overall: ProgressBar,
+}
+
+impl fmt::Debug for DownloadBars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DownloadBars")
+            .field("finished", &self.overall.position())
+            .field("total", &self.overall.length())
+            .finish()
+    }
+}
+
+impl DownloadBars {
+    /// Create the progress bars of a batch download
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The number of packages of the batch
+    ///
+    /// # Returns
+    ///
+    /// The progress bars
+    pub fn new(total: usize) -> Self {
+        Self::with_target(total, ProgressDrawTarget::stderr())
+    }
+
+    fn with_target(total: usize, target: ProgressDrawTarget) -> Self {
+        let bars = MultiProgress::with_draw_target(target);
+        let overall = bars.add(ProgressBar::new(total as u64));
+        overall.set_style(
+            ProgressStyle::with_template(OVERALL_TEMPLATE)
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        Self { bars, overall }
+    }
+
+    /// Add the bar of a package download
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The file name of the package
+    /// * `size` - The size of the package, None if unknown
+    /// * `offset` - The number of bytes already received, by a previous download
+    ///
+    /// # Returns
+    ///
+    /// The bar of the package, to advance with the received bytes
+    pub fn package(&self, name: &str, size: Option<u64>, offset: u64) -> PackageBar {
+        let bar = match size {
+            Some(size) => ProgressBar::new(size).with_style(
+                ProgressStyle::with_template(PACKAGE_TEMPLATE)
+                    .unwrap()
+                    .progress_chars("=> "),
+            ),
+            None => ProgressBar::no_length()
+                .with_style(ProgressStyle::with_template(PACKAGE_SPINNER_TEMPLATE).unwrap()),
+        };
+        let bar = self.bars.add(bar.with_message(name.to_string()));
+        bar.set_position(offset);
+        bar.reset_eta();
+        PackageBar(Some(bar))
+    }
+
+    /// Count a package of the batch as finished, downloaded or not
+    pub fn finish_package(&self) {
+        self.overall.inc(1);
+    }
+
+    /// Remove the bars once the batch is finished
+    pub fn finish(&self) {
+        self.overall.finish_and_clear();
+        let _ = self.bars.clear();
+    }
+}
+
+/// The bar of a package download, removed when dropped
+#[derive(Debug, Default)]
+pub struct PackageBar(Option<ProgressBar>);
+
+impl PackageBar {
+    /// Advance the bar with received bytes
+    pub fn inc(&self, bytes: u64) {
+        if let Some(bar) = &self.0 {
+            bar.inc(bytes);
+        }
+    }
+}
+
+impl Drop for PackageBar {
+    fn drop(&mut self) {
+        if let Some(bar) = self.0.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_bars() {
+        let bars = DownloadBars::with_target(2, ProgressDrawTarget::hidden());
+        let package = bars.package("golang.Go-0.41.2.vsix", Some(100), 40);
+        package.inc(60);
+        assert_eq!(package.0.as_ref().unwrap().position(), 100);
+        drop(package);
+        bars.finish_package();
+        assert_eq!(bars.overall.position(), 1);
+        assert_eq!(bars.overall.length(), Some(2));
+        bars.finish();
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::{PROVENANCE_SIDECAR_SUFFIX, PROVENANCE_XATTR};
+use crate::error::Result;
+use crate::temp::{copy_atomic, write_atomic};
+use crate::timezone::ReportTimezone;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Where the provenance of the downloaded packages is recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProvenanceStorage {
+    /// An extended attribute of the package, a sidecar file where they are not supported
+    Xattr,
+    /// A `.provenance.json` file next to the package
+    Sidecar,
+}
+
+/// How a package was verified when it was retrieved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verification {
+    /// The archive is a valid VSIX package
+    Validated,
+    /// The content matches the hash pinned in the lockfile and the archive is valid
+    Pinned,
+    /// The archive is valid and matches the digests of its Marketplace signature manifest,
+    /// whose PKCS#7 signature is not validated
+    DigestsMatched,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verification::Validated => write!(f, "valid VSIX package"),
+            Verification::Pinned => write!(f, "matches the pinned hash"),
+            Verification::DigestsMatched => {
+                write!(
+                    f,
+                    "matches the digests of the marketplace signature manifest"
+                )
+            }
+        }
+    }
+}
+
+/// Provenance of a downloaded package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    /// The URL the package was retrieved from
+    pub source_url: String,
+    pub retrieved_at: DateTime<Utc>,
+    /// When the version was published on the marketplace, if known when it was retrieved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<DateTime<Utc>>,
+    /// The SHA-256 of the package when it was retrieved
+    pub sha256: String,
+    pub verification: Verification,
+}
+
+impl Provenance {
+    /// Create the provenance of a package retrieved now
+    pub fn new(source_url: &str, sha256: &str, verification: Verification) -> Self {
+        Self {
+            source_url: source_url.to_string(),
+            retrieved_at: Utc::now(),
+            published_at: None,
+            sha256: sha256.to_string(),
+            verification,
+        }
+    }
+}
+
+/// Get the path of the provenance sidecar of a package
+pub fn sidecar_path(file_path: &str) -> String {
+    format!("{}{}", file_path, PROVENANCE_SIDECAR_SUFFIX)
+}
+
+/// Record the provenance of a package
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the package
+/// * `provenance` - The provenance of the package
+/// * `storage` - Where the provenance is recorded
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn record_provenance(
+    file_path: &str,
+    provenance: &Provenance,
+    storage: ProvenanceStorage,
+) -> Result<()> {
+    let content = serde_json::to_string_pretty(provenance)?;
+    if storage == ProvenanceStorage::Xattr {
+        match xattr::set(file_path, PROVENANCE_XATTR, content.as_bytes()) {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!(
+                "Extended attributes unsupported for {} ({}), using a sidecar file",
+                file_path, e
+            ),
+        }
+    }
+    write_atomic(Path::new(&sidecar_path(file_path)), content.as_bytes())
+}
+
+/// Read the provenance of a package, from its extended attribute or its sidecar file
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the package
+///
+/// # Returns
+///
+/// A Result containing the provenance, None if none was recorded, or an error that occurred
+pub fn read_provenance(file_path: &str) -> Result<Option<Provenance>> {
+    if let Ok(Some(content)) = xattr::get(file_path, PROVENANCE_XATTR) {
+        return Ok(Some(serde_json::from_slice(&content)?));
+    }
+    let sidecar = sidecar_path(file_path);
+    if Path::new(&sidecar).exists() {
+        return Ok(Some(serde_json::from_str(&fs::read_to_string(sidecar)?)?));
+    }
+    Ok(None)
+}
+
+/// Copy the provenance of a package to a copy of it
+///
+/// Extended attributes are not kept by plain copies, the provenance is recorded
+/// again on the copy in the same way.
+///
+/// # Arguments
+///
+/// * `source` - The path of the package
+/// * `target` - The path of the copy
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn copy_provenance(source: &str, target: &str) -> Result<()> {
+    if let Ok(Some(content)) = xattr::get(source, PROVENANCE_XATTR) {
+        if xattr::set(target, PROVENANCE_XATTR, &content).is_ok() {
+            return Ok(());
+        }
+        return write_atomic(Path::new(&sidecar_path(target)), &content);
+    }
+    let sidecar = sidecar_path(source);
+    if Path::new(&sidecar).exists() {
+        copy_atomic(Path::new(&sidecar), Path::new(&sidecar_path(target)))?;
+    }
+    Ok(())
+}
+
+/// Print the provenance of a package
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the package
+/// * `json` - Whether to print JSON instead of text
+/// * `timezone` - The timezone of the printed timestamps
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn print_provenance(file_path: &str, json: bool, timezone: ReportTimezone) -> Result<()> {
+    let Some(provenance) = read_provenance(file_path)? else {
+        println!("No provenance recorded for {}", file_path);
+        return Ok(());
+    };
+    if json {
+        let mut value = serde_json::to_value(&provenance)?;
+        value["retrievedAt"] = timezone.format(&provenance.retrieved_at).into();
+        if let Some(published_at) = &provenance.published_at {
+            value["publishedAt"] = timezone.format(published_at).into();
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+    println!("Source:       {}", provenance.source_url);
+    println!(
+        "Retrieved at: {}",
+        timezone.format(&provenance.retrieved_at)
+    );
+    if let Some(published_at) = &provenance.published_at {
+        println!("Published at: {}", timezone.format(published_at));
+    }
+    println!("SHA-256:      {}", provenance.sha256);
+    println!("Verification: {}", provenance.verification);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_provenance_sidecar() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = format!("{}/golang.Go-0.41.2.vsix", temp_dir.path().display());
+        let target = format!("{}/copy.vsix", temp_dir.path().display());
+        fs::write(&source, "go").unwrap();
+        fs::write(&target, "go").unwrap();
+        assert_eq!(read_provenance(&source).unwrap(), None);
+
+        let provenance = Provenance::new(
+            "https://marketplace.visualstudio.com/golang/Go",
+            "abc123",
+            Verification::Pinned,
+        );
+        record_provenance(&source, &provenance, ProvenanceStorage::Sidecar).unwrap();
+        assert!(Path::new(&sidecar_path(&source)).exists());
+        assert_eq!(read_provenance(&source).unwrap(), Some(provenance.clone()));
+
+        copy_provenance(&source, &target).unwrap();
+        assert_eq!(read_provenance(&target).unwrap(), Some(provenance));
+    }
+}
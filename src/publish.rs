@@ -0,0 +1,95 @@
+use crate::args::Args;
+use crate::compression::StoreCompression;
+use crate::config::VSCODE_SERVER_DIR;
+use crate::error::{Result, VsixHarvesterError};
+use crate::git::{is_git_target, publish_to_git};
+use crate::marketplace::DownloadOptions;
+use crate::oci::{push_bundle, BundleLayer, ARCHIVE_MEDIA_TYPE, JSON_MEDIA_TYPE, VSIX_MEDIA_TYPE};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+/// Collect the files of the harvest bundle: the VSIX packages of the destination
+/// directory, the VS Code server archives, the manifest and the lockfile when they exist
+///
+/// Packages only kept as a compressed copy are part of the bundle, they are
+/// decompressed when pushed.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result containing the layers of the bundle or an error that occurred
+pub fn collect_bundle(args: &Args) -> Result<Vec<BundleLayer>> {
+    let mut layers = Vec::new();
+    let mut packages: Vec<String> = fs::read_dir(args.primary_destination())?
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .filter(|path| {
+            path.ends_with(".vsix")
+                || path
+                    .strip_suffix(StoreCompression::Zstd.suffix())
+                    .is_some_and(|vsix_path| {
+                        vsix_path.ends_with(".vsix") && !Path::new(vsix_path).exists()
+                    })
+        })
+        .collect();
+    packages.sort();
+    layers.extend(packages.into_iter().map(|path| BundleLayer {
+        path,
+        media_type: VSIX_MEDIA_TYPE,
+    }));
+    let server_dir = format!("{}/{}", args.primary_destination(), VSCODE_SERVER_DIR);
+    if Path::new(&server_dir).is_dir() {
+        let mut archives: Vec<String> = fs::read_dir(server_dir)?
+            .filter_map(std::result::Result::ok)
+            .map(|entry| entry.path().to_string_lossy().into_owned())
+            .filter(|path| path.ends_with(".tar.gz") || path.ends_with(".zip"))
+            .collect();
+        archives.sort();
+        layers.extend(archives.into_iter().map(|path| BundleLayer {
+            path,
+            media_type: ARCHIVE_MEDIA_TYPE,
+        }));
+    }
+    for path in [args.input.clone(), args.lockfile_path()] {
+        if Path::new(&path).exists() {
+            layers.push(BundleLayer {
+                path,
+                media_type: JSON_MEDIA_TYPE,
+            });
+        }
+    }
+    Ok(layers)
+}
+
+/// Publish the harvest to the target given with --publish
+///
+/// # Arguments
+///
+/// * `target` - The publish target (e.g., "oci://registry.local/vscode/extensions:latest"
+///   or "git@host:mirror.git")
+/// * `args` - The command line arguments
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn publish(target: &str, args: &Args) -> Result<()> {
+    if target.starts_with("oci://") || target.starts_with("oci+http://") {
+        let layers = collect_bundle(args)?;
+        let digest = push_bundle(target, &layers, &DownloadOptions::from(args)).await?;
+        info!(
+            "Published {} files to {} ({})",
+            layers.len(),
+            target,
+            digest
+        );
+        return Ok(());
+    }
+    if is_git_target(target) {
+        return publish_to_git(target, args);
+    }
+    Err(VsixHarvesterError::InvalidPublishTarget(target.to_string()))
+}
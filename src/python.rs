@@ -0,0 +1,186 @@
+//! Python bindings of the harvesting library
+//!
+//! Built as the `vsixharvester` module with `maturin` and the `python` feature,
+//! so provisioning pipelines call the library instead of parsing the CLI output.
+
+use crate::architecture::TargetPlatform;
+use crate::args::{Args, Parser};
+use crate::config::{MAX_CONCURRENT_DOWNLOADS, VERSION};
+use crate::create_directory_if_not_exists;
+use crate::error::{ErrorReport, Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::marketplace::{download_extension, resolve_extension, DownloadOptions};
+use crate::run_command;
+use crate::verify::verify_mirror;
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::future::Future;
+
+create_exception!(
+    vsixharvester,
+    HarvestError,
+    PyRuntimeError,
+    "A harvest failure, raised with the error message and the error code (e.g. 'hash_mismatch')"
+);
+
+impl From<VsixHarvesterError> for PyErr {
+    fn from(error: VsixHarvesterError) -> Self {
+        let report = ErrorReport::from(&error);
+        HarvestError::new_err((report.message, report.code))
+    }
+}
+
+/// Run a future of the library on a runtime of its own, without holding the GIL
+fn block_on<T, F>(py: Python<'_>, future: impl FnOnce() -> F + Send) -> PyResult<T>
+where
+    T: Send,
+    F: Future<Output = Result<T>>,
+{
+    py.allow_threads(|| tokio::runtime::Runtime::new()?.block_on(future()))
+        .map_err(PyErr::from)
+}
+
+/// Convert a JSON value to the matching Python object
+fn to_python(py: Python<'_>, value: Value) -> PyResult<PyObject> {
+    Ok(py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+/// Build the download options of a call
+fn download_options(
+    destination: String,
+    engine_version: Option<String>,
+    allow_pre_release: bool,
+    no_cache: bool,
+    proxy: Option<String>,
+) -> DownloadOptions {
+    DownloadOptions {
+        destination,
+        engine_version,
+        allow_pre_release,
+        no_cache,
+        proxy,
+        pin_hashes: true,
+        ..Default::default()
+    }
+}
+
+/// Resolve the version of an extension to download, returned as a dict
+#[pyfunction]
+#[pyo3(signature = (id, target_platform=None, engine_version=None, allow_pre_release=false, proxy=None))]
+fn resolve(
+    py: Python<'_>,
+    id: &str,
+    target_platform: Option<String>,
+    engine_version: Option<String>,
+    allow_pre_release: bool,
+    proxy: Option<String>,
+) -> PyResult<PyObject> {
+    let options = download_options(
+        String::new(),
+        engine_version,
+        allow_pre_release,
+        false,
+        proxy,
+    );
+    let target_platform = target_platform.as_deref().map(TargetPlatform::new);
+    let resolved = block_on(py, || async {
+        resolve_extension(Extension::from_id(id)?, &options, target_platform.as_ref()).await
+    })?;
+    to_python(
+        py,
+        json!({
+            "id": resolved.id,
+            "version": resolved.version,
+            "targetPlatform": resolved.target_platform,
+            "downloadUrl": resolved.download_url,
+        }),
+    )
+}
+
+/// Download an extension into a directory, returning the path and SHA-256 of the package as a dict
+#[pyfunction]
+#[pyo3(signature = (id, destination="./extensions".to_string(), target_platform=None, engine_version=None, allow_pre_release=false, no_cache=false, proxy=None))]
+#[allow(clippy::too_many_arguments)]
+fn download(
+    py: Python<'_>,
+    id: &str,
+    destination: String,
+    target_platform: Option<String>,
+    engine_version: Option<String>,
+    allow_pre_release: bool,
+    no_cache: bool,
+    proxy: Option<String>,
+) -> PyResult<PyObject> {
+    create_directory_if_not_exists(&destination)?;
+    let options = download_options(
+        destination,
+        engine_version,
+        allow_pre_release,
+        no_cache,
+        proxy,
+    );
+    let target_platform = target_platform.as_deref().map(TargetPlatform::new);
+    let outcome = block_on(py, || async {
+        download_extension(
+            Extension::from_id(id)?,
+            &options,
+            target_platform.as_ref(),
+            None,
+        )
+        .await
+    })?;
+    to_python(
+        py,
+        json!({
+            "id": outcome.id,
+            "version": outcome.version,
+            "targetPlatform": outcome.target_platform,
+            "filePath": outcome.file_path,
+            "sha256": outcome.sha256,
+        }),
+    )
+}
+
+/// Re-check the integrity and the marketplace state of the versions of a mirror, returning the
+/// flagged and corrupt packages as a dict
+#[pyfunction]
+#[pyo3(signature = (destination="./extensions".to_string(), proxy=None, concurrency=MAX_CONCURRENT_DOWNLOADS, offline=false))]
+fn verify(
+    py: Python<'_>,
+    destination: String,
+    proxy: Option<String>,
+    concurrency: usize,
+    offline: bool,
+) -> PyResult<PyObject> {
+    let options = download_options(destination, None, false, false, proxy);
+    let verification = block_on(py, || verify_mirror(&options, concurrency, offline, None))?;
+    to_python(
+        py,
+        serde_json::to_value(verification).map_err(VsixHarvesterError::from)?,
+    )
+}
+
+/// Run the harvester with command line arguments (e.g. ["--input", "extensions.json"])
+#[pyfunction]
+fn harvest(py: Python<'_>, arguments: Vec<String>) -> PyResult<()> {
+    let args = Args::try_parse_from(std::iter::once("vsixHarvester".to_string()).chain(arguments))
+        .map_err(|e| HarvestError::new_err((e.to_string(), "invalid_arguments")))?;
+    block_on(py, || run_command(&args))
+}
+
+/// The `vsixharvester` Python module
+#[pymodule]
+fn vsixharvester(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", VERSION)?;
+    m.add("HarvestError", m.py().get_type::<HarvestError>())?;
+    m.add_function(wrap_pyfunction!(resolve, m)?)?;
+    m.add_function(wrap_pyfunction!(download, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    m.add_function(wrap_pyfunction!(harvest, m)?)?;
+    Ok(())
+}
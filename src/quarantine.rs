@@ -0,0 +1,130 @@
+use crate::config::QUARANTINE_DIR;
+use crate::create_directory_if_not_exists;
+use crate::error::Result;
+use crate::marketplace::sha256_hex;
+use crate::temp::{copy_atomic, write_atomic};
+use chrono::Utc;
+use log::warn;
+use std::fs;
+use std::path::Path;
+
+/// Get the path of the reason file of a quarantined package
+pub fn reason_path(quarantined_path: &str) -> String {
+    format!("{}.reason", quarantined_path)
+}
+
+/// Move the content of a package that failed verification to the quarantine directory
+///
+/// The content is saved as is in `<destination>/quarantine/` with a `.reason` file
+/// describing the failure, so operators can inspect suspicious artifacts safely.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `file_name` - The file name of the package
+/// * `content` - The content of the package
+/// * `reason` - Why the package failed verification
+///
+/// # Returns
+///
+/// A Result containing the path of the quarantined package or an error that occurred
+pub fn quarantine_package(
+    destination: &str,
+    file_name: &str,
+    content: &[u8],
+    reason: &str,
+) -> Result<String> {
+    let quarantined_path = quarantine_path(destination, file_name)?;
+    write_atomic(Path::new(&quarantined_path), content)?;
+    write_reason(&quarantined_path, &sha256_hex(content), reason)?;
+    Ok(quarantined_path)
+}
+
+/// Move a package file that failed verification to the quarantine directory
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `file_name` - The file name of the package
+/// * `path` - The path of the package file, moved to the quarantine directory
+/// * `sha256` - The SHA-256 of the package
+/// * `reason` - Why the package failed verification
+///
+/// # Returns
+///
+/// A Result containing the path of the quarantined package or an error that occurred
+pub fn quarantine_package_file(
+    destination: &str,
+    file_name: &str,
+    path: &Path,
+    sha256: &str,
+    reason: &str,
+) -> Result<String> {
+    let quarantined_path = quarantine_path(destination, file_name)?;
+    // The quarantine directory may live on another file system than the package
+    if fs::rename(path, &quarantined_path).is_err() {
+        copy_atomic(path, Path::new(&quarantined_path))?;
+        fs::remove_file(path)?;
+    }
+    write_reason(&quarantined_path, sha256, reason)?;
+    Ok(quarantined_path)
+}
+
+/// Get the path of a package in the quarantine directory, creating the directory
+fn quarantine_path(destination: &str, file_name: &str) -> Result<String> {
+    let quarantine_dir = format!("{}/{}", destination, QUARANTINE_DIR);
+    create_directory_if_not_exists(&quarantine_dir)?;
+    Ok(format!("{}/{}", quarantine_dir, file_name))
+}
+
+/// Write the reason file of a quarantined package
+fn write_reason(quarantined_path: &str, sha256: &str, reason: &str) -> Result<()> {
+    write_atomic(
+        Path::new(&reason_path(quarantined_path)),
+        format!(
+            "reason: {}\nsha256: {}\nquarantined at: {}\n",
+            reason,
+            sha256,
+            Utc::now().to_rfc3339()
+        )
+        .as_bytes(),
+    )?;
+    warn!("Quarantined {}: {}", quarantined_path, reason);
+    Ok(())
+}
+
+/// Get the file name of a package path
+pub fn package_file_name(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_name()
+        .map_or(file_path.to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_quarantine_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let destination = temp_dir.path().display().to_string();
+        let path = quarantine_package(
+            &destination,
+            &package_file_name(&format!("{}/golang.Go-0.41.2.vsix", destination)),
+            b"not a zip",
+            "invalid archive",
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            format!("{}/quarantine/golang.Go-0.41.2.vsix", destination)
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"not a zip");
+        let reason = fs::read_to_string(reason_path(&path)).unwrap();
+        assert!(reason.starts_with("reason: invalid archive\n"));
+        assert!(reason.contains(&sha256_hex(b"not a zip")));
+    }
+}
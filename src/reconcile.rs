@@ -0,0 +1,173 @@
+use crate::architecture::TargetPlatform;
+use crate::error::Result;
+use std::fmt;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// A difference between the manifest and the lockfile found by a `--locked` run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockfileChange {
+    /// An extension of the manifest missing from the lockfile
+    Added { package: String, version: String },
+    /// An extension resolved to another version than the locked one
+    Changed {
+        package: String,
+        locked: String,
+        resolved: String,
+    },
+    /// A locked extension no longer in the manifest
+    Removed { package: String, version: String },
+}
+
+impl LockfileChange {
+    /// Get the name of a package of the lockfile
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier in the format "publisher.name"
+    /// * `target_platform` - The target platform, None for universal packages
+    ///
+    /// # Returns
+    ///
+    /// The name of the package (e.g., "golang.Go (linux-x64)")
+    pub fn package(id: &str, target_platform: Option<&TargetPlatform>) -> String {
+        match target_platform {
+            Some(target_platform) => format!("{} ({})", id, target_platform),
+            None => id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LockfileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { package, version } => write!(f, "add {} {}", package, version),
+            Self::Changed {
+                package,
+                locked,
+                resolved,
+            } => write!(f, "update {} from {} to {}", package, locked, resolved),
+            Self::Removed { package, version } => write!(f, "remove {} {}", package, version),
+        }
+    }
+}
+
+/// Decides which differences between the manifest and the lockfile are accepted
+///
+/// Without `--accept-new`, every change is confirmed on the terminal. Runs without
+/// a terminal accept nothing, so their differences still fail the run.
+#[derive(Debug)]
+pub struct Reconciler {
+    accept_new: bool,
+    interactive: bool,
+    accept_all: bool,
+}
+
+impl Reconciler {
+    /// Create a reconciler
+    ///
+    /// # Arguments
+    ///
+    /// * `accept_new` - Accept every change without asking
+    /// * `prompt` - Ask on the terminal, when stdin and stderr are terminals
+    ///
+    /// # Returns
+    ///
+    /// The reconciler
+    pub fn new(accept_new: bool, prompt: bool) -> Self {
+        Self {
+            accept_new,
+            interactive: prompt
+                && std::io::stdin().is_terminal()
+                && std::io::stderr().is_terminal(),
+            accept_all: false,
+        }
+    }
+
+    /// Check if a change is accepted, asking on the terminal when interactive
+    ///
+    /// # Arguments
+    ///
+    /// * `change` - The difference between the manifest and the lockfile
+    ///
+    /// # Returns
+    ///
+    /// A Result containing true if the lockfile may be updated, or an error that occurred
+    pub fn accept(&mut self, change: &LockfileChange) -> Result<bool> {
+        if !self.interactive {
+            return Ok(self.accept_new);
+        }
+        self.accept_with(change, &mut std::io::stdin().lock(), &mut std::io::stderr())
+    }
+
+    fn accept_with(
+        &mut self,
+        change: &LockfileChange,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<bool> {
+        if self.accept_new || self.accept_all {
+            return Ok(true);
+        }
+        write!(output, "Lockfile differs: {}. Accept? [y/N/a] ", change)?;
+        output.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "all" => {
+                self.accept_all = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconciler() {
+        let change = LockfileChange::Changed {
+            package: LockfileChange::package("golang.Go", Some(&"linux-x64".into())),
+            locked: "0.40.0".to_string(),
+            resolved: "0.41.2".to_string(),
+        };
+        assert_eq!(
+            change.to_string(),
+            "update golang.Go (linux-x64) from 0.40.0 to 0.41.2"
+        );
+
+        let mut reconciler = Reconciler {
+            accept_new: false,
+            interactive: true,
+            accept_all: false,
+        };
+        let mut output = Vec::new();
+        let mut input: &[u8] = b"n\ny\na\n";
+        assert!(!reconciler
+            .accept_with(&change, &mut input, &mut output)
+            .unwrap());
+        assert!(reconciler
+            .accept_with(&change, &mut input, &mut output)
+            .unwrap());
+        assert!(reconciler
+            .accept_with(&change, &mut input, &mut output)
+            .unwrap());
+        // Every later change is accepted without asking
+        assert!(reconciler
+            .accept_with(&change, &mut input, &mut output)
+            .unwrap());
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .matches("Accept?")
+                .count(),
+            3
+        );
+
+        assert!(Reconciler::new(true, false).accept(&change).unwrap());
+        assert!(!Reconciler::new(false, false).accept(&change).unwrap());
+    }
+}
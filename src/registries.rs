@@ -0,0 +1,191 @@
+use crate::config::{API_URL, MARKETPLACE_API_VERSION, USER_AGENT};
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{build_client, DownloadOptions, Flags};
+use futures::future::join_all;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// A registry the extensions can be harvested from
+#[derive(Debug, Clone)]
+pub struct RegistryEndpoint {
+    /// The name of the registry
+    pub name: &'static str,
+    /// The URL of the query API
+    pub api_url: &'static str,
+    /// The API version requested in the Accept header
+    pub api_version: &'static str,
+}
+
+/// Health of a registry as seen from this host
+#[derive(Debug, Clone)]
+pub struct RegistryStatus {
+    pub name: String,
+    pub api_url: String,
+    /// HTTP status returned by the probe, None if the registry could not be reached
+    pub http_status: Option<u16>,
+    pub latency: Duration,
+    pub api_version: String,
+    /// Authentication used by the probe
+    pub auth: String,
+    pub error: Option<String>,
+}
+
+impl RegistryStatus {
+    /// Check if the registry answered the probe successfully
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none() && self.http_status.is_some_and(|status| status < 400)
+    }
+}
+
+/// Get the registries configured for harvesting
+pub fn configured_registries() -> Vec<RegistryEndpoint> {
+    vec![RegistryEndpoint {
+        name: "marketplace",
+        api_url: API_URL,
+        api_version: MARKETPLACE_API_VERSION,
+    }]
+}
+
+/// Probe a registry with a minimal extension query
+///
+/// # Arguments
+///
+/// * `registry` - The registry to probe
+/// * `options` - The download options (proxy and connection tuning)
+///
+/// # Returns
+///
+/// The status of the registry
+pub async fn probe_registry(
+    registry: &RegistryEndpoint,
+    options: &DownloadOptions,
+) -> RegistryStatus {
+    let mut status = RegistryStatus {
+        name: registry.name.to_string(),
+        api_url: registry.api_url.to_string(),
+        http_status: None,
+        latency: Duration::ZERO,
+        api_version: registry.api_version.to_string(),
+        auth: "anonymous".to_string(),
+        error: None,
+    };
+    let client = match build_client(options) {
+        Ok(client) => client,
+        Err(e) => {
+            status.error = Some(e.to_string());
+            return status;
+        }
+    };
+    let payload = json!({
+        "filters": [{
+            "criteria": [{"filterType": 8, "value": "Microsoft.VisualStudio.Code"}],
+            "pageSize": 1
+        }],
+        "flags": Flags::NONE.bits()
+    });
+    let start = Instant::now();
+    let resp = client
+        .post(registry.api_url)
+        .header("Content-Type", "application/json")
+        .header(
+            "Accept",
+            format!("application/json;api-version={}", registry.api_version),
+        )
+        .header("User-Agent", USER_AGENT)
+        .json(&payload)
+        .send()
+        .await;
+    status.latency = start.elapsed();
+    match resp {
+        Ok(resp) => {
+            status.http_status = Some(resp.status().as_u16());
+            match resp.status().as_u16() {
+                401 | 403 => status.auth = "rejected".to_string(),
+                // The registry refuses the requested API version with a bad request
+                400 => status.api_version = format!("{} (unsupported)", registry.api_version),
+                _ => {}
+            }
+        }
+        Err(e) => status.error = Some(e.to_string()),
+    }
+    status
+}
+
+/// Probe all the configured registries in parallel and print their status
+///
+/// # Arguments
+///
+/// * `options` - The download options (proxy and connection tuning)
+///
+/// # Returns
+///
+/// A Result indicating that all the registries are healthy or an error naming the failing ones
+pub async fn print_registries_status(options: &DownloadOptions) -> Result<()> {
+    let registries = configured_registries();
+    let statuses = join_all(
+        registries
+            .iter()
+            .map(|registry| probe_registry(registry, options)),
+    )
+    .await;
+
+    println!(
+        "{:<12} {:<8} {:>10} {:<16} {:<10} URL",
+        "REGISTRY", "STATUS", "LATENCY", "API VERSION", "AUTH"
+    );
+    for status in &statuses {
+        let http_status = status
+            .http_status
+            .map_or("down".to_string(), |code| code.to_string());
+        println!(
+            "{:<12} {:<8} {:>8}ms {:<16} {:<10} {}",
+            status.name,
+            http_status,
+            status.latency.as_millis(),
+            status.api_version,
+            status.auth,
+            status.api_url
+        );
+        if let Some(error) = &status.error {
+            println!("  error: {}", error);
+        }
+    }
+
+    let failing: Vec<_> = statuses
+        .iter()
+        .filter(|status| !status.is_healthy())
+        .map(|status| status.name.clone())
+        .collect();
+    if failing.is_empty() {
+        Ok(())
+    } else {
+        Err(VsixHarvesterError::ApiError(format!(
+            "unhealthy registries: {}",
+            failing.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_status_health() {
+        let mut status = RegistryStatus {
+            name: "marketplace".to_string(),
+            api_url: API_URL.to_string(),
+            http_status: Some(200),
+            latency: Duration::from_millis(42),
+            api_version: MARKETPLACE_API_VERSION.to_string(),
+            auth: "anonymous".to_string(),
+            error: None,
+        };
+        assert!(status.is_healthy());
+        status.http_status = Some(503);
+        assert!(!status.is_healthy());
+        status.http_status = None;
+        status.error = Some("dns error".to_string());
+        assert!(!status.is_healthy());
+    }
+}
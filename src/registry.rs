@@ -0,0 +1,334 @@
+use crate::architecture::TargetPlatform;
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::marketplace::{
+    download_package, DownloadOptions, DownloadedPackage, Marketplace, ResolvedExtension,
+};
+use crate::openvsx::OpenVsx;
+use crate::types::Extension as MarketplaceExtension;
+use clap::ValueEnum;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A registry the extensions are queried and downloaded from
+///
+/// The Visual Studio Marketplace and Open VSX are built in. Private galleries and
+/// mirrors implement this trait and are used by setting `DownloadOptions::registry`,
+/// the resolution, pinning and validation of the packages stay the same.
+pub trait Registry: fmt::Debug + Send + Sync {
+    /// Get the name of the registry (e.g., "marketplace")
+    fn name(&self) -> &str;
+
+    /// Query the metadata and versions of an extension
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension to query
+    /// * `options` - The download options (proxy, polite mode)
+    /// * `all_versions` - Whether to get every version instead of the latest one
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the metadata in the marketplace format, with the versions
+    /// from the latest, or an error that occurred
+    fn query_extension<'a>(
+        &'a self,
+        extension: &'a Extension<'_>,
+        options: &'a DownloadOptions,
+        all_versions: bool,
+    ) -> BoxFuture<'a, Result<MarketplaceExtension>>;
+
+    /// Query the metadata and versions of several extensions at once
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The extension identifiers in the format "publisher.name"
+    /// * `options` - The download options (proxy, polite mode)
+    /// * `all_versions` - Whether to get every version instead of the latest one
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the metadata keyed by lowercase identifier, empty when the
+    /// registry cannot query extensions in a batch
+    fn query_extensions<'a>(
+        &'a self,
+        _ids: &'a [String],
+        _options: &'a DownloadOptions,
+        _all_versions: bool,
+    ) -> BoxFuture<'a, Result<HashMap<String, MarketplaceExtension>>> {
+        Box::pin(async { Ok(HashMap::new()) })
+    }
+
+    /// Get the target platforms the registry serves packages for
+    ///
+    /// # Returns
+    ///
+    /// The target platform identifiers, None to accept any of them
+    fn target_platforms(&self) -> Option<&'static [&'static str]> {
+        None
+    }
+
+    /// Get the URL of the VSIX package asset of an extension version
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension of the package
+    /// * `version` - The version of the package
+    /// * `target_platform` - Optional target platform
+    /// * `metadata` - The metadata of the extension, None when the version is pinned
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// The URL of the package
+    fn package_url(
+        &self,
+        extension: &Extension<'_>,
+        version: &str,
+        target_platform: Option<&TargetPlatform>,
+        metadata: Option<&MarketplaceExtension>,
+        options: &DownloadOptions,
+    ) -> String;
+
+    /// Get the URL of the signature archive of a resolved package
+    ///
+    /// # Arguments
+    ///
+    /// * `resolved` - The resolved extension
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// The URL of the signature, None if the registry does not sign the packages
+    fn signature_url(
+        &self,
+        _resolved: &ResolvedExtension,
+        _options: &DownloadOptions,
+    ) -> Option<String> {
+        None
+    }
+
+    /// Query the last update time of extensions without resolving their versions
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The extension identifiers in the format "publisher.name"
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the `lastUpdated` timestamps keyed by lowercase identifier,
+    /// empty when the registry cannot tell them in a batch
+    fn last_updated<'a>(
+        &'a self,
+        _ids: &'a [String],
+        _options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<HashMap<String, String>>> {
+        Box::pin(async { Ok(HashMap::new()) })
+    }
+
+    /// Download a resolved package, streamed into its partial file when it is saved locally
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The HTTP client
+    /// * `resolved` - The resolved extension, with the URL given by `package_url`
+    /// * `options` - The download options
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the downloaded package or an error that occurred
+    fn download<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        resolved: &'a ResolvedExtension,
+        options: &'a DownloadOptions,
+    ) -> BoxFuture<'a, Result<DownloadedPackage>> {
+        Box::pin(download_package(client, resolved, options))
+    }
+}
+
+/// Check a target platform of the command line against the target platforms of a registry
+///
+/// # Arguments
+///
+/// * `target_platform` - The target platform, None for universal packages
+/// * `registry` - The registry the packages are downloaded from
+///
+/// # Returns
+///
+/// A Result containing the target platform, None for universal packages, or an error
+/// if the registry does not serve packages for it
+pub fn registry_target_platform(
+    target_platform: Option<TargetPlatform>,
+    registry: &dyn Registry,
+) -> Result<Option<TargetPlatform>> {
+    let Some(target_platform) = target_platform else {
+        return Ok(None);
+    };
+    match registry.target_platforms() {
+        Some(supported) if !supported.contains(&target_platform.as_str()) => {
+            Err(VsixHarvesterError::UnsupportedTargetPlatform(
+                target_platform.to_string(),
+                registry.name().to_string(),
+                supported.join(", "),
+            ))
+        }
+        _ => Ok(Some(target_platform)),
+    }
+}
+
+/// The built-in registries, selected on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RegistrySource {
+    /// The Visual Studio Marketplace
+    #[default]
+    Marketplace,
+    /// The Open VSX registry (open-vsx.org)
+    Openvsx,
+}
+
+impl RegistrySource {
+    /// Get the registry implementation
+    pub fn registry(self) -> Arc<dyn Registry> {
+        match self {
+            RegistrySource::Marketplace => Arc::new(Marketplace),
+            RegistrySource::Openvsx => Arc::new(OpenVsx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::normalize_target_platform;
+    use crate::marketplace::{resolve_extension, resolve_pinned_extension};
+    use crate::test_support;
+    use serde_json::json;
+
+    /// A private gallery serving a fixed version from a mirror
+    #[derive(Debug)]
+    struct PrivateGallery;
+
+    impl Registry for PrivateGallery {
+        fn name(&self) -> &str {
+            "private"
+        }
+
+        fn query_extension<'a>(
+            &'a self,
+            extension: &'a Extension<'_>,
+            _options: &'a DownloadOptions,
+            _all_versions: bool,
+        ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+            Box::pin(async move {
+                Ok(test_support::metadata(
+                    &extension.to_id(),
+                    json!({"versions": [test_support::version("1.2.3", json!({}))]}),
+                ))
+            })
+        }
+
+        fn package_url(
+            &self,
+            extension: &Extension<'_>,
+            version: &str,
+            _target_platform: Option<&TargetPlatform>,
+            _metadata: Option<&MarketplaceExtension>,
+            _options: &DownloadOptions,
+        ) -> String {
+            format!(
+                "https://gallery.example.com/{}/{}",
+                extension.to_id(),
+                version
+            )
+        }
+    }
+
+    #[test]
+    fn test_custom_registry() {
+        let options = DownloadOptions {
+            destination: "./ext".to_string(),
+            registry: Arc::new(PrivateGallery),
+            ..Default::default()
+        };
+        assert_eq!(options.registry.name(), "private");
+        let resolved = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(resolve_extension(
+                Extension::from_id("golang.Go").unwrap(),
+                &options,
+                Some(&"linux-x64".into()),
+            ))
+            .unwrap();
+        assert_eq!(resolved.version, "1.2.3");
+        assert_eq!(
+            resolved.download_url,
+            "https://gallery.example.com/golang.Go/1.2.3"
+        );
+        assert_eq!(resolved.file_path, "./ext/golang.Go-1.2.3@linux-x64.vsix");
+
+        let pinned = resolve_pinned_extension(
+            Extension::from_id("golang.Go").unwrap(),
+            "1.0.0",
+            &options,
+            None,
+        );
+        assert_eq!(
+            pinned.download_url,
+            "https://gallery.example.com/golang.Go/1.0.0"
+        );
+        assert_eq!(RegistrySource::Openvsx.registry().name(), "openvsx");
+    }
+
+    #[test]
+    fn test_registry_target_platform() {
+        let marketplace = RegistrySource::Marketplace.registry();
+        let openvsx = RegistrySource::Openvsx.registry();
+        for (arch, target_platform) in [
+            ("linux_x64", Some("linux-x64")),
+            ("linux-aarch64", Some("linux-arm64")),
+            ("macos_arm64", Some("darwin-arm64")),
+            ("windows-amd64", Some("win32-x64")),
+            ("alpine_arm64", Some("alpine-arm64")),
+            ("universal", None),
+        ] {
+            assert_eq!(
+                registry_target_platform(normalize_target_platform(arch), marketplace.as_ref())
+                    .unwrap()
+                    .as_ref()
+                    .map(TargetPlatform::as_str),
+                target_platform
+            );
+        }
+        assert_eq!(
+            registry_target_platform(normalize_target_platform("win32_ia32"), openvsx.as_ref())
+                .unwrap()
+                .as_ref()
+                .map(TargetPlatform::as_str),
+            Some("win32-ia32")
+        );
+        let e = registry_target_platform(
+            normalize_target_platform("win32_ia32"),
+            marketplace.as_ref(),
+        )
+        .unwrap_err();
+        assert_eq!(e.code(), "unsupported_target_platform");
+        assert!(e.to_string().contains("not supported by marketplace"));
+        assert!(registry_target_platform(
+            normalize_target_platform("linux_riscv64"),
+            openvsx.as_ref()
+        )
+        .is_err());
+        // Custom registries accept any target platform
+        assert_eq!(
+            registry_target_platform(normalize_target_platform("linux_riscv64"), &PrivateGallery)
+                .unwrap()
+                .as_ref()
+                .map(TargetPlatform::as_str),
+            Some("linux-riscv64")
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::error::Result;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Known extensions renamed or superseded by another extension of the marketplace
+const BUILTIN_RENAMES: &[(&str, &str)] = &[
+    ("eg2.tslint", "dbaeumer.vscode-eslint"),
+    ("matklad.rust-analyzer", "rust-lang.rust-analyzer"),
+    ("ms-vscode.csharp", "ms-dotnettools.csharp"),
+    ("ms-vscode.Go", "golang.Go"),
+    (
+        "ms-vscode.vscode-typescript-tslint-plugin",
+        "dbaeumer.vscode-eslint",
+    ),
+    ("octref.vetur", "Vue.volar"),
+    ("rust-lang.rust", "rust-lang.rust-analyzer"),
+];
+
+/// Map of renamed extension identifiers to their successor
+#[derive(Debug, Clone, Default)]
+pub struct RenameMap {
+    /// The successors keyed by the lowercase identifier of the renamed extension
+    successors: BTreeMap<String, String>,
+}
+
+impl RenameMap {
+    /// Get the built-in rename map
+    pub fn builtin() -> Self {
+        let mut renames = Self::default();
+        for (renamed, successor) in BUILTIN_RENAMES {
+            renames.insert(renamed, successor);
+        }
+        renames
+    }
+
+    /// Get the built-in rename map extended by a JSON file of `"old.id": "new.id"` entries
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Optional path of the rename map file, its entries override the built-in ones
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the rename map or an error that occurred
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let mut renames = Self::builtin();
+        if let Some(path) = path {
+            let entries: BTreeMap<String, String> =
+                serde_json::from_str(&fs::read_to_string(path)?)?;
+            for (renamed, successor) in &entries {
+                renames.insert(renamed, successor);
+            }
+        }
+        Ok(renames)
+    }
+
+    /// Add a renamed extension
+    pub fn insert(&mut self, renamed: &str, successor: &str) {
+        self.successors
+            .insert(renamed.to_lowercase(), successor.to_string());
+    }
+
+    /// Get the latest successor of an extension, following chained renames
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The extension identifier (e.g., "ms-vscode.Go")
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the successor, None if the extension was not renamed
+    pub fn successor(&self, id: &str) -> Option<&str> {
+        let mut current = self.successors.get(&id.to_lowercase())?;
+        // Bounded by the map size in case of a cycle
+        for _ in 0..self.successors.len() {
+            match self.successors.get(&current.to_lowercase()) {
+                Some(next) if !next.eq_ignore_ascii_case(id) => current = next,
+                _ => break,
+            }
+        }
+        Some(current)
+    }
+
+    /// Warn about a renamed manifest entry and get the identifier to harvest
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier of the manifest entry
+    /// * `apply` - Whether to harvest the successor instead of the renamed extension
+    ///
+    /// # Returns
+    ///
+    /// The identifier to harvest
+    pub fn apply<'a>(&'a self, id: &'a str, apply: bool) -> &'a str {
+        let Some(successor) = self.successor(id) else {
+            return id;
+        };
+        if apply {
+            warn!(
+                "{} has been superseded by {}, harvesting {}",
+                id, successor, successor
+            );
+            successor
+        } else {
+            warn!(
+                "{} has been superseded by {}, update the manifest or use --apply-renames",
+                id, successor
+            );
+            id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_map() {
+        let mut renames = RenameMap::builtin();
+        assert_eq!(renames.successor("MS-VSCODE.go"), Some("golang.Go"));
+        assert_eq!(renames.successor("golang.Go"), None);
+        assert_eq!(
+            renames.successor("eg2.tslint"),
+            Some("dbaeumer.vscode-eslint")
+        );
+
+        // Chained renames resolve to the latest successor, cycles stop
+        renames.insert("a.old", "a.middle");
+        renames.insert("a.middle", "a.new");
+        assert_eq!(renames.successor("a.old"), Some("a.new"));
+        renames.insert("b.one", "b.two");
+        renames.insert("b.two", "b.one");
+        assert_eq!(renames.successor("b.one"), Some("b.two"));
+
+        assert_eq!(renames.apply("ms-vscode.Go", true), "golang.Go");
+        assert_eq!(renames.apply("ms-vscode.Go", false), "ms-vscode.Go");
+    }
+}
@@ -0,0 +1,368 @@
+use crate::architecture::TargetPlatform;
+use crate::config::{CYCLONEDX_SBOM_FILE_NAME, SPDX_SBOM_FILE_NAME, VERSION};
+use crate::error::Result;
+use crate::marketplace::{sha256_hex, DownloadOutcome};
+use crate::status::scan_mirror;
+use crate::temp::write_atomic;
+use crate::vsix::{read_license, read_vsix_info, VsixLicense};
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+
+/// Formats of the software bill of materials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON, `sbom.cdx.json`
+    Cyclonedx,
+    /// SPDX 2.3 JSON, `sbom.spdx.json`
+    Spdx,
+}
+
+impl SbomFormat {
+    /// Get the name of the SBOM file
+    pub fn file_name(self) -> &'static str {
+        match self {
+            SbomFormat::Cyclonedx => CYCLONEDX_SBOM_FILE_NAME,
+            SbomFormat::Spdx => SPDX_SBOM_FILE_NAME,
+        }
+    }
+}
+
+/// A package listed in the SBOM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomComponent {
+    pub publisher: String,
+    pub name: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    /// The file name of the package in the destination
+    pub file: String,
+    /// The SHA-256 of the package, hex encoded
+    pub sha256: String,
+    /// The URL the package was downloaded from by the run, None for the packages it kept
+    pub download_url: Option<String>,
+    pub license: VsixLicense,
+}
+
+impl SbomComponent {
+    /// Get the reference of the package, unique in the SBOM
+    fn reference(&self) -> String {
+        match &self.target_platform {
+            Some(target_platform) => format!(
+                "{}.{}@{}@{}",
+                self.publisher, self.name, self.version, target_platform
+            ),
+            None => format!("{}.{}@{}", self.publisher, self.name, self.version),
+        }
+    }
+}
+
+/// Get the declared license if it is an SPDX expression (e.g., "MIT", "(MIT OR Apache-2.0)")
+///
+/// Without their parentheses, the identifiers and operators of an expression alternate.
+/// Free-form declarations such as "SEE LICENSE IN LICENSE.txt" are not expressions.
+fn spdx_expression(declared: &str) -> Option<&str> {
+    let declared = declared.trim();
+    let tokens: Vec<&str> = declared
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|token| !token.is_empty())
+        .collect();
+    let alternating = tokens.iter().enumerate().all(|(index, token)| {
+        let operator = matches!(*token, "AND" | "OR" | "WITH");
+        let identifier = token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | ':'));
+        if index % 2 == 0 {
+            identifier && !operator
+        } else {
+            operator
+        }
+    });
+    (tokens.len() % 2 == 1 && alternating && !declared.eq_ignore_ascii_case("UNLICENSED"))
+        .then_some(declared)
+}
+
+/// Draw a random UUID (version 4)
+fn random_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        chunk.copy_from_slice(&RandomState::new().build_hasher().finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Render a CycloneDX 1.5 SBOM
+///
+/// # Arguments
+///
+/// * `components` - The packages of the SBOM
+///
+/// # Returns
+///
+/// The SBOM document
+pub fn render_cyclonedx(components: &[SbomComponent]) -> Value {
+    let components: Vec<Value> = components
+        .iter()
+        .map(|component| {
+            let declared = component.license.declared.as_deref();
+            let licenses = match (declared.and_then(spdx_expression), &component.license.text) {
+                (Some(expression), _) => json!([{"expression": expression}]),
+                (None, Some(text)) => json!([{"license": {
+                    "name": declared.unwrap_or("LICENSE"),
+                    "text": {"contentType": "text/plain", "content": text},
+                }}]),
+                (None, None) => json!([]),
+            };
+            let mut properties =
+                vec![json!({"name": "vsixharvester:file", "value": component.file})];
+            if let Some(target_platform) = &component.target_platform {
+                properties.push(
+                    json!({"name": "vsixharvester:targetPlatform", "value": target_platform}),
+                );
+            }
+            let mut value = json!({
+                "type": "application",
+                "bom-ref": component.reference(),
+                "publisher": component.publisher,
+                "group": component.publisher,
+                "name": component.name,
+                "version": component.version,
+                "hashes": [{"alg": "SHA-256", "content": component.sha256}],
+                "licenses": licenses,
+                "properties": properties,
+            });
+            if let Some(url) = &component.download_url {
+                value["externalReferences"] = json!([{"type": "distribution", "url": url}]);
+            }
+            value
+        })
+        .collect();
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", random_uuid()),
+        "version": 1,
+        "metadata": {
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "tools": {"components": [{"type": "application", "name": "vsixHarvester", "version": VERSION}]},
+        },
+        "components": components,
+    })
+}
+
+/// Render an SPDX 2.3 SBOM
+///
+/// Licenses declared without an SPDX expression are recorded with the text of the
+/// License asset as `LicenseRef-` licenses.
+///
+/// # Arguments
+///
+/// * `components` - The packages of the SBOM
+///
+/// # Returns
+///
+/// The SBOM document
+pub fn render_spdx(components: &[SbomComponent]) -> Value {
+    let mut packages = Vec::new();
+    let mut relationships = Vec::new();
+    let mut extracted_licenses = Vec::new();
+    for (index, component) in components.iter().enumerate() {
+        let spdx_id = format!("SPDXRef-Package-{}", index + 1);
+        let declared = component.license.declared.as_deref();
+        let license_declared = match (declared.and_then(spdx_expression), &component.license.text) {
+            (Some(expression), _) => expression.to_string(),
+            (None, Some(text)) => {
+                let license_id = format!("LicenseRef-Package-{}", index + 1);
+                extracted_licenses.push(json!({
+                    "licenseId": license_id,
+                    "name": declared.unwrap_or("LICENSE"),
+                    "extractedText": text,
+                }));
+                license_id
+            }
+            (None, None) => "NOASSERTION".to_string(),
+        };
+        packages.push(json!({
+            "SPDXID": spdx_id,
+            "name": format!("{}.{}", component.publisher, component.name),
+            "versionInfo": component.version,
+            "packageFileName": component.file,
+            "supplier": format!("Organization: {}", component.publisher),
+            "downloadLocation": component.download_url.as_deref().unwrap_or("NOASSERTION"),
+            "filesAnalyzed": false,
+            "checksums": [{"algorithm": "SHA256", "checksumValue": component.sha256}],
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": license_declared,
+            "copyrightText": "NOASSERTION",
+        }));
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": spdx_id,
+        }));
+    }
+    let mut document = json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": "vsixHarvester harvest",
+        "documentNamespace": format!("https://spdx.org/spdxdocs/vsixharvester-{}", random_uuid()),
+        "creationInfo": {
+            "created": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "creators": [format!("Tool: vsixHarvester-{}", VERSION)],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    });
+    if !extracted_licenses.is_empty() {
+        document["hasExtractedLicensingInfos"] = Value::Array(extracted_licenses);
+    }
+    document
+}
+
+/// Write the SBOM of every package of a destination directory
+///
+/// The packages downloaded by the run were hashed while being downloaded, the
+/// others are read again.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `format` - The format of the SBOM
+/// * `outcomes` - The outcomes of the downloads of the run
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_sbom(
+    destination: &str,
+    format: SbomFormat,
+    outcomes: &[DownloadOutcome],
+) -> Result<()> {
+    let by_file: HashMap<&str, &DownloadOutcome> = outcomes
+        .iter()
+        .filter_map(|outcome| {
+            let file_name = Path::new(&outcome.file_path).file_name()?.to_str()?;
+            Some((file_name, outcome))
+        })
+        .collect();
+    let mut components = Vec::new();
+    for package in scan_mirror(destination)? {
+        let path = format!("{}/{}", destination, package.file_name);
+        let (info, license) =
+            match read_vsix_info(&path).and_then(|info| Ok((info, read_license(&path)?))) {
+                Ok(read) => read,
+                Err(e) => {
+                    warn!("Not listing {} in the SBOM: {}", package.file_name, e);
+                    continue;
+                }
+            };
+        let outcome = by_file.get(package.file_name.as_str());
+        let sha256 = match outcome.and_then(|outcome| outcome.sha256.clone()) {
+            Some(sha256) => sha256,
+            None => sha256_hex(&fs::read(&path)?),
+        };
+        components.push(SbomComponent {
+            publisher: info.publisher,
+            name: info.name,
+            version: info.version,
+            target_platform: info.target_platform,
+            file: package.file_name,
+            sha256,
+            download_url: outcome
+                .map(|outcome| outcome.download_url.clone())
+                .filter(|url| !url.is_empty()),
+            license,
+        });
+    }
+    let document = match format {
+        SbomFormat::Cyclonedx => render_cyclonedx(&components),
+        SbomFormat::Spdx => render_spdx(&components),
+    };
+    let path = format!("{}/{}", destination, format.file_name());
+    write_atomic(
+        Path::new(&path),
+        serde_json::to_string_pretty(&document)?.as_bytes(),
+    )?;
+    info!("SBOM of {} package(s) saved in {}", components.len(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(declared: Option<&str>, text: Option<&str>) -> SbomComponent {
+        SbomComponent {
+            publisher: "golang".to_string(),
+            name: "Go".to_string(),
+            version: "0.41.2".to_string(),
+            target_platform: Some("linux-x64".into()),
+            file: "golang.Go-0.41.2@linux-x64.vsix".to_string(),
+            sha256: sha256_hex(b"go"),
+            download_url: None,
+            license: VsixLicense {
+                declared: declared.map(str::to_string),
+                text: text.map(str::to_string),
+            },
+        }
+    }
+
+    #[test]
+    fn test_render_sbom() {
+        assert_eq!(spdx_expression("MIT"), Some("MIT"));
+        assert_eq!(
+            spdx_expression("(MIT OR Apache-2.0)"),
+            Some("(MIT OR Apache-2.0)")
+        );
+        assert_eq!(spdx_expression("SEE LICENSE IN LICENSE.txt"), None);
+        assert_eq!(spdx_expression("MIT License"), None);
+
+        let components = [
+            component(Some("MIT"), Some("MIT License")),
+            component(Some("SEE LICENSE IN LICENSE.txt"), Some("Proprietary")),
+            component(None, None),
+        ];
+        let cyclonedx = render_cyclonedx(&components);
+        assert_eq!(cyclonedx["bomFormat"], "CycloneDX");
+        let first = &cyclonedx["components"][0];
+        assert_eq!(first["bom-ref"], "golang.Go@0.41.2@linux-x64");
+        assert_eq!(first["hashes"][0]["content"], sha256_hex(b"go"));
+        assert_eq!(first["licenses"][0]["expression"], "MIT");
+        assert_eq!(
+            cyclonedx["components"][1]["licenses"][0]["license"]["text"]["content"],
+            "Proprietary"
+        );
+        assert_eq!(cyclonedx["components"][2]["licenses"], json!([]));
+
+        let spdx = render_spdx(&components);
+        assert_eq!(spdx["packages"][0]["licenseDeclared"], "MIT");
+        assert_eq!(spdx["packages"][0]["checksums"][0]["algorithm"], "SHA256");
+        assert_eq!(
+            spdx["packages"][1]["licenseDeclared"],
+            "LicenseRef-Package-2"
+        );
+        assert_eq!(
+            spdx["hasExtractedLicensingInfos"][0]["extractedText"],
+            "Proprietary"
+        );
+        assert_eq!(spdx["packages"][2]["licenseDeclared"], "NOASSERTION");
+        assert_eq!(spdx["relationships"].as_array().unwrap().len(), 3);
+    }
+}
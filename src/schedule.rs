@@ -0,0 +1,246 @@
+use crate::args::{Args, Parser};
+use crate::error::{Result, VsixHarvesterError};
+use crate::paths::resolve_path;
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// A harvest run periodically by the service
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    /// The path of the manifest
+    pub input: String,
+    /// The directory receiving the packages
+    pub destination: String,
+    /// The interval between two runs (e.g., "30m", "6h", "1d")
+    pub every: String,
+    /// Optional maximum number of concurrent downloads of the runs
+    pub concurrency: Option<usize>,
+    /// Additional command line options of the runs (e.g., ["--engine-version", "1.97.0"])
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Schedules file of the service
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schedules {
+    /// The schedules keyed by name
+    pub schedules: BTreeMap<String, Schedule>,
+}
+
+impl Schedules {
+    /// Load a schedules file
+    ///
+    /// The relative `input` and `destination` of the schedules are resolved against
+    /// the directory of the file, after expanding `~` and `%NAME%`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the schedules file
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the schedules or an error that occurred
+    pub fn load(path: &str) -> Result<Self> {
+        let mut schedules: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        for schedule in schedules.schedules.values_mut() {
+            schedule.input = resolve_path(&schedule.input, path);
+            schedule.destination = resolve_path(&schedule.destination, path);
+        }
+        Ok(schedules)
+    }
+}
+
+impl Schedule {
+    /// Get the command line options of a run, without the program name
+    pub fn arguments(&self) -> Vec<String> {
+        let mut arguments = vec![
+            "--input".to_string(),
+            self.input.clone(),
+            "--destination".to_string(),
+            self.destination.clone(),
+        ];
+        arguments.extend(self.args.iter().cloned());
+        arguments
+    }
+
+    /// Build the arguments of a run
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the schedule
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the arguments or an error if the options are invalid
+    pub fn run_args(&self, name: &str) -> Result<Args> {
+        let invalid = |reason: String| {
+            VsixHarvesterError::InvalidArgument(format!("schedule {}: {}", name, reason))
+        };
+        let mut args = Args::try_parse_from(
+            std::iter::once("vsixHarvester".to_string()).chain(self.arguments()),
+        )
+        .map_err(|e| invalid(e.to_string()))?;
+        if args.command.is_some() {
+            return Err(invalid("subcommands cannot be scheduled".to_string()));
+        }
+        args.concurrency_limit = self.concurrency;
+        Ok(args)
+    }
+}
+
+/// Parse an interval with a unit suffix
+///
+/// # Arguments
+///
+/// * `interval` - The interval (e.g., "90s", "30m", "6h", "1d")
+///
+/// # Returns
+///
+/// A Result containing the duration or an error if the interval is invalid
+pub fn parse_interval(interval: &str) -> Result<Duration> {
+    let invalid = || VsixHarvesterError::InvalidArgument(format!("invalid interval: {}", interval));
+    let interval = interval.trim();
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let value: u64 = interval[..split].parse().map_err(|_| invalid())?;
+    let unit = match &interval[split..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(invalid()),
+    };
+    if value == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(value * unit))
+}
+
+/// A daily time window, in local time, the scheduled runs start in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncWindow {
+    pub start: NaiveTime,
+    /// The end of the window, before its start for windows spanning midnight
+    pub end: NaiveTime,
+}
+
+impl SyncWindow {
+    /// Parse a window of the form "HH:MM-HH:MM"
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The window (e.g., "01:00-05:00", "22:00-02:00")
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the window or an error if the window is invalid
+    pub fn parse(window: &str) -> Result<Self> {
+        let invalid = || VsixHarvesterError::InvalidArgument(format!("invalid window: {}", window));
+        let (start, end) = window.trim().split_once('-').ok_or_else(invalid)?;
+        let parse_time =
+            |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(invalid());
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Check if a time of day is in the window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Get the delay until the window opens, zero when it is open
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The current time of day
+    ///
+    /// # Returns
+    ///
+    /// The delay to wait
+    pub fn delay_until_open(&self, time: NaiveTime) -> Duration {
+        if self.contains(time) {
+            return Duration::ZERO;
+        }
+        let delay = self.start.signed_duration_since(time).num_seconds();
+        Duration::from_secs(delay.rem_euclid(86400) as u64)
+    }
+}
+
+/// Draw a random delay, so the mirrors sharing a schedule do not all run at the same minute
+///
+/// # Arguments
+///
+/// * `max` - The maximum delay
+///
+/// # Returns
+///
+/// A delay between zero and `max`
+pub fn random_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // The hasher keys are random for each process, unlike the clock of hosts started together
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedules() {
+        let schedules: Schedules = serde_json::from_str(
+            r#"{"schedules": {"team-a": {"input": "team-a.json", "destination": "/srv/a", "every": "6h", "concurrency": 2, "args": ["--engine-version", "1.97.0"]}}}"#,
+        )
+        .unwrap();
+        let schedule = &schedules.schedules["team-a"];
+        let args = schedule.run_args("team-a").unwrap();
+        assert_eq!(args.input, "team-a.json");
+        assert_eq!(args.primary_destination(), "/srv/a");
+        assert_eq!(args.engine_version.as_deref(), Some("1.97.0"));
+        assert_eq!(args.concurrency(), 2);
+
+        assert_eq!(parse_interval("6h").unwrap(), Duration::from_secs(21600));
+        assert_eq!(parse_interval("90s").unwrap(), Duration::from_secs(90));
+        for invalid in ["6", "h", "0m", "1w", "-1h"] {
+            assert!(parse_interval(invalid).is_err(), "{}", invalid);
+        }
+
+        let time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        let window = SyncWindow::parse("01:00-05:00").unwrap();
+        assert!(window.contains(time("01:00")));
+        assert!(!window.contains(time("05:00")));
+        assert_eq!(
+            window.delay_until_open(time("23:30")),
+            Duration::from_secs(5400)
+        );
+        assert_eq!(window.delay_until_open(time("04:59")), Duration::ZERO);
+        let window = SyncWindow::parse("22:00-02:00").unwrap();
+        assert!(window.contains(time("23:00")) && window.contains(time("01:00")));
+        assert_eq!(
+            window.delay_until_open(time("12:00")),
+            Duration::from_secs(36000)
+        );
+        for invalid in ["01:00", "01:00-01:00", "1h-5h", "25:00-05:00"] {
+            assert!(SyncWindow::parse(invalid).is_err(), "{}", invalid);
+        }
+        let jitter = Duration::from_secs(900);
+        assert!(random_jitter(jitter) <= jitter);
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::config::{API_URL, MARKETPLACE_API_VERSION, USER_AGENT};
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{build_client, send_request, DownloadOptions, Flags};
+use crate::types::{Extension as MarketplaceExtension, MarketplaceResponse};
+use clap::ValueEnum;
+use log::error;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Order of the extensions returned by a search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SearchSort {
+    /// The marketplace relevance to the search text
+    #[default]
+    Relevance,
+    /// Most installed first
+    Installs,
+    /// Best rated first
+    Rating,
+}
+
+impl SearchSort {
+    /// Get the `sortBy` value of the marketplace query
+    pub fn sort_by(self) -> u32 {
+        match self {
+            SearchSort::Relevance => 0,
+            SearchSort::Installs => 4,
+            SearchSort::Rating => 6,
+        }
+    }
+}
+
+/// Criteria of a marketplace search
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    /// The search text (e.g., "python")
+    pub text: String,
+    /// Optional category (e.g., "Linters")
+    pub category: Option<String>,
+    pub sort: SearchSort,
+    /// Maximum number of results
+    pub limit: usize,
+}
+
+/// An extension found by a search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub id: String,
+    pub version: Option<String>,
+    pub installs: u64,
+    /// The average rating, None when the extension was never rated
+    pub rating: Option<f64>,
+    #[serde(rename = "ratingCount")]
+    pub rating_count: u64,
+}
+
+impl From<&MarketplaceExtension> for SearchResult {
+    fn from(extension: &MarketplaceExtension) -> Self {
+        let rating_count = extension.get_statistic("ratingcount").unwrap_or_default() as u64;
+        Self {
+            id: extension.get_identifier(),
+            version: extension
+                .versions
+                .first()
+                .map(|version| version.version.clone()),
+            installs: extension.get_install_count(),
+            rating: extension
+                .get_statistic("averagerating")
+                .filter(|_| rating_count > 0),
+            rating_count,
+        }
+    }
+}
+
+/// Build the marketplace query of a search
+///
+/// # Arguments
+///
+/// * `query` - The search criteria
+/// * `minimal` - Whether to only ask for the latest version and the statistics
+///
+/// # Returns
+///
+/// The JSON payload of the extensionquery API
+pub fn build_search_payload(query: &SearchQuery, minimal: bool) -> Value {
+    let mut criteria = vec![
+        json!({"filterType": 8, "value": "Microsoft.VisualStudio.Code"}),
+        // Exclude the unpublished extensions
+        json!({"filterType": 12, "value": Flags::UNPUBLISHED.bits().to_string()}),
+        json!({"filterType": 10, "value": query.text}),
+    ];
+    if let Some(category) = &query.category {
+        // filterType 5 is the category
+        criteria.push(json!({"filterType": 5, "value": category}));
+    }
+    json!({
+        "filters": [{
+            "criteria": criteria,
+            "pageNumber": 1,
+            "pageSize": query.limit,
+            "sortBy": query.sort.sort_by(),
+            "sortOrder": 0
+        }],
+        "flags": Flags::search(minimal).bits()
+    })
+}
+
+/// Search the marketplace for extensions
+///
+/// # Arguments
+///
+/// * `query` - The search criteria
+/// * `options` - The download options (proxy, polite mode)
+///
+/// # Returns
+///
+/// A Result containing the extensions found, at most `query.limit`, or an error that occurred
+pub async fn search_extensions(
+    query: &SearchQuery,
+    options: &DownloadOptions,
+) -> Result<Vec<SearchResult>> {
+    let client = build_client(options)?;
+    let resp = send_request(
+        client
+            .post(API_URL)
+            .header("Content-Type", "application/json")
+            .header(
+                "Accept",
+                format!("application/json;api-version={}", MARKETPLACE_API_VERSION),
+            )
+            .header("User-Agent", USER_AGENT)
+            .json(&build_search_payload(query, options.minimal_requests)),
+        options,
+    )
+    .await?;
+    if !resp.status().is_success() {
+        error!("Failed search query for Marketplace API");
+        return Err(VsixHarvesterError::ApiError(format!(
+            "search query failed ({})",
+            resp.status()
+        )));
+    }
+    let response: MarketplaceResponse = resp.json().await?;
+    Ok(response
+        .results
+        .iter()
+        .flat_map(|result| &result.extensions)
+        .take(query.limit)
+        .map(SearchResult::from)
+        .collect())
+}
+
+/// Print the extensions found by a search
+///
+/// # Arguments
+///
+/// * `query` - The search criteria
+/// * `options` - The download options
+/// * `json` - Whether to print JSON instead of a table
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_search(
+    query: &SearchQuery,
+    options: &DownloadOptions,
+    json: bool,
+) -> Result<()> {
+    let results = search_extensions(query, options).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    println!(
+        "{:<50} {:<12} {:>12} {:>8}",
+        "EXTENSION", "VERSION", "INSTALLS", "RATING"
+    );
+    for result in &results {
+        let rating = match result.rating {
+            Some(rating) => format!("{:.2}", rating),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<50} {:<12} {:>12} {:>8}",
+            result.id,
+            result.version.as_deref().unwrap_or("-"),
+            result.installs,
+            rating
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn test_search() {
+        let query = SearchQuery {
+            text: "python".to_string(),
+            category: Some("Linters".to_string()),
+            sort: SearchSort::Rating,
+            limit: 20,
+        };
+        assert_eq!(
+            build_search_payload(&query, true)["flags"],
+            (Flags::minimal(false) | Flags::INCLUDE_STATISTICS).bits()
+        );
+        let payload = build_search_payload(&query, false);
+        let filter = &payload["filters"][0];
+        assert_eq!(filter["sortBy"], 6);
+        assert_eq!(filter["pageSize"], 20);
+        let criteria = filter["criteria"].as_array().unwrap();
+        assert!(criteria.contains(&json!({"filterType": 10, "value": "python"})));
+        assert!(criteria.contains(&json!({"filterType": 5, "value": "Linters"})));
+
+        let extension = test_support::metadata(
+            "ms-python.python",
+            json!({
+                "statistics": [
+                    {"statisticName": "install", "value": 180000000.0},
+                    {"statisticName": "averagerating", "value": 4.19},
+                    {"statisticName": "ratingcount", "value": 612.0}
+                ]
+            }),
+        );
+        let result = SearchResult::from(&extension);
+        assert_eq!(result.id, "ms-python.python");
+        assert_eq!(result.version, None);
+        assert_eq!(result.installs, 180000000);
+        assert_eq!(result.rating, Some(4.19));
+        assert_eq!(result.rating_count, 612);
+    }
+}
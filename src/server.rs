@@ -0,0 +1,222 @@
+use crate::architecture::TargetPlatform;
+use crate::config::{VSCODE_SERVER_DIR, VSCODE_UPDATE_URL};
+use crate::create_directory_if_not_exists;
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{build_client, send_request, write_package, DownloadOptions};
+use crate::pool::{TaskMeta, TaskPool};
+use log::{error, info};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The VS Code builds needed on a remote host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerArtifact {
+    /// The server used by Remote-SSH, Remote-Containers and WSL
+    Server,
+    /// The standalone CLI, running the tunnels and `code serve-web`
+    Cli,
+}
+
+impl ServerArtifact {
+    /// Get the build name of the artifact for a target platform on the update server
+    ///
+    /// # Arguments
+    ///
+    /// * `target_platform` - The marketplace target platform (e.g., "linux-x64")
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the build name (e.g., "server-linux-x64"), None if
+    /// VS Code does not publish this artifact for the platform
+    pub fn build_name(self, target_platform: &str) -> Option<&'static str> {
+        let (server, cli) = match target_platform {
+            "linux-x64" => ("server-linux-x64", "cli-linux-x64"),
+            "linux-arm64" => ("server-linux-arm64", "cli-linux-arm64"),
+            "linux-armhf" => ("server-linux-armhf", "cli-linux-armhf"),
+            "alpine-x64" => ("server-linux-alpine", "cli-alpine-x64"),
+            "alpine-arm64" => ("server-alpine-arm64", "cli-alpine-arm64"),
+            "darwin-x64" => ("server-darwin", "cli-darwin-x64"),
+            "darwin-arm64" => ("server-darwin-arm64", "cli-darwin-arm64"),
+            "win32-x64" => ("server-win32-x64", "cli-win32-x64"),
+            "win32-arm64" => ("server-win32-arm64", "cli-win32-arm64"),
+            _ => return None,
+        };
+        Some(match self {
+            ServerArtifact::Server => server,
+            ServerArtifact::Cli => cli,
+        })
+    }
+}
+
+/// A VS Code server or CLI archive to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerDownload {
+    pub build_name: String,
+    pub download_url: String,
+    pub file_path: String,
+}
+
+/// Get the VS Code version of the server builds from the engine version
+///
+/// # Arguments
+///
+/// * `engine_version` - Optional engine version (e.g., "1.90.0" or "^1.90.0")
+///
+/// # Returns
+///
+/// The version on the update server, "latest" without engine version
+pub fn server_version(engine_version: Option<&str>) -> String {
+    engine_version
+        .map(|version| version.trim_start_matches(|c: char| !c.is_ascii_digit()))
+        .filter(|version| !version.is_empty())
+        .unwrap_or("latest")
+        .to_string()
+}
+
+/// List the server and CLI archives of the target platforms
+///
+/// # Arguments
+///
+/// * `target_platforms` - The target platforms of the remote hosts
+/// * `version` - The VS Code version
+/// * `destination` - The directory where the extensions are saved
+///
+/// # Returns
+///
+/// The archives to download
+pub fn server_downloads(
+    target_platforms: &[TargetPlatform],
+    version: &str,
+    destination: &str,
+) -> Vec<ServerDownload> {
+    let mut downloads = Vec::new();
+    for target_platform in target_platforms {
+        for artifact in [ServerArtifact::Server, ServerArtifact::Cli] {
+            let Some(build_name) = artifact.build_name(target_platform.as_str()) else {
+                continue;
+            };
+            // Linux builds are tarballs, the other ones zip archives
+            let archive = if build_name.contains("linux") || build_name.contains("alpine") {
+                "tar.gz"
+            } else {
+                "zip"
+            };
+            downloads.push(ServerDownload {
+                build_name: build_name.to_string(),
+                download_url: format!("{}/{}/{}/stable", VSCODE_UPDATE_URL, version, build_name),
+                file_path: format!(
+                    "{}/{}/vscode-{}-{}.{}",
+                    destination, VSCODE_SERVER_DIR, build_name, version, archive
+                ),
+            });
+        }
+    }
+    downloads
+}
+
+/// Download a VS Code server or CLI archive unless it is already there
+async fn download_server_archive(
+    download: &ServerDownload,
+    options: &DownloadOptions,
+) -> Result<()> {
+    if !options.no_cache && Path::new(&download.file_path).exists() {
+        info!("Skip download: {} already exists", download.file_path);
+        return Ok(());
+    }
+    let client = build_client(options)?;
+    info!("Download form {}", download.download_url);
+    let resp = send_request(client.get(&download.download_url), options).await?;
+    if !resp.status().is_success() {
+        return Err(VsixHarvesterError::DownloadError(format!(
+            "{} ({})",
+            download.build_name,
+            resp.status()
+        )));
+    }
+    write_package(&download.file_path, &resp.bytes().await?)?;
+    info!("Saved in {}", download.file_path);
+    Ok(())
+}
+
+/// Download the VS Code server and CLI archives of the target platforms
+///
+/// Remote-SSH and code-server hosts need the server matching the engine version,
+/// an extension mirror alone is not enough on air-gapped networks.
+///
+/// # Arguments
+///
+/// * `target_platforms` - The target platforms of the remote hosts
+/// * `options` - The download options, the version follows the engine version
+/// * `concurrency` - The maximum number of concurrent downloads
+///
+/// # Returns
+///
+/// A Result containing the number of archives in the destination or an error that occurred
+pub async fn harvest_vscode_server(
+    target_platforms: &[TargetPlatform],
+    options: &DownloadOptions,
+    concurrency: usize,
+) -> Result<usize> {
+    let version = server_version(options.engine_version.as_deref());
+    let downloads = server_downloads(target_platforms, &version, &options.destination);
+    create_directory_if_not_exists(&format!("{}/{}", options.destination, VSCODE_SERVER_DIR))?;
+
+    let inputs = downloads
+        .into_iter()
+        .map(|download| (TaskMeta::new(&download.build_name, None), download))
+        .collect();
+    let shared_options = Arc::new(options.clone());
+    let reports = TaskPool::new(concurrency)
+        .cancellation(options.cancellation.clone())
+        .run(inputs, |download: ServerDownload| {
+            let options = shared_options.clone();
+            async move { download_server_archive(&download, &options).await }
+        })
+        .await;
+    let mut harvested = 0;
+    for report in reports {
+        match report.result {
+            Ok(()) => harvested += 1,
+            Err(e) => error!(
+                "Error occurred when downloading VS Code {}: {}",
+                report.meta, e
+            ),
+        }
+    }
+    info!(
+        "{} VS Code server archive(s) for version {}",
+        harvested, version
+    );
+    Ok(harvested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_downloads() {
+        assert_eq!(server_version(Some("^1.90.0")), "1.90.0");
+        assert_eq!(server_version(None), "latest");
+
+        let downloads = server_downloads(
+            &["linux-x64".into(), "darwin-x64".into(), "web".into()],
+            "1.90.0",
+            "./ext",
+        );
+        assert_eq!(downloads.len(), 4);
+        assert_eq!(
+            downloads[0],
+            ServerDownload {
+                build_name: "server-linux-x64".to_string(),
+                download_url: "https://update.code.visualstudio.com/1.90.0/server-linux-x64/stable"
+                    .to_string(),
+                file_path: "./ext/vscode-server/vscode-server-linux-x64-1.90.0.tar.gz".to_string(),
+            }
+        );
+        assert_eq!(
+            downloads[3].file_path,
+            "./ext/vscode-server/vscode-cli-darwin-x64-1.90.0.zip"
+        );
+    }
+}
@@ -0,0 +1,658 @@
+use crate::args::{Args, Parser};
+use crate::config::VERSION;
+use crate::credentials::resolve_secret;
+use crate::error::{ErrorReport, Result, VsixHarvesterError};
+use crate::http_server::{serve_connections, Request, Response};
+use crate::pool::{HarvestProgress, ProgressSnapshot};
+use crate::schedule::{parse_interval, random_jitter, Schedules, SyncWindow};
+use crate::stats::mirror_stats;
+use crate::status::scan_mirror;
+use crate::timezone::ReportTimezone;
+use chrono::{DateTime, Utc};
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// The state of a harvest job
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// A harvest submitted to the service
+#[derive(Clone)]
+struct Job {
+    id: u64,
+    arguments: Vec<String>,
+    args: Args,
+    /// The schedule that queued the job, None for submitted jobs
+    schedule: Option<String>,
+    status: JobStatus,
+    submitted_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    error: Option<Value>,
+}
+
+/// A job as returned by the API
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobView {
+    pub id: u64,
+    pub arguments: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+    pub status: JobStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub progress: ProgressSnapshot,
+    /// The failure of the job, in the format of `--errors-json`
+    pub error: Option<Value>,
+}
+
+impl From<&Job> for JobView {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            arguments: job.arguments.clone(),
+            schedule: job.schedule.clone(),
+            status: job.status,
+            submitted_at: job.submitted_at,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+            progress: job.args.progress.snapshot(),
+            error: job.error.clone(),
+        }
+    }
+}
+
+/// The body of a job submission
+#[derive(Debug, Deserialize)]
+struct JobRequest {
+    /// The command line arguments of the harvest, without the program name
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// A harvest the service runs periodically
+struct ScheduledHarvest {
+    name: String,
+    every: Duration,
+    arguments: Vec<String>,
+    args: Args,
+}
+
+/// How the runs of the schedules are spread in time
+#[derive(Debug, Clone, Copy, Default)]
+struct Pacing {
+    /// The maximum random delay added to every run
+    jitter: Duration,
+    /// The daily window the runs start in, None to run at any time
+    window: Option<SyncWindow>,
+    /// The timezone of the window
+    timezone: ReportTimezone,
+}
+
+/// A schedule as returned by the API
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleView {
+    pub name: String,
+    pub every: String,
+    pub destination: String,
+    pub next_run: DateTime<Utc>,
+    /// The last job queued by the schedule
+    pub last_job: Option<u64>,
+}
+
+/// The jobs of the service and the queue of the worker running them
+struct ServiceState {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    next_id: AtomicU64,
+    queue: mpsc::UnboundedSender<u64>,
+    schedules: Mutex<BTreeMap<String, ScheduleView>>,
+    /// The bearer token the requests must present, None to accept any request
+    token: Option<String>,
+    /// Cancelled when the service stops, cancelling the running jobs
+    shutdown: CancellationToken,
+}
+
+/// Build a JSON error response
+fn error_response(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
+
+/// Route a request of the API
+///
+/// # Arguments
+///
+/// * `state` - The state of the service
+/// * `request` - The request
+///
+/// # Returns
+///
+/// The status code and the JSON body of the response
+fn handle_request(state: &ServiceState, request: &Request) -> (u16, Value) {
+    let segments: Vec<&str> = request
+        .path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    if let (Some(token), ["jobs" | "schedules", ..]) = (&state.token, segments.as_slice()) {
+        if request.header("authorization") != Some(format!("Bearer {}", token).as_str()) {
+            return error_response(401, "missing or invalid bearer token");
+        }
+    }
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["health"]) => (200, json!({ "status": "ok", "version": VERSION })),
+        ("POST", ["jobs"]) => submit_job(state, &request.body),
+        ("GET", ["jobs"]) => {
+            let jobs = state.jobs.lock().unwrap();
+            let views: Vec<JobView> = jobs.values().map(JobView::from).collect();
+            (200, json!({ "jobs": views }))
+        }
+        ("GET", ["schedules"]) => {
+            let schedules = state.schedules.lock().unwrap();
+            let views: Vec<&ScheduleView> = schedules.values().collect();
+            (200, json!({ "schedules": views }))
+        }
+        (method, ["jobs", id, rest @ ..]) => {
+            let Ok(id) = id.parse::<u64>() else {
+                return error_response(404, "unknown job");
+            };
+            let mut jobs = state.jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(&id) else {
+                return error_response(404, "unknown job");
+            };
+            match (method, rest) {
+                ("GET", []) => (200, json!(JobView::from(&*job))),
+                ("GET", ["report"]) => job_report(job),
+                ("DELETE", []) => cancel_job(job),
+                _ => error_response(405, "method not allowed"),
+            }
+        }
+        _ => error_response(404, "not found"),
+    }
+}
+
+/// Queue a harvest job from the body of a submission
+fn submit_job(state: &ServiceState, body: &[u8]) -> (u16, Value) {
+    let request: JobRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error_response(400, &format!("invalid job: {}", e)),
+    };
+    let args = match Args::try_parse_from(
+        std::iter::once("vsixHarvester".to_string()).chain(request.args.iter().cloned()),
+    ) {
+        Ok(args) => args,
+        Err(e) => return error_response(400, &e.to_string()),
+    };
+    if args.command.is_some() {
+        return error_response(400, "only harvests can be submitted, not subcommands");
+    }
+    match queue_job(state, request.args, args, None) {
+        Some(view) => (202, json!(view)),
+        None => error_response(503, "the service is stopping"),
+    }
+}
+
+/// Queue a harvest job
+///
+/// # Arguments
+///
+/// * `state` - The state of the service
+/// * `arguments` - The command line arguments of the harvest, without the program name
+/// * `args` - The parsed arguments of the harvest
+/// * `schedule` - Optional name of the schedule queuing the job
+///
+/// # Returns
+///
+/// The queued job, or None if the service is stopping
+fn queue_job(
+    state: &ServiceState,
+    arguments: Vec<String>,
+    mut args: Args,
+    schedule: Option<String>,
+) -> Option<JobView> {
+    args.cancellation = state.shutdown.child_token();
+    args.progress = HarvestProgress::default();
+    args.no_prompt = true;
+
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let job = Job {
+        id,
+        arguments,
+        args,
+        schedule,
+        status: JobStatus::Queued,
+        submitted_at: Utc::now(),
+        started_at: None,
+        finished_at: None,
+        error: None,
+    };
+    let view = JobView::from(&job);
+    state.jobs.lock().unwrap().insert(id, job);
+    state.queue.send(id).ok()?;
+    info!("Job {} queued", id);
+    Some(view)
+}
+
+/// Queue the run of a schedule, unless its previous run is still queued or running
+fn queue_scheduled_run(state: &ServiceState, schedule: &ScheduledHarvest) {
+    let last_job = state
+        .schedules
+        .lock()
+        .unwrap()
+        .get(&schedule.name)
+        .and_then(|view| view.last_job);
+    let unfinished = last_job.filter(|id| {
+        state
+            .jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_some_and(|job| matches!(job.status, JobStatus::Queued | JobStatus::Running))
+    });
+    let queued = match unfinished {
+        Some(id) => {
+            warn!(
+                "Schedule {}: job {} is not finished, run skipped",
+                schedule.name, id
+            );
+            None
+        }
+        None => queue_job(
+            state,
+            schedule.arguments.clone(),
+            schedule.args.clone(),
+            Some(schedule.name.clone()),
+        ),
+    };
+    if let (Some(view), Some(job)) = (
+        state.schedules.lock().unwrap().get_mut(&schedule.name),
+        queued,
+    ) {
+        view.last_job = Some(job.id);
+    }
+}
+
+/// Record when a schedule runs next
+fn set_next_run(state: &ServiceState, name: &str, delay: Duration) {
+    if let Some(view) = state.schedules.lock().unwrap().get_mut(name) {
+        view.next_run =
+            Utc::now() + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::MAX);
+    }
+}
+
+/// Queue the runs of the schedules when they are due, until the service stops
+///
+/// Each schedule runs when the service starts, then at its interval, both delayed by
+/// a random jitter. A run due outside the sync window is postponed to its opening.
+async fn run_schedules(state: Arc<ServiceState>, schedules: Vec<ScheduledHarvest>, pacing: Pacing) {
+    let mut next_runs = Vec::new();
+    for schedule in &schedules {
+        let delay = random_jitter(pacing.jitter);
+        set_next_run(&state, &schedule.name, delay);
+        next_runs.push(Instant::now() + delay);
+    }
+    loop {
+        let now = Instant::now();
+        for (schedule, next_run) in schedules.iter().zip(next_runs.iter_mut()) {
+            if *next_run > now {
+                continue;
+            }
+            let closed = pacing
+                .window
+                .map(|window| window.delay_until_open(pacing.timezone.convert(&Utc::now()).time()))
+                .unwrap_or_default();
+            let delay = if closed.is_zero() {
+                queue_scheduled_run(&state, schedule);
+                schedule.every + random_jitter(pacing.jitter)
+            } else {
+                info!(
+                    "Schedule {}: outside the sync window, run postponed",
+                    schedule.name
+                );
+                closed + random_jitter(pacing.jitter)
+            };
+            set_next_run(&state, &schedule.name, delay);
+            *next_run = now + delay;
+        }
+        let Some(wake) = next_runs.iter().min().copied() else {
+            return;
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(wake) => {}
+            _ = state.shutdown.cancelled() => return,
+        }
+    }
+}
+
+/// Build the report of a finished job, with the statistics of its destination
+fn job_report(job: &Job) -> (u16, Value) {
+    if matches!(job.status, JobStatus::Queued | JobStatus::Running) {
+        return error_response(409, "the job is not finished");
+    }
+    let destination = job.args.primary_destination();
+    let stats = match scan_mirror(&destination) {
+        Ok(packages) => json!(mirror_stats(&packages, 10)),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+    (
+        200,
+        json!({
+            "job": JobView::from(job),
+            "destination": destination,
+            "stats": stats,
+        }),
+    )
+}
+
+/// Cancel a queued or running job
+fn cancel_job(job: &mut Job) -> (u16, Value) {
+    match job.status {
+        JobStatus::Queued => {
+            job.status = JobStatus::Cancelled;
+            job.finished_at = Some(Utc::now());
+        }
+        JobStatus::Running => job.args.cancellation.cancel(),
+        _ => return error_response(409, "the job is already finished"),
+    }
+    info!("Job {} cancelled", job.id);
+    (202, json!(JobView::from(&*job)))
+}
+
+/// Run the queued jobs, in parallel when they write different destinations
+///
+/// The jobs of a destination run one after the other, so two harvests never write
+/// the same files. Once the service stops, the queued jobs are left and the running
+/// ones are cancelled and awaited.
+async fn run_jobs(state: Arc<ServiceState>, mut queue: mpsc::UnboundedReceiver<u64>) {
+    let mut pending: VecDeque<u64> = VecDeque::new();
+    let mut busy: HashSet<String> = HashSet::new();
+    let mut running = FuturesUnordered::new();
+    let mut accepting = true;
+    loop {
+        if accepting {
+            for id in std::mem::take(&mut pending) {
+                let mut jobs = state.jobs.lock().unwrap();
+                let Some(job) = jobs
+                    .get_mut(&id)
+                    .filter(|job| job.status == JobStatus::Queued)
+                else {
+                    continue;
+                };
+                let destination = job.args.primary_destination();
+                if busy.contains(&destination) {
+                    pending.push_back(id);
+                    continue;
+                }
+                job.status = JobStatus::Running;
+                job.started_at = Some(Utc::now());
+                let args = job.args.clone();
+                info!("Job {} started", id);
+                busy.insert(destination);
+                running.push(async move {
+                    let result = crate::process_extensions(&args).await;
+                    (id, args, result)
+                });
+            }
+        } else if running.is_empty() {
+            return;
+        }
+        tokio::select! {
+            id = queue.recv(), if accepting => match id {
+                Some(id) => pending.push_back(id),
+                None => accepting = false,
+            },
+            Some((id, args, result)) = running.next(), if !running.is_empty() => {
+                busy.remove(&args.primary_destination());
+                finish_job(&state, id, &args, result);
+            }
+            _ = state.shutdown.cancelled(), if accepting => accepting = false,
+        }
+    }
+}
+
+/// Record the outcome of a job
+fn finish_job(state: &ServiceState, id: u64, args: &Args, result: Result<()>) {
+    let mut jobs = state.jobs.lock().unwrap();
+    let Some(job) = jobs.get_mut(&id) else {
+        return;
+    };
+    job.finished_at = Some(Utc::now());
+    job.status = match result {
+        Err(VsixHarvesterError::Cancelled) => JobStatus::Cancelled,
+        Ok(()) if args.cancellation.is_cancelled() => JobStatus::Cancelled,
+        Ok(()) => JobStatus::Succeeded,
+        Err(e) => {
+            error!("Job {} failed: {}", id, e);
+            job.error = serde_json::to_value(ErrorReport::from(&e)).ok();
+            JobStatus::Failed
+        }
+    };
+    info!("Job {} finished: {:?}", id, job.status);
+}
+
+/// Serve the harvest REST API until the shutdown token is cancelled
+///
+/// The jobs run in the service process, along with the runs of the schedules.
+/// Cancelling the shutdown token cancels the running jobs and stops the service
+/// once they have recorded what they downloaded.
+///
+/// # Arguments
+///
+/// * `listen` - The address to listen on (e.g., "127.0.0.1:8787")
+/// * `token` - Optional reference to the bearer token of the `/jobs` endpoints (e.g., "env:SERVICE_TOKEN")
+/// * `schedules` - Optional path of the schedules file
+/// * `jitter` - Optional maximum random delay of the scheduled runs (e.g., "15m")
+/// * `window` - Optional daily window the scheduled runs start in (e.g., "01:00-05:00")
+/// * `timezone` - Optional timezone of the window, the one of the host by default
+/// * `shutdown` - The token stopping the service
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn serve(
+    listen: &str,
+    token: Option<&str>,
+    schedules: Option<&str>,
+    jitter: Option<&str>,
+    window: Option<&str>,
+    timezone: Option<ReportTimezone>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let token = token.map(resolve_secret).transpose()?;
+    let pacing = Pacing {
+        jitter: jitter.map(parse_interval).transpose()?.unwrap_or_default(),
+        window: window.map(SyncWindow::parse).transpose()?,
+        timezone: timezone.unwrap_or(ReportTimezone::Local),
+    };
+    let mut harvests = Vec::new();
+    let mut views = BTreeMap::new();
+    if let Some(path) = schedules {
+        for (name, schedule) in Schedules::load(path)?.schedules {
+            harvests.push(ScheduledHarvest {
+                every: parse_interval(&schedule.every)?,
+                arguments: schedule.arguments(),
+                args: schedule.run_args(&name)?,
+                name: name.clone(),
+            });
+            views.insert(
+                name.clone(),
+                ScheduleView {
+                    name,
+                    every: schedule.every,
+                    destination: schedule.destination,
+                    next_run: Utc::now(),
+                    last_job: None,
+                },
+            );
+        }
+        info!("{} schedule(s) loaded from {}", harvests.len(), path);
+    }
+    if token.is_none() {
+        warn!(
+            "No service token, any client reaching {} may submit harvests",
+            listen
+        );
+    }
+    let listener = TcpListener::bind(listen).await?;
+    info!("Harvest service listening on {}", listener.local_addr()?);
+    let (queue, jobs) = mpsc::unbounded_channel();
+    let state = Arc::new(ServiceState {
+        jobs: Mutex::new(BTreeMap::new()),
+        next_id: AtomicU64::new(1),
+        queue,
+        schedules: Mutex::new(views),
+        token,
+        shutdown: shutdown.clone(),
+    });
+
+    let api = state.clone();
+    let accept = serve_connections(listener, "service", shutdown, move |request| {
+        let (status, body) = handle_request(&api, &request);
+        async move { Response::json(status, &body) }
+    });
+    tokio::join!(
+        accept,
+        run_schedules(state.clone(), harvests, pacing),
+        run_jobs(state.clone(), jobs)
+    );
+    info!("Harvest service stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: BTreeMap::from([("authorization".to_string(), "Bearer s3cret".to_string())]),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn service_state(queue: mpsc::UnboundedSender<u64>) -> ServiceState {
+        ServiceState {
+            jobs: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            queue,
+            schedules: Mutex::new(BTreeMap::new()),
+            token: Some("s3cret".to_string()),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    #[test]
+    fn test_job_api() {
+        let (queue, mut queued) = mpsc::unbounded_channel();
+        let state = service_state(queue);
+
+        let (status, body) = handle_request(
+            &state,
+            &request(
+                "POST",
+                "/jobs",
+                r#"{"args": ["--destination", "/nonexistent/mirror"]}"#,
+            ),
+        );
+        assert_eq!(status, 202);
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["status"], "queued");
+        assert_eq!(queued.try_recv().unwrap(), 1);
+
+        // Subcommands and unknown arguments are refused
+        let (status, _) =
+            handle_request(&state, &request("POST", "/jobs", r#"{"args": ["stats"]}"#));
+        assert_eq!(status, 400);
+        let (status, _) = handle_request(
+            &state,
+            &request("POST", "/jobs", r#"{"args": ["--unknown"]}"#),
+        );
+        assert_eq!(status, 400);
+
+        let mut anonymous = request("GET", "/jobs", "");
+        anonymous.headers.remove("authorization");
+        assert_eq!(handle_request(&state, &anonymous).0, 401);
+        anonymous.path = "/health".to_string();
+        assert_eq!(handle_request(&state, &anonymous).0, 200);
+
+        assert_eq!(
+            handle_request(&state, &request("GET", "/jobs/1/report", "")).0,
+            409
+        );
+        let (status, body) = handle_request(&state, &request("DELETE", "/jobs/1", ""));
+        assert_eq!(status, 202);
+        assert_eq!(body["status"], "cancelled");
+        let (status, body) = handle_request(&state, &request("GET", "/jobs/1/report", ""));
+        assert_eq!(status, 200);
+        assert_eq!(body["destination"], "/nonexistent/mirror");
+        assert!(body["stats"]["error"].is_string());
+
+        let (_, body) = handle_request(&state, &request("GET", "/jobs", ""));
+        assert_eq!(body["jobs"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            handle_request(&state, &request("GET", "/jobs/7", "")).0,
+            404
+        );
+    }
+
+    #[test]
+    fn test_scheduled_runs() {
+        let (queue, mut queued) = mpsc::unbounded_channel();
+        let state = service_state(queue);
+        let schedule = ScheduledHarvest {
+            name: "team-a".to_string(),
+            every: Duration::from_secs(3600),
+            arguments: vec!["--destination".to_string(), "/srv/a".to_string()],
+            args: Args::try_parse_from(["vsixHarvester", "--destination", "/srv/a"]).unwrap(),
+        };
+        state.schedules.lock().unwrap().insert(
+            schedule.name.clone(),
+            ScheduleView {
+                name: schedule.name.clone(),
+                every: "1h".to_string(),
+                destination: "/srv/a".to_string(),
+                next_run: Utc::now(),
+                last_job: None,
+            },
+        );
+
+        queue_scheduled_run(&state, &schedule);
+        assert_eq!(queued.try_recv().unwrap(), 1);
+        // The previous run is still queued
+        queue_scheduled_run(&state, &schedule);
+        assert!(queued.try_recv().is_err());
+
+        let (_, body) = handle_request(&state, &request("GET", "/jobs/1", ""));
+        assert_eq!(body["schedule"], "team-a");
+        let (_, body) = handle_request(&state, &request("GET", "/schedules", ""));
+        assert_eq!(body["schedules"][0]["lastJob"], 1);
+
+        state.jobs.lock().unwrap().get_mut(&1).unwrap().status = JobStatus::Succeeded;
+        queue_scheduled_run(&state, &schedule);
+        assert_eq!(queued.try_recv().unwrap(), 2);
+    }
+}
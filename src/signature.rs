@@ -0,0 +1,239 @@
+use crate::crypto::Sha256Hasher;
+use crate::error::{Result, VsixHarvesterError};
+use crate::marketplace::{request_package, DownloadOptions, PackageContent, ResolvedExtension};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use zip::ZipArchive;
+
+/// The entry of the signature archive listing the digests of the package and its files
+pub const SIGNATURE_MANIFEST_ENTRY: &str = ".signature.manifest";
+/// The entry of the signature archive holding the PKCS#7 signature of the manifest
+pub const SIGNATURE_P7S_ENTRY: &str = ".signature.p7s";
+
+/// Whether the Marketplace signatures of the packages are checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Do not download the signatures
+    #[default]
+    Skip,
+    /// Check the signature of the packages that have one
+    Verify,
+    /// Check the signatures and refuse the packages without one
+    Require,
+}
+
+/// The size and digests of a file
+#[derive(Debug, Clone, Deserialize)]
+struct FileDigest {
+    size: u64,
+    digests: BTreeMap<String, String>,
+}
+
+/// The manifest of a signature archive
+#[derive(Debug, Clone, Deserialize)]
+struct SignatureManifest {
+    package: FileDigest,
+    /// The digests of the files of the package, keyed by their base64-encoded path
+    entries: BTreeMap<String, FileDigest>,
+}
+
+/// Check that a file matches its digest in the signature manifest
+fn check_digest(
+    name: &str,
+    mut content: impl Read,
+    expected: &FileDigest,
+) -> std::result::Result<(), String> {
+    let mut hasher = Sha256Hasher::new();
+    let size =
+        io::copy(&mut content, &mut hasher).map_err(|e| format!("unreadable {}: {}", name, e))?;
+    if size != expected.size {
+        return Err(format!(
+            "{} has {} bytes, the signature lists {}",
+            name, size, expected.size
+        ));
+    }
+    let Some(sha256) = expected.digests.get("sha256") else {
+        return Err(format!("the signature has no SHA-256 digest of {}", name));
+    };
+    if *sha256 != BASE64.encode(hasher.finalize()) {
+        return Err(format!("{} differs from the digest of the manifest", name));
+    }
+    Ok(())
+}
+
+/// Check a VSIX package against the digests of its Marketplace signature archive
+///
+/// The digests of the signature manifest must match the package and each of its
+/// files, and every file of the package must be listed. This is a consistency check
+/// only: the PKCS#7 signature of the manifest must be present, but neither the
+/// signature nor its certificate chain is validated, so a forged manifest is not
+/// detected.
+///
+/// # Arguments
+///
+/// * `vsix` - The reader of the package
+/// * `signature` - The content of the signature archive (`.sigzip`)
+///
+/// # Returns
+///
+/// A Result indicating success or the reason of the mismatch
+pub fn check_signature_digests<R: Read + Seek>(
+    mut vsix: R,
+    signature: &[u8],
+) -> std::result::Result<(), String> {
+    let mut signature_archive = ZipArchive::new(Cursor::new(signature))
+        .map_err(|e| format!("invalid signature archive: {}", e))?;
+    let mut read = |name: &str| -> std::result::Result<Vec<u8>, String> {
+        let mut entry = signature_archive
+            .by_name(name)
+            .map_err(|_| format!("the signature archive has no {}", name))?;
+        let mut content = Vec::new();
+        entry
+            .read_to_end(&mut content)
+            .map_err(|e| format!("unreadable {}: {}", name, e))?;
+        Ok(content)
+    };
+    if read(SIGNATURE_P7S_ENTRY)?.is_empty() {
+        return Err(format!("{} is empty", SIGNATURE_P7S_ENTRY));
+    }
+    let manifest: SignatureManifest = serde_json::from_slice(&read(SIGNATURE_MANIFEST_ENTRY)?)
+        .map_err(|e| format!("invalid {}: {}", SIGNATURE_MANIFEST_ENTRY, e))?;
+    check_digest("the package", &mut vsix, &manifest.package)?;
+    vsix.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("unreadable package: {}", e))?;
+
+    let mut archive = ZipArchive::new(vsix).map_err(|e| format!("invalid package: {}", e))?;
+    let mut unsigned: BTreeSet<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    for (encoded_name, expected) in &manifest.entries {
+        let name = BASE64
+            .decode(encoded_name)
+            .ok()
+            .and_then(|name| String::from_utf8(name).ok())
+            .unwrap_or_else(|| encoded_name.clone());
+        let entry = archive
+            .by_name(&name)
+            .map_err(|_| format!("{} is listed but missing from the package", name))?;
+        check_digest(&name, entry, expected)?;
+        unsigned.remove(&name);
+    }
+    if let Some(name) = unsigned.into_iter().next() {
+        return Err(format!("{} is not listed in the signature manifest", name));
+    }
+    Ok(())
+}
+
+/// Download the signature archive of a package and check the package against its digests
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client
+/// * `resolved` - The resolved extension of the package
+/// * `vsix` - The downloaded package
+/// * `options` - The download options, with the signature policy
+///
+/// # Returns
+///
+/// A Result containing whether the package was checked against the digests, or a
+/// `SignatureMismatch` error for packages to refuse
+pub async fn verify_package_signature(
+    client: &reqwest::Client,
+    resolved: &ResolvedExtension,
+    vsix: &PackageContent,
+    options: &DownloadOptions,
+) -> Result<bool> {
+    if options.signature_policy == SignaturePolicy::Skip {
+        return Ok(false);
+    }
+    let package = format!("{}@{}", resolved.id, resolved.version);
+    let mismatch = |reason: String| VsixHarvesterError::SignatureMismatch(package.clone(), reason);
+    let signature = match options.registry.signature_url(resolved, options) {
+        Some(url) => {
+            let resp = request_package(client, &url, options).await?;
+            if resp.status().is_success() {
+                Some(resp.bytes().await?.to_vec())
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+    let Some(signature) = signature else {
+        if options.signature_policy == SignaturePolicy::Require {
+            return Err(mismatch("the package has no signature".to_string()));
+        }
+        warn!("{} has no signature, not checked", package);
+        return Ok(false);
+    };
+    check_signature_digests(vsix.reader()?, &signature).map_err(mismatch)?;
+    info!("{} matches the digests of its signature manifest", package);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sha256;
+    use crate::test_support::zip;
+
+    fn digest(content: &[u8]) -> serde_json::Value {
+        serde_json::json!({
+            "size": content.len(),
+            "digests": {"sha256": BASE64.encode(sha256(content))}
+        })
+    }
+
+    fn signature(vsix: &[u8], entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let entries: serde_json::Map<_, _> = entries
+            .iter()
+            .map(|(name, content)| (BASE64.encode(name), digest(content)))
+            .collect();
+        let manifest = serde_json::json!({"package": digest(vsix), "entries": entries});
+        zip(&[
+            (SIGNATURE_MANIFEST_ENTRY, manifest.to_string().as_bytes()),
+            (SIGNATURE_P7S_ENTRY, b"pkcs7"),
+        ])
+    }
+
+    #[test]
+    fn test_check_signature_digests() {
+        let files: [(&str, &[u8]); 2] = [
+            ("extension.vsixmanifest", b"<PackageManifest/>"),
+            ("extension/package.json", b"{}"),
+        ];
+        let vsix = zip(&files);
+        assert_eq!(
+            check_signature_digests(Cursor::new(&vsix), &signature(&vsix, &files)),
+            Ok(())
+        );
+
+        // A file added to the package after signing
+        let tampered = zip(&[files[0], files[1], ("extension/extra.js", b"x")]);
+        assert!(
+            check_signature_digests(Cursor::new(&tampered), &signature(&tampered, &files))
+                .unwrap_err()
+                .contains("extension/extra.js")
+        );
+
+        // A file modified after signing
+        let modified = zip(&[files[0], ("extension/package.json", b"{\"a\":1}")]);
+        assert!(
+            check_signature_digests(Cursor::new(&modified), &signature(&modified, &files))
+                .unwrap_err()
+                .contains("extension/package.json")
+        );
+
+        // A signature of another package
+        let other = zip(&files[..1]);
+        assert!(
+            check_signature_digests(Cursor::new(&vsix), &signature(&other, &files[..1])).is_err()
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::config::{STATIC_MIRROR_EXTENSIONS_DIR, STATIC_MIRROR_INDEX_FILE_NAME, VERSION};
+use crate::create_directory_if_not_exists;
+use crate::error::Result;
+use crate::hash::{Digest, HashAlgorithm};
+use crate::provenance::read_provenance;
+use crate::status::scan_mirror;
+use crate::temp::{copy_atomic, write_atomic};
+use crate::timezone::ReportTimezone;
+use crate::types::compare_versions;
+use crate::vsix::read_vsix_info;
+use chrono::{DateTime, FixedOffset, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A package of the static mirror
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticPackage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_platform: Option<TargetPlatform>,
+    /// The path of the package, relative to the root of the static mirror
+    pub path: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    pub extension_dependencies: Vec<String>,
+    pub extension_pack: Vec<String>,
+    /// Serialized as a field named after the algorithm (e.g., "sha256")
+    #[serde(flatten)]
+    pub digest: Digest,
+    /// When the package was harvested, from its provenance or its modification time
+    pub harvested_at: DateTime<FixedOffset>,
+    /// When the version was published on the marketplace, from the provenance of the package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<DateTime<FixedOffset>>,
+}
+
+/// A version of an extension of the static mirror with its packages
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticVersion {
+    pub version: String,
+    pub packages: Vec<StaticPackage>,
+}
+
+/// The index of an extension, written in its directory
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticExtension {
+    pub id: String,
+    pub publisher: String,
+    pub name: String,
+    /// The versions, the newest first
+    pub versions: Vec<StaticVersion>,
+}
+
+/// An extension of the top-level catalog
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogEntry {
+    pub id: String,
+    pub latest_version: String,
+    pub versions: Vec<String>,
+    /// The target platforms of the packages, "universal" for the universal ones
+    pub platforms: Vec<String>,
+    /// The path of the index of the extension, relative to the root of the static mirror
+    pub index: String,
+}
+
+/// The top-level `index.json` catalog of the static mirror
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticCatalog {
+    pub harvester: String,
+    pub generated_at: DateTime<FixedOffset>,
+    pub extensions: Vec<CatalogEntry>,
+}
+
+/// Get the directory of an extension, relative to the root of the static mirror
+///
+/// Identifiers are lowercased, static file servers being case-sensitive while the
+/// marketplace identifiers are not.
+fn extension_dir(publisher: &str, name: &str) -> String {
+    format!(
+        "{}/{}/{}",
+        STATIC_MIRROR_EXTENSIONS_DIR,
+        publisher.to_lowercase(),
+        name.to_lowercase()
+    )
+}
+
+/// Copy a package unless the output already holds a file of the same size
+fn copy_package(source: &str, target: &str, size: u64) -> Result<()> {
+    if fs::metadata(target).is_ok_and(|metadata| metadata.len() == size) {
+        return Ok(());
+    }
+    copy_atomic(Path::new(source), Path::new(target))
+}
+
+/// Lay out the packages of a mirror for static file hosting
+///
+/// Every package is copied to `extensions/<publisher>/<name>/<version>/`, next to
+/// an `index.json` per extension listing its versions and packages, and a top-level
+/// `index.json` catalog of the extensions, their versions and platforms. Running the
+/// export again only copies the new packages.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `output` - The directory of the static mirror
+/// * `algorithm` - The hash algorithm of the package digests
+/// * `timezone` - The timezone of the timestamps of the indexes
+///
+/// # Returns
+///
+/// A Result containing the catalog or an error that occurred
+pub fn export_static_mirror(
+    destination: &str,
+    output: &str,
+    algorithm: HashAlgorithm,
+    timezone: ReportTimezone,
+) -> Result<StaticCatalog> {
+    let mut extensions: BTreeMap<String, StaticExtension> = BTreeMap::new();
+    for package in scan_mirror(destination)? {
+        let source = format!("{}/{}", destination, package.file_name);
+        let info = match read_vsix_info(&source) {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Not exporting {}: {}", package.file_name, e);
+                continue;
+            }
+        };
+        let version_dir = format!(
+            "{}/{}",
+            extension_dir(&info.publisher, &info.name),
+            info.version
+        );
+        create_directory_if_not_exists(&format!("{}/{}", output, version_dir))?;
+        let path = format!("{}/{}", version_dir, package.file_name);
+        let content = fs::read(&source)?;
+        copy_package(&source, &format!("{}/{}", output, path), package.size)?;
+        let provenance = read_provenance(&source).unwrap_or_else(|e| {
+            warn!(
+                "Could not read the provenance of {}: {}",
+                package.file_name, e
+            );
+            None
+        });
+        let harvested_at = match &provenance {
+            Some(provenance) => provenance.retrieved_at,
+            None => fs::metadata(&source)?.modified()?.into(),
+        };
+
+        let extension = extensions
+            .entry(info.id().to_lowercase())
+            .or_insert_with(|| StaticExtension {
+                id: info.id(),
+                publisher: info.publisher.clone(),
+                name: info.name.clone(),
+                versions: Vec::new(),
+            });
+        let static_package = StaticPackage {
+            target_platform: info.target_platform,
+            path,
+            size: package.size,
+            engine: info.engine,
+            extension_dependencies: info.extension_dependencies,
+            extension_pack: info.extension_pack,
+            digest: algorithm.digest(&content),
+            harvested_at: timezone.convert(&harvested_at),
+            published_at: provenance
+                .and_then(|provenance| provenance.published_at)
+                .map(|published_at| timezone.convert(&published_at)),
+        };
+        match extension
+            .versions
+            .iter_mut()
+            .find(|version| version.version == info.version)
+        {
+            Some(version) => version.packages.push(static_package),
+            None => extension.versions.push(StaticVersion {
+                version: info.version,
+                packages: vec![static_package],
+            }),
+        }
+    }
+
+    let mut catalog = StaticCatalog {
+        harvester: format!("vsixHarvester {}", VERSION),
+        generated_at: timezone.convert(&Utc::now()),
+        extensions: Vec::new(),
+    };
+    for extension in extensions.values_mut() {
+        extension
+            .versions
+            .sort_by(|a, b| compare_versions(&b.version, &a.version).cmp(&0));
+        let mut platforms: Vec<String> = extension
+            .versions
+            .iter()
+            .flat_map(|version| &version.packages)
+            .map(|package| target_platform_name(package.target_platform.as_ref()).to_string())
+            .collect();
+        platforms.sort();
+        platforms.dedup();
+        let index = format!(
+            "{}/{}",
+            extension_dir(&extension.publisher, &extension.name),
+            STATIC_MIRROR_INDEX_FILE_NAME
+        );
+        write_atomic(
+            Path::new(&format!("{}/{}", output, index)),
+            serde_json::to_string_pretty(extension)?.as_bytes(),
+        )?;
+        catalog.extensions.push(CatalogEntry {
+            id: extension.id.clone(),
+            latest_version: extension.versions[0].version.clone(),
+            versions: extension
+                .versions
+                .iter()
+                .map(|version| version.version.clone())
+                .collect(),
+            platforms,
+            index,
+        });
+    }
+    create_directory_if_not_exists(output)?;
+    write_atomic(
+        Path::new(&format!("{}/{}", output, STATIC_MIRROR_INDEX_FILE_NAME)),
+        serde_json::to_string_pretty(&catalog)?.as_bytes(),
+    )?;
+    info!(
+        "Static mirror of {} extension(s) exported to {}",
+        catalog.extensions.len(),
+        output
+    );
+    Ok(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn vsix(version: &str, target_platform: Option<&str>) -> Vec<u8> {
+        test_support::vsix("golang.Go", version, target_platform)
+    }
+
+    #[test]
+    fn test_export_static_mirror() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("mirror");
+        let destination = destination.to_str().unwrap();
+        let output = temp_dir.path().join("static");
+        let output = output.to_str().unwrap();
+        fs::create_dir(destination).unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.2.vsix", destination),
+            vsix("0.41.2", None),
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.10@linux-x64.vsix", destination),
+            vsix("0.41.10", Some("linux-x64")),
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/broken.Ext-1.0.0.vsix", destination),
+            "not a zip",
+        )
+        .unwrap();
+
+        let catalog = export_static_mirror(
+            destination,
+            output,
+            HashAlgorithm::Sha256,
+            ReportTimezone::Utc,
+        )
+        .unwrap();
+        assert_eq!(catalog.extensions.len(), 1);
+        let entry = &catalog.extensions[0];
+        assert_eq!(entry.id, "golang.Go");
+        assert_eq!(entry.latest_version, "0.41.10");
+        assert_eq!(entry.versions, vec!["0.41.10", "0.41.2"]);
+        assert_eq!(entry.platforms, vec!["linux-x64", "universal"]);
+        assert_eq!(entry.index, "extensions/golang/go/index.json");
+
+        let index: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(format!("{}/{}", output, entry.index)).unwrap(),
+        )
+        .unwrap();
+        let package = &index["versions"][1]["packages"][0];
+        assert_eq!(
+            package["path"],
+            "extensions/golang/go/0.41.2/golang.Go-0.41.2.vsix"
+        );
+        assert_eq!(package["engine"], "^1.90.0");
+        // Without provenance, the harvest time is the modification time of the package
+        assert!(package["harvestedAt"]
+            .as_str()
+            .unwrap()
+            .parse::<DateTime<Utc>>()
+            .is_ok());
+        assert!(package.get("publishedAt").is_none());
+        assert_eq!(
+            fs::read(format!("{}/{}", output, package["path"].as_str().unwrap())).unwrap(),
+            vsix("0.41.2", None)
+        );
+        assert_eq!(
+            package["sha256"],
+            HashAlgorithm::Sha256.digest(&vsix("0.41.2", None)).value
+        );
+        let catalog: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(format!("{}/index.json", output)).unwrap())
+                .unwrap();
+        assert_eq!(catalog["extensions"][0]["latestVersion"], "0.41.10");
+    }
+}
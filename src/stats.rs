@@ -0,0 +1,245 @@
+use crate::architecture::{join_target_platforms, target_platform_name, TargetPlatform};
+use crate::diff::format_size;
+use crate::error::Result;
+use crate::status::{scan_mirror, MirroredPackage, PlatformTotals};
+use crate::types::compare_versions;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The disk usage of an extension, every version and platform included
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ExtensionUsage {
+    pub id: String,
+    pub packages: usize,
+    pub size: u64,
+}
+
+/// A version mirrored both as a universal package and as platform-specific packages
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatePair {
+    pub id: String,
+    pub version: String,
+    pub target_platforms: Vec<TargetPlatform>,
+    /// The size of the universal package, redundant with the platform-specific ones
+    pub universal_size: u64,
+}
+
+/// Storage statistics of a mirror
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorStats {
+    pub packages: usize,
+    pub total_size: u64,
+    /// Totals keyed by target platform, "universal" for universal packages
+    pub platforms: BTreeMap<String, PlatformTotals>,
+    /// The extensions using the most space, largest first
+    pub largest: Vec<ExtensionUsage>,
+    pub duplicates: Vec<DuplicatePair>,
+    /// The packages of versions older than the newest one of their extension and platform
+    pub outdated_packages: usize,
+    pub outdated_size: u64,
+    /// The space freed by removing the outdated packages and the redundant universal packages
+    pub potential_savings: u64,
+}
+
+/// Compute the storage statistics of the packages of a mirror
+///
+/// # Arguments
+///
+/// * `packages` - The packages of the mirror
+/// * `top` - The number of largest extensions to report
+///
+/// # Returns
+///
+/// The statistics of the mirror
+pub fn mirror_stats(packages: &[MirroredPackage], top: usize) -> MirrorStats {
+    let mut stats = MirrorStats {
+        packages: packages.len(),
+        ..Default::default()
+    };
+    let mut usages: BTreeMap<String, ExtensionUsage> = BTreeMap::new();
+    let mut newest: BTreeMap<(String, Option<&TargetPlatform>), &MirroredPackage> = BTreeMap::new();
+    let mut versions: BTreeMap<(String, &str), Vec<&MirroredPackage>> = BTreeMap::new();
+    for package in packages {
+        stats.total_size += package.size;
+        let totals = stats
+            .platforms
+            .entry(target_platform_name(package.target_platform.as_ref()).to_string())
+            .or_default();
+        totals.packages += 1;
+        totals.size += package.size;
+
+        let id = package.id.to_lowercase();
+        let usage = usages.entry(id.clone()).or_insert_with(|| ExtensionUsage {
+            id: package.id.clone(),
+            packages: 0,
+            size: 0,
+        });
+        usage.packages += 1;
+        usage.size += package.size;
+
+        let current = newest
+            .entry((id.clone(), package.target_platform.as_ref()))
+            .or_insert(package);
+        if compare_versions(&package.version, &current.version) > 0 {
+            *current = package;
+        }
+        versions
+            .entry((id, package.version.as_str()))
+            .or_default()
+            .push(package);
+    }
+
+    let newest_files: Vec<&str> = newest
+        .values()
+        .map(|package| package.file_name.as_str())
+        .collect();
+    for package in packages {
+        if !newest_files.contains(&package.file_name.as_str()) {
+            stats.outdated_packages += 1;
+            stats.outdated_size += package.size;
+        }
+    }
+
+    for builds in versions.values() {
+        let Some(universal) = builds
+            .iter()
+            .find(|package| package.target_platform.is_none())
+        else {
+            continue;
+        };
+        let target_platforms: Vec<TargetPlatform> = builds
+            .iter()
+            .filter_map(|package| package.target_platform.clone())
+            .collect();
+        if target_platforms.is_empty() {
+            continue;
+        }
+        stats.duplicates.push(DuplicatePair {
+            id: universal.id.clone(),
+            version: universal.version.clone(),
+            target_platforms,
+            universal_size: universal.size,
+        });
+        // An outdated universal package is already counted in the outdated size
+        if newest_files.contains(&universal.file_name.as_str()) {
+            stats.potential_savings += universal.size;
+        }
+    }
+    stats.potential_savings += stats.outdated_size;
+
+    let mut largest: Vec<ExtensionUsage> = usages.into_values().collect();
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.id.cmp(&b.id)));
+    largest.truncate(top);
+    stats.largest = largest;
+    stats
+}
+
+/// Print the storage statistics of a mirror as tables or as JSON
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `top` - The number of largest extensions to report
+/// * `json` - Whether to print JSON
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn print_mirror_stats(destination: &str, top: usize, json: bool) -> Result<()> {
+    let stats = mirror_stats(&scan_mirror(destination)?, top);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("Mirror:   {}", destination);
+    println!(
+        "Packages: {} ({})",
+        stats.packages,
+        format_size(stats.total_size)
+    );
+    println!();
+    println!("{:<14} {:>8} {:>12}", "PLATFORM", "PACKAGES", "SIZE");
+    for (platform, totals) in &stats.platforms {
+        println!(
+            "{:<14} {:>8} {:>12}",
+            platform,
+            totals.packages,
+            format_size(totals.size)
+        );
+    }
+    println!();
+    println!("{:<50} {:>8} {:>12}", "EXTENSION", "PACKAGES", "SIZE");
+    for usage in &stats.largest {
+        println!(
+            "{:<50} {:>8} {:>12}",
+            usage.id,
+            usage.packages,
+            format_size(usage.size)
+        );
+    }
+    println!();
+    println!(
+        "{} version(s) mirrored both as universal and platform-specific packages",
+        stats.duplicates.len()
+    );
+    for duplicate in &stats.duplicates {
+        println!(
+            "  {} {} (universal {}, {})",
+            duplicate.id,
+            duplicate.version,
+            format_size(duplicate.universal_size),
+            join_target_platforms(&duplicate.target_platforms, ", ")
+        );
+    }
+    println!(
+        "{} outdated package(s) ({})",
+        stats.outdated_packages,
+        format_size(stats.outdated_size)
+    );
+    println!(
+        "Potential savings from pruning: {}",
+        format_size(stats.potential_savings)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mirrored_package as package;
+
+    #[test]
+    fn test_mirror_stats() {
+        let packages = [
+            package("golang.Go", "0.41.1", None, 10),
+            package("golang.Go", "0.41.2", None, 12),
+            package("rust-lang.rust-analyzer", "0.3.1", None, 30),
+            package("rust-lang.rust-analyzer", "0.3.1", Some("linux-x64"), 40),
+            package("rust-lang.rust-analyzer", "0.3.1", Some("win32-x64"), 45),
+        ];
+        let stats = mirror_stats(&packages, 1);
+        assert_eq!(stats.packages, 5);
+        assert_eq!(stats.total_size, 137);
+        assert_eq!(stats.platforms["universal"].packages, 3);
+        assert_eq!(stats.platforms["win32-x64"].size, 45);
+        assert_eq!(
+            stats.largest,
+            [ExtensionUsage {
+                id: "rust-lang.rust-analyzer".to_string(),
+                packages: 3,
+                size: 115,
+            }]
+        );
+        assert_eq!(stats.duplicates.len(), 1);
+        assert_eq!(
+            stats.duplicates[0].target_platforms,
+            ["linux-x64", "win32-x64"]
+        );
+        assert_eq!(stats.outdated_packages, 1);
+        assert_eq!(stats.outdated_size, 10);
+        assert_eq!(stats.potential_savings, 40);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::architecture::{target_platform_name, TargetPlatform};
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::inventory::Inventory;
+use crate::marketplace::{query_extension_version, DownloadOptions};
+use crate::timezone::ReportTimezone;
+use crate::types::compare_versions;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use futures::stream::{self, StreamExt};
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// A VSIX package found in the destination directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirroredPackage {
+    pub file_name: String,
+    pub id: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    pub size: u64,
+}
+
+/// Parse the name of a package saved by the harvester
+///
+/// # Arguments
+///
+/// * `file_name` - The file name (e.g., "golang.Go-0.41.2@linux-x64.vsix")
+///
+/// # Returns
+///
+/// An Option containing the extension identifier, version and target platform,
+/// or None if the name does not follow the naming of the harvester
+pub fn parse_package_file_name(
+    file_name: &str,
+) -> Option<(String, String, Option<TargetPlatform>)> {
+    let stem = file_name.strip_suffix(".vsix")?;
+    let (stem, target_platform) = match stem.rsplit_once('@') {
+        Some((stem, target_platform)) => (stem, Some(TargetPlatform::new(target_platform))),
+        None => (stem, None),
+    };
+    let (id, version) = stem.rsplit_once('-')?;
+    Extension::from_id(id).ok()?;
+    Some((id.to_string(), version.to_string(), target_platform))
+}
+
+/// List the packages saved in a destination directory
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+///
+/// # Returns
+///
+/// A Result containing the packages sorted by file name or an error that occurred
+pub fn scan_mirror(destination: &str) -> Result<Vec<MirroredPackage>> {
+    let mut entries: Vec<_> = fs::read_dir(destination)?
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut packages = Vec::new();
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if let Some((id, version, target_platform)) = parse_package_file_name(&file_name) {
+            packages.push(MirroredPackage {
+                file_name,
+                id,
+                version,
+                target_platform,
+                size: entry.metadata()?.len(),
+            });
+        }
+    }
+    Ok(packages)
+}
+
+/// Totals of the packages of a platform
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct PlatformTotals {
+    pub packages: usize,
+    pub size: u64,
+}
+
+/// An extension whose latest marketplace version is missing from the mirror for too long
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleExtension {
+    pub id: String,
+    pub mirrored_version: String,
+    pub latest_version: String,
+    /// When the latest version was published, in the timezone of the report
+    pub latest_released: DateTime<FixedOffset>,
+    pub days_behind: i64,
+}
+
+/// Summary of a mirror for dashboards
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorStatus {
+    pub extensions: usize,
+    pub versions: usize,
+    pub packages: usize,
+    pub total_size: u64,
+    /// When the mirror was last synced, in the timezone of the report
+    pub last_sync: Option<DateTime<FixedOffset>>,
+    /// Totals keyed by target platform, "universal" for universal packages
+    pub platforms: BTreeMap<String, PlatformTotals>,
+    pub stale_days: i64,
+    pub stale: Vec<StaleExtension>,
+    /// Extensions whose latest version could not be checked
+    pub unchecked: Vec<String>,
+}
+
+/// Summarize the packages of a mirror, without the staleness check
+///
+/// # Arguments
+///
+/// * `packages` - The packages of the mirror
+/// * `inventory` - The inventory of the mirror
+/// * `timezone` - The timezone of the timestamps of the status
+///
+/// # Returns
+///
+/// The mirror status
+pub fn summarize_mirror(
+    packages: &[MirroredPackage],
+    inventory: &Inventory,
+    timezone: ReportTimezone,
+) -> MirrorStatus {
+    let mut status = MirrorStatus {
+        packages: packages.len(),
+        last_sync: inventory
+            .extensions
+            .values()
+            .map(|entry| entry.last_seen)
+            .max()
+            .map(|last_sync| timezone.convert(&last_sync)),
+        ..Default::default()
+    };
+    let mut versions = BTreeMap::new();
+    for package in packages {
+        status.total_size += package.size;
+        let totals = status
+            .platforms
+            .entry(target_platform_name(package.target_platform.as_ref()).to_string())
+            .or_default();
+        totals.packages += 1;
+        totals.size += package.size;
+        versions
+            .entry(package.id.to_lowercase())
+            .or_insert_with(Vec::new)
+            .push(package.version.as_str());
+    }
+    status.extensions = versions.len();
+    status.versions = versions
+        .values_mut()
+        .map(|versions| {
+            versions.sort();
+            versions.dedup();
+            versions.len()
+        })
+        .sum();
+    status
+}
+
+/// Get the newest mirrored version of each extension
+fn newest_versions(packages: &[MirroredPackage]) -> BTreeMap<String, &MirroredPackage> {
+    let mut newest: BTreeMap<String, &MirroredPackage> = BTreeMap::new();
+    for package in packages {
+        let current = newest.entry(package.id.to_lowercase()).or_insert(package);
+        if compare_versions(&package.version, &current.version) > 0 {
+            *current = package;
+        }
+    }
+    newest
+}
+
+/// Compute the status of a mirror, checking the latest marketplace versions unless offline
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `stale_days` - Number of days after which a missing latest version makes an extension stale
+/// * `offline` - Whether to skip the marketplace queries
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+/// * `timezone` - The timezone of the timestamps of the status
+///
+/// # Returns
+///
+/// A Result containing the mirror status or an error that occurred
+pub async fn mirror_status(
+    options: &DownloadOptions,
+    stale_days: i64,
+    offline: bool,
+    concurrency: usize,
+    timezone: ReportTimezone,
+) -> Result<MirrorStatus> {
+    let packages = scan_mirror(&options.destination)?;
+    let inventory = Inventory::load(&options.destination)?;
+    let mut status = summarize_mirror(&packages, &inventory, timezone);
+    status.stale_days = stale_days;
+    if offline {
+        return Ok(status);
+    }
+
+    let now = Utc::now();
+    let newest = newest_versions(&packages);
+    let checks = newest.into_values().map(|package| async move {
+        let result = match Extension::from_id(&package.id) {
+            Ok(extension) => query_extension_version(extension, options).await,
+            Err(e) => Err(e),
+        };
+        (package, result)
+    });
+    let results: Vec<_> = stream::iter(checks).buffered(concurrency).collect().await;
+    for (package, result) in results {
+        let (metadata, latest_version) = match result {
+            Ok(latest) => latest,
+            Err(e) => {
+                warn!(
+                    "Could not check the latest version of {}: {}",
+                    package.id, e
+                );
+                status.unchecked.push(package.id.clone());
+                continue;
+            }
+        };
+        if compare_versions(&latest_version, &package.version) <= 0 {
+            continue;
+        }
+        let latest_released = metadata
+            .versions
+            .iter()
+            .find(|version| version.version == latest_version)
+            .and_then(|version| version.last_updated.parse::<DateTime<Utc>>().ok());
+        let Some(latest_released) = latest_released else {
+            status.unchecked.push(package.id.clone());
+            continue;
+        };
+        let days_behind = (now - latest_released).num_days();
+        if days_behind >= stale_days {
+            status.stale.push(StaleExtension {
+                id: package.id.clone(),
+                mirrored_version: package.version.clone(),
+                latest_version,
+                latest_released: timezone.convert(&latest_released),
+                days_behind,
+            });
+        }
+    }
+    Ok(status)
+}
+
+/// Print the status of a mirror as a table or as JSON
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `stale_days` - Number of days after which a missing latest version makes an extension stale
+/// * `offline` - Whether to skip the marketplace queries
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+/// * `json` - Whether to print JSON
+/// * `timezone` - The timezone of the printed timestamps
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_mirror_status(
+    options: &DownloadOptions,
+    stale_days: i64,
+    offline: bool,
+    concurrency: usize,
+    json: bool,
+    timezone: ReportTimezone,
+) -> Result<()> {
+    let status = mirror_status(options, stale_days, offline, concurrency, timezone).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    println!("Mirror:     {}", options.destination);
+    println!("Extensions: {}", status.extensions);
+    println!("Versions:   {}", status.versions);
+    println!(
+        "Packages:   {} ({} bytes)",
+        status.packages, status.total_size
+    );
+    println!(
+        "Last sync:  {}",
+        status
+            .last_sync
+            .map_or("never".to_string(), |last_sync| last_sync
+                .to_rfc3339_opts(SecondsFormat::Secs, true))
+    );
+    println!();
+    println!("{:<14} {:>8} {:>14}", "PLATFORM", "PACKAGES", "SIZE");
+    for (platform, totals) in &status.platforms {
+        println!(
+            "{:<14} {:>8} {:>14}",
+            platform, totals.packages, totals.size
+        );
+    }
+    if !offline {
+        println!();
+        println!(
+            "{} extension(s) behind a release older than {} days",
+            status.stale.len(),
+            stale_days
+        );
+        for stale in &status.stale {
+            println!(
+                "  {} {} -> {} released {} ({} days)",
+                stale.id,
+                stale.mirrored_version,
+                stale.latest_version,
+                stale
+                    .latest_released
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                stale.days_behind
+            );
+        }
+        if !status.unchecked.is_empty() {
+            println!("Unchecked: {}", status.unchecked.join(", "));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mirrored_package;
+
+    #[test]
+    fn test_parse_package_file_name() {
+        assert_eq!(
+            parse_package_file_name("golang.Go-0.41.2.vsix"),
+            Some(("golang.Go".to_string(), "0.41.2".to_string(), None))
+        );
+        assert_eq!(
+            parse_package_file_name("ms-python.vscode-pylance-2025.3.1@linux-x64.vsix"),
+            Some((
+                "ms-python.vscode-pylance".to_string(),
+                "2025.3.1".to_string(),
+                Some("linux-x64".into())
+            ))
+        );
+        assert_eq!(parse_package_file_name("inventory.json"), None);
+        assert_eq!(parse_package_file_name("golang-0.41.2.vsix"), None);
+    }
+
+    #[test]
+    fn test_summarize_mirror() {
+        let package = |id: &str, version: &str, target_platform: Option<&str>| {
+            mirrored_package(id, version, target_platform, 10)
+        };
+        let packages = [
+            package("golang.Go", "0.41.1", None),
+            package("golang.Go", "0.41.2", None),
+            package("rust-lang.rust-analyzer", "0.3.1", Some("linux-x64")),
+            package("rust-lang.rust-analyzer", "0.3.1", Some("win32-x64")),
+        ];
+        let status = summarize_mirror(&packages, &Inventory::default(), ReportTimezone::Utc);
+        assert_eq!(status.extensions, 2);
+        assert_eq!(status.versions, 3);
+        assert_eq!(status.total_size, 40);
+        assert_eq!(status.platforms["universal"].packages, 2);
+        assert_eq!(status.platforms["linux-x64"].size, 10);
+        assert_eq!(newest_versions(&packages)["golang.go"].version, "0.41.2");
+    }
+}
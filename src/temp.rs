@@ -0,0 +1,255 @@
+use crate::config::{PARTIAL_SUFFIX, TEMP_DIR};
+use crate::error::Result;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Counter making the temporary file names of the process unique
+static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// Get the temp workspace of a directory, where its temporary files are written
+///
+/// The workspace lives in the directory itself, so a temporary file is renamed to
+/// its final path on the same file system.
+pub fn temp_workspace(dir: &Path) -> PathBuf {
+    dir.join(TEMP_DIR)
+}
+
+/// Get the directory of a target path, the current directory for a bare file name
+fn parent_dir(target: &Path) -> &Path {
+    target
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Get the path of the partial download of a file, in the temp workspace of its directory
+///
+/// Unlike the temporary files, its name only depends on the target, so the next
+/// harvest finds an interrupted download and resumes it.
+///
+/// # Arguments
+///
+/// * `target` - The path the downloaded file is renamed to once complete
+///
+/// # Returns
+///
+/// The path of the partial file
+pub fn partial_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    temp_workspace(parent_dir(target)).join(name)
+}
+
+/// A temporary file of a temp workspace, removed when dropped unless persisted
+///
+/// The file is removed when the write fails, when the task writing it is cancelled
+/// and when the harvest panics, so an interrupted write never leaves a truncated file
+/// under its final name nor a stray file next to it. A directory created at its path
+/// (e.g., an unpacked extension) is removed the same way.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Create a temporary file name in the temp workspace of the directory of a target
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The path the file is persisted to
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the temporary file or an error if the workspace cannot be created
+    pub fn for_target(target: &Path) -> Result<Self> {
+        let workspace = temp_workspace(parent_dir(target));
+        fs::create_dir_all(&workspace)?;
+        let name = format!(
+            "{}-{}.tmp",
+            process::id(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+        );
+        Ok(Self {
+            path: workspace.join(name),
+            persisted: false,
+        })
+    }
+
+    /// Get the path of the temporary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the temporary file to its final path
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The final path of the file
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error that occurred
+    pub fn persist(mut self, target: &Path) -> Result<()> {
+        fs::rename(&self.path, target)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted && self.path.exists() {
+            let removed = if self.path.is_dir() {
+                fs::remove_dir_all(&self.path)
+            } else {
+                fs::remove_file(&self.path)
+            };
+            if let Err(e) = removed {
+                warn!("Failed to remove {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Write a file through a temporary file of the temp workspace, renamed once complete
+///
+/// # Arguments
+///
+/// * `path` - The path of the file
+/// * `content` - The content of the file
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let temp_file = TempFile::for_target(path)?;
+    fs::write(temp_file.path(), content)?;
+    temp_file.persist(path)
+}
+
+/// Copy a file through a temporary file of the temp workspace, renamed once complete
+///
+/// # Arguments
+///
+/// * `source` - The path of the copied file
+/// * `target` - The path of the copy
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn copy_atomic(source: &Path, target: &Path) -> Result<()> {
+    let temp_file = TempFile::for_target(target)?;
+    fs::copy(source, temp_file.path())?;
+    temp_file.persist(target)
+}
+
+/// Summary of the leftovers removed by `clean`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanSummary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Remove a leftover file if it was not modified for `max_age`
+fn remove_leftover(path: &Path, max_age: Duration, summary: &mut CleanSummary) -> Result<()> {
+    let metadata = fs::metadata(path)?;
+    let age = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .unwrap_or_default();
+    if age < max_age {
+        debug!(
+            "Keeping {}, modified {}s ago",
+            path.display(),
+            age.as_secs()
+        );
+        return Ok(());
+    }
+    fs::remove_file(path)?;
+    summary.files += 1;
+    summary.bytes += metadata.len();
+    Ok(())
+}
+
+/// Remove the temporary files left in a destination directory by killed harvests
+///
+/// The files of the temp workspace, including the partial downloads kept to be
+/// resumed, are removed once they were not modified for `max_age`, so a running
+/// harvest does not lose its files.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `max_age` - The age from which a file is a leftover
+///
+/// # Returns
+///
+/// A Result containing the summary of the removed files or an error that occurred
+pub fn clean_temp_files(destination: &str, max_age: Duration) -> Result<CleanSummary> {
+    let mut summary = CleanSummary::default();
+    let workspace = temp_workspace(Path::new(destination));
+    if workspace.is_dir() {
+        for entry in fs::read_dir(&workspace)? {
+            let path = entry?.path();
+            if path.is_file() {
+                remove_leftover(&path, max_age, &mut summary)?;
+            }
+        }
+        // Only removed once empty, a harvest may be writing in it
+        let _ = fs::remove_dir(&workspace);
+    }
+    info!(
+        "{} leftover file(s) removed from {}, {} bytes freed",
+        summary.files, destination, summary.bytes
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join("golang.Go-0.41.2.vsix");
+
+        let temp_file = TempFile::for_target(&target).unwrap();
+        let temp_path = temp_file.path().to_path_buf();
+        assert!(temp_path.starts_with(temp_workspace(temp_dir.path())));
+        fs::write(&temp_path, b"partial").unwrap();
+        drop(temp_file);
+        assert!(!temp_path.exists());
+        assert!(!target.exists());
+
+        write_atomic(&target, b"PK").unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"PK");
+
+        let destination = temp_dir.path().to_str().unwrap();
+        let leftover = temp_workspace(temp_dir.path()).join("1-0.tmp");
+        fs::write(&leftover, b"left").unwrap();
+        let partial = partial_path(&temp_dir.path().join("golang.Go-0.41.3.vsix"));
+        assert_eq!(
+            partial,
+            temp_workspace(temp_dir.path()).join("golang.Go-0.41.3.vsix.part")
+        );
+        fs::write(&partial, b"PK\x03").unwrap();
+        // Too recent to be a leftover
+        assert_eq!(
+            clean_temp_files(destination, Duration::from_secs(3600)).unwrap(),
+            CleanSummary::default()
+        );
+        assert_eq!(
+            clean_temp_files(destination, Duration::ZERO).unwrap(),
+            CleanSummary { files: 2, bytes: 7 }
+        );
+        assert!(!temp_workspace(temp_dir.path()).exists());
+        assert!(target.exists());
+    }
+}
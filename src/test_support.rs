@@ -0,0 +1,190 @@
+//! Fixtures shared by the unit tests of the modules
+
+use crate::architecture::TargetPlatform;
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{DownloadOptions, ResolvedExtension};
+use crate::registry::Registry;
+use crate::status::MirroredPackage;
+use crate::types::Extension as MarketplaceExtension;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Build a ZIP archive from its entries
+pub(crate) fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in entries {
+        writer
+            .start_file(*name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Write a ZIP archive of text entries to a file
+pub(crate) fn write_zip(path: &str, entries: &[(&str, &str)]) {
+    let entries: Vec<(&str, &[u8])> = entries
+        .iter()
+        .map(|(name, content)| (*name, content.as_bytes()))
+        .collect();
+    fs::write(path, zip(&entries)).unwrap();
+}
+
+/// Build the VSIX package of an extension, with its manifest and package.json
+pub(crate) fn vsix(id: &str, version: &str, target_platform: Option<&str>) -> Vec<u8> {
+    vsix_with(id, version, target_platform, json!({}), &[])
+}
+
+/// Build the VSIX package of an extension with extra package.json fields and entries
+pub(crate) fn vsix_with(
+    id: &str,
+    version: &str,
+    target_platform: Option<&str>,
+    package_json: Value,
+    entries: &[(&str, &[u8])],
+) -> Vec<u8> {
+    let (publisher, name) = id.split_once('.').unwrap();
+    let target_platform = target_platform
+        .map(|target_platform| format!(r#" TargetPlatform="{}""#, target_platform))
+        .unwrap_or_default();
+    let manifest = format!(
+        r#"<PackageManifest><Metadata><Identity Language="en-US" Id="{}" Version="{}" Publisher="{}"{}/></Metadata></PackageManifest>"#,
+        name, version, publisher, target_platform
+    );
+    let mut package = json!({
+        "publisher": publisher,
+        "name": name,
+        "version": version,
+        "engines": {"vscode": "^1.90.0"}
+    });
+    merge(&mut package, package_json);
+    let package = package.to_string();
+    let mut all: Vec<(&str, &[u8])> = vec![
+        ("extension.vsixmanifest", manifest.as_bytes()),
+        ("extension/package.json", package.as_bytes()),
+    ];
+    all.extend_from_slice(entries);
+    zip(&all)
+}
+
+/// Build the marketplace metadata of an extension without versions
+///
+/// The top-level `fields` replace the defaults (e.g., `{"versions": [...]}`).
+pub(crate) fn metadata(id: &str, fields: Value) -> MarketplaceExtension {
+    let (publisher, name) = id.split_once('.').unwrap();
+    let mut metadata = json!({
+        "publisher": {
+            "publisherId": "id",
+            "publisherName": publisher,
+            "displayName": publisher,
+            "flags": "",
+            "domain": null,
+            "isDomainVerified": false
+        },
+        "extensionId": "ext-id",
+        "extensionName": name,
+        "displayName": name,
+        "flags": "",
+        "lastUpdated": "2025-03-01T00:00:00Z",
+        "publishedDate": "2025-03-01T00:00:00Z",
+        "releaseDate": "2025-03-01T00:00:00Z",
+        "shortDescription": "",
+        "versions": [],
+        "deploymentType": 0
+    });
+    merge(&mut metadata, fields);
+    serde_json::from_value(metadata).unwrap()
+}
+
+/// Build a version of the marketplace metadata, `fields` replacing the defaults
+pub(crate) fn version(version: &str, fields: Value) -> Value {
+    let mut version = json!({
+        "version": version,
+        "flags": "validated",
+        "lastUpdated": "2025-03-01T00:00:00Z",
+        "files": [],
+        "properties": null,
+        "assetUri": "",
+        "fallbackAssetUri": ""
+    });
+    merge(&mut version, fields);
+    version
+}
+
+/// Build a resolved universal package without metadata
+pub(crate) fn resolved(id: &str, version: &str) -> ResolvedExtension {
+    ResolvedExtension {
+        id: id.to_string(),
+        version: version.to_string(),
+        target_platform: None,
+        download_url: String::new(),
+        file_path: String::new(),
+        metadata: None,
+        override_sha256: None,
+    }
+}
+
+/// Build a package found in a mirror, named like the packages of the harvester
+pub(crate) fn mirrored_package(
+    id: &str,
+    version: &str,
+    target_platform: Option<&str>,
+    size: u64,
+) -> MirroredPackage {
+    MirroredPackage {
+        file_name: match target_platform {
+            Some(target_platform) => format!("{}-{}@{}.vsix", id, version, target_platform),
+            None => format!("{}-{}.vsix", id, version),
+        },
+        id: id.to_string(),
+        version: version.to_string(),
+        target_platform: target_platform.map(TargetPlatform::new),
+        size,
+    }
+}
+
+/// A registry serving the same versions for every extension, without network
+#[derive(Debug, Default)]
+pub(crate) struct FakeRegistry {
+    /// The versions of the metadata, from the latest (see `version`)
+    pub(crate) versions: Vec<Value>,
+}
+
+impl Registry for FakeRegistry {
+    fn name(&self) -> &str {
+        "fake"
+    }
+
+    fn query_extension<'a>(
+        &'a self,
+        extension: &'a Extension<'_>,
+        _options: &'a DownloadOptions,
+        _all_versions: bool,
+    ) -> BoxFuture<'a, Result<MarketplaceExtension>> {
+        let metadata = metadata(&extension.to_id(), json!({"versions": self.versions}));
+        Box::pin(async move { Ok(metadata) })
+    }
+
+    fn package_url(
+        &self,
+        extension: &Extension<'_>,
+        version: &str,
+        _target_platform: Option<&TargetPlatform>,
+        _metadata: Option<&MarketplaceExtension>,
+        _options: &DownloadOptions,
+    ) -> String {
+        format!("https://registry.invalid/{}/{}", extension.to_id(), version)
+    }
+}
+
+/// Replace the top-level fields of a JSON object
+fn merge(target: &mut Value, fields: Value) {
+    if let (Some(target), Value::Object(fields)) = (target.as_object_mut(), fields) {
+        target.extend(fields);
+    }
+}
@@ -1,8 +1,19 @@
-use crate::marketplace::{build_download_url_and_file_path, get_extension_version};
+use crate::architecture::PlatformRegistry;
+use crate::config::ALL_PLATFORMS_FIELD;
+use crate::extension::Extensions;
+use crate::marketplace::{
+    build_download_url_and_file_path, download_extension, get_extension_version, resolve_extension,
+    DownloadOptions,
+};
+use crate::naming::NamingPreset;
+use crate::test_support::{self, FakeRegistry};
 use crate::{
-    create_directory_if_not_exists, download_extension, process_extensions, Args, Extension,
+    create_directory_if_not_exists, expand_all_platforms, process_extensions, resolve_for_platform,
+    Args, Extension,
 };
+use serde_json::json;
 use std::fs;
+use std::sync::Arc;
 use tempfile::TempDir;
 
 #[test]
@@ -18,11 +29,92 @@ fn test_invalid_extension_id() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_resolve_pinned_extension_id() {
+    // A pinned version is used without querying the marketplace
+    let options = DownloadOptions {
+        destination: "./extensions".to_string(),
+        ..Default::default()
+    };
+    let resolved = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(resolve_extension(
+            Extension::from_id("golang.Go@0.41.2").unwrap(),
+            &options,
+            Some(&"linux-x64".into()),
+        ))
+        .unwrap();
+    assert_eq!(resolved.version, "0.41.2");
+    assert_eq!(
+        resolved.file_path,
+        "./extensions/golang.Go-0.41.2@linux-x64.vsix"
+    );
+    assert!(resolved.metadata.is_none());
+}
+
+#[test]
+fn test_resolve_pinned_extension_for_platform() {
+    // A pinned version gets the build of the preferred platform when it publishes one
+    let options = DownloadOptions {
+        destination: "./extensions".to_string(),
+        registry: Arc::new(FakeRegistry {
+            versions: vec![
+                test_support::version("0.41.2", json!({"targetPlatform": "linux-x64"})),
+                test_support::version("0.41.2", json!({"targetPlatform": "win32-x64"})),
+                test_support::version("0.41.1", json!({})),
+            ],
+        }),
+        ..Default::default()
+    };
+    let resolve = |id: &str| {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(resolve_for_platform(
+                Extension::from_id(id).unwrap(),
+                Some(&"linux-x64".into()),
+                &options,
+            ))
+            .unwrap()
+    };
+    let resolved = resolve("golang.Go@0.41.2");
+    assert_eq!(resolved.target_platform, Some("linux-x64".into()));
+    assert_eq!(
+        resolved.file_path,
+        "./extensions/golang.Go-0.41.2@linux-x64.vsix"
+    );
+    assert!(resolved.metadata.is_some());
+    // A version without platform-specific builds keeps its universal package
+    assert_eq!(resolve("golang.Go@0.41.1").target_platform, None);
+}
+
+#[test]
+fn test_expand_all_platforms_without_versions() {
+    // An extension without any published version is reported, not expanded
+    let options = DownloadOptions {
+        registry: Arc::new(FakeRegistry::default()),
+        ..Default::default()
+    };
+    let mut extensions: Extensions =
+        serde_json::from_str(r#"{"allPlatforms": ["golang.Go"]}"#).unwrap();
+    let entries = extensions.take_section(ALL_PLATFORMS_FIELD);
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(expand_all_platforms(
+            entries,
+            &mut extensions,
+            &PlatformRegistry::default(),
+            &options,
+            1,
+        ));
+    assert!(extensions.entries().next().is_none());
+}
+
 #[test]
 fn test_extension_to_id() {
     let ext = Extension {
         publisher: "publisher",
         name: "name",
+        version: None,
     };
     assert_eq!(ext.to_id(), "publisher.name");
 }
@@ -32,11 +124,12 @@ fn test_build_download_url_and_file_path() {
     let ext = Extension {
         publisher: "publisher",
         name: "name",
+        version: None,
     };
     let version = "1.0.0";
     let destination = "./extensions";
     let (download_url, file_path) =
-        build_download_url_and_file_path(ext, version, destination, None);
+        build_download_url_and_file_path(ext, version, destination, None, NamingPreset::Harvester);
     assert_eq!(download_url, "https://marketplace.visualstudio.com/_apis/public/gallery/publishers/publisher/vsextensions/name/1.0.0/vspackage");
     assert_eq!(file_path, "./extensions/publisher.name-1.0.0.vsix");
 }
@@ -46,10 +139,11 @@ fn test_get_extension_version() {
     let ext = Extension {
         publisher: "golang",
         name: "Go",
+        version: None,
     };
     let version = tokio::runtime::Runtime::new()
         .unwrap()
-        .block_on(get_extension_version(ext, None, None))
+        .block_on(get_extension_version(ext, None, None, false))
         .unwrap();
     assert!(!version.is_empty());
 }
@@ -82,18 +176,15 @@ fn test_download_extension_without_arch() {
     let ext = Extension {
         publisher: "golang",
         name: "Go",
+        version: None,
+    };
+    let options = DownloadOptions {
+        destination: String::from(path),
+        ..Default::default()
     };
-    let destination = path;
     let result = tokio::runtime::Runtime::new()
         .unwrap()
-        .block_on(download_extension(
-            ext,
-            destination,
-            false,
-            None,
-            None,
-            None,
-        ));
+        .block_on(download_extension(ext, &options, None, None));
     assert!(result.is_ok());
 }
 
@@ -104,16 +195,18 @@ fn test_download_extension_with_arch() {
     let ext = Extension {
         publisher: "ms-python",
         name: "python",
+        version: None,
+    };
+    let options = DownloadOptions {
+        destination: String::from(path),
+        ..Default::default()
     };
-    let destination = path;
     let result = tokio::runtime::Runtime::new()
         .unwrap()
         .block_on(download_extension(
             ext,
-            destination,
-            false,
-            None,
-            Some("linux-x64"),
+            &options,
+            Some(&"linux-x64".into()),
             None,
         ));
     assert!(result.is_ok());
@@ -157,8 +250,8 @@ fn test_download_extensions() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
     let result = runtime.block_on(async {
         let args = Args {
-            input: String::from(format!("{}/test_extensions.json", path)),
-            destination: String::from(path),
+            input: format!("{}/test_extensions.json", path),
+            destination: vec![String::from(path)],
             no_cache: true,
             proxy: None,
             verbose: true,
@@ -166,6 +259,7 @@ fn test_download_extensions() {
             arch: None,
             serial: true,
             engine_version: None,
+            ..Default::default()
         };
 
         process_extensions(&args).await
@@ -239,8 +333,8 @@ fn test_download_extensions_for_specific_engine() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
     let result = runtime.block_on(async {
         let args = Args {
-            input: String::from(format!("{}/test_extensions.json", path)),
-            destination: String::from(path),
+            input: format!("{}/test_extensions.json", path),
+            destination: vec![String::from(path)],
             no_cache: true,
             proxy: None,
             verbose: true,
@@ -248,6 +342,7 @@ fn test_download_extensions_for_specific_engine() {
             arch: None,
             serial: true,
             engine_version: Some(String::from("1.97.0")),
+            ..Default::default()
         };
 
         process_extensions(&args).await
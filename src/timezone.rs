@@ -0,0 +1,85 @@
+use crate::error::VsixHarvesterError;
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// The timezone the timestamps of the reports are rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportTimezone {
+    #[default]
+    Utc,
+    /// The timezone of the host
+    Local,
+    /// An IANA timezone (e.g., "Europe/Paris")
+    Named(Tz),
+}
+
+impl FromStr for ReportTimezone {
+    type Err = VsixHarvesterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "utc" | "z" => Ok(Self::Utc),
+            "local" => Ok(Self::Local),
+            _ => s.parse().map(Self::Named).map_err(|_| {
+                VsixHarvesterError::InvalidArgument(format!(
+                    "{} is not a timezone, use UTC, local or an IANA name (e.g., Europe/Paris)",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+impl ReportTimezone {
+    /// Convert a timestamp to the timezone, keeping its UTC offset at that time
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The timestamp
+    ///
+    /// # Returns
+    ///
+    /// The timestamp in the timezone, serialized as ISO-8601 with its offset
+    pub fn convert(&self, time: &DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Self::Utc => time.fixed_offset(),
+            Self::Local => time.with_timezone(&Local).fixed_offset(),
+            Self::Named(tz) => time.with_timezone(tz).fixed_offset(),
+        }
+    }
+
+    /// Render a timestamp in the timezone as ISO-8601 (e.g., "2025-06-01T14:00:00+02:00")
+    pub fn format(&self, time: &DateTime<Utc>) -> String {
+        self.convert(time)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_timezone() {
+        let summer: DateTime<Utc> = "2025-06-01T12:00:00Z".parse().unwrap();
+        let winter: DateTime<Utc> = "2025-01-15T12:00:00Z".parse().unwrap();
+        assert_eq!(
+            ReportTimezone::default().format(&summer),
+            "2025-06-01T12:00:00Z"
+        );
+        let paris: ReportTimezone = "Europe/Paris".parse().unwrap();
+        assert_eq!(paris.format(&summer), "2025-06-01T14:00:00+02:00");
+        assert_eq!(paris.format(&winter), "2025-01-15T13:00:00+01:00");
+        assert_eq!(paris.convert(&summer), summer);
+        assert_eq!(
+            "UTC".parse::<ReportTimezone>().unwrap(),
+            ReportTimezone::Utc
+        );
+        assert_eq!(
+            "local".parse::<ReportTimezone>().unwrap(),
+            ReportTimezone::Local
+        );
+        assert!("Mars/Olympus".parse::<ReportTimezone>().is_err());
+    }
+}
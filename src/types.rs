@@ -1,3 +1,4 @@
+use crate::architecture::TargetPlatform;
 use serde::{Deserialize, Serialize};
 
 /// Response from the VS Code marketplace API
@@ -38,6 +39,16 @@ pub struct Extension {
     pub versions: Vec<Version>,
     #[serde(rename = "deploymentType")]
     pub deployment_type: i32,
+    #[serde(default)]
+    pub statistics: Vec<Statistic>,
+}
+
+/// Statistic of an extension (e.g., "install", "averagerating")
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Statistic {
+    #[serde(rename = "statisticName")]
+    pub statistic_name: String,
+    pub value: f64,
 }
 
 /// Publisher information for an extension
@@ -62,12 +73,16 @@ pub struct Version {
     pub flags: String,
     #[serde(rename = "lastUpdated")]
     pub last_updated: String,
+    /// Missing from the responses of the minimal queries, as the asset URIs
+    #[serde(default)]
     pub files: Vec<File>,
     pub properties: Option<Vec<Property>>,
-    #[serde(rename = "assetUri")]
+    #[serde(rename = "assetUri", default)]
     pub asset_uri: String,
-    #[serde(rename = "fallbackAssetUri")]
+    #[serde(rename = "fallbackAssetUri", default)]
     pub fallback_asset_uri: String,
+    #[serde(rename = "targetPlatform", default)]
+    pub target_platform: Option<TargetPlatform>,
 }
 
 /// File information for an extension version
@@ -115,11 +130,41 @@ impl Extension {
     }
 
     /// Gets the identifier of the extension in the format "publisher.name"
-    #[allow(dead_code)]
     pub fn get_identifier(&self) -> String {
         format!("{}.{}", self.publisher.publisher_name, self.extension_name)
     }
 
+    /// Gets the value of a statistic of the extension
+    pub fn get_statistic(&self, name: &str) -> Option<f64> {
+        self.statistics
+            .iter()
+            .find(|statistic| statistic.statistic_name == name)
+            .map(|statistic| statistic.value)
+    }
+
+    /// Gets the install count of the extension, 0 if the statistics were not requested
+    pub fn get_install_count(&self) -> u64 {
+        self.get_statistic("install").unwrap_or(0.0) as u64
+    }
+
+    /// Gets the target platforms published for a version, empty for universal versions
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version (e.g., "0.41.2")
+    ///
+    /// # Returns
+    ///
+    /// A vector of the target platforms (e.g., "linux-x64")
+    pub fn get_target_platforms(&self, version: &str) -> Vec<&TargetPlatform> {
+        self.versions
+            .iter()
+            .filter(|candidate| candidate.version == version)
+            .filter_map(|candidate| candidate.target_platform.as_ref())
+            .filter(|target_platform| *target_platform != "universal")
+            .collect()
+    }
+
     /// Gets versions compatible with a specific VS Code engine version
     ///
     /// # Arguments
@@ -136,22 +181,7 @@ impl Extension {
     ) -> Vec<&'a Version> {
         self.versions
             .iter()
-            .filter(|version| {
-                version
-                    .get_vscode_engine_version()
-                    .map_or(false, |req| is_compatible(req.as_str(), engine))
-            })
-            .filter(|version| {
-                if allow_prerelease {
-                    true
-                } else {
-                    version
-                        .get_vscode_prerelease()
-                        .map_or(true, |property_prerelease_value| {
-                            !property_prerelease_value.contains("true")
-                        })
-                }
-            })
+            .filter(|version| version.is_compatible_with(engine, allow_prerelease))
             .collect()
     }
 
@@ -160,13 +190,14 @@ impl Extension {
     /// # Returns
     ///
     /// A vector of references to non prerelease versions
-    pub fn get_non_prerelease_versions<'a>(&'a self) -> Vec<&'a Version> {
+    #[allow(dead_code)]
+    pub fn get_non_prerelease_versions(&self) -> Vec<&Version> {
         self.versions
             .iter()
             .filter(|version| {
                 version
                     .get_vscode_prerelease()
-                    .map_or(false, |req| req.is_empty() || req != "true")
+                    .is_some_and(|req| req.is_empty() || req != "true")
             })
             .collect()
     }
@@ -174,7 +205,6 @@ impl Extension {
 
 impl Version {
     /// Gets the VSIX package URL for this version
-    #[allow(dead_code)]
     pub fn get_vsix_url(&self) -> Option<String> {
         self.files
             .iter()
@@ -199,6 +229,42 @@ impl Version {
             .find(|property| property.key == "Microsoft.VisualStudio.Code.PreRelease")
             .map(|property| property.value.clone())
     }
+    /// Whether the version is a pre-release
+    pub fn is_prerelease(&self) -> bool {
+        self.get_vscode_prerelease()
+            .is_some_and(|prerelease| prerelease.contains("true"))
+    }
+    /// Whether the version supports a VS Code engine version
+    ///
+    /// # Arguments
+    ///
+    /// * `engine` - The VS Code engine version (e.g., "1.97.0")
+    /// * `allow_prerelease` - Whether a pre-release version is acceptable
+    pub fn is_compatible_with(&self, engine: &str, allow_prerelease: bool) -> bool {
+        self.get_vscode_engine_version()
+            .is_some_and(|req| is_compatible(req.as_str(), engine))
+            && (allow_prerelease || !self.is_prerelease())
+    }
+    /// Split the comma separated extension identifiers of a property
+    fn get_extension_list(&self, key: &str) -> Vec<String> {
+        self.properties
+            .iter()
+            .flatten()
+            .filter(|property| property.key == key)
+            .flat_map(|property| property.value.split(','))
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+    /// Get the extensions listed in "Microsoft.VisualStudio.Code.ExtensionDependencies"
+    pub fn get_extension_dependencies(&self) -> Vec<String> {
+        self.get_extension_list("Microsoft.VisualStudio.Code.ExtensionDependencies")
+    }
+    /// Get the extensions listed in "Microsoft.VisualStudio.Code.ExtensionPack"
+    pub fn get_extension_pack(&self) -> Vec<String> {
+        self.get_extension_list("Microsoft.VisualStudio.Code.ExtensionPack")
+    }
 }
 
 /// Checks if the required version is compatible with the provided engine version
@@ -230,7 +296,7 @@ fn is_compatible(requirement: &str, engine_version: &str) -> bool {
         return compare_versions(engine_version, req_version.trim()) >= 0;
     }
     // Handle exact version match (no prefix)
-    else if !requirement.contains(|c: char| !c.is_digit(10) && c != '.') {
+    else if !requirement.contains(|c: char| !c.is_ascii_digit() && c != '.') {
         return requirement == engine_version;
     }
     // Handle simple contains check as a fallback
@@ -253,7 +319,7 @@ fn is_compatible(requirement: &str, engine_version: &str) -> bool {
 /// * `1` if version_a > version_b
 /// * `0` if version_a == version_b
 /// * `-1` if version_a < version_b
-fn compare_versions(version_a: &str, version_b: &str) -> i32 {
+pub(crate) fn compare_versions(version_a: &str, version_b: &str) -> i32 {
     let parts_a: Vec<u32> = version_a
         .split('.')
         .filter_map(|s| s.parse::<u32>().ok())
@@ -370,6 +436,54 @@ mod tests {
         assert!(vsix_url.contains("Microsoft.VisualStudio.Services.VSIXPackage"));
     }
 
+    #[test]
+    fn test_parse_minimal_response() {
+        // A response to Flags::minimal has neither files nor asset URIs nor statistics
+        let json = r#"{
+            "results": [{
+                "extensions": [{
+                    "publisher": {
+                        "publisherId": "id",
+                        "publisherName": "golang",
+                        "displayName": "Go Team at Google",
+                        "flags": "verified",
+                        "domain": null,
+                        "isDomainVerified": true
+                    },
+                    "extensionId": "ext-id",
+                    "extensionName": "Go",
+                    "displayName": "Go",
+                    "flags": "validated, public",
+                    "lastUpdated": "2025-03-01T00:00:00Z",
+                    "publishedDate": "2019-01-01T00:00:00Z",
+                    "releaseDate": "2019-01-01T00:00:00Z",
+                    "shortDescription": "Go support",
+                    "versions": [{
+                        "version": "0.41.2",
+                        "flags": "validated",
+                        "lastUpdated": "2025-03-01T00:00:00Z",
+                        "properties": [
+                            {"key": "Microsoft.VisualStudio.Code.Engine", "value": "^1.75.0"}
+                        ]
+                    }],
+                    "deploymentType": 0
+                }],
+                "pagingToken": null,
+                "resultMetadata": []
+            }]
+        }"#;
+
+        let response = parse_marketplace_response(json).unwrap();
+        let extension = &response.results[0].extensions[0];
+        assert_eq!(extension.versions[0].version, "0.41.2");
+        assert!(extension.versions[0].files.is_empty());
+        assert_eq!(extension.get_latest_vsix_url(), None);
+        assert_eq!(
+            extension.versions[0].get_vscode_engine_version().as_deref(),
+            Some("^1.75.0")
+        );
+    }
+
     #[cfg(test)]
     #[test]
     fn test_get_compatible_versions() {
@@ -393,7 +507,12 @@ mod tests {
             short_description: "".to_string(),
             versions: vec![],
             deployment_type: 0,
+            statistics: vec![Statistic {
+                statistic_name: "install".to_string(),
+                value: 1234.0,
+            }],
         };
+        assert_eq!(extension.get_install_count(), 1234);
 
         // Add versions with different engine requirements
         extension.versions.push(Version {
@@ -408,6 +527,7 @@ mod tests {
             .into(),
             asset_uri: "".to_string(),
             fallback_asset_uri: "".to_string(),
+            target_platform: None,
         });
 
         extension.versions.push(Version {
@@ -422,6 +542,7 @@ mod tests {
             .into(),
             asset_uri: "".to_string(),
             fallback_asset_uri: "".to_string(),
+            target_platform: None,
         });
 
         extension.versions.push(Version {
@@ -436,6 +557,7 @@ mod tests {
             .into(),
             asset_uri: "".to_string(),
             fallback_asset_uri: "".to_string(),
+            target_platform: None,
         });
 
         // Tester la fonction
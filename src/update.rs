@@ -0,0 +1,170 @@
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{
+    download_resolved_extension, query_extension_version, resolve_extension, DownloadOptions,
+    DownloadOutcome,
+};
+use crate::types::compare_versions;
+use crate::vsix::{read_vsix_info, VsixPackageInfo};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+/// Result of an update check of a VSIX package
+#[derive(Debug, Clone)]
+pub struct UpdateCheck {
+    /// The identity of the checked package
+    pub package: VsixPackageInfo,
+    /// The version the marketplace would resolve with the same options
+    pub latest_version: String,
+}
+
+impl UpdateCheck {
+    /// Check if the marketplace has a newer version than the package
+    pub fn is_update_available(&self) -> bool {
+        compare_versions(&self.latest_version, &self.package.version) > 0
+    }
+}
+
+/// Check if a newer compatible version of a VSIX package is available
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+/// * `options` - The download options (engine version, pre-release, proxy)
+///
+/// # Returns
+///
+/// A Result containing the update check or an error that occurred
+pub async fn check_vsix_update(vsix_path: &str, options: &DownloadOptions) -> Result<UpdateCheck> {
+    let package = read_vsix_info(vsix_path)?;
+    let (_, latest_version) = query_extension_version(
+        Extension {
+            publisher: &package.publisher,
+            name: &package.name,
+            version: None,
+        },
+        options,
+    )
+    .await?;
+    Ok(UpdateCheck {
+        package,
+        latest_version,
+    })
+}
+
+/// Replace a VSIX package by its latest compatible version if one is available
+///
+/// The new package is saved in the directory of the old one, for the same target
+/// platform, and the old package is removed.
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+/// * `options` - The download options, the destination is replaced by the package directory
+///
+/// # Returns
+///
+/// A Result containing the download outcome, None if the package is up to date
+pub async fn update_vsix_in_place(
+    vsix_path: &str,
+    options: &DownloadOptions,
+) -> Result<Option<DownloadOutcome>> {
+    let check = check_vsix_update(vsix_path, options).await?;
+    if !check.is_update_available() {
+        info!(
+            "{} {} is up to date",
+            check.package.id(),
+            check.package.version
+        );
+        return Ok(None);
+    }
+    let directory = Path::new(vsix_path)
+        .parent()
+        .map(|parent| parent.to_string_lossy().into_owned())
+        .filter(|parent| !parent.is_empty())
+        .unwrap_or_else(|| ".".to_string());
+    let options = DownloadOptions {
+        destination: directory,
+        ..options.clone()
+    };
+    let resolved = resolve_extension(
+        Extension {
+            publisher: &check.package.publisher,
+            name: &check.package.name,
+            version: None,
+        },
+        &options,
+        check.package.target_platform.as_ref(),
+    )
+    .await?;
+    let outcome = download_resolved_extension(&resolved, &options, None).await?;
+    if Path::new(&outcome.file_path) != Path::new(vsix_path) {
+        fs::remove_file(vsix_path)?;
+        info!("Replaced {} by {}", vsix_path, outcome.file_path);
+    }
+    Ok(Some(outcome))
+}
+
+/// Check a VSIX package for updates and print the result
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+/// * `options` - The download options
+/// * `download` - Whether to replace the package by the newer version
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_vsix_update(
+    vsix_path: &str,
+    options: &DownloadOptions,
+    download: bool,
+) -> Result<()> {
+    let check = check_vsix_update(vsix_path, options).await?;
+    if !check.is_update_available() {
+        println!(
+            "{} {} is up to date",
+            check.package.id(),
+            check.package.version
+        );
+        return Ok(());
+    }
+    println!(
+        "{} {} -> {}",
+        check.package.id(),
+        check.package.version,
+        check.latest_version
+    );
+    if download {
+        if let Some(outcome) = update_vsix_in_place(vsix_path, options).await? {
+            println!("Downloaded {}", outcome.file_path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_update_available() {
+        let mut check = UpdateCheck {
+            package: VsixPackageInfo {
+                publisher: "golang".to_string(),
+                name: "Go".to_string(),
+                version: "0.41.2".to_string(),
+                target_platform: None,
+                engine: None,
+                extension_dependencies: Vec::new(),
+                extension_pack: Vec::new(),
+            },
+            latest_version: "0.41.10".to_string(),
+        };
+        assert!(check.is_update_available());
+        check.latest_version = "0.41.2".to_string();
+        assert!(!check.is_update_available());
+    }
+}
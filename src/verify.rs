@@ -0,0 +1,489 @@
+use crate::architecture::TargetPlatform;
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::hash::{Digest, HashAlgorithm};
+use crate::lockfile::Lockfile;
+use crate::marketplace::{query_marketplace_extension, DownloadOptions};
+use crate::quarantine::quarantine_package;
+use crate::status::{scan_mirror, MirroredPackage};
+use crate::types::Extension as MarketplaceExtension;
+use crate::vsix::validate_vsix;
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+
+/// Marketplace state of a mirrored version which should be pulled from the offline catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarketplaceState {
+    /// The whole extension was unpublished
+    Unpublished,
+    /// The version is no longer listed by the marketplace
+    Removed,
+    /// The version is not validated by the marketplace, its validation failed or is pending
+    NotValidated,
+}
+
+impl fmt::Display for MarketplaceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketplaceState::Unpublished => write!(f, "extension unpublished"),
+            MarketplaceState::Removed => write!(f, "version removed"),
+            MarketplaceState::NotValidated => write!(f, "version not validated"),
+        }
+    }
+}
+
+/// A mirrored package whose version should be pulled from the offline catalog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlaggedPackage {
+    pub file_name: String,
+    pub id: String,
+    pub version: String,
+    pub target_platform: Option<TargetPlatform>,
+    pub state: MarketplaceState,
+}
+
+/// A mirrored package that is not a readable VSIX package or differs from its stored SHA-256
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptPackage {
+    pub file_name: String,
+    pub reason: String,
+}
+
+/// Result of the verification of a mirror against the marketplace
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MirrorVerification {
+    pub flagged: Vec<FlaggedPackage>,
+    /// Packages to fetch again, corrupt or truncated
+    pub corrupt: Vec<CorruptPackage>,
+    /// Extensions whose state could not be checked
+    pub unchecked: Vec<String>,
+}
+
+/// Collect the digests stored for the packages of a mirror
+///
+/// The sidecar files (`.sha256`, `.sha512`, `.b3`) win over the checksums files
+/// (SHA256SUMS, SHA512SUMS, B3SUMS), which win over the lockfile. A package listed with
+/// several algorithms is checked with the last of SHA-256, SHA-512 and BLAKE3.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `packages` - The packages of the mirror
+/// * `lockfile` - The lockfile of the mirror
+///
+/// # Returns
+///
+/// The stored digests keyed by file name
+pub fn stored_checksums(
+    destination: &str,
+    packages: &[MirroredPackage],
+    lockfile: &Lockfile,
+) -> HashMap<String, Digest> {
+    let mut checksums: HashMap<String, Digest> = packages
+        .iter()
+        .filter_map(|package| {
+            let entry = lockfile.find(
+                &package.id,
+                &package.version,
+                package.target_platform.as_ref(),
+            )?;
+            Some((package.file_name.clone(), entry.stored_digest()))
+        })
+        .collect();
+    let mut lines = Vec::new();
+    for &algorithm in HashAlgorithm::ALL {
+        if let Ok(sums) =
+            fs::read_to_string(format!("{}/{}", destination, algorithm.sums_file_name()))
+        {
+            lines.extend(sums.lines().map(|line| (algorithm, line.to_string())));
+        }
+    }
+    for &algorithm in HashAlgorithm::ALL {
+        for package in packages {
+            if let Ok(sidecar) = fs::read_to_string(format!(
+                "{}/{}.{}",
+                destination,
+                package.file_name,
+                algorithm.sidecar_extension()
+            )) {
+                lines.extend(sidecar.lines().map(|line| (algorithm, line.to_string())));
+            }
+        }
+    }
+    for (algorithm, line) in lines {
+        // The format of `sha256sum`, a '*' marks the binary mode
+        if let Some((digest, file)) = line.split_once(char::is_whitespace) {
+            let file = file.trim_start().trim_start_matches('*');
+            checksums.insert(file.to_string(), Digest::new(algorithm, digest));
+        }
+    }
+    checksums
+}
+
+/// Check that a mirrored package is a readable VSIX package with the expected digest
+///
+/// # Arguments
+///
+/// * `content` - The content of the package
+/// * `expected` - Optional stored digest of the package, hashed with its own algorithm
+///
+/// # Returns
+///
+/// The reason to fetch the package again, None if it is intact
+pub fn check_integrity(content: &[u8], expected: Option<&Digest>) -> Option<String> {
+    if let Err(e) = validate_vsix(content) {
+        return Some(format!("corrupt package: {}", e));
+    }
+    let expected = expected?;
+    let actual = expected.algorithm.digest(content);
+    (actual != *expected).then(|| {
+        format!(
+            "{} {} differs from the stored {}",
+            expected.algorithm, actual.value, expected.value
+        )
+    })
+}
+
+/// Re-open every package of a mirror and report the corrupt or truncated ones
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `packages` - The packages of the mirror
+/// * `checksums` - Optional stored digests keyed by file name, to check them again
+///
+/// # Returns
+///
+/// A Result containing the corrupt packages or an error that occurred
+pub fn verify_integrity(
+    destination: &str,
+    packages: &[MirroredPackage],
+    checksums: Option<&HashMap<String, Digest>>,
+) -> Result<Vec<CorruptPackage>> {
+    let mut corrupt = Vec::new();
+    for package in packages {
+        let content = fs::read(format!("{}/{}", destination, package.file_name))?;
+        let expected = checksums.and_then(|checksums| checksums.get(&package.file_name));
+        if let Some(reason) = check_integrity(&content, expected) {
+            corrupt.push(CorruptPackage {
+                file_name: package.file_name.clone(),
+                reason,
+            });
+        }
+    }
+    Ok(corrupt)
+}
+
+/// Check the marketplace state of a mirrored package
+///
+/// # Arguments
+///
+/// * `package` - The mirrored package
+/// * `metadata` - The marketplace metadata of the extension, with every version
+///
+/// # Returns
+///
+/// The state to report, None if the version is still published and validated
+pub fn check_package(
+    package: &MirroredPackage,
+    metadata: &MarketplaceExtension,
+) -> Option<MarketplaceState> {
+    if metadata.flags.to_lowercase().contains("unpublished") {
+        return Some(MarketplaceState::Unpublished);
+    }
+    // Universal packages are served from any build of the version
+    let versions: Vec<_> = metadata
+        .versions
+        .iter()
+        .filter(|version| version.version == package.version)
+        .filter(|version| {
+            package.target_platform.is_none()
+                || version.target_platform.as_ref() == package.target_platform.as_ref()
+        })
+        .collect();
+    if versions.is_empty() {
+        return Some(MarketplaceState::Removed);
+    }
+    if !versions
+        .iter()
+        .any(|version| version.flags.to_lowercase().contains("validated"))
+    {
+        return Some(MarketplaceState::NotValidated);
+    }
+    None
+}
+
+/// Re-check the integrity and the marketplace state of every version of a mirror
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+/// * `offline` - Whether to skip the marketplace queries
+/// * `lockfile` - Optional lockfile, to check the stored digests of the packages again
+///
+/// # Returns
+///
+/// A Result containing the verification or an error that occurred
+pub async fn verify_mirror(
+    options: &DownloadOptions,
+    concurrency: usize,
+    offline: bool,
+    lockfile: Option<&Lockfile>,
+) -> Result<MirrorVerification> {
+    let packages = scan_mirror(&options.destination)?;
+    let checksums =
+        lockfile.map(|lockfile| stored_checksums(&options.destination, &packages, lockfile));
+    let mut verification = MirrorVerification {
+        corrupt: verify_integrity(&options.destination, &packages, checksums.as_ref())?,
+        ..Default::default()
+    };
+    if offline {
+        return Ok(verification);
+    }
+
+    let mut by_extension: BTreeMap<String, Vec<MirroredPackage>> = BTreeMap::new();
+    for package in packages {
+        by_extension
+            .entry(package.id.to_lowercase())
+            .or_default()
+            .push(package);
+    }
+
+    let checks = by_extension.into_values().map(|packages| async move {
+        let result = match Extension::from_id(&packages[0].id) {
+            Ok(extension) => query_marketplace_extension(&extension, options, true).await,
+            Err(e) => Err(e),
+        };
+        (packages, result)
+    });
+    let results: Vec<_> = stream::iter(checks).buffered(concurrency).collect().await;
+    for (packages, result) in results {
+        let metadata = match result {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Could not check the state of {}: {}", packages[0].id, e);
+                verification.unchecked.push(packages[0].id.clone());
+                continue;
+            }
+        };
+        for package in packages {
+            if let Some(state) = check_package(&package, &metadata) {
+                verification.flagged.push(FlaggedPackage {
+                    file_name: package.file_name,
+                    id: package.id,
+                    version: package.version,
+                    target_platform: package.target_platform,
+                    state,
+                });
+            }
+        }
+    }
+    Ok(verification)
+}
+
+/// Verify a mirror and print the flagged and corrupt packages
+///
+/// # Arguments
+///
+/// * `options` - The download options, the destination is the mirror
+/// * `concurrency` - The maximum number of concurrent marketplace queries
+/// * `quarantine` - Whether to move the flagged and corrupt packages to the quarantine directory
+/// * `offline` - Whether to skip the marketplace queries
+/// * `lockfile` - Optional lockfile, to check the stored digests of the packages again
+/// * `json` - Whether to print JSON instead of a table
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_verification(
+    options: &DownloadOptions,
+    concurrency: usize,
+    quarantine: bool,
+    offline: bool,
+    lockfile: Option<&Lockfile>,
+    json: bool,
+) -> Result<()> {
+    let verification = verify_mirror(options, concurrency, offline, lockfile).await?;
+    if quarantine {
+        let pulled = verification
+            .flagged
+            .iter()
+            .map(|flagged| (&flagged.file_name, flagged.state.to_string()))
+            .chain(
+                verification
+                    .corrupt
+                    .iter()
+                    .map(|corrupt| (&corrupt.file_name, corrupt.reason.clone())),
+            );
+        for (file_name, reason) in pulled {
+            let path = format!("{}/{}", options.destination, file_name);
+            quarantine_package(&options.destination, file_name, &fs::read(&path)?, &reason)?;
+            fs::remove_file(&path)?;
+            info!("Pulled {} from the mirror", path);
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&verification)?);
+        return Ok(());
+    }
+
+    if !offline {
+        println!(
+            "{} package(s) to pull from the offline catalog",
+            verification.flagged.len()
+        );
+        for flagged in &verification.flagged {
+            println!("  {:<60} {}", flagged.file_name, flagged.state);
+        }
+    }
+    println!(
+        "{} corrupt package(s) to fetch again",
+        verification.corrupt.len()
+    );
+    for corrupt in &verification.corrupt {
+        println!("  {:<60} {}", corrupt.file_name, corrupt.reason);
+    }
+    if !verification.unchecked.is_empty() {
+        println!(
+            "{} extension(s) could not be checked: {}",
+            verification.unchecked.len(),
+            verification.unchecked.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CHECKSUMS_FILE_NAME;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_package() {
+        let mut metadata: MarketplaceExtension = serde_json::from_value(json!({
+            "publisher": {
+                "publisherId": "id",
+                "publisherName": "golang",
+                "displayName": "Go Team at Google",
+                "flags": "verified",
+                "domain": null,
+                "isDomainVerified": true
+            },
+            "extensionId": "ext-id",
+            "extensionName": "Go",
+            "displayName": "Go",
+            "flags": "validated, public",
+            "lastUpdated": "2025-03-01T00:00:00Z",
+            "publishedDate": "2019-01-01T00:00:00Z",
+            "releaseDate": "2019-01-01T00:00:00Z",
+            "shortDescription": "Go support",
+            "versions": [
+                {
+                    "version": "0.41.2",
+                    "flags": "validated",
+                    "lastUpdated": "2025-03-01T00:00:00Z",
+                    "files": [],
+                    "properties": null,
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                },
+                {
+                    "version": "0.41.1",
+                    "targetPlatform": "linux-x64",
+                    "flags": "none",
+                    "lastUpdated": "2025-02-01T00:00:00Z",
+                    "files": [],
+                    "properties": null,
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                }
+            ],
+            "deploymentType": 0
+        }))
+        .unwrap();
+        let package = |version: &str, target_platform: Option<&str>| {
+            test_support::mirrored_package("golang.Go", version, target_platform, 0)
+        };
+
+        assert_eq!(check_package(&package("0.41.2", None), &metadata), None);
+        assert_eq!(
+            check_package(&package("0.41.1", Some("linux-x64")), &metadata),
+            Some(MarketplaceState::NotValidated)
+        );
+        assert_eq!(
+            check_package(&package("0.40.0", None), &metadata),
+            Some(MarketplaceState::Removed)
+        );
+        metadata.flags = "unpublished".to_string();
+        assert_eq!(
+            check_package(&package("0.41.2", None), &metadata),
+            Some(MarketplaceState::Unpublished)
+        );
+    }
+
+    #[test]
+    fn test_verify_integrity() {
+        let vsix = test_support::zip(&[("extension.vsixmanifest", b"<PackageManifest/>")]);
+        let sha256 = HashAlgorithm::Sha256.digest(&vsix);
+
+        assert_eq!(check_integrity(&vsix, Some(&sha256)), None);
+        assert!(check_integrity(&vsix[..vsix.len() / 2], None)
+            .unwrap()
+            .starts_with("corrupt package"));
+        assert!(check_integrity(
+            &vsix,
+            Some(&Digest::new(HashAlgorithm::Sha256, &"0".repeat(64)))
+        )
+        .unwrap()
+        .contains("differs"));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        fs::write(format!("{}/golang.Go-0.41.2.vsix", destination), &vsix).unwrap();
+        fs::write(
+            format!("{}/golang.Go-0.41.1.vsix", destination),
+            &vsix[..vsix.len() - 10],
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/{}", destination, CHECKSUMS_FILE_NAME),
+            format!("{}  golang.Go-0.41.2.vsix\n", "0".repeat(64)),
+        )
+        .unwrap();
+        let packages = scan_mirror(destination).unwrap();
+        let corrupt = verify_integrity(destination, &packages, None).unwrap();
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(corrupt[0].file_name, "golang.Go-0.41.1.vsix");
+
+        let checksums = stored_checksums(destination, &packages, &Lockfile::default());
+        let corrupt = verify_integrity(destination, &packages, Some(&checksums)).unwrap();
+        assert_eq!(corrupt.len(), 2);
+
+        // A SHA-512 sidecar is checked with its own algorithm, and wins over SHA256SUMS
+        fs::write(
+            format!("{}/golang.Go-0.41.2.vsix.sha512", destination),
+            format!(
+                "{}  golang.Go-0.41.2.vsix\n",
+                HashAlgorithm::Sha512.digest(&vsix).value
+            ),
+        )
+        .unwrap();
+        let checksums = stored_checksums(destination, &packages, &Lockfile::default());
+        assert_eq!(
+            checksums["golang.Go-0.41.2.vsix"].algorithm,
+            HashAlgorithm::Sha512
+        );
+        let corrupt = verify_integrity(destination, &packages, Some(&checksums)).unwrap();
+        assert_eq!(corrupt.len(), 1);
+    }
+}
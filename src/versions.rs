@@ -0,0 +1,221 @@
+use crate::architecture::{join_target_platforms, TargetPlatform};
+use crate::error::Result;
+use crate::extension::Extension;
+use crate::marketplace::{query_marketplace_extension, DownloadOptions};
+use crate::types::Extension as MarketplaceExtension;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Properties of a published version, merged over its target platforms
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionDetails {
+    pub version: String,
+    #[serde(rename = "lastUpdated")]
+    pub last_updated: String,
+    /// The target platforms, empty for universal versions
+    #[serde(rename = "targetPlatforms")]
+    pub target_platforms: Vec<TargetPlatform>,
+    /// The VS Code engine requirement (e.g., "^1.97.0")
+    pub engine: Option<String>,
+    pub prerelease: bool,
+    pub dependencies: Vec<String>,
+    #[serde(rename = "extensionPack")]
+    pub extension_pack: Vec<String>,
+    /// Every raw property of the version
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Every version of an extension with its properties
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtensionVersions {
+    pub id: String,
+    pub versions: Vec<VersionDetails>,
+}
+
+/// Describe the versions of an extension, newest first
+///
+/// The marketplace lists a version once per target platform, these entries are merged.
+///
+/// # Arguments
+///
+/// * `marketplace_extension` - The marketplace metadata queried with every version
+///
+/// # Returns
+///
+/// A vector of the version details
+pub fn describe_versions(marketplace_extension: &MarketplaceExtension) -> Vec<VersionDetails> {
+    let mut details: Vec<VersionDetails> = Vec::new();
+    for version in &marketplace_extension.versions {
+        let index = match details
+            .iter()
+            .position(|existing| existing.version == version.version)
+        {
+            Some(index) => index,
+            None => {
+                details.push(VersionDetails {
+                    version: version.version.clone(),
+                    last_updated: version.last_updated.clone(),
+                    target_platforms: Vec::new(),
+                    engine: version.get_vscode_engine_version(),
+                    prerelease: version
+                        .get_vscode_prerelease()
+                        .is_some_and(|prerelease| prerelease.contains("true")),
+                    dependencies: version.get_extension_dependencies(),
+                    extension_pack: version.get_extension_pack(),
+                    properties: BTreeMap::new(),
+                });
+                details.len() - 1
+            }
+        };
+        let entry = &mut details[index];
+        if let Some(target_platform) = version
+            .target_platform
+            .as_ref()
+            .filter(|target_platform| *target_platform != "universal")
+        {
+            entry.target_platforms.push(target_platform.clone());
+        }
+        for property in version.properties.iter().flatten() {
+            entry
+                .properties
+                .entry(property.key.clone())
+                .or_insert_with(|| property.value.clone());
+        }
+    }
+    details
+}
+
+/// Query every version of an extension with its properties
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier (e.g., "golang.Go")
+/// * `options` - The download options (proxy, polite mode)
+///
+/// # Returns
+///
+/// A Result containing the versions or an error that occurred
+pub async fn list_versions(id: &str, options: &DownloadOptions) -> Result<ExtensionVersions> {
+    let extension = Extension::from_id(id)?;
+    let marketplace_extension = query_marketplace_extension(&extension, options, true).await?;
+    Ok(ExtensionVersions {
+        id: extension.to_id(),
+        versions: describe_versions(&marketplace_extension),
+    })
+}
+
+/// Print every version of an extension with its properties
+///
+/// # Arguments
+///
+/// * `id` - The extension identifier (e.g., "golang.Go")
+/// * `options` - The download options
+/// * `json` - Whether to print JSON instead of a table
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub async fn print_versions(id: &str, options: &DownloadOptions, json: bool) -> Result<()> {
+    let versions = list_versions(id, options).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&versions)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:<12} {:<10} PLATFORMS",
+        "VERSION", "ENGINE", "PRERELEASE"
+    );
+    for version in &versions.versions {
+        println!(
+            "{:<16} {:<12} {:<10} {}",
+            version.version,
+            version.engine.as_deref().unwrap_or("-"),
+            version.prerelease,
+            if version.target_platforms.is_empty() {
+                "universal".to_string()
+            } else {
+                join_target_platforms(&version.target_platforms, ",")
+            }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_versions() {
+        let marketplace_extension: MarketplaceExtension = serde_json::from_value(serde_json::json!({
+            "publisher": {
+                "publisherId": "id",
+                "publisherName": "rust-lang",
+                "displayName": "The Rust Programming Language",
+                "flags": "verified",
+                "domain": null,
+                "isDomainVerified": false
+            },
+            "extensionId": "ext-id",
+            "extensionName": "rust-analyzer",
+            "displayName": "rust-analyzer",
+            "flags": "validated, public",
+            "lastUpdated": "2025-03-01T00:00:00Z",
+            "publishedDate": "2018-01-01T00:00:00Z",
+            "releaseDate": "2018-01-01T00:00:00Z",
+            "shortDescription": "Rust language support",
+            "versions": [
+                {
+                    "version": "0.4.2",
+                    "targetPlatform": "linux-x64",
+                    "flags": "validated",
+                    "lastUpdated": "2025-03-01T00:00:00Z",
+                    "files": [],
+                    "properties": [
+                        {"key": "Microsoft.VisualStudio.Code.Engine", "value": "^1.97.0"},
+                        {"key": "Microsoft.VisualStudio.Code.PreRelease", "value": "true"},
+                        {"key": "Microsoft.VisualStudio.Code.ExtensionDependencies", "value": "a.b, c.d"}
+                    ],
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                },
+                {
+                    "version": "0.4.2",
+                    "targetPlatform": "win32-x64",
+                    "flags": "validated",
+                    "lastUpdated": "2025-03-01T00:00:00Z",
+                    "files": [],
+                    "properties": null,
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                },
+                {
+                    "version": "0.3.0",
+                    "flags": "validated",
+                    "lastUpdated": "2025-01-01T00:00:00Z",
+                    "files": [],
+                    "properties": [
+                        {"key": "Microsoft.VisualStudio.Code.ExtensionPack", "value": ""}
+                    ],
+                    "assetUri": "",
+                    "fallbackAssetUri": ""
+                }
+            ],
+            "deploymentType": 0
+        }))
+        .unwrap();
+
+        let details = describe_versions(&marketplace_extension);
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].version, "0.4.2");
+        assert_eq!(details[0].target_platforms, vec!["linux-x64", "win32-x64"]);
+        assert_eq!(details[0].engine.as_deref(), Some("^1.97.0"));
+        assert!(details[0].prerelease);
+        assert_eq!(details[0].dependencies, vec!["a.b", "c.d"]);
+        assert_eq!(details[0].properties.len(), 3);
+        assert!(details[1].target_platforms.is_empty());
+        assert!(!details[1].prerelease);
+        assert!(details[1].extension_pack.is_empty());
+    }
+}
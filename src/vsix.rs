@@ -0,0 +1,338 @@
+use crate::architecture::TargetPlatform;
+use crate::error::{Result, VsixHarvesterError};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+/// Identity of an extension read from a VSIX package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VsixPackageInfo {
+    pub publisher: String,
+    pub name: String,
+    pub version: String,
+    /// The target platform of the package, None for universal packages
+    pub target_platform: Option<TargetPlatform>,
+    /// The VS Code engine requirement (e.g., "^1.97.0")
+    pub engine: Option<String>,
+    /// The extensions listed in the `extensionDependencies` of the package.json
+    pub extension_dependencies: Vec<String>,
+    /// The members listed in the `extensionPack` of the package.json
+    pub extension_pack: Vec<String>,
+}
+
+impl VsixPackageInfo {
+    /// Gets the identifier of the extension in the format "publisher.name"
+    pub fn id(&self) -> String {
+        format!("{}.{}", self.publisher, self.name)
+    }
+}
+
+/// Read an entry of a VSIX package as a string
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Extract the value of an XML attribute from the first element containing it
+fn xml_attribute<'a>(xml: &'a str, attribute: &str) -> Option<&'a str> {
+    let pattern = format!("{}=\"", attribute);
+    let start = xml.find(&pattern)? + pattern.len();
+    let end = xml[start..].find('"')? + start;
+    Some(&xml[start..end])
+}
+
+/// Get the strings of a JSON array, skipping the other values
+fn string_list(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read the identity of the extension embedded in a VSIX package
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+///
+/// # Returns
+///
+/// A Result containing the package identity or an error if the package is not a valid VSIX
+pub fn read_vsix_info(vsix_path: &str) -> Result<VsixPackageInfo> {
+    let mut archive = ZipArchive::new(File::open(vsix_path)?)?;
+    let invalid =
+        |reason: &str| VsixHarvesterError::InvalidVsix(format!("{}: {}", vsix_path, reason));
+    let package_json = read_entry(&mut archive, "extension/package.json")?
+        .ok_or_else(|| invalid("missing extension/package.json"))?;
+    let package: Value = serde_json::from_str(&package_json)?;
+    let field = |name: &str| {
+        package[name]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| invalid(&format!("missing {} in package.json", name)))
+    };
+    let target_platform = read_entry(&mut archive, "extension.vsixmanifest")?
+        .and_then(|manifest| xml_attribute(&manifest, "TargetPlatform").map(TargetPlatform::new));
+    Ok(VsixPackageInfo {
+        publisher: field("publisher")?,
+        name: field("name")?,
+        version: field("version")?,
+        target_platform,
+        engine: package["engines"]["vscode"].as_str().map(str::to_string),
+        extension_dependencies: string_list(&package["extensionDependencies"]),
+        extension_pack: string_list(&package["extensionPack"]),
+    })
+}
+
+/// Read the `extension/package.json` manifest of a VSIX package
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+///
+/// # Returns
+///
+/// A Result containing the manifest as stored in the package or an error if it is missing
+pub fn read_package_json(vsix_path: &str) -> Result<String> {
+    let mut archive = ZipArchive::new(File::open(vsix_path)?)?;
+    read_entry(&mut archive, "extension/package.json")?.ok_or_else(|| {
+        VsixHarvesterError::InvalidVsix(format!("{}: missing extension/package.json", vsix_path))
+    })
+}
+
+/// The license of a VSIX package
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VsixLicense {
+    /// The `license` of the package.json, usually an SPDX expression (e.g., "MIT")
+    pub declared: Option<String>,
+    /// The text of the license file the manifest publishes as the License asset
+    pub text: Option<String>,
+}
+
+/// Read the license of a VSIX package
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+///
+/// # Returns
+///
+/// A Result containing the license or an error if the package is not a valid VSIX
+pub fn read_license(vsix_path: &str) -> Result<VsixLicense> {
+    let mut archive = ZipArchive::new(File::open(vsix_path)?)?;
+    let declared = read_entry(&mut archive, "extension/package.json")?
+        .and_then(|package_json| serde_json::from_str::<Value>(&package_json).ok())
+        .and_then(|package| package["license"].as_str().map(str::to_string));
+    let license_entry = read_entry(&mut archive, "extension.vsixmanifest")?.and_then(|manifest| {
+        let start = manifest.find("<License>")? + "<License>".len();
+        let end = manifest[start..].find("</License>")? + start;
+        Some(manifest[start..end].trim().to_string())
+    });
+    let text = match license_entry {
+        Some(name) => read_entry(&mut archive, &name)?,
+        None => None,
+    };
+    Ok(VsixLicense { declared, text })
+}
+
+/// Check that downloaded content is a readable VSIX package
+///
+/// Every entry is read to verify its checksum, and the package must contain an
+/// `extension.vsixmanifest`.
+///
+/// # Arguments
+///
+/// * `content` - The content of the package
+///
+/// # Returns
+///
+/// A Result indicating success or the validation error
+pub fn validate_vsix(content: &[u8]) -> Result<()> {
+    validate_vsix_reader(Cursor::new(content))
+}
+
+/// Check that a package read from a file or a buffer is a readable VSIX package
+///
+/// # Arguments
+///
+/// * `reader` - The reader of the package
+///
+/// # Returns
+///
+/// A Result indicating success or the validation error
+pub fn validate_vsix_reader<R: Read + Seek>(reader: R) -> Result<()> {
+    let mut archive = ZipArchive::new(reader)?;
+    for index in 0..archive.len() {
+        io::copy(&mut archive.by_index(index)?, &mut io::sink())?;
+    }
+    if archive.index_for_name("extension.vsixmanifest").is_none() {
+        return Err(VsixHarvesterError::InvalidVsix(
+            "missing extension.vsixmanifest".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Write a normalized copy of a VSIX package
+///
+/// The copy stores the entries uncompressed, sorted by name and with a fixed
+/// modification time, so two versions of an extension share most of their bytes
+/// and delta transfer tools (rsync, zsync) only ship what changed. The copy is
+/// still a valid VSIX package.
+///
+/// # Arguments
+///
+/// * `vsix_path` - The path of the VSIX package
+/// * `output_path` - The path of the normalized copy
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn write_normalized_copy(vsix_path: &str, output_path: &str) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(vsix_path)?)?;
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+
+    let mut writer = ZipWriter::new(File::create(output_path)?);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(DateTime::default());
+    for name in names {
+        let mut entry = archive.by_name(&name)?;
+        if entry.is_dir() {
+            writer.add_directory(name, options)?;
+            continue;
+        }
+        let mut content = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut content)?;
+        writer.start_file(name, options)?;
+        writer.write_all(&content)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write_zip;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_vsix() {
+        let temp_dir = TempDir::new().unwrap();
+        let vsix_path = format!("{}/test.vsix", temp_dir.path().display());
+        write_zip(&vsix_path, &[("extension.vsixmanifest", "<xml/>")]);
+        let content = std::fs::read(&vsix_path).unwrap();
+        assert!(validate_vsix(&content).is_ok());
+        assert!(validate_vsix(&content[..content.len() / 2]).is_err());
+        assert!(validate_vsix(b"<html>throttled</html>").is_err());
+
+        write_zip(&vsix_path, &[("extension/package.json", "{}")]);
+        assert!(validate_vsix(&std::fs::read(&vsix_path).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_read_vsix_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let vsix_path = format!("{}/test.vsix", temp_dir.path().display());
+        write_zip(
+            &vsix_path,
+            &[
+                (
+                    "extension/package.json",
+                    r#"{"publisher": "golang", "name": "Go", "version": "0.41.2", "engines": {"vscode": "^1.75.0"}, "extensionDependencies": ["ms-toolsai.jupyter-keymap"], "extensionPack": ["golang.go-nightly", 42]}"#,
+                ),
+                (
+                    "extension.vsixmanifest",
+                    r#"<Identity Language="en-US" Id="Go" Version="0.41.2" Publisher="golang" TargetPlatform="linux-x64"/>"#,
+                ),
+            ],
+        );
+        let info = read_vsix_info(&vsix_path).unwrap();
+        assert_eq!(info.id(), "golang.Go");
+        assert_eq!(info.version, "0.41.2");
+        assert_eq!(info.target_platform, Some("linux-x64".into()));
+        assert_eq!(info.engine.as_deref(), Some("^1.75.0"));
+        assert_eq!(
+            info.extension_dependencies,
+            vec!["ms-toolsai.jupyter-keymap"]
+        );
+        assert_eq!(info.extension_pack, vec!["golang.go-nightly"]);
+        assert!(read_package_json(&vsix_path)
+            .unwrap()
+            .starts_with(r#"{"publisher": "golang""#));
+        assert_eq!(read_license(&vsix_path).unwrap(), VsixLicense::default());
+
+        write_zip(
+            &vsix_path,
+            &[
+                ("extension/package.json", r#"{"license": "MIT"}"#),
+                (
+                    "extension.vsixmanifest",
+                    "<Assets/><License>extension/LICENSE.txt</License>",
+                ),
+                ("extension/LICENSE.txt", "MIT License"),
+            ],
+        );
+        assert_eq!(
+            read_license(&vsix_path).unwrap(),
+            VsixLicense {
+                declared: Some("MIT".to_string()),
+                text: Some("MIT License".to_string()),
+            }
+        );
+
+        write_zip(&vsix_path, &[("extension.vsixmanifest", "<xml/>")]);
+        assert!(read_vsix_info(&vsix_path).is_err());
+        assert!(read_package_json(&vsix_path).is_err());
+    }
+
+    #[test]
+    fn test_write_normalized_copy() {
+        let temp_dir = TempDir::new().unwrap();
+        let vsix_path = format!("{}/test.vsix", temp_dir.path().display());
+        let output_path = format!("{}/normalized.vsix", temp_dir.path().display());
+        write_zip(
+            &vsix_path,
+            &[
+                ("extension/package.json", "{}"),
+                ("extension.vsixmanifest", "<xml/>"),
+            ],
+        );
+
+        write_normalized_copy(&vsix_path, &output_path).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&output_path).unwrap()).unwrap();
+        let first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "extension.vsixmanifest");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+        let mut content = String::new();
+        archive
+            .by_name("extension/package.json")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "{}");
+
+        // Normalizing twice gives the same bytes
+        let second_path = format!("{}/normalized2.vsix", temp_dir.path().display());
+        write_normalized_copy(&vsix_path, &second_path).unwrap();
+        assert_eq!(
+            std::fs::read(&output_path).unwrap(),
+            std::fs::read(&second_path).unwrap()
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::{Extension, ManifestEntry};
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Directories never holding workspace settings worth scanning
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Remove the comments and trailing commas of a JSON with comments document
+///
+/// VS Code settings files (`.vscode/extensions.json`, `devcontainer.json`) are JSONC.
+///
+/// # Arguments
+///
+/// * `content` - The JSONC document
+///
+/// # Returns
+///
+/// The plain JSON document
+pub fn strip_jsonc(content: &str) -> String {
+    let mut json = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            json.push(c);
+            match c {
+                '\\' => json.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                json.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        json.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            (']' | '}', _) => {
+                // Drop the trailing comma of the closed array or object
+                let trimmed = json.trim_end().len();
+                if json[..trimmed].ends_with(',') {
+                    json.truncate(trimmed - 1);
+                }
+                json.push(c);
+            }
+            _ => json.push(c),
+        }
+    }
+    json
+}
+
+/// Get the extension identifiers referenced by a workspace settings file
+///
+/// `.vscode/extensions.json` lists `recommendations`, `devcontainer.json` lists
+/// `customizations.vscode.extensions` (or the legacy top-level `extensions`) and
+/// `.code-workspace` files list `extensions.recommendations`. Version suffixes are
+/// dropped and excluded extensions (`-publisher.name`) are skipped.
+///
+/// # Arguments
+///
+/// * `settings` - The parsed settings file
+///
+/// # Returns
+///
+/// The valid extension identifiers
+pub fn referenced_extensions(settings: &Value) -> Vec<String> {
+    [
+        &settings["recommendations"],
+        &settings["customizations"]["vscode"]["extensions"],
+        &settings["extensions"],
+        &settings["extensions"]["recommendations"],
+    ]
+    .into_iter()
+    .filter_map(Value::as_array)
+    .flatten()
+    .filter_map(Value::as_str)
+    .filter(|id| !id.starts_with('-'))
+    .map(|id| id.split('@').next().unwrap_or_default().trim())
+    .filter(|id| Extension::from_id(id).is_ok())
+    .map(str::to_string)
+    .collect()
+}
+
+/// Check if a file is a workspace settings file referencing extensions
+fn is_settings_file(path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let parent = path
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    (file_name == "extensions.json" && parent == ".vscode")
+        || file_name == ".devcontainer.json"
+        || (file_name == "devcontainer.json"
+            && path
+                .ancestors()
+                .any(|ancestor| ancestor.ends_with(".devcontainer")))
+        || file_name.ends_with(".code-workspace")
+}
+
+/// Find the workspace settings files under a directory
+fn find_settings_files(directory: &Path, files: &mut Vec<String>) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(directory)?
+        .filter_map(std::result::Result::ok)
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            let name = entry.file_name();
+            if !SKIPPED_DIRS.iter().any(|skipped| name == *skipped) {
+                find_settings_files(&path, files)?;
+            }
+        } else if is_settings_file(&path) {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Collect the extensions recommended by the repositories of a directory
+///
+/// # Arguments
+///
+/// * `root` - The directory holding the repositories
+///
+/// # Returns
+///
+/// A Result containing the universal manifest entries, without duplicates, or an error that occurred
+pub fn scan_workspaces(root: &str) -> Result<Vec<ManifestEntry>> {
+    let mut files = Vec::new();
+    find_settings_files(Path::new(root), &mut files)?;
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for file in &files {
+        let settings = fs::read_to_string(file)
+            .map_err(VsixHarvesterError::from)
+            .and_then(|content| {
+                serde_json::from_str::<Value>(&strip_jsonc(&content))
+                    .map_err(VsixHarvesterError::from)
+            });
+        let settings = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Ignoring {}: {}", file, e);
+                continue;
+            }
+        };
+        for id in referenced_extensions(&settings) {
+            if seen.insert(id.to_lowercase()) {
+                entries.push(ManifestEntry::Id(id));
+            }
+        }
+    }
+    info!(
+        "{} extension(s) recommended by {} workspace file(s) under {}",
+        entries.len(),
+        files.len(),
+        root
+    );
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_workspaces() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api/.vscode")).unwrap();
+        fs::write(
+            root.join("api/.vscode/extensions.json"),
+            r#"{
+                // Go tooling
+                "recommendations": ["golang.Go", "ms-azuretools.vscode-docker",],
+                /* "unwantedRecommendations": [] */
+            }"#,
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("web/.devcontainer")).unwrap();
+        fs::write(
+            root.join("web/.devcontainer/devcontainer.json"),
+            r#"{
+                "image": "mcr.microsoft.com/devcontainers/go",
+                "customizations": {"vscode": {"extensions": [
+                    "GOLANG.go", "dbaeumer.vscode-eslint@2.4.4", "-ms-python.python"
+                ]}}
+            }"#,
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("web/node_modules/pkg/.vscode")).unwrap();
+        fs::write(
+            root.join("web/node_modules/pkg/.vscode/extensions.json"),
+            r#"{"recommendations": ["vendored.extension"]}"#,
+        )
+        .unwrap();
+
+        let entries = scan_workspaces(&root.to_string_lossy()).unwrap();
+        let ids: Vec<&str> = entries.iter().map(ManifestEntry::id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "golang.Go",
+                "ms-azuretools.vscode-docker",
+                "dbaeumer.vscode-eslint"
+            ]
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use crate::assets::asset_cache_path;
+use crate::compression::{compressed_path, StoreCompression};
+use crate::config::{CHECKSUMS_FILE_NAME, NORMALIZED_DIR};
+use crate::error::{Result, VsixHarvesterError};
+use crate::extension::Extension;
+use crate::inventory::Inventory;
+use crate::lockfile::Lockfile;
+use crate::provenance::sidecar_path;
+use crate::quarantine::quarantine_package;
+use crate::status::scan_mirror;
+use crate::temp::write_atomic;
+use clap::ValueEnum;
+use log::{info, warn};
+use std::fs;
+use std::path::Path;
+
+/// What a yank removed from the mirror
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct YankReport {
+    /// The file names of the removed packages, one per target platform
+    pub packages: Vec<String>,
+    /// The number of lockfile entries removed
+    pub lock_entries: usize,
+    /// Whether the extension was removed from the inventory, no version being left
+    pub inventory_removed: bool,
+}
+
+/// Parse the target of a yank
+///
+/// # Arguments
+///
+/// * `target` - The version to yank in the format "publisher.name@version"
+///
+/// # Returns
+///
+/// A Result containing the extension identifier and the version or an error that occurred
+pub fn parse_yank_target(target: &str) -> Result<(String, String)> {
+    let Some((id, version)) = target
+        .split_once('@')
+        .filter(|(_, version)| !version.is_empty())
+    else {
+        return Err(VsixHarvesterError::InvalidExtensionId(target.to_string()));
+    };
+    Extension::from_id(id)?;
+    Ok((id.to_string(), version.to_string()))
+}
+
+/// Remove a file if it exists
+fn remove_if_exists(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        fs::remove_file(path)?;
+        info!("Removed {}", path);
+    }
+    Ok(())
+}
+
+/// Remove a version of an extension from the mirror in one operation
+///
+/// The packages of every target platform are removed with their compressed,
+/// normalized, checksum and provenance siblings and their cached assets, then the version is
+/// dropped from `SHA256SUMS`, the lockfile and, when no other version is left,
+/// the inventory.
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `lockfile_path` - The path of the lockfile
+/// * `id` - The extension identifier in the format "publisher.name"
+/// * `version` - The version to yank
+/// * `quarantine` - Optional reason to move the packages to the quarantine directory instead of deleting them
+///
+/// # Returns
+///
+/// A Result containing what was removed or an error that occurred
+pub fn yank_version(
+    destination: &str,
+    lockfile_path: &str,
+    id: &str,
+    version: &str,
+    quarantine: Option<&str>,
+) -> Result<YankReport> {
+    let mut report = YankReport::default();
+    let (yanked, kept): (Vec<_>, Vec<_>) = scan_mirror(destination)?
+        .into_iter()
+        .filter(|package| package.id.eq_ignore_ascii_case(id))
+        .partition(|package| package.version == version);
+
+    for package in &yanked {
+        let path = format!("{}/{}", destination, package.file_name);
+        if let Some(reason) = quarantine {
+            quarantine_package(destination, &package.file_name, &fs::read(&path)?, reason)?;
+        }
+        fs::remove_file(&path)?;
+        info!("Yanked {}", path);
+        for compression in StoreCompression::value_variants() {
+            remove_if_exists(&compressed_path(&path, *compression))?;
+        }
+        remove_if_exists(&format!("{}.sha256", path))?;
+        remove_if_exists(&sidecar_path(&path))?;
+        remove_if_exists(&format!(
+            "{}/{}/{}",
+            destination, NORMALIZED_DIR, package.file_name
+        ))?;
+        report.packages.push(package.file_name.clone());
+    }
+    if let Some(package) = yanked.first() {
+        let assets_dir = asset_cache_path(destination, &package.id, version, "");
+        if Path::new(&assets_dir).is_dir() {
+            fs::remove_dir_all(&assets_dir)?;
+            info!("Removed {}", assets_dir);
+        }
+    }
+
+    let checksums_path = format!("{}/{}", destination, CHECKSUMS_FILE_NAME);
+    if Path::new(&checksums_path).exists() {
+        let sums: String = fs::read_to_string(&checksums_path)?
+            .lines()
+            .filter(|line| {
+                !report
+                    .packages
+                    .iter()
+                    .any(|file_name| line.ends_with(&format!("  {}", file_name)))
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+        write_atomic(Path::new(&checksums_path), sums.as_bytes())?;
+    }
+
+    if Path::new(lockfile_path).exists() {
+        let mut lockfile = Lockfile::load(lockfile_path)?;
+        let before = lockfile.extensions.len();
+        lockfile
+            .extensions
+            .retain(|entry| !(entry.id.eq_ignore_ascii_case(id) && entry.version == version));
+        report.lock_entries = before - lockfile.extensions.len();
+        if report.lock_entries > 0 {
+            lockfile.save(lockfile_path)?;
+            warn!(
+                "{}@{} removed from {}, pin another version before the next harvest or it will be downloaded again",
+                id, version, lockfile_path
+            );
+        }
+    }
+
+    if kept.is_empty() && Path::new(&Inventory::path(destination)).exists() {
+        let mut inventory = Inventory::load(destination)?;
+        if inventory.extensions.remove(&id.to_lowercase()).is_some() {
+            inventory.save(destination)?;
+            report.inventory_removed = true;
+        }
+    }
+    Ok(report)
+}
+
+/// Yank a version from the mirror and print what was removed
+///
+/// # Arguments
+///
+/// * `destination` - The directory where the extensions are saved
+/// * `lockfile_path` - The path of the lockfile
+/// * `target` - The version to yank in the format "publisher.name@version"
+/// * `quarantine` - Whether to move the packages to the quarantine directory instead of deleting them
+/// * `reason` - The reason recorded with the quarantined packages
+///
+/// # Returns
+///
+/// A Result indicating success or an error that occurred
+pub fn print_yank(
+    destination: &str,
+    lockfile_path: &str,
+    target: &str,
+    quarantine: bool,
+    reason: &str,
+) -> Result<()> {
+    let (id, version) = parse_yank_target(target)?;
+    let report = yank_version(
+        destination,
+        lockfile_path,
+        &id,
+        &version,
+        quarantine.then_some(reason),
+    )?;
+    if report.packages.is_empty() && report.lock_entries == 0 {
+        warn!("{}@{} is not in the mirror", id, version);
+        return Ok(());
+    }
+    println!(
+        "Yanked {}@{}: {} package(s), {} lockfile entry(ies){}",
+        id,
+        version,
+        report.packages.len(),
+        report.lock_entries,
+        if report.inventory_removed {
+            ", removed from the inventory"
+        } else {
+            ""
+        }
+    );
+    for file_name in &report.packages {
+        println!("  {}", file_name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockEntry;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_yank_version() {
+        assert!(parse_yank_target("golang.Go").is_err());
+        assert_eq!(
+            parse_yank_target("golang.Go@0.41.2").unwrap(),
+            ("golang.Go".to_string(), "0.41.2".to_string())
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let destination = temp_dir.path().display().to_string();
+        for file_name in [
+            "golang.Go-0.41.2@linux-x64.vsix",
+            "golang.Go-0.41.2@linux-x64.vsix.zst",
+            "golang.Go-0.41.2@darwin-arm64.vsix",
+            "golang.Go-0.41.1@linux-x64.vsix",
+        ] {
+            fs::write(format!("{}/{}", destination, file_name), "go").unwrap();
+        }
+        fs::write(
+            format!("{}/{}", destination, CHECKSUMS_FILE_NAME),
+            "aa  golang.Go-0.41.2@linux-x64.vsix\nbb  golang.Go-0.41.1@linux-x64.vsix\n",
+        )
+        .unwrap();
+        let lockfile_path = format!("{}/extensions.lock", destination);
+        let mut lockfile = Lockfile::default();
+        for version in ["0.41.2", "0.41.1"] {
+            lockfile.extensions.push(LockEntry {
+                id: "golang.Go".to_string(),
+                version: version.to_string(),
+                target_platform: Some("linux-x64".into()),
+                download_url: None,
+                sha256: "aa".to_string(),
+                digest: None,
+                locked_at: None,
+            });
+        }
+        lockfile.save(&lockfile_path).unwrap();
+
+        let report = yank_version(
+            &destination,
+            &lockfile_path,
+            "golang.go",
+            "0.41.2",
+            Some("broken"),
+        )
+        .unwrap();
+        assert_eq!(
+            report.packages,
+            vec![
+                "golang.Go-0.41.2@darwin-arm64.vsix",
+                "golang.Go-0.41.2@linux-x64.vsix"
+            ]
+        );
+        assert_eq!(report.lock_entries, 1);
+        assert!(!report.inventory_removed);
+        assert!(!Path::new(&format!(
+            "{}/golang.Go-0.41.2@linux-x64.vsix.zst",
+            destination
+        ))
+        .exists());
+        assert!(Path::new(&format!(
+            "{}/quarantine/golang.Go-0.41.2@linux-x64.vsix",
+            destination
+        ))
+        .exists());
+        assert_eq!(
+            fs::read_to_string(format!("{}/{}", destination, CHECKSUMS_FILE_NAME)).unwrap(),
+            "bb  golang.Go-0.41.1@linux-x64.vsix\n"
+        );
+        assert_eq!(Lockfile::load(&lockfile_path).unwrap().extensions.len(), 1);
+    }
+}